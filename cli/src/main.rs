@@ -0,0 +1,296 @@
+//! Claude Agent command-line interface
+//!
+//! Runs the ACP server over stdin and stdout so editors can launch it directly as an
+//! agent process. Stdout carries JSON-RPC messages only; logs go to stderr or a log file.
+
+use anyhow::{Context, Result};
+use clap::Parser;
+use claude_agent_lib::{
+    config::{AgentConfig, McpServerConfig},
+    config_watcher::ConfigSource,
+    error::AgentError,
+    server::ClaudeAgentServer,
+};
+use serde::de::DeserializeOwned;
+use std::fs::OpenOptions;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+use tracing_subscriber::{layer::SubscriberExt, reload, util::SubscriberInitExt, EnvFilter};
+
+/// How often the configuration and policy files are checked for changes
+const WATCH_INTERVAL: Duration = Duration::from_secs(2);
+
+/// ACP server for Claude Code, speaking JSON-RPC over stdin and stdout
+#[derive(Debug, Parser)]
+#[command(name = "claude-agent", version, about)]
+struct Args {
+    /// Agent configuration file (JSON)
+    #[arg(short, long, value_name = "FILE")]
+    config: Option<PathBuf>,
+
+    /// Log level: error, warn, info, debug or trace (overrides RUST_LOG and the config file)
+    #[arg(long, value_name = "LEVEL")]
+    log_level: Option<String>,
+
+    /// Append logs to this file instead of writing them to stderr
+    #[arg(long, value_name = "FILE")]
+    log_file: Option<PathBuf>,
+
+    /// MCP server definition as JSON, may be repeated
+    #[arg(long = "mcp-server", value_name = "JSON")]
+    mcp_servers: Vec<String>,
+
+    /// File with a JSON array of MCP server definitions
+    #[arg(long, value_name = "FILE")]
+    mcp_config: Option<PathBuf>,
+
+    /// File with a JSON array of permission policies replacing the built-in defaults
+    #[arg(long, value_name = "FILE")]
+    permission_policy: Option<PathBuf>,
+
+    /// Global TOML or JSON policy file, used instead of ~/.claude-agent/policy.*
+    #[arg(long, value_name = "FILE")]
+    policy_file: Option<PathBuf>,
+
+    /// Append an audit record of every permission decision and tool call to this file
+    #[arg(long, value_name = "FILE")]
+    audit_log: Option<PathBuf>,
+
+    /// Do not reload the configuration and policy files when they change
+    #[arg(long)]
+    no_watch: bool,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = Args::parse();
+    let config = load_config(&args)?;
+    let log_filter = init_logging(&args, &config)?;
+
+    tracing::info!("Starting claude-agent {}", env!("CARGO_PKG_VERSION"));
+    let server = ClaudeAgentServer::new(config)
+        .await
+        .context("Failed to create the agent")?;
+
+    tokio::select! {
+        result = server.start_with_streams(tokio::io::stdin(), tokio::io::stdout()) => {
+            result.context("ACP server stopped with an error")?;
+        }
+        _ = server.agent().watch_config(&args, WATCH_INTERVAL, |config, reload| {
+            if reload.applied.iter().any(|field| field == "server.log_level") {
+                log_filter.set(&config.server.log_level);
+            }
+        }), if !args.no_watch => {}
+    }
+
+    tracing::info!("Client disconnected, shutting down");
+    Ok(())
+}
+
+/// Build the agent configuration from the config file and command-line overrides
+fn load_config(args: &Args) -> Result<AgentConfig> {
+    let mut config: AgentConfig = match &args.config {
+        Some(path) => read_json(path)?,
+        None => AgentConfig::default(),
+    };
+
+    if let Some(level) = &args.log_level {
+        config.server.log_level = level.clone();
+    }
+
+    if let Some(path) = &args.mcp_config {
+        let servers: Vec<McpServerConfig> = read_json(path)?;
+        config.mcp_servers.extend(servers);
+    }
+    for definition in &args.mcp_servers {
+        let server: McpServerConfig = serde_json::from_str(definition)
+            .with_context(|| format!("Invalid MCP server definition: {}", definition))?;
+        config.mcp_servers.push(server);
+    }
+
+    if let Some(path) = &args.permission_policy {
+        config.permission_policies = Some(read_json(path)?);
+    }
+    if let Some(path) = &args.policy_file {
+        config.policy_file = Some(path.clone());
+    }
+    if let Some(path) = &args.audit_log {
+        config.audit.path = Some(path.clone());
+    }
+
+    config.validate().context("Invalid configuration")?;
+    Ok(config)
+}
+
+/// The configuration files named on the command line, reloaded by the agent
+impl ConfigSource for Args {
+    fn load(&self) -> claude_agent_lib::Result<AgentConfig> {
+        load_config(self).map_err(|e| AgentError::Config(format!("{:#}", e)))
+    }
+
+    fn paths(&self) -> Vec<PathBuf> {
+        [&self.config, &self.mcp_config, &self.permission_policy]
+            .into_iter()
+            .flatten()
+            .cloned()
+            .collect()
+    }
+}
+
+/// Read and parse a JSON file
+fn read_json<T: DeserializeOwned>(path: &Path) -> Result<T> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    serde_json::from_str(&contents).with_context(|| format!("Failed to parse {}", path.display()))
+}
+
+/// Log filter that follows `server.log_level` unless RUST_LOG chose it
+struct LogFilter {
+    handle: reload::Handle<EnvFilter, tracing_subscriber::Registry>,
+    follows_config: bool,
+}
+
+impl LogFilter {
+    /// Switch to a new log level from a reloaded configuration
+    fn set(&self, level: &str) {
+        if !self.follows_config {
+            return;
+        }
+        match self.handle.reload(EnvFilter::new(level)) {
+            Ok(()) => tracing::info!("Log level set to {}", level),
+            Err(e) => tracing::warn!("Failed to change the log level: {}", e),
+        }
+    }
+}
+
+/// Send logs to stderr or the log file, never to stdout
+fn init_logging(args: &Args, config: &AgentConfig) -> Result<LogFilter> {
+    let (filter, follows_config) = match (&args.log_level, EnvFilter::try_from_default_env()) {
+        (None, Ok(filter)) => (filter, false),
+        _ => (EnvFilter::new(&config.server.log_level), true),
+    };
+    let (filter, handle) = reload::Layer::new(filter);
+    let registry = tracing_subscriber::registry().with(filter);
+
+    match &args.log_file {
+        Some(path) => {
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .with_context(|| format!("Failed to open log file {}", path.display()))?;
+            registry
+                .with(
+                    tracing_subscriber::fmt::layer()
+                        .with_ansi(false)
+                        .with_writer(Mutex::new(file)),
+                )
+                .init();
+        }
+        None => registry
+            .with(tracing_subscriber::fmt::layer().with_writer(std::io::stderr))
+            .init(),
+    }
+
+    Ok(LogFilter {
+        handle,
+        follows_config,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use claude_agent_lib::config_watcher::watched_paths;
+
+    fn parse(args: &[&str]) -> Args {
+        Args::try_parse_from(std::iter::once("claude-agent").chain(args.iter().copied())).unwrap()
+    }
+
+    #[test]
+    fn test_defaults_without_flags() {
+        let config = load_config(&parse(&[])).unwrap();
+        assert_eq!(config.server.log_level, "info");
+        assert!(config.mcp_servers.is_empty());
+        assert!(config.permission_policies.is_none());
+    }
+
+    #[test]
+    fn test_flags_override_config() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let policy_path = temp_dir.path().join("policy.json");
+        std::fs::write(
+            &policy_path,
+            r#"[{"tool_pattern":"*","default_action":"Deny","require_user_consent":false,"allow_always_option":false,"risk_level":"High"}]"#,
+        )
+        .unwrap();
+        let mcp_path = temp_dir.path().join("mcp.json");
+        std::fs::write(
+            &mcp_path,
+            r#"[{"name":"files","command":"mcp-files","args":[]}]"#,
+        )
+        .unwrap();
+
+        let config = load_config(&parse(&[
+            "--log-level",
+            "debug",
+            "--mcp-config",
+            mcp_path.to_str().unwrap(),
+            "--mcp-server",
+            r#"{"name":"git","command":"mcp-git","args":["--repo","."]}"#,
+            "--permission-policy",
+            policy_path.to_str().unwrap(),
+            "--policy-file",
+            "/etc/claude-agent/policy.toml",
+            "--audit-log",
+            "/var/log/claude-agent/audit.jsonl",
+        ]))
+        .unwrap();
+
+        assert_eq!(config.server.log_level, "debug");
+        let names: Vec<_> = config.mcp_servers.iter().map(|s| s.name()).collect();
+        assert_eq!(names, ["files", "git"]);
+        assert_eq!(config.permission_policies.unwrap().len(), 1);
+        assert_eq!(
+            config.policy_file,
+            Some(PathBuf::from("/etc/claude-agent/policy.toml"))
+        );
+        assert_eq!(
+            config.audit.path,
+            Some(PathBuf::from("/var/log/claude-agent/audit.jsonl"))
+        );
+    }
+
+    #[test]
+    fn test_watched_paths_cover_config_and_policy_files() {
+        let args = parse(&[
+            "--config",
+            "/etc/claude-agent/config.json",
+            "--policy-file",
+            "/etc/claude-agent/policy.toml",
+            "--no-watch",
+        ]);
+        assert!(args.no_watch);
+
+        let config = AgentConfig {
+            policy_file: args.policy_file.clone(),
+            ..Default::default()
+        };
+        let project_dir = std::env::current_dir().unwrap();
+        let paths = watched_paths(&args, &config, &[project_dir]);
+        assert_eq!(paths[0], PathBuf::from("/etc/claude-agent/config.json"));
+        assert_eq!(paths[1], PathBuf::from("/etc/claude-agent/policy.toml"));
+        assert!(paths[2..]
+            .iter()
+            .all(|path| path.ends_with(".claude-agent/policy.toml")
+                || path.ends_with(".claude-agent/policy.json")));
+    }
+
+    #[test]
+    fn test_invalid_input_is_rejected() {
+        assert!(load_config(&parse(&["--log-level", "verbose"])).is_err());
+        assert!(load_config(&parse(&["--mcp-server", "not json"])).is_err());
+        assert!(load_config(&parse(&["--config", "/nonexistent/config.json"])).is_err());
+    }
+}
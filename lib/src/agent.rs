@@ -7,7 +7,8 @@ use crate::{
     constants::sizes,
     content_block_processor::ContentBlockProcessor,
     content_capability_validator::ContentCapabilityValidator,
-    permissions::{FilePermissionStorage, PermissionPolicyEngine, PolicyEvaluation},
+    permission_flow::PermissionFlow,
+    permissions::{MemoryPermissionStorage, PermissionPolicyEngine},
     plan::{PlanGenerator, PlanManager},
     session::SessionManager,
     tools::ToolCallHandler,
@@ -16,17 +17,14 @@ use crate::{
 use agent_client_protocol::SessionModeId;
 use agent_client_protocol::{
     Agent, AgentCapabilities, AuthenticateRequest, AuthenticateResponse, CancelNotification,
-    ContentBlock, ExtNotification, ExtRequest, InitializeRequest, InitializeResponse,
+    Client, ContentBlock, ExtNotification, ExtRequest, InitializeRequest, InitializeResponse,
     LoadSessionRequest, LoadSessionResponse, NewSessionRequest, NewSessionResponse, PromptRequest,
     PromptResponse, RawValue, SessionId, SessionNotification, SessionUpdate, SetSessionModeRequest,
     SetSessionModeResponse, StopReason, TextContent,
 };
 use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
-use std::time::{Duration, SystemTime};
-
-/// Default timeout for user permission prompts in seconds
-const PERMISSION_PROMPT_TIMEOUT_SECS: u64 = 60;
+use std::time::SystemTime;
 
 /// ACP tool call information for permission requests
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -302,6 +300,65 @@ impl CancellationManager {
     }
 }
 
+/// Subscribed MCP resources linked in the prompts of each session
+///
+/// When a server reports that one of them changed, its contents are embedded again in
+/// the next prompt of every session that linked it.
+#[derive(Debug, Default)]
+struct LinkedResources {
+    /// Sessions that linked each subscribed URI
+    sessions: HashMap<String, HashSet<String>>,
+    /// URIs that changed since each session's last prompt
+    updated: HashMap<String, HashSet<String>>,
+}
+
+impl LinkedResources {
+    /// Record that a session linked `uri`; returns whether `uri` was not linked before
+    fn link(&mut self, session_id: &str, uri: &str) -> bool {
+        let first_link = !self.sessions.contains_key(uri);
+        self.sessions
+            .entry(uri.to_string())
+            .or_default()
+            .insert(session_id.to_string());
+        first_link
+    }
+
+    /// Stop tracking `uri`, whose subscription failed
+    fn unlink(&mut self, uri: &str) {
+        self.sessions.remove(uri);
+    }
+
+    /// Mark `uri` as changed for every session that linked it
+    fn resource_updated(&mut self, uri: &str) {
+        for session_id in self.sessions.get(uri).into_iter().flatten() {
+            self.updated
+                .entry(session_id.clone())
+                .or_default()
+                .insert(uri.to_string());
+        }
+    }
+
+    /// Mark every linked resource as changed, after updates were missed
+    fn all_updated(&mut self) {
+        let uris: Vec<String> = self.sessions.keys().cloned().collect();
+        for uri in uris {
+            self.resource_updated(&uri);
+        }
+    }
+
+    /// URIs that changed since the session's last prompt, sorted
+    fn take_updated(&mut self, session_id: &str) -> Vec<String> {
+        let mut uris: Vec<String> = self
+            .updated
+            .remove(session_id)
+            .unwrap_or_default()
+            .into_iter()
+            .collect();
+        uris.sort_unstable();
+        uris
+    }
+}
+
 /// Notification sender for streaming updates
 ///
 /// Manages the broadcasting of session update notifications to multiple receivers.
@@ -373,28 +430,35 @@ pub struct ClaudeAgent {
     claude_client: Arc<ClaudeClient>,
     tool_handler: Arc<RwLock<ToolCallHandler>>,
     mcp_manager: Option<Arc<crate::mcp::McpServerManager>>,
-    config: AgentConfig,
+    /// Configuration, replaced by `reload_config` when the config files change
+    config: std::sync::RwLock<Arc<AgentConfig>>,
     capabilities: AgentCapabilities,
     client_capabilities: Arc<RwLock<Option<agent_client_protocol::ClientCapabilities>>>,
     notification_sender: Arc<NotificationSender>,
+    /// Requests from the agent to the client, written by the server on the same connection
+    client: Arc<crate::client_connection::ClientConnection>,
     cancellation_manager: Arc<CancellationManager>,
-    permission_engine: Arc<PermissionPolicyEngine>,
     plan_generator: Arc<PlanGenerator>,
     plan_manager: Arc<RwLock<PlanManager>>,
     base64_processor: Arc<Base64Processor>,
     content_block_processor: Arc<ContentBlockProcessor>,
     editor_state_manager: Arc<crate::editor_state::EditorStateManager>,
-    /// Handler for interactive user prompts during tool execution
-    ///
-    /// Used to prompt the user for permission decisions when tool calls require user approval.
-    /// Supports both console-based prompts and mock implementations for testing.
-    user_prompt_handler: Arc<dyn crate::user_prompt::UserPromptHandler>,
-    /// Storage for user permission preferences
+    /// Permission decision flow for tool calls
     ///
-    /// Stores "always" decisions (allow-always, reject-always) across tool calls
-    /// to avoid re-prompting the user for the same tool. Preferences are stored
-    /// in-memory and do not persist across agent restarts.
-    permission_storage: Arc<crate::permission_storage::PermissionStorage>,
+    /// Combines the policy engine, stored "always" preferences and the user prompt
+    /// handler. It answers `session/request_permission` and is registered with the
+    /// Claude client to gate the tools the claude CLI runs.
+    permission_flow: Arc<PermissionFlow>,
+    /// Spending budgets checked before and after each turn
+    budget: Arc<crate::budget::BudgetTracker>,
+    /// Policy engine shared with the tool handler and the permission flow
+    permission_engine: Arc<PermissionPolicyEngine>,
+    /// Audit trail of permission decisions and tool executions
+    audit_log: Arc<crate::audit::AuditLog>,
+    /// Directory searched for the global policy file when none is configured
+    policy_home: Option<std::path::PathBuf>,
+    /// MCP resources whose links were expanded, kept up to date by subscriptions
+    linked_resources: Arc<std::sync::Mutex<LinkedResources>>,
 }
 
 impl ClaudeAgent {
@@ -422,131 +486,57 @@ impl ClaudeAgent {
     pub async fn new(
         config: AgentConfig,
     ) -> crate::Result<(Self, broadcast::Receiver<SessionNotification>)> {
-        // Validate configuration including MCP servers
-        config.validate()?;
-
-        let session_manager = Arc::new(SessionManager::new());
-        let claude_client = Arc::new(ClaudeClient::new_with_config(&config.claude)?);
-
-        let (notification_sender, notification_receiver) =
-            NotificationSender::new(config.notification_buffer_size);
-
-        // Create permission policy engine with file-based storage
-        let storage_path = std::env::current_dir()
-            .unwrap_or_else(|_| std::path::PathBuf::from("."))
-            .join(".claude-agent")
-            .join("permissions");
-        let storage = FilePermissionStorage::new(storage_path);
-        let permission_engine = Arc::new(PermissionPolicyEngine::new(Box::new(storage)));
-
-        // Create and initialize MCP manager
-        let mut mcp_manager = crate::mcp::McpServerManager::new();
-        mcp_manager
-            .connect_servers(config.mcp_servers.clone())
-            .await?;
-        let mcp_manager = Arc::new(mcp_manager);
-
-        // Create tool handler with MCP support
-        let tool_handler = Arc::new(RwLock::new(ToolCallHandler::new_with_mcp_manager(
-            config.security.to_tool_permissions(),
-            Arc::clone(&mcp_manager),
-            Arc::clone(&session_manager),
-            Arc::clone(&permission_engine),
-        )));
-
-        // Get all available tools for capabilities
-        let available_tools = {
-            let handler = tool_handler.read().await;
-            handler.list_all_available_tools().await
-        };
-
-        let capabilities = AgentCapabilities {
-            load_session: true,
-            prompt_capabilities: agent_client_protocol::PromptCapabilities {
-                audio: true,
-                embedded_context: true,
-                image: true,
-                meta: Some(serde_json::json!({"streaming": true})),
-            },
-            // We only support HTTP MCP connections, not SSE (which is deprecated in MCP spec).
-            // This is an architectural decision for simplicity and modern standards.
-            mcp_capabilities: agent_client_protocol::McpCapabilities {
-                http: true,
-                sse: false,
-                meta: None,
-            },
-            meta: Some(serde_json::json!({
-                "tools": available_tools,
-                "streaming": true
-            })),
-        };
-
-        // Create cancellation manager for session cancellation support
-        let (cancellation_manager, _cancellation_receiver) =
-            CancellationManager::new(config.cancellation_buffer_size);
-
-        // Initialize plan generation system for ACP plan reporting
-        let plan_generator = Arc::new(PlanGenerator::new());
-        let plan_manager = Arc::new(RwLock::new(PlanManager::new()));
-
-        // Initialize base64 processor with default size limits
-        let base64_processor = Arc::new(Base64Processor::default());
-
-        // Initialize content block processor with base64 processor
-        let content_block_processor = Arc::new(ContentBlockProcessor::new(
-            (*base64_processor).clone(),
-            sizes::content::MAX_RESOURCE_MODERATE,
-            true,
-        ));
-
-        // Initialize editor state manager for ACP editor integration
-        let editor_state_manager = Arc::new(crate::editor_state::EditorStateManager::new());
-
-        let agent = Self {
-            session_manager,
-            claude_client,
-            tool_handler,
-            mcp_manager: Some(mcp_manager),
-            config,
-            capabilities,
-            client_capabilities: Arc::new(RwLock::new(None)),
-            notification_sender: Arc::new(notification_sender),
-            cancellation_manager: Arc::new(cancellation_manager),
-            permission_engine,
-            plan_generator,
-            plan_manager,
-            base64_processor,
-            content_block_processor,
-            editor_state_manager,
-            user_prompt_handler: Arc::new(crate::user_prompt::ConsolePromptHandler::new()),
-            permission_storage: Arc::new(crate::permission_storage::PermissionStorage::new()),
-        };
-
-        Ok((agent, notification_receiver))
+        Self::build(config, None, crate::permission_storage::home_dir()).await
     }
 
     /// Create a new Claude Agent with a custom user prompt handler (for testing)
+    ///
+    /// No global policy file is read unless the configuration names one.
     #[cfg(test)]
     pub async fn new_with_prompt_handler(
         config: AgentConfig,
         user_prompt_handler: Arc<dyn crate::user_prompt::UserPromptHandler>,
+    ) -> crate::Result<(Self, broadcast::Receiver<SessionNotification>)> {
+        Self::build(config, Some(user_prompt_handler), None).await
+    }
+
+    /// Create a new Claude Agent looking for the global policy file in `policy_home` (for testing)
+    #[cfg(test)]
+    pub async fn new_with_policy_home(
+        config: AgentConfig,
+        policy_home: Option<std::path::PathBuf>,
+    ) -> crate::Result<(Self, broadcast::Receiver<SessionNotification>)> {
+        Self::build(config, None, policy_home).await
+    }
+
+    /// Build the agent and its components
+    ///
+    /// Without a `user_prompt_handler` the user is asked for permissions through the
+    /// client with `session/request_permission`. Unless the configuration names a
+    /// global policy file, it is looked for in `policy_home`.
+    async fn build(
+        config: AgentConfig,
+        user_prompt_handler: Option<Arc<dyn crate::user_prompt::UserPromptHandler>>,
+        policy_home: Option<std::path::PathBuf>,
     ) -> crate::Result<(Self, broadcast::Receiver<SessionNotification>)> {
         // Validate configuration including MCP servers
         config.validate()?;
 
-        let session_manager = Arc::new(SessionManager::new());
+        let session_manager = Arc::new(SessionManager::with_store(config.create_session_store()));
         let claude_client = Arc::new(ClaudeClient::new_with_config(&config.claude)?);
 
         let (notification_sender, notification_receiver) =
             NotificationSender::new(config.notification_buffer_size);
 
-        // Create permission policy engine with file-based storage
-        let storage_path = std::env::current_dir()
-            .unwrap_or_else(|_| std::path::PathBuf::from("."))
-            .join(".claude-agent")
-            .join("permissions");
-        let storage = FilePermissionStorage::new(storage_path);
-        let permission_engine = Arc::new(PermissionPolicyEngine::new(Box::new(storage)));
+        // Remembered "always" answers are scoped per project and per user and are
+        // consulted by the permission flow; the engine only evaluates policies, with
+        // the repository policy file of each session's working directory
+        let storage = MemoryPermissionStorage::new();
+        let policies = crate::policy_file::PolicySet::load(&config, policy_home.as_deref())?;
+        let permission_engine = Arc::new(PermissionPolicyEngine::with_policy_set(
+            Box::new(storage),
+            policies,
+        ));
 
         // Create and initialize MCP manager
         let mut mcp_manager = crate::mcp::McpServerManager::new();
@@ -555,13 +545,19 @@ impl ClaudeAgent {
             .await?;
         let mcp_manager = Arc::new(mcp_manager);
 
-        // Create tool handler with MCP support
-        let tool_handler = Arc::new(RwLock::new(ToolCallHandler::new_with_mcp_manager(
-            config.security.to_tool_permissions(),
-            Arc::clone(&mcp_manager),
-            Arc::clone(&session_manager),
-            Arc::clone(&permission_engine),
-        )));
+        // Create tool handler with MCP support and the configured terminal shell
+        let terminal_manager = Arc::new(crate::terminal_manager::TerminalManager::with_config(
+            config.terminal.clone(),
+        ));
+        let tool_handler = Arc::new(RwLock::new(
+            ToolCallHandler::new_with_terminal_and_mcp_manager(
+                config.security.to_tool_permissions(),
+                terminal_manager,
+                Arc::clone(&mcp_manager),
+                Arc::clone(&session_manager),
+                Arc::clone(&permission_engine),
+            ),
+        ));
 
         // Get all available tools for capabilities
         let available_tools = {
@@ -577,6 +573,8 @@ impl ClaudeAgent {
                 image: true,
                 meta: Some(serde_json::json!({"streaming": true})),
             },
+            // We only support HTTP MCP connections, not SSE (which is deprecated in MCP spec).
+            // This is an architectural decision for simplicity and modern standards.
             mcp_capabilities: agent_client_protocol::McpCapabilities {
                 http: true,
                 sse: false,
@@ -609,24 +607,67 @@ impl ClaudeAgent {
         // Initialize editor state manager for ACP editor integration
         let editor_state_manager = Arc::new(crate::editor_state::EditorStateManager::new());
 
+        let cancellation_manager = Arc::new(cancellation_manager);
+
+        let client = Arc::new(crate::client_connection::ClientConnection::new(
+            std::time::Duration::from_secs(config.server.client_request_timeout_secs),
+        ));
+
+        let audit_log = Arc::new(crate::audit::AuditLog::new(&config.audit));
+        {
+            let mut tool_handler = tool_handler.write().await;
+            tool_handler.set_audit_log(Arc::clone(&audit_log));
+            tool_handler.set_client_connection(Arc::clone(&client));
+        }
+
+        // Route tool permission checks from the claude CLI through the same
+        // policy and user prompt flow as ACP permission requests
+        let permission_flow = Arc::new(PermissionFlow::new(
+            Arc::clone(&permission_engine),
+            Arc::new(crate::permission_storage::PermissionStorage::from_config(
+                &config.permission_store,
+            )),
+            user_prompt_handler.unwrap_or_else(|| {
+                Arc::new(crate::user_prompt::ClientPromptHandler::new(
+                    Arc::clone(&client),
+                    Arc::clone(&cancellation_manager),
+                ))
+            }),
+            Arc::clone(&tool_handler),
+            Arc::clone(&cancellation_manager),
+            Arc::clone(&session_manager),
+            config.security.require_permission_for.clone(),
+            Arc::clone(&audit_log),
+            std::time::Duration::from_secs(config.server.client_request_timeout_secs),
+        ));
+        claude_client.set_permission_handler(permission_flow.clone());
+        claude_client.set_audit_log(Arc::clone(&audit_log));
+        let budget = Arc::new(crate::budget::BudgetTracker::new(config.budget.clone()));
+        let linked_resources = Arc::new(std::sync::Mutex::new(LinkedResources::default()));
+        Self::track_resource_updates(&mcp_manager, &linked_resources);
+
         let agent = Self {
             session_manager,
             claude_client,
             tool_handler,
             mcp_manager: Some(mcp_manager),
-            config,
+            config: std::sync::RwLock::new(Arc::new(config)),
             capabilities,
             client_capabilities: Arc::new(RwLock::new(None)),
             notification_sender: Arc::new(notification_sender),
-            cancellation_manager: Arc::new(cancellation_manager),
-            permission_engine,
+            client,
+            cancellation_manager,
             plan_generator,
             plan_manager,
             base64_processor,
             content_block_processor,
             editor_state_manager,
-            user_prompt_handler,
-            permission_storage: Arc::new(crate::permission_storage::PermissionStorage::new()),
+            permission_flow,
+            budget,
+            permission_engine,
+            audit_log,
+            policy_home,
+            linked_resources,
         };
 
         Ok((agent, notification_receiver))
@@ -644,6 +685,206 @@ impl ClaudeAgent {
         Ok(())
     }
 
+    /// Note the resources MCP servers report as updated until the agent is dropped
+    fn track_resource_updates(
+        mcp_manager: &crate::mcp::McpServerManager,
+        linked_resources: &Arc<std::sync::Mutex<LinkedResources>>,
+    ) {
+        let mut updates = mcp_manager.subscribe_resource_updates();
+        let linked_resources = Arc::downgrade(linked_resources);
+        tokio::spawn(async move {
+            loop {
+                let update = updates.recv().await;
+                let Some(linked_resources) = linked_resources.upgrade() else {
+                    break;
+                };
+                let mut linked_resources = linked_resources
+                    .lock()
+                    .unwrap_or_else(|poisoned| poisoned.into_inner());
+                match update {
+                    Ok(uri) => linked_resources.resource_updated(&uri),
+                    Err(broadcast::error::RecvError::Lagged(missed)) => {
+                        tracing::warn!("Missed {} MCP resource updates", missed);
+                        linked_resources.all_updated();
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+    }
+
+    /// Watch for MCP servers announcing that their tools or prompts changed
+    ///
+    /// The server calls `refresh_available_commands` whenever the receiver is notified.
+    /// Without an MCP manager the receiver never fires.
+    pub fn mcp_command_changes(&self) -> tokio::sync::watch::Receiver<()> {
+        match &self.mcp_manager {
+            Some(mcp_manager) => mcp_manager.subscribe_command_changes(),
+            None => tokio::sync::watch::channel(()).1,
+        }
+    }
+
+    /// Send an `available_commands_update` to every active session whose commands changed
+    pub async fn refresh_available_commands(&self) -> crate::Result<()> {
+        for session_id in self.session_manager.active_sessions()? {
+            let session_id = SessionId(session_id.to_string().into());
+            let commands = self.get_available_commands_for_session(&session_id).await;
+            self.update_session_available_commands(&session_id, commands)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Current configuration, including changes applied by `reload_config`
+    ///
+    /// The snapshot is shared, so reading it is cheap; a later reload replaces it rather
+    /// than changing it.
+    pub fn config(&self) -> Arc<AgentConfig> {
+        Arc::clone(
+            &self
+                .config
+                .read()
+                .unwrap_or_else(|poisoned| poisoned.into_inner()),
+        )
+    }
+
+    /// Apply a new configuration without dropping active sessions
+    ///
+    /// Limits, budgets, permission policies and MCP server definitions take effect
+    /// immediately. When the MCP tools change, every active session receives an
+    /// `available_commands_update`. Other changed fields are kept at their old value
+    /// and reported as requiring a restart.
+    ///
+    /// Nothing is applied until the new configuration and its policy files are valid
+    /// and the MCP servers are in line with it; the new values then replace the old
+    /// ones together.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error, leaving the current configuration in place, if the new
+    /// configuration or a policy file is invalid or the MCP servers cannot be updated.
+    pub async fn reload_config(
+        &self,
+        new_config: AgentConfig,
+    ) -> crate::Result<crate::config_watcher::ConfigReload> {
+        use crate::config_watcher::{changed_fields, ConfigReload, LIVE_FIELDS};
+
+        new_config.validate()?;
+        let policies =
+            crate::policy_file::PolicySet::load(&new_config, self.policy_home.as_deref())?;
+
+        let old_config = self.config();
+        let (applied, restart_required): (Vec<String>, Vec<String>) =
+            changed_fields(&old_config, &new_config)
+                .into_iter()
+                .partition(|field| LIVE_FIELDS.contains(&field.as_str()));
+
+        // Live fields take the new value, all others keep the running value
+        let mut config = AgentConfig::clone(&old_config);
+        config.max_prompt_length = new_config.max_prompt_length;
+        config.max_tokens_per_turn = new_config.max_tokens_per_turn;
+        config.max_turn_requests = new_config.max_turn_requests;
+        config.budget = new_config.budget.clone();
+        config.permission_policies = new_config.permission_policies.clone();
+        config.policy_file = new_config.policy_file.clone();
+        config.trusted_projects = new_config.trusted_projects.clone();
+        config.mcp_servers = new_config.mcp_servers.clone();
+        config.server.log_level = new_config.server.log_level.clone();
+
+        let tools_changed = match &self.mcp_manager {
+            Some(mcp_manager) => mcp_manager.sync_servers(new_config.mcp_servers).await?,
+            None => false,
+        };
+
+        self.permission_engine.set_policy_set(policies);
+        self.budget.set_config(new_config.budget);
+        *self
+            .config
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = Arc::new(config);
+
+        // The reload is in effect; a client missing the new commands is not a reason to
+        // report it as failed
+        if tools_changed {
+            if let Err(e) = self.refresh_available_commands().await {
+                tracing::warn!("Failed to send the updated commands to sessions: {}", e);
+            }
+        }
+
+        for field in &restart_required {
+            tracing::warn!(
+                "Configuration field '{}' changed; restart the agent to apply it",
+                field
+            );
+        }
+        tracing::info!("Configuration reloaded, applied: {:?}", applied);
+
+        Ok(ConfigReload {
+            applied,
+            restart_required,
+            tools_changed,
+        })
+    }
+
+    /// Reload the configuration from `source` whenever one of its files changes
+    ///
+    /// The files of `source` and the policy files the configuration uses, in the current
+    /// directory and in the working directory of every open session, are checked every
+    /// `interval`. Each successful reload is passed to `on_reload` with the new
+    /// configuration, so the caller can apply fields the agent does not own, such as
+    /// `server.log_level`. Invalid changes are logged and ignored; the agent keeps its
+    /// current configuration. Runs until the future is dropped.
+    pub async fn watch_config(
+        &self,
+        source: &dyn crate::config_watcher::ConfigSource,
+        interval: std::time::Duration,
+        mut on_reload: impl FnMut(&AgentConfig, &crate::config_watcher::ConfigReload),
+    ) {
+        use crate::config_watcher::{watched_paths, ConfigWatcher};
+
+        let mut config = AgentConfig::clone(&self.config());
+        let mut watcher = ConfigWatcher::new(
+            watched_paths(source, &config, &self.policy_project_dirs()),
+            interval,
+        );
+        loop {
+            watcher
+                .changed_in(|| watched_paths(source, &config, &self.policy_project_dirs()))
+                .await;
+            tracing::info!("Configuration files changed, reloading");
+
+            let new_config = match source.load() {
+                Ok(new_config) => new_config,
+                Err(e) => {
+                    tracing::error!("Ignoring configuration change: {}", e);
+                    continue;
+                }
+            };
+            match self.reload_config(new_config.clone()).await {
+                Ok(reload) => {
+                    on_reload(&new_config, &reload);
+                    config = new_config;
+                }
+                Err(e) => tracing::error!("Ignoring configuration change: {}", e),
+            }
+        }
+    }
+
+    /// Directories whose repository policy files sessions may use
+    ///
+    /// The current directory and the working directory of every session in memory.
+    fn policy_project_dirs(&self) -> Vec<std::path::PathBuf> {
+        let mut dirs: Vec<std::path::PathBuf> = std::env::current_dir().into_iter().collect();
+        for session_id in self.session_manager.active_sessions().unwrap_or_default() {
+            if let Ok(Some(session)) = self.session_manager.get_session(&session_id) {
+                if !dirs.contains(&session.cwd) {
+                    dirs.push(session.cwd);
+                }
+            }
+        }
+        dirs
+    }
+
     /// Log incoming request for debugging purposes
     fn log_request<T: std::fmt::Debug>(&self, method: &str, request: &T) {
         tracing::debug!("Handling {} request: {:?}", method, request);
@@ -654,6 +895,14 @@ impl ClaudeAgent {
         tracing::debug!("Returning {} response: {:?}", method, response);
     }
 
+    /// Connection for calling methods the client implements
+    ///
+    /// Requests only reach a client while a [`crate::server::ClaudeAgentServer`] is
+    /// serving this agent.
+    pub fn client_connection(&self) -> &Arc<crate::client_connection::ClientConnection> {
+        &self.client
+    }
+
     /// Get the tool handler for processing tool calls
     ///
     /// Returns a reference to the tool call handler that manages the execution
@@ -1214,52 +1463,437 @@ impl ClaudeAgent {
         }
 
         // Check if text portion is too long (configurable limit)
-        if prompt_text.len() > self.config.max_prompt_length {
+        if prompt_text.len() > self.config().max_prompt_length {
             return Err(agent_client_protocol::Error::invalid_params());
         }
 
         Ok(())
     }
 
-    /// Check if streaming is supported for this session
-    fn should_stream(&self, session: &crate::session::Session, _request: &PromptRequest) -> bool {
-        // Check if client supports streaming
-        session
-            .client_capabilities
-            .as_ref()
-            .and_then(|caps| caps.meta.as_ref())
-            .and_then(|meta| meta.get("streaming"))
-            .and_then(|v| v.as_bool())
-            .unwrap_or(false)
+    /// Convert a Claude client error to an ACP error
+    ///
+    /// Process failures, including crashes of the claude CLI, keep their code, message and
+    /// structured data so the client can show why the turn failed. Anything else is reported
+    /// as an internal error.
+    fn claude_error_to_acp(error: &crate::AgentError) -> agent_client_protocol::Error {
+        use crate::error::ToJsonRpcError;
+
+        match error {
+            crate::AgentError::ProcessCrashed { .. } | crate::AgentError::Process(_) => {
+                agent_client_protocol::Error {
+                    code: error.to_json_rpc_code(),
+                    message: error.to_string(),
+                    data: error.to_error_data(),
+                }
+            }
+            _ => agent_client_protocol::Error::internal_error(),
+        }
     }
 
-    /// Handle streaming prompt request
-    async fn handle_streaming_prompt(
+    /// Expand MCP prompt commands and MCP resource links in a prompt
+    ///
+    /// A prompt starting with `/server:prompt arguments`, where `server:prompt` is an MCP
+    /// prompt, has that text block replaced by the content of the messages the server
+    /// renders for it. Resource links to resources listed by a connected MCP server are
+    /// replaced by the resource's contents, so they reach the content processing as
+    /// embedded resources. A link that cannot be read is kept as it is.
+    ///
+    /// Expanded resources are subscribed to when their server supports it. Resources
+    /// that changed since the session's previous prompt are embedded again after the
+    /// prompt's own content.
+    async fn expand_mcp_content(
         &self,
-        session_id: &crate::session::SessionId,
-        request: &PromptRequest,
-        session: &crate::session::Session,
-    ) -> Result<PromptResponse, agent_client_protocol::Error> {
-        tracing::info!("Handling streaming prompt for session: {}", session_id);
+        mut request: PromptRequest,
+    ) -> Result<PromptRequest, agent_client_protocol::Error> {
+        let Some(mcp_manager) = &self.mcp_manager else {
+            return Ok(request);
+        };
+        let session_id = request.session_id.0.to_string();
+
+        let command = match request.prompt.first() {
+            Some(ContentBlock::Text(text)) => text
+                .text
+                .trim_start()
+                .strip_prefix('/')
+                .map(|command| {
+                    command
+                        .split_once(char::is_whitespace)
+                        .unwrap_or((command, ""))
+                })
+                .map(|(name, input)| (name.to_string(), input.to_string())),
+            _ => None,
+        };
+        if let Some((name, input)) = command {
+            if let Some(prompt) = mcp_manager.prompt_definition(&name).await {
+                let rendered = Self::render_mcp_prompt(mcp_manager, &name, &prompt, &input)
+                    .await
+                    .map_err(|e| Self::mcp_error_to_acp(&e))?;
+                request.prompt.splice(0..1, rendered);
+            }
+        }
 
-        // Send execution thought
-        let execution_thought = AgentThought::new(
-            ReasoningPhase::Execution,
-            "Executing the planned approach using streaming response generation...",
+        let mut updated = self
+            .linked_resources
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .take_updated(&session_id);
+        let mut prompt = Vec::with_capacity(request.prompt.len());
+        for block in request.prompt {
+            match block {
+                ContentBlock::ResourceLink(link) => {
+                    // Linked again, so the prompt already carries the new contents
+                    updated.retain(|uri| *uri != link.uri);
+                    match Self::read_mcp_resource(mcp_manager, &link.uri, &link.annotations).await {
+                        Some(contents) => {
+                            self.link_mcp_resource(mcp_manager, &session_id, &link.uri)
+                                .await;
+                            prompt.extend(contents);
+                        }
+                        None => prompt.push(ContentBlock::ResourceLink(link)),
+                    }
+                }
+                block => prompt.push(block),
+            }
+        }
+        for uri in updated {
+            tracing::info!("Embedding updated MCP resource {} again", uri);
+            prompt.extend(
+                Self::read_mcp_resource(mcp_manager, &uri, &None)
+                    .await
+                    .unwrap_or_default(),
+            );
+        }
+        request.prompt = prompt;
+        Ok(request)
+    }
+
+    /// Fetch the messages of an MCP prompt as content blocks
+    ///
+    /// When the prompt has messages from the assistant, each message is labelled with
+    /// its role the way conversation history is, so claude can tell the turns apart.
+    /// Content the ACP cannot represent is skipped.
+    async fn render_mcp_prompt(
+        mcp_manager: &crate::mcp::McpServerManager,
+        name: &str,
+        prompt: &crate::mcp::McpPrompt,
+        input: &str,
+    ) -> crate::Result<Vec<ContentBlock>> {
+        let arguments = prompt.arguments_from_text(input)?;
+        let messages = mcp_manager.get_prompt(name, arguments).await?;
+        tracing::info!(
+            "Expanded MCP prompt {} into {} messages",
+            name,
+            messages.len()
         );
-        let _ = self
-            .send_agent_thought(&request.session_id, &execution_thought)
-            .await;
 
-        // Validate content blocks against prompt capabilities before processing
-        let content_validator =
-            ContentCapabilityValidator::new(self.capabilities.prompt_capabilities.clone());
-        if let Err(capability_error) = content_validator.validate_content_blocks(&request.prompt) {
-            tracing::warn!(
-                "Content capability validation failed for session {}: {}",
-                session_id,
-                capability_error
-            );
+        let messages: Vec<(&str, ContentBlock)> = messages
+            .iter()
+            .filter_map(|message| {
+                let role = message.get("role").and_then(|role| role.as_str());
+                let content = message.get("content").cloned().unwrap_or_default();
+                serde_json::from_value(content)
+                    .map_err(|e| {
+                        tracing::warn!("Skipping unsupported content of MCP prompt {}: {}", name, e)
+                    })
+                    .ok()
+                    .map(|content| (role.unwrap_or("user"), content))
+            })
+            .collect();
+        if messages.iter().all(|(role, _)| *role == "user") {
+            return Ok(messages.into_iter().map(|(_, content)| content).collect());
+        }
+
+        let mut blocks = Vec::with_capacity(messages.len());
+        for (role, content) in messages {
+            let label = match role {
+                "assistant" => "Assistant",
+                _ => "User",
+            };
+            match content {
+                ContentBlock::Text(mut text) => {
+                    text.text = format!("{}: {}", label, text.text);
+                    blocks.push(ContentBlock::Text(text));
+                }
+                content => {
+                    blocks.push(ContentBlock::Text(TextContent {
+                        text: format!("{}:", label),
+                        annotations: None,
+                        meta: None,
+                    }));
+                    blocks.push(content);
+                }
+            }
+        }
+        Ok(blocks)
+    }
+
+    /// Read a resource of a connected MCP server as embedded resource blocks
+    ///
+    /// Returns `None` if no server lists the resource or it cannot be read.
+    async fn read_mcp_resource(
+        mcp_manager: &crate::mcp::McpServerManager,
+        uri: &str,
+        annotations: &Option<agent_client_protocol::Annotations>,
+    ) -> Option<Vec<ContentBlock>> {
+        mcp_manager.resource_server(uri).await?;
+        let contents = match mcp_manager.read_resource(uri).await {
+            Ok(contents) => contents,
+            Err(e) => {
+                tracing::warn!("Failed to read MCP resource {}: {}", uri, e);
+                return None;
+            }
+        };
+
+        let resources: Vec<agent_client_protocol::EmbeddedResourceResource> = contents
+            .into_iter()
+            .filter_map(|content| {
+                serde_json::from_value(content)
+                    .map_err(|e| tracing::warn!("Skipping malformed contents of {}: {}", uri, e))
+                    .ok()
+            })
+            .collect();
+        if resources.is_empty() {
+            return None;
+        }
+        Some(
+            resources
+                .into_iter()
+                .map(|resource| {
+                    ContentBlock::Resource(agent_client_protocol::EmbeddedResource {
+                        annotations: annotations.clone(),
+                        resource,
+                        meta: None,
+                    })
+                })
+                .collect(),
+        )
+    }
+
+    /// Remember that a session linked an MCP resource, subscribing to its updates
+    ///
+    /// Resources of servers without subscription support are not tracked.
+    async fn link_mcp_resource(
+        &self,
+        mcp_manager: &crate::mcp::McpServerManager,
+        session_id: &str,
+        uri: &str,
+    ) {
+        // Linked before subscribing, so an update sent right away is not missed
+        let first_link = self
+            .linked_resources
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .link(session_id, uri);
+        if !first_link {
+            return;
+        }
+        if let Err(e) = mcp_manager.subscribe_resource(uri).await {
+            tracing::debug!("Not watching MCP resource {}: {}", uri, e);
+            self.linked_resources
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .unlink(uri);
+        }
+    }
+
+    /// Convert an error of an MCP request made for a prompt to an ACP error
+    fn mcp_error_to_acp(error: &crate::AgentError) -> agent_client_protocol::Error {
+        use crate::error::ToJsonRpcError;
+
+        agent_client_protocol::Error {
+            code: error.to_json_rpc_code(),
+            message: error.to_string(),
+            data: None,
+        }
+    }
+
+    /// Persist claude's own conversation id for a session after a turn
+    ///
+    /// The id is captured by the process manager from stream-json output; storing it on the
+    /// session lets `session/load` resume the same claude conversation after a restart.
+    /// The session is only written when the id changed, not on every turn.
+    fn record_claude_session_id(&self, session_id: &crate::session::SessionId) {
+        let Some(claude_session_id) = self
+            .claude_client
+            .process_manager()
+            .claude_session_id(session_id)
+        else {
+            return;
+        };
+        let recorded = self
+            .session_manager
+            .get_session(session_id)
+            .ok()
+            .flatten()
+            .and_then(|session| session.claude_session_id);
+        if recorded.as_deref() == Some(claude_session_id.as_str()) {
+            return;
+        }
+
+        let result = self.session_manager.update_session(session_id, |session| {
+            session.claude_session_id = Some(claude_session_id);
+        });
+        if let Err(e) = result {
+            tracing::warn!(
+                "Failed to record claude session id for session {}: {}",
+                session_id,
+                e
+            );
+        }
+    }
+
+    /// Record the token usage and cost Claude reported for a completed turn
+    ///
+    /// The real usage feeds the turn token count checked against `max_tokens_per_turn`,
+    /// the session's accumulated usage and the spending budgets. Budget warnings crossed
+    /// by the turn are sent to the client as `_claude_agent/budget_warning` notifications.
+    ///
+    /// # Returns
+    /// The usage report for `PromptResponse.meta` and whether the turn exceeded the
+    /// token limit or used up a budget
+    async fn record_turn_usage(
+        &self,
+        session_id: &crate::session::SessionId,
+        usage: Option<&crate::claude::TokenUsageInfo>,
+        total_cost_usd: Option<f64>,
+    ) -> (serde_json::Value, bool) {
+        let mut recorded = None;
+        let result = self.session_manager.update_session(session_id, |session| {
+            let before = session.usage.clone();
+            let turn_tokens = session.add_turn_tokens(usage.map_or(0, |usage| usage.total()));
+            let turn_cost_usd = session.usage.record_turn(usage, total_cost_usd);
+            recorded = Some((turn_tokens, turn_cost_usd, before, session.usage.clone()));
+        });
+        if let Err(e) = result {
+            tracing::warn!("Failed to record usage for session {}: {}", session_id, e);
+        }
+
+        let Some((turn_tokens, turn_cost_usd, before, session_usage)) = recorded else {
+            return (serde_json::json!({ "turn": usage }), false);
+        };
+        let limit_reached = turn_tokens > self.config().max_tokens_per_turn;
+        if limit_reached {
+            tracing::info!(
+                "Token limit exceeded ({} > {}) for session: {}",
+                turn_tokens,
+                self.config().max_tokens_per_turn,
+                session_id
+            );
+        }
+
+        for warning in self.budget.record_turn(&before, &session_usage) {
+            tracing::warn!(
+                "Budget warning for session {}: {}",
+                session_id,
+                warning.status
+            );
+            let params = serde_json::json!({
+                "sessionId": session_id.to_string(),
+                "warning": warning,
+                "message": format!("Budget warning: {} used", warning.status),
+            });
+            if let Err(e) = self.client.notify("_claude_agent/budget_warning", &params) {
+                tracing::debug!("Budget warning not sent to the client: {}", e.message);
+            }
+        }
+        let budget_exhausted = self.budget.exhausted(&session_usage);
+
+        let report = serde_json::json!({
+            "turn": usage,
+            "turn_tokens": turn_tokens,
+            "turn_cost_usd": turn_cost_usd,
+            "session": {
+                "total_tokens": session_usage.total_tokens(),
+                "total_cost_usd": session_usage.total_cost_usd,
+                "turns": session_usage.turns
+            },
+            "budget_exhausted": budget_exhausted
+        });
+        (report, limit_reached || budget_exhausted.is_some())
+    }
+
+    /// Whether a turn that is still streaming has passed the token limit or used up a budget
+    ///
+    /// `turn_usage` is the usage Claude reported for the turn so far and `session` the
+    /// session as it was when the turn started.
+    ///
+    /// # Returns
+    /// The meta describing the exceeded limit, or `None` while the turn is within them
+    fn turn_limit_exceeded(
+        &self,
+        session: &crate::session::Session,
+        turn_usage: &crate::claude::TokenUsageInfo,
+    ) -> Option<serde_json::Value> {
+        let max_tokens_per_turn = self.config().max_tokens_per_turn;
+        if turn_usage.total() > max_tokens_per_turn {
+            return Some(serde_json::json!({
+                "turn_tokens": turn_usage.total(),
+                "max_tokens_per_turn": max_tokens_per_turn
+            }));
+        }
+        let status = self
+            .budget
+            .exhausted_during_turn(&session.usage, turn_usage)?;
+        Some(serde_json::json!({
+            "budget_exhausted": status,
+            "message": format!("Spending budget exhausted: {} used", status)
+        }))
+    }
+
+    /// Response for a prompt refused because a spending budget is used up
+    fn budget_exhausted_response(
+        session_id: &crate::session::SessionId,
+        status: &crate::budget::BudgetStatus,
+    ) -> PromptResponse {
+        PromptResponse {
+            stop_reason: StopReason::MaxTokens,
+            meta: Some(serde_json::json!({
+                "budget_exhausted": status,
+                "message": format!("Spending budget exhausted: {} used", status),
+                "session_id": session_id.to_string()
+            })),
+        }
+    }
+
+    /// Check if streaming is supported for this session
+    fn should_stream(&self, session: &crate::session::Session, _request: &PromptRequest) -> bool {
+        // Check if client supports streaming
+        session
+            .client_capabilities
+            .as_ref()
+            .and_then(|caps| caps.meta.as_ref())
+            .and_then(|meta| meta.get("streaming"))
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false)
+    }
+
+    /// Handle streaming prompt request
+    async fn handle_streaming_prompt(
+        &self,
+        session_id: &crate::session::SessionId,
+        request: &PromptRequest,
+        session: &crate::session::Session,
+    ) -> Result<PromptResponse, agent_client_protocol::Error> {
+        tracing::info!("Handling streaming prompt for session: {}", session_id);
+
+        // Send execution thought
+        let execution_thought = AgentThought::new(
+            ReasoningPhase::Execution,
+            "Executing the planned approach using streaming response generation...",
+        );
+        let _ = self
+            .send_agent_thought(&request.session_id, &execution_thought)
+            .await;
+
+        // Validate content blocks against prompt capabilities before processing
+        let content_validator =
+            ContentCapabilityValidator::new(self.capabilities.prompt_capabilities.clone());
+        if let Err(capability_error) = content_validator.validate_content_blocks(&request.prompt) {
+            tracing::warn!(
+                "Content capability validation failed for session {}: {}",
+                session_id,
+                capability_error
+            );
 
             // Convert to ACP-compliant error response
             let acp_error_data = capability_error.to_acp_error();
@@ -1282,7 +1916,6 @@ impl ClaudeAgent {
                 agent_client_protocol::Error::invalid_params()
             })?;
 
-        let prompt_text = content_summary.combined_text;
         let has_binary_content = content_summary.has_binary_content;
 
         if has_binary_content {
@@ -1291,6 +1924,11 @@ impl ClaudeAgent {
                 session_id
             );
         }
+        let prompt = crate::claude::ProcessedPrompt::new(request.prompt.clone(), content_summary)
+            .map_err(|e| {
+            tracing::error!("Failed to prepare prompt: {}", e);
+            agent_client_protocol::Error::internal_error()
+        })?;
 
         // ACP Compliance: Check turn request limit before making LM request
         // This mirrors the non-streaming path check (see handle_prompt around line 2833).
@@ -1298,18 +1936,18 @@ impl ClaudeAgent {
         // when tool call loops are implemented, this will prevent infinite loops.
         let mut updated_session = session.clone();
         let current_requests = updated_session.increment_turn_requests();
-        if current_requests > self.config.max_turn_requests {
+        if current_requests > self.config().max_turn_requests {
             tracing::info!(
                 "Turn request limit exceeded ({} > {}) for session: {} (streaming path)",
                 current_requests,
-                self.config.max_turn_requests,
+                self.config().max_turn_requests,
                 session_id
             );
             return Ok(PromptResponse {
                 stop_reason: StopReason::MaxTurnRequests,
                 meta: Some(serde_json::json!({
                     "turn_requests": current_requests,
-                    "max_turn_requests": self.config.max_turn_requests,
+                    "max_turn_requests": self.config().max_turn_requests,
                     "session_id": session_id.to_string(),
                     "streaming": true
                 })),
@@ -1326,17 +1964,19 @@ impl ClaudeAgent {
         let context: crate::claude::SessionContext = session.into();
         let mut stream = self
             .claude_client
-            .query_stream_with_context(&prompt_text, &context)
+            .query_stream_content_with_context(prompt, &context)
             .await
             .map_err(|e| {
                 tracing::error!("Failed to create streaming query: {}", e);
-                agent_client_protocol::Error::internal_error()
+                Self::claude_error_to_acp(&e)
             })?;
 
         let mut full_response = String::new();
         let mut chunk_count = 0;
         let session_id_str = session_id.to_string();
         let mut claude_stop_reason: Option<String> = None;
+        let mut claude_token_usage = None;
+        let mut claude_total_cost_usd = None;
 
         while let Some(chunk) = stream.next().await {
             // Check for cancellation before processing each chunk
@@ -1360,10 +2000,90 @@ impl ClaudeAgent {
                 });
             }
 
-            // Capture stop_reason from chunk if present
+            // The claude process failed mid-turn
+            if let Some(error) = &chunk.error {
+                tracing::error!(
+                    "Streaming failed for session {} after {} chunks: {}",
+                    session_id,
+                    chunk_count,
+                    error
+                );
+                return Err(Self::claude_error_to_acp(error));
+            }
+
+            // Stop the turn as soon as its usage passes the token limit or a budget
+            if let crate::claude::ChunkType::Usage = chunk.chunk_type {
+                let Some(turn_usage) = chunk.token_usage else {
+                    continue;
+                };
+                let Some(exceeded) = self.turn_limit_exceeded(session, &turn_usage) else {
+                    continue;
+                };
+                tracing::info!(
+                    "Stopping streaming for session {} after {} chunks: {}",
+                    session_id,
+                    chunk_count,
+                    exceeded
+                );
+                self.claude_client
+                    .process_manager()
+                    .interrupt_session(session_id)
+                    .await;
+
+                // The interrupted turn still ends with a result that reports its cost; a
+                // process killed instead leaves the cost to the next turn's report
+                let mut turn_usage = turn_usage;
+                let mut total_cost_usd = None;
+                while let Some(chunk) = stream.next().await {
+                    if chunk.stop_reason.is_some() || chunk.total_cost_usd.is_some() {
+                        turn_usage = chunk.token_usage.unwrap_or(turn_usage);
+                        total_cost_usd = chunk.total_cost_usd;
+                    }
+                }
+                let (usage_report, _) = self
+                    .record_turn_usage(session_id, Some(&turn_usage), total_cost_usd)
+                    .await;
+                let mut meta = exceeded;
+                meta["stopped_during_streaming"] = true.into();
+                meta["chunks_processed"] = chunk_count.into();
+                meta["partial_response_length"] = full_response.len().into();
+                meta["usage"] = usage_report;
+                meta["session_id"] = session_id.to_string().into();
+                return Ok(PromptResponse {
+                    stop_reason: StopReason::MaxTokens,
+                    meta: Some(meta),
+                });
+            }
+
+            // Capture stop_reason, usage and cost from the result chunk if present
             if let Some(reason) = &chunk.stop_reason {
                 claude_stop_reason = Some(reason.clone());
             }
+            if chunk.token_usage.is_some() {
+                claude_token_usage = chunk.token_usage;
+            }
+            if chunk.total_cost_usd.is_some() {
+                claude_total_cost_usd = chunk.total_cost_usd;
+            }
+
+            // Forward tool call lifecycle updates as-is; they carry no response text
+            if let Some(update) = chunk.tool_call_update {
+                if let Err(e) = self
+                    .send_session_update(SessionNotification {
+                        session_id: SessionId(session_id_str.clone().into()),
+                        update,
+                        meta: None,
+                    })
+                    .await
+                {
+                    tracing::error!(
+                        session_id = %session_id,
+                        error = %e,
+                        "Failed to send tool call notification"
+                    );
+                }
+                continue;
+            }
 
             chunk_count += 1;
             full_response.push_str(&chunk.content);
@@ -1393,6 +2113,14 @@ impl ClaudeAgent {
                 // to avoid interrupting the main streaming flow
             }
         }
+        self.record_claude_session_id(session_id);
+        let (usage_report, limit_reached) = self
+            .record_turn_usage(
+                session_id,
+                claude_token_usage.as_ref(),
+                claude_total_cost_usd,
+            )
+            .await;
 
         // Final cancellation check before storing response
         if self
@@ -1439,9 +2167,7 @@ impl ClaudeAgent {
         };
 
         self.session_manager
-            .update_session(session_id, |session| {
-                session.add_message(assistant_message);
-            })
+            .add_message(session_id, assistant_message)
             .map_err(|_| agent_client_protocol::Error::internal_error())?;
 
         // Send result evaluation thought
@@ -1462,6 +2188,7 @@ impl ClaudeAgent {
 
         // Map Claude's stop_reason to ACP StopReason
         let stop_reason = match claude_stop_reason.as_deref() {
+            _ if limit_reached => StopReason::MaxTokens,
             Some("max_tokens") => StopReason::MaxTokens,
             Some("end_turn") | None => StopReason::EndTurn,
             Some(other) => {
@@ -1477,7 +2204,9 @@ impl ClaudeAgent {
                 "streaming": true,
                 "chunks_sent": chunk_count,
                 "session_messages": session.context.len() + 1,
-                "claude_stop_reason": claude_stop_reason
+                "claude_stop_reason": claude_stop_reason,
+                "usage": usage_report,
+                "max_tokens_per_turn": self.config().max_tokens_per_turn
             })),
         })
     }
@@ -1522,66 +2251,15 @@ impl ClaudeAgent {
             });
         }
 
-        // Extract and process all content from the prompt
-        let mut prompt_text = String::new();
-        let mut has_binary_content = false;
-
-        for content_block in &request.prompt {
-            match content_block {
-                ContentBlock::Text(text_content) => {
-                    prompt_text.push_str(&text_content.text);
-                }
-                ContentBlock::Image(image_content) => {
-                    // Process image data (already validated in validate_prompt_request)
-                    let _decoded = self
-                        .base64_processor
-                        .decode_image_data(&image_content.data, &image_content.mime_type)
-                        .map_err(|e| {
-                            tracing::error!("Failed to decode image data: {}", e);
-                            agent_client_protocol::Error::invalid_params()
-                        })?;
-
-                    // Add descriptive text for now until full multimodal support
-                    prompt_text.push_str(&format!(
-                        "\n[Image content: {} ({})]",
-                        image_content.mime_type,
-                        if let Some(ref uri) = image_content.uri {
-                            uri
-                        } else {
-                            "embedded data"
-                        }
-                    ));
-                    has_binary_content = true;
-                }
-                ContentBlock::Audio(audio_content) => {
-                    // Process audio data (already validated in validate_prompt_request)
-                    let _decoded = self
-                        .base64_processor
-                        .decode_audio_data(&audio_content.data, &audio_content.mime_type)
-                        .map_err(|e| {
-                            tracing::error!("Failed to decode audio data: {}", e);
-                            agent_client_protocol::Error::invalid_params()
-                        })?;
-
-                    // Add descriptive text for now until full multimodal support
-                    prompt_text.push_str(&format!(
-                        "\n[Audio content: {} (embedded data)]",
-                        audio_content.mime_type
-                    ));
-                    has_binary_content = true;
-                }
-                ContentBlock::Resource(_resource_content) => {
-                    // Add descriptive text for the resource
-                    prompt_text.push_str("\n[Embedded Resource]");
-                    has_binary_content = true;
-                }
-                ContentBlock::ResourceLink(resource_link) => {
-                    // Add descriptive text for the resource link
-                    prompt_text.push_str(&format!("\n[Resource Link: {}]", resource_link.uri));
-                    // ResourceLink is just a URI reference, not binary content
-                }
-            }
-        }
+        // Decode and validate all content blocks; Claude receives the processed content
+        let content_summary = self
+            .content_block_processor
+            .process_content_blocks(&request.prompt)
+            .map_err(|e| {
+                tracing::error!("Failed to process content blocks: {}", e);
+                agent_client_protocol::Error::invalid_params()
+            })?;
+        let has_binary_content = content_summary.has_binary_content;
 
         if has_binary_content {
             tracing::info!(
@@ -1589,6 +2267,11 @@ impl ClaudeAgent {
                 session_id
             );
         }
+        let prompt = crate::claude::ProcessedPrompt::new(request.prompt.clone(), content_summary)
+            .map_err(|e| {
+            tracing::error!("Failed to prepare prompt: {}", e);
+            agent_client_protocol::Error::internal_error()
+        })?;
 
         let context: crate::claude::SessionContext = session.into();
         let session_id_str = session_id.to_string();
@@ -1609,19 +2292,46 @@ impl ClaudeAgent {
         }
 
         tracing::info!("Calling Claude API for session: {}", session_id);
-        let response_content = self
+        let response = match self
             .claude_client
-            .query_with_context(&prompt_text, &context)
+            .query_content_with_context(prompt, &context)
             .await
-            .map_err(|e| {
+        {
+            Ok(response) => response,
+            // Cancellation kills the claude process, which ends the query with an error
+            Err(_)
+                if self
+                    .cancellation_manager
+                    .is_cancelled(&session_id_str)
+                    .await =>
+            {
+                tracing::info!("Session {} cancelled during Claude API request", session_id);
+                return Ok(PromptResponse {
+                    stop_reason: StopReason::Cancelled,
+                    meta: Some(serde_json::json!({
+                        "cancelled_during_api_request": true
+                    })),
+                });
+            }
+            Err(e) => {
                 tracing::error!("Claude API error: {:?}", e);
-                agent_client_protocol::Error::internal_error()
-            })?;
+                return Err(Self::claude_error_to_acp(&e));
+            }
+        };
+        let response_content = response.content;
         tracing::info!(
             "Received Claude API response ({} bytes) for session: {}",
             response_content.len(),
             session_id
         );
+        self.record_claude_session_id(session_id);
+        let (usage_report, limit_reached) = self
+            .record_turn_usage(
+                session_id,
+                response.token_usage.as_ref(),
+                response.total_cost_usd,
+            )
+            .await;
 
         // ACP requires specific stop reasons for all prompt turn completions:
         // Check for refusal patterns in Claude's response content
@@ -1661,9 +2371,7 @@ impl ClaudeAgent {
         };
 
         self.session_manager
-            .update_session(session_id, |session| {
-                session.add_message(assistant_message);
-            })
+            .add_message(session_id, assistant_message)
             .map_err(|_| agent_client_protocol::Error::internal_error())?;
 
         // Send result evaluation thought
@@ -1682,13 +2390,22 @@ impl ClaudeAgent {
             .send_agent_thought(&request.session_id, &result_thought)
             .await;
 
+        let stop_reason = match response.stop_reason.as_deref() {
+            _ if limit_reached => StopReason::MaxTokens,
+            Some("max_tokens") => StopReason::MaxTokens,
+            _ => StopReason::EndTurn,
+        };
+
         Ok(PromptResponse {
-            stop_reason: StopReason::EndTurn,
+            stop_reason,
             meta: Some(serde_json::json!({
                 "processed": true,
                 "streaming": false,
                 "claude_response": response_content,
-                "session_messages": session.context.len() + 1
+                "session_messages": session.context.len() + 1,
+                "claude_stop_reason": response.stop_reason,
+                "usage": usage_report,
+                "max_tokens_per_turn": self.config().max_tokens_per_turn
             })),
         })
     }
@@ -2052,15 +2769,58 @@ impl ClaudeAgent {
 
         // Add commands from MCP servers
         if let Some(mcp_manager) = &self.mcp_manager {
-            let mcp_tools = mcp_manager.list_available_tools().await;
-            for tool_name in mcp_tools {
+            // `/server:name` runs the prompt, so a tool of the same name is listed as
+            // `server:tool:name`
+            let mcp_prompts = mcp_manager.list_prompt_definitions().await;
+            let prompt_names: HashSet<&str> =
+                mcp_prompts.iter().map(|(name, _)| name.as_str()).collect();
+            let mcp_tools = mcp_manager.list_tool_definitions().await;
+            for (tool_name, tool) in mcp_tools {
+                let command_name = match tool_name.split_once(':') {
+                    Some((server, tool)) if prompt_names.contains(tool_name.as_str()) => {
+                        format!("{}:tool:{}", server, tool)
+                    }
+                    _ => tool_name.clone(),
+                };
+                let mut meta = serde_json::json!({
+                    "category": "mcp",
+                    "source": "mcp_server"
+                });
+                if let Some(schema) = &tool.input_schema {
+                    meta["inputSchema"] = schema.clone();
+                }
+                if let Some(annotations) = &tool.annotations {
+                    meta["annotations"] = serde_json::json!(annotations);
+                }
+
                 commands.push(agent_client_protocol::AvailableCommand {
-                    name: tool_name.clone(),
-                    description: format!("MCP tool: {}", tool_name),
-                    input: None,
+                    description: tool
+                        .description
+                        .clone()
+                        .unwrap_or_else(|| format!("MCP tool: {}", tool_name)),
+                    input: tool.input_hint().map(|hint| {
+                        agent_client_protocol::AvailableCommandInput::Unstructured { hint }
+                    }),
+                    name: command_name,
+                    meta: Some(meta),
+                });
+            }
+
+            // MCP prompts run as slash commands, e.g. `/server:prompt arguments`
+            for (prompt_name, prompt) in mcp_prompts {
+                commands.push(agent_client_protocol::AvailableCommand {
+                    description: prompt
+                        .description
+                        .clone()
+                        .unwrap_or_else(|| format!("MCP prompt: {}", prompt_name)),
+                    input: prompt.input_hint().map(|hint| {
+                        agent_client_protocol::AvailableCommandInput::Unstructured { hint }
+                    }),
+                    name: prompt_name,
                     meta: Some(serde_json::json!({
                         "category": "mcp",
-                        "source": "mcp_server"
+                        "source": "mcp_prompt",
+                        "arguments": prompt.arguments
                     })),
                 });
             }
@@ -2146,10 +2906,8 @@ impl ClaudeAgent {
 
     /// Cancel ongoing Claude API requests for a session
     ///
-    /// Note: This is a minimal implementation that registers cancellation state.
-    /// Individual request cancellation is not yet implemented as the ClaudeClient
-    /// doesn't currently track requests by session. The cancellation state is
-    /// checked before making new requests to prevent further API calls.
+    /// Registers cancellation state and interrupts the turn the session's claude process
+    /// is running, see [`crate::claude_process::ClaudeProcessManager::interrupt_session`].
     async fn cancel_claude_requests(&self, session_id: &str) {
         tracing::debug!("Cancelling Claude API requests for session: {}", session_id);
 
@@ -2158,6 +2916,14 @@ impl ClaudeAgent {
             .add_cancelled_operation(session_id, "claude_requests".to_string())
             .await;
 
+        // Stop the running turn; the conversation continues with the next prompt
+        if let Ok(parsed_session_id) = crate::session::SessionId::parse(session_id) {
+            self.claude_client
+                .process_manager()
+                .interrupt_session(&parsed_session_id)
+                .await;
+        }
+
         tracing::debug!(
             "Claude API request cancellation registered for session: {}",
             session_id
@@ -2421,6 +3187,11 @@ impl Agent for ClaudeAgent {
             .create_session(request.cwd.clone(), client_caps)
             .map_err(|_e| agent_client_protocol::Error::internal_error())?;
 
+        // The claude process for this session runs in the session's working directory
+        self.claude_client
+            .process_manager()
+            .set_session_cwd(session_id, request.cwd.clone());
+
         // Store MCP servers in the session if provided
         if !request.mcp_servers.is_empty() {
             self.session_manager
@@ -2513,6 +3284,17 @@ impl Agent for ClaudeAgent {
                     session_id,
                     session.context.len()
                 );
+                let process_manager = self.claude_client.process_manager();
+                process_manager.set_session_cwd(session_id, session.cwd.clone());
+
+                // A fresh claude process resumes the stored conversation, so the model's
+                // context matches the history replayed below
+                if let Some(claude_session_id) = &session.claude_session_id {
+                    if process_manager.claude_session_id(&session_id).is_none() {
+                        process_manager
+                            .set_claude_session_id(session_id, claude_session_id.clone());
+                    }
+                }
 
                 // Step 2-3: Stream ALL historical messages via session/update notifications
                 // Maintain exact chronological order using message timestamps
@@ -2673,6 +3455,11 @@ impl Agent for ClaudeAgent {
         // Parse session ID
         let session_id = self.parse_session_id(&request.session_id)?;
 
+        // A cancellation applies to the turn it interrupted, not to later prompts
+        self.cancellation_manager
+            .cleanup_session(&session_id.to_string())
+            .await;
+
         // ACP requires user message chunk updates for conversation transparency:
         // 1. Echo user input via session/update with user_message_chunk
         // 2. Send before agent processing begins
@@ -2699,6 +3486,10 @@ impl Agent for ClaudeAgent {
             }
         }
 
+        // MCP prompt commands and resource links are expanded after the echo, so the
+        // client sees what the user typed
+        let request = self.expand_mcp_content(request).await?;
+
         // Send initial analysis thought
         let analysis_thought = AgentThought::new(
             ReasoningPhase::PromptAnalysis,
@@ -2840,6 +3631,16 @@ impl Agent for ClaudeAgent {
             .map_err(|_| agent_client_protocol::Error::internal_error())?
             .ok_or_else(agent_client_protocol::Error::invalid_params)?;
 
+        // Refuse the turn once the session or the agent has used up a spending budget
+        if let Some(status) = self.budget.exhausted(&session.usage) {
+            tracing::info!(
+                "Spending budget exhausted for session {}: {}",
+                session_id,
+                status
+            );
+            return Ok(Self::budget_exhausted_response(&session_id, &status));
+        }
+
         // Reset turn counters at the start of each new turn.
         // ACP defines a turn as: a single user prompt and all subsequent LM requests
         // until the final response. This prevents unbounded counter growth across turns.
@@ -2857,9 +3658,7 @@ impl Agent for ClaudeAgent {
         };
 
         self.session_manager
-            .update_session(&session_id, |session| {
-                session.add_message(user_message);
-            })
+            .add_message(&session_id, user_message)
             .map_err(|_| agent_client_protocol::Error::internal_error())?;
 
         // Get updated session for context
@@ -2876,38 +3675,40 @@ impl Agent for ClaudeAgent {
 
         // Check turn request limit
         let current_requests = updated_session.increment_turn_requests();
-        if current_requests > self.config.max_turn_requests {
+        if current_requests > self.config().max_turn_requests {
             tracing::info!(
                 "Turn request limit exceeded ({} > {}) for session: {}",
                 current_requests,
-                self.config.max_turn_requests,
+                self.config().max_turn_requests,
                 session_id
             );
             return Ok(PromptResponse {
                 stop_reason: StopReason::MaxTurnRequests,
                 meta: Some(serde_json::json!({
                     "turn_requests": current_requests,
-                    "max_turn_requests": self.config.max_turn_requests,
+                    "max_turn_requests": self.config().max_turn_requests,
                     "session_id": session_id.to_string()
                 })),
             });
         }
 
-        // Estimate token usage for the prompt (rough approximation: 4 chars per token)
+        // Estimate token usage for the prompt (rough approximation: 4 chars per token).
+        // The estimate is not stored: the real usage Claude reports replaces it when the
+        // turn's result is recorded, and is checked against the limit again then.
         let estimated_tokens = (prompt_text.len() as u64) / 4;
         let current_tokens = updated_session.add_turn_tokens(estimated_tokens);
-        if current_tokens > self.config.max_tokens_per_turn {
+        if current_tokens > self.config().max_tokens_per_turn {
             tracing::info!(
                 "Token limit exceeded ({} > {}) for session: {}",
                 current_tokens,
-                self.config.max_tokens_per_turn,
+                self.config().max_tokens_per_turn,
                 session_id
             );
             return Ok(PromptResponse {
                 stop_reason: StopReason::MaxTokens,
                 meta: Some(serde_json::json!({
                     "turn_tokens": current_tokens,
-                    "max_tokens_per_turn": self.config.max_tokens_per_turn,
+                    "max_tokens_per_turn": self.config().max_tokens_per_turn,
                     "session_id": session_id.to_string()
                 })),
             });
@@ -2917,18 +3718,20 @@ impl Agent for ClaudeAgent {
         self.session_manager
             .update_session(&session_id, |session| {
                 session.turn_request_count = updated_session.turn_request_count;
-                session.turn_token_count = updated_session.turn_token_count;
             })
             .map_err(|_| agent_client_protocol::Error::internal_error())?;
 
         // Check if streaming is supported and requested
         let response = if self.should_stream(&session, &request) {
             self.handle_streaming_prompt(&session_id, &request, &updated_session)
-                .await?
+                .await
         } else {
             self.handle_non_streaming_prompt(&session_id, &request, &updated_session)
-                .await?
+                .await
         };
+        // Tool calls the turn left unfinished will not report a result
+        self.audit_log.clear_pending(&session_id.to_string());
+        let response = response?;
 
         self.log_response("prompt", &response);
         Ok(response)
@@ -2992,27 +3795,19 @@ impl Agent for ClaudeAgent {
 
     /// Handle extension method requests
     ///
-    /// Extension methods allow clients to call custom methods not defined in the core
-    /// Agent Client Protocol specification. This implementation returns a placeholder
-    /// response indicating that extension methods are not currently supported.
+    /// ACP reserves method names starting with `_` for extensions. Clients call the
+    /// methods of this agent as `_claude_agent/...`; the protocol layer strips the
+    /// underscore before they reach this handler.
     ///
-    /// ## Design Decision
+    /// - `_claude_agent/editor/update_buffers`: cache the client's editor buffers, an
+    ///   [`crate::editor_state::EditorBufferResponse`], so tools read unsaved content.
+    ///   Returns `null`.
+    /// - `_claude_agent/session/usage`: token usage and cost of a session, see
+    ///   [`crate::session::SessionUsageResponse`]
+    /// - `_claude_agent/audit/query`: search the audit log, see [`crate::audit::AuditQuery`]
+    /// - `_claude_agent/mcp/status`: connection state of every configured MCP server
     ///
-    /// Claude Agent currently does not require any extension methods beyond the standard
-    /// ACP specification. The core protocol provides sufficient capabilities for:
-    /// - Session management (new_session, load_session, set_session_mode)
-    /// - Authentication (handled via empty auth_methods)
-    /// - Tool execution (via prompt requests)
-    /// - Session updates and notifications
-    ///
-    /// If future requirements emerge for custom extension methods, this implementation
-    /// can be enhanced to dispatch to specific handlers based on the method name.
-    ///
-    /// ## Protocol Compliance
-    ///
-    /// This implementation satisfies the ACP requirement that agents must respond to
-    /// extension method calls, even if they don't implement any specific extensions.
-    /// Returning a structured response (rather than an error) maintains client compatibility.
+    /// Any other method gets a result saying it is not implemented.
     async fn ext_method(
         &self,
         request: ExtRequest,
@@ -3020,295 +3815,7 @@ impl Agent for ClaudeAgent {
         self.log_request("ext_method", &request);
         tracing::info!("Extension method called: {}", request.method);
 
-        // Handle fs/read_text_file extension method
-        if request.method == "fs/read_text_file".into() {
-            // Validate client capabilities for file system read operations
-            {
-                let client_caps = self.client_capabilities.read().await;
-                match &*client_caps {
-                    Some(caps) if caps.fs.read_text_file => {
-                        tracing::debug!("File system read capability validated");
-                    }
-                    Some(_) => {
-                        tracing::error!("fs/read_text_file capability not declared by client");
-                        return Err(agent_client_protocol::Error::invalid_params());
-                    }
-                    None => {
-                        tracing::error!(
-                            "No client capabilities available for fs/read_text_file validation"
-                        );
-                        return Err(agent_client_protocol::Error::invalid_params());
-                    }
-                }
-            }
-
-            // Parse the request parameters from RawValue
-            let params_value: serde_json::Value = serde_json::from_str(request.params.get())
-                .map_err(|e| {
-                    tracing::error!("Failed to parse fs/read_text_file parameters: {}", e);
-                    agent_client_protocol::Error::invalid_params()
-                })?;
-
-            let params: ReadTextFileParams = serde_json::from_value(params_value).map_err(|e| {
-                tracing::error!("Failed to deserialize fs/read_text_file parameters: {}", e);
-                agent_client_protocol::Error::invalid_params()
-            })?;
-
-            // Handle the file reading request
-            let response = self.handle_read_text_file(params).await?;
-
-            // Convert response to RawValue
-            let response_json = serde_json::to_value(response)
-                .map_err(|_e| agent_client_protocol::Error::internal_error())?;
-
-            let raw_value = RawValue::from_string(response_json.to_string())
-                .map_err(|_e| agent_client_protocol::Error::internal_error())?;
-
-            return Ok(Arc::from(raw_value));
-        }
-
-        // Handle fs/write_text_file extension method
-        if request.method == "fs/write_text_file".into() {
-            // Validate client capabilities for file system write operations
-            {
-                let client_caps = self.client_capabilities.read().await;
-                match &*client_caps {
-                    Some(caps) if caps.fs.write_text_file => {
-                        tracing::debug!("File system write capability validated");
-                    }
-                    Some(_) => {
-                        tracing::error!("fs/write_text_file capability not declared by client");
-                        return Err(agent_client_protocol::Error::invalid_params());
-                    }
-                    None => {
-                        tracing::error!(
-                            "No client capabilities available for fs/write_text_file validation"
-                        );
-                        return Err(agent_client_protocol::Error::invalid_params());
-                    }
-                }
-            }
-
-            // Parse the request parameters from RawValue
-            let params_value: serde_json::Value = serde_json::from_str(request.params.get())
-                .map_err(|e| {
-                    tracing::error!("Failed to parse fs/write_text_file parameters: {}", e);
-                    agent_client_protocol::Error::invalid_params()
-                })?;
-
-            let params: WriteTextFileParams =
-                serde_json::from_value(params_value).map_err(|e| {
-                    tracing::error!("Failed to deserialize fs/write_text_file parameters: {}", e);
-                    agent_client_protocol::Error::invalid_params()
-                })?;
-
-            // Handle the file writing request
-            let response = self.handle_write_text_file(params).await?;
-
-            // Convert response to RawValue
-            let response_json = serde_json::to_value(response)
-                .map_err(|_e| agent_client_protocol::Error::internal_error())?;
-
-            let raw_value = RawValue::from_string(response_json.to_string())
-                .map_err(|_e| agent_client_protocol::Error::internal_error())?;
-
-            return Ok(Arc::from(raw_value));
-        }
-
-        // Handle terminal/output extension method
-        if request.method == "terminal/output".into() {
-            // Validate client capabilities for terminal operations
-            {
-                let client_caps = self.client_capabilities.read().await;
-                match &*client_caps {
-                    Some(caps) if caps.terminal => {
-                        tracing::debug!("Terminal capability validated");
-                    }
-                    Some(_) => {
-                        tracing::error!("terminal/output capability not declared by client");
-                        return Err(agent_client_protocol::Error::invalid_params());
-                    }
-                    None => {
-                        tracing::error!(
-                            "No client capabilities available for terminal/output validation"
-                        );
-                        return Err(agent_client_protocol::Error::invalid_params());
-                    }
-                }
-            }
-
-            // Parse the request parameters from RawValue
-            let params_value: serde_json::Value = serde_json::from_str(request.params.get())
-                .map_err(|e| {
-                    tracing::error!("Failed to parse terminal/output parameters: {}", e);
-                    agent_client_protocol::Error::invalid_params()
-                })?;
-
-            let params: crate::terminal_manager::TerminalOutputParams =
-                serde_json::from_value(params_value).map_err(|e| {
-                    tracing::error!("Failed to deserialize terminal/output parameters: {}", e);
-                    agent_client_protocol::Error::invalid_params()
-                })?;
-
-            // Handle the terminal output request
-            let response = self.handle_terminal_output(params).await?;
-
-            // Convert response to RawValue
-            let response_json = serde_json::to_value(response)
-                .map_err(|_e| agent_client_protocol::Error::internal_error())?;
-
-            let raw_value = RawValue::from_string(response_json.to_string())
-                .map_err(|_e| agent_client_protocol::Error::internal_error())?;
-
-            return Ok(Arc::from(raw_value));
-        }
-
-        // Handle terminal/release extension method
-        if request.method == "terminal/release".into() {
-            // Validate client capabilities for terminal operations
-            {
-                let client_caps = self.client_capabilities.read().await;
-                match &*client_caps {
-                    Some(caps) if caps.terminal => {
-                        tracing::debug!("Terminal capability validated");
-                    }
-                    Some(_) => {
-                        tracing::error!("terminal/release capability not declared by client");
-                        return Err(agent_client_protocol::Error::invalid_params());
-                    }
-                    None => {
-                        tracing::error!(
-                            "No client capabilities available for terminal/release validation"
-                        );
-                        return Err(agent_client_protocol::Error::invalid_params());
-                    }
-                }
-            }
-
-            // Parse the request parameters from RawValue
-            let params_value: serde_json::Value = serde_json::from_str(request.params.get())
-                .map_err(|e| {
-                    tracing::error!("Failed to parse terminal/release parameters: {}", e);
-                    agent_client_protocol::Error::invalid_params()
-                })?;
-
-            let params: crate::terminal_manager::TerminalReleaseParams =
-                serde_json::from_value(params_value).map_err(|e| {
-                    tracing::error!("Failed to deserialize terminal/release parameters: {}", e);
-                    agent_client_protocol::Error::invalid_params()
-                })?;
-
-            // Handle the terminal release request
-            let response = self.handle_terminal_release(params).await?;
-
-            // Convert response to RawValue (should be null per ACP spec)
-            let response_json = serde_json::to_value(response)
-                .map_err(|_e| agent_client_protocol::Error::internal_error())?;
-
-            let raw_value = RawValue::from_string(response_json.to_string())
-                .map_err(|_e| agent_client_protocol::Error::internal_error())?;
-
-            return Ok(Arc::from(raw_value));
-        }
-
-        // Handle terminal/wait_for_exit extension method
-        if request.method == "terminal/wait_for_exit".into() {
-            // Validate terminal capability
-            {
-                let client_caps = self.client_capabilities.read().await;
-                match &*client_caps {
-                    Some(caps) if caps.terminal => {
-                        tracing::debug!("Terminal capability validated for wait_for_exit");
-                    }
-                    Some(_) => {
-                        tracing::error!("terminal/wait_for_exit capability not declared by client");
-                        return Err(agent_client_protocol::Error::invalid_params());
-                    }
-                    None => {
-                        tracing::error!(
-                            "No client capabilities available for terminal/wait_for_exit validation"
-                        );
-                        return Err(agent_client_protocol::Error::invalid_params());
-                    }
-                }
-            }
-
-            // Parse and validate parameters
-            let params_value: serde_json::Value = serde_json::from_str(request.params.get())
-                .map_err(|e| {
-                    tracing::error!("Failed to parse terminal/wait_for_exit parameters: {}", e);
-                    agent_client_protocol::Error::invalid_params()
-                })?;
-
-            let params: crate::terminal_manager::TerminalOutputParams =
-                serde_json::from_value(params_value).map_err(|e| {
-                    tracing::error!(
-                        "Failed to deserialize terminal/wait_for_exit parameters: {}",
-                        e
-                    );
-                    agent_client_protocol::Error::invalid_params()
-                })?;
-
-            // Handle the wait for exit request
-            let response = self.handle_terminal_wait_for_exit(params).await?;
-
-            // Convert response to RawValue
-            let response_json = serde_json::to_value(response)
-                .map_err(|_e| agent_client_protocol::Error::internal_error())?;
-
-            let raw_value = RawValue::from_string(response_json.to_string())
-                .map_err(|_e| agent_client_protocol::Error::internal_error())?;
-
-            return Ok(Arc::from(raw_value));
-        }
-
-        // Handle terminal/kill extension method
-        if request.method == "terminal/kill".into() {
-            // Validate terminal capability
-            {
-                let client_caps = self.client_capabilities.read().await;
-                match &*client_caps {
-                    Some(caps) if caps.terminal => {
-                        tracing::debug!("Terminal capability validated for kill");
-                    }
-                    Some(_) => {
-                        tracing::error!("terminal/kill capability not declared by client");
-                        return Err(agent_client_protocol::Error::invalid_params());
-                    }
-                    None => {
-                        tracing::error!(
-                            "No client capabilities available for terminal/kill validation"
-                        );
-                        return Err(agent_client_protocol::Error::invalid_params());
-                    }
-                }
-            }
-
-            // Parse and validate parameters
-            let params_value: serde_json::Value = serde_json::from_str(request.params.get())
-                .map_err(|e| {
-                    tracing::error!("Failed to parse terminal/kill parameters: {}", e);
-                    agent_client_protocol::Error::invalid_params()
-                })?;
-
-            let params: crate::terminal_manager::TerminalOutputParams =
-                serde_json::from_value(params_value).map_err(|e| {
-                    tracing::error!("Failed to deserialize terminal/kill parameters: {}", e);
-                    agent_client_protocol::Error::invalid_params()
-                })?;
-
-            // Handle the kill request
-            self.handle_terminal_kill(params).await?;
-
-            // Return null result per ACP specification
-            let response_json = serde_json::Value::Null;
-            let raw_value = RawValue::from_string(response_json.to_string())
-                .map_err(|_e| agent_client_protocol::Error::internal_error())?;
-
-            return Ok(Arc::from(raw_value));
-        }
-
-        // Handle editor/update_buffers extension method
+        // Handle _claude_agent/editor/update_buffers extension method
         //
         // This extension method allows clients to push editor buffer state to the agent,
         // enabling the agent to access unsaved file content when executing tools that read files.
@@ -3333,7 +3840,7 @@ impl Agent for ClaudeAgent {
         //
         // ```typescript
         // await agent.ext_method({
-        //   method: "editor/update_buffers",
+        //   method: "_claude_agent/editor/update_buffers",
         //   params: {
         //     buffers: {
         //       "/path/to/file.rs": {
@@ -3348,7 +3855,7 @@ impl Agent for ClaudeAgent {
         //   }
         // });
         // ```
-        if request.method == "editor/update_buffers".into() {
+        if request.method == "claude_agent/editor/update_buffers".into() {
             // Note: editorState capability is optional for this method
             // Clients can push editor state updates without advertising the capability.
             // This allows for flexible integration where clients proactively send updates.
@@ -3360,7 +3867,7 @@ impl Agent for ClaudeAgent {
                     }
                     Some(_) | None => {
                         tracing::debug!(
-                            "Processing editor/update_buffers without declared editorState capability"
+                            "Processing _claude_agent/editor/update_buffers without declared editorState capability"
                         );
                     }
                 }
@@ -3369,14 +3876,17 @@ impl Agent for ClaudeAgent {
             // Parse the request parameters from RawValue
             let params_value: serde_json::Value = serde_json::from_str(request.params.get())
                 .map_err(|e| {
-                    tracing::error!("Failed to parse editor/update_buffers parameters: {}", e);
+                    tracing::error!(
+                        "Failed to parse _claude_agent/editor/update_buffers parameters: {}",
+                        e
+                    );
                     agent_client_protocol::Error::invalid_params()
                 })?;
 
             let response: crate::editor_state::EditorBufferResponse =
                 serde_json::from_value(params_value).map_err(|e| {
                     tracing::error!(
-                        "Failed to deserialize editor/update_buffers parameters: {}",
+                        "Failed to deserialize _claude_agent/editor/update_buffers parameters: {}",
                         e
                     );
                     agent_client_protocol::Error::invalid_params()
@@ -3399,6 +3909,79 @@ impl Agent for ClaudeAgent {
             return Ok(Arc::from(raw_value));
         }
 
+        // Handle _claude_agent/session/usage extension method
+        //
+        // Returns the token usage and cost accumulated by a session, as reported by the
+        // claude CLI at the end of each turn.
+        if request.method == "claude_agent/session/usage".into() {
+            let params: crate::session::SessionUsageParams =
+                serde_json::from_str(request.params.get()).map_err(|e| {
+                    tracing::error!(
+                        "Failed to parse _claude_agent/session/usage parameters: {}",
+                        e
+                    );
+                    agent_client_protocol::Error::invalid_params()
+                })?;
+
+            let session_id = self.parse_session_id(&SessionId(params.session_id.into()))?;
+            let session = self
+                .session_manager
+                .get_session(&session_id)
+                .map_err(|_| agent_client_protocol::Error::internal_error())?
+                .ok_or_else(agent_client_protocol::Error::invalid_params)?;
+
+            let response = crate::session::SessionUsageResponse::new(&session_id, &session.usage);
+            let response_json = serde_json::to_value(response)
+                .map_err(|_e| agent_client_protocol::Error::internal_error())?;
+
+            let raw_value = RawValue::from_string(response_json.to_string())
+                .map_err(|_e| agent_client_protocol::Error::internal_error())?;
+
+            return Ok(Arc::from(raw_value));
+        }
+
+        // Handle _claude_agent/audit/query extension method
+        //
+        // Searches the audit log of permission decisions and tool executions.
+        if request.method == "claude_agent/audit/query".into() {
+            let params: crate::audit::AuditQuery = serde_json::from_str(request.params.get())
+                .map_err(|e| {
+                    tracing::error!(
+                        "Failed to parse _claude_agent/audit/query parameters: {}",
+                        e
+                    );
+                    agent_client_protocol::Error::invalid_params()
+                })?;
+
+            let response = self.audit_log.query(&params).await.map_err(|e| {
+                tracing::error!("Failed to query the audit log: {}", e);
+                agent_client_protocol::Error::internal_error()
+            })?;
+            let response_json = serde_json::to_value(response)
+                .map_err(|_e| agent_client_protocol::Error::internal_error())?;
+
+            let raw_value = RawValue::from_string(response_json.to_string())
+                .map_err(|_e| agent_client_protocol::Error::internal_error())?;
+
+            return Ok(Arc::from(raw_value));
+        }
+
+        // Handle _claude_agent/mcp/status extension method
+        //
+        // Reports the connection state of every configured MCP server.
+        if request.method == "claude_agent/mcp/status".into() {
+            let servers = match &self.mcp_manager {
+                Some(mcp_manager) => mcp_manager.server_statuses().await,
+                None => Vec::new(),
+            };
+            let response_json = serde_json::json!({ "servers": servers });
+
+            let raw_value = RawValue::from_string(response_json.to_string())
+                .map_err(|_e| agent_client_protocol::Error::internal_error())?;
+
+            return Ok(Arc::from(raw_value));
+        }
+
         // Return a structured response indicating no other extensions are implemented
         // This maintains ACP compliance while clearly communicating capability limitations
         let response = serde_json::json!({
@@ -3450,21 +4033,6 @@ impl ClaudeAgent {
         // Parse session ID
         let session_id = self.parse_session_id(&request.session_id)?;
 
-        // Check if session is cancelled
-        if self
-            .cancellation_manager
-            .is_cancelled(&session_id.to_string())
-            .await
-        {
-            tracing::info!(
-                "Session {} is cancelled, returning cancelled outcome",
-                session_id
-            );
-            return Ok(PermissionResponse {
-                outcome: crate::tools::PermissionOutcome::Cancelled,
-            });
-        }
-
         // Extract tool name and arguments from the active tool call
         let (tool_name, tool_args) = {
             let tool_handler = self.tool_handler.read().await;
@@ -3489,125 +4057,17 @@ impl ClaudeAgent {
             }
         };
 
-        // Use permission policy engine to evaluate the tool call
-        let policy_result = match self
-            .permission_engine
-            .evaluate_tool_call(&tool_name, &tool_args)
-            .await
-        {
-            Ok(evaluation) => evaluation,
-            Err(e) => {
-                tracing::error!("Permission policy evaluation failed: {}", e);
-                return Ok(PermissionResponse {
-                    outcome: crate::tools::PermissionOutcome::Cancelled,
-                });
-            }
-        };
-
-        let selected_outcome = match policy_result {
-            PolicyEvaluation::Allowed => {
-                tracing::info!("Tool '{}' allowed by policy", tool_name);
-                crate::tools::PermissionOutcome::Selected {
-                    option_id: "allow-once".to_string(),
-                }
-            }
-            PolicyEvaluation::Denied { reason } => {
-                tracing::info!("Tool '{}' denied by policy: {}", tool_name, reason);
-                crate::tools::PermissionOutcome::Selected {
-                    option_id: "reject-once".to_string(),
-                }
-            }
-            PolicyEvaluation::RequireUserConsent { options } => {
-                tracing::info!("Tool '{}' requires user consent", tool_name);
-
-                // If options were provided in request, use those; otherwise use policy-generated options
-                let permission_options = if !request.options.is_empty() {
-                    request.options
-                } else {
-                    options
-                };
-
-                // Check if there's a stored preference for this tool
-                if let Some(stored_kind) = self.permission_storage.get_preference(&tool_name).await
-                {
-                    let option_id = match stored_kind {
-                        crate::tools::PermissionOptionKind::AllowAlways => "allow-always",
-                        crate::tools::PermissionOptionKind::RejectAlways => "reject-always",
-                        _ => {
-                            tracing::warn!("Unexpected stored permission kind: {:?}", stored_kind);
-                            "allow-once"
-                        }
-                    };
-
-                    tracing::info!(
-                        "Using stored permission preference for '{}': {}",
-                        tool_name,
-                        option_id
-                    );
-
-                    return Ok(PermissionResponse {
-                        outcome: crate::tools::PermissionOutcome::Selected {
-                            option_id: option_id.to_string(),
-                        },
-                    });
-                }
-
-                // Create a description from tool arguments
-                let description = format!(
-                    "Execute {} with arguments: {}",
-                    tool_name,
-                    serde_json::to_string_pretty(&tool_args).unwrap_or_else(|_| "{}".to_string())
-                );
-
-                // Prompt user for permission with timeout
-                let prompt_result = tokio::time::timeout(
-                    Duration::from_secs(PERMISSION_PROMPT_TIMEOUT_SECS),
-                    self.user_prompt_handler.prompt_for_permission(
-                        &tool_name,
-                        &description,
-                        &permission_options,
-                    ),
-                )
-                .await;
-
-                let selected_option_id = match prompt_result {
-                    Ok(Ok(option_id)) => option_id,
-                    Ok(Err(e)) => {
-                        tracing::error!("User prompt failed: {}", e);
-                        return Ok(PermissionResponse {
-                            outcome: crate::tools::PermissionOutcome::Cancelled,
-                        });
-                    }
-                    Err(_) => {
-                        tracing::warn!("User permission prompt timed out after 60 seconds");
-                        return Ok(PermissionResponse {
-                            outcome: crate::tools::PermissionOutcome::Cancelled,
-                        });
-                    }
-                };
-
-                // Find the selected option to get its kind
-                if let Some(selected_option) = permission_options
-                    .iter()
-                    .find(|opt| opt.option_id == selected_option_id)
-                {
-                    // Store the permission decision if user selected "always" option
-                    self.permission_storage
-                        .store_preference(&tool_name, selected_option.kind.clone())
-                        .await;
-
-                    tracing::info!(
-                        "User selected permission option: {} ({:?})",
-                        selected_option.name,
-                        selected_option.kind
-                    );
-                }
-
-                crate::tools::PermissionOutcome::Selected {
-                    option_id: selected_option_id,
-                }
-            }
-        };
+        // Evaluate policy, stored preferences and, if needed, ask the user
+        let selected_outcome = self
+            .permission_flow
+            .decide(
+                &session_id.to_string(),
+                &request.tool_call.tool_call_id,
+                &tool_name,
+                &tool_args,
+                request.options,
+            )
+            .await;
 
         let response = PermissionResponse {
             outcome: selected_outcome,
@@ -3623,13 +4083,14 @@ impl ClaudeAgent {
         Ok(response)
     }
 
-    /// Handle fs/read_text_file ACP extension method
+    /// Read a text file for a session
     ///
     /// ACP requires integration with client editor state to access unsaved changes.
     /// This method:
-    /// 1. Checks if an editor buffer is available for the file
-    /// 2. Falls back to disk content if no editor buffer exists
-    /// 3. Applies line filtering if requested
+    /// 1. Sends `fs/read_text_file` to the client if it declared `fs.read_text_file`
+    /// 2. Otherwise checks if an editor buffer is available for the file
+    /// 3. Falls back to disk content if no editor buffer exists
+    /// 4. Applies line filtering if requested
     ///
     /// This ensures agents work with current, not stale, file content.
     pub async fn handle_read_text_file(
@@ -3654,9 +4115,26 @@ impl ClaudeAgent {
             }
         }
 
+        // The client knows about unsaved buffers, so it reads the file when it can
+        if self.client_supports(|caps| caps.fs.read_text_file).await {
+            let response = self
+                .client
+                .read_text_file(agent_client_protocol::ReadTextFileRequest {
+                    session_id: SessionId(params.session_id.into()),
+                    path: params.path.into(),
+                    line: params.line,
+                    limit: params.limit,
+                    meta: None,
+                })
+                .await?;
+            return Ok(ReadTextFileResponse {
+                content: response.content,
+            });
+        }
+
         let path = std::path::Path::new(&params.path);
 
-        // ACP requires integration with client editor state for unsaved changes
+        // Without a client, use the editor state pushed with _claude_agent/editor/update_buffers
         // Try to get content from editor buffer first
         match self
             .editor_state_manager
@@ -3698,7 +4176,10 @@ impl ClaudeAgent {
         }
     }
 
-    /// Handle fs/write_text_file ACP extension method
+    /// Write a text file for a session
+    ///
+    /// The write is sent to the client as `fs/write_text_file` if it declared
+    /// `fs.write_text_file`, and done on disk otherwise.
     pub async fn handle_write_text_file(
         &self,
         params: WriteTextFileParams,
@@ -3714,6 +4195,18 @@ impl ClaudeAgent {
             return Err(agent_client_protocol::Error::invalid_params());
         }
 
+        if self.client_supports(|caps| caps.fs.write_text_file).await {
+            self.client
+                .write_text_file(agent_client_protocol::WriteTextFileRequest {
+                    session_id: SessionId(params.session_id.into()),
+                    path: params.path.into(),
+                    content: params.content,
+                    meta: None,
+                })
+                .await?;
+            return Ok(serde_json::Value::Null);
+        }
+
         // Perform atomic write operation
         self.write_file_atomically(&params.path, &params.content)
             .await?;
@@ -3722,13 +4215,32 @@ impl ClaudeAgent {
         Ok(serde_json::Value::Null)
     }
 
-    /// Handle terminal/output ACP extension method
+    /// Get the output of a terminal
+    ///
+    /// The request is sent to the client as `terminal/output` if it declared the `terminal`
+    /// capability, and served by the agent's terminal manager otherwise.
     pub async fn handle_terminal_output(
         &self,
         params: crate::terminal_manager::TerminalOutputParams,
     ) -> Result<crate::terminal_manager::TerminalOutputResponse, agent_client_protocol::Error> {
         tracing::debug!("Processing terminal/output request: {:?}", params);
 
+        if self.client_supports(|caps| caps.terminal).await {
+            let response = self
+                .client
+                .terminal_output(agent_client_protocol::TerminalOutputRequest {
+                    session_id: SessionId(params.session_id.into()),
+                    terminal_id: agent_client_protocol::TerminalId(params.terminal_id.into()),
+                    meta: None,
+                })
+                .await?;
+            return Ok(crate::terminal_manager::TerminalOutputResponse {
+                output: response.output,
+                truncated: response.truncated,
+                exit_status: response.exit_status.map(Self::exit_status_from_client),
+            });
+        }
+
         // Get terminal manager from tool handler
         let tool_handler = self.tool_handler.read().await;
         let terminal_manager = tool_handler.get_terminal_manager();
@@ -3743,13 +4255,27 @@ impl ClaudeAgent {
             })
     }
 
-    /// Handle terminal/release ACP extension method
+    /// Release a terminal
+    ///
+    /// The request is sent to the client as `terminal/release` if it declared the `terminal`
+    /// capability, and served by the agent's terminal manager otherwise.
     pub async fn handle_terminal_release(
         &self,
         params: crate::terminal_manager::TerminalReleaseParams,
     ) -> Result<serde_json::Value, agent_client_protocol::Error> {
         tracing::debug!("Processing terminal/release request: {:?}", params);
 
+        if self.client_supports(|caps| caps.terminal).await {
+            self.client
+                .release_terminal(agent_client_protocol::ReleaseTerminalRequest {
+                    session_id: SessionId(params.session_id.into()),
+                    terminal_id: agent_client_protocol::TerminalId(params.terminal_id.into()),
+                    meta: None,
+                })
+                .await?;
+            return Ok(serde_json::Value::Null);
+        }
+
         // Get terminal manager from tool handler
         let tool_handler = self.tool_handler.read().await;
         let terminal_manager = tool_handler.get_terminal_manager();
@@ -3764,13 +4290,28 @@ impl ClaudeAgent {
             })
     }
 
-    /// Handle terminal/wait_for_exit ACP extension method
+    /// Wait for the command of a terminal to exit
+    ///
+    /// The request is sent to the client as `terminal/wait_for_exit` if it declared the `terminal`
+    /// capability, and served by the agent's terminal manager otherwise.
     pub async fn handle_terminal_wait_for_exit(
         &self,
         params: crate::terminal_manager::TerminalOutputParams,
     ) -> Result<crate::terminal_manager::ExitStatus, agent_client_protocol::Error> {
         tracing::debug!("Processing terminal/wait_for_exit request: {:?}", params);
 
+        if self.client_supports(|caps| caps.terminal).await {
+            let response = self
+                .client
+                .wait_for_terminal_exit(agent_client_protocol::WaitForTerminalExitRequest {
+                    session_id: SessionId(params.session_id.into()),
+                    terminal_id: agent_client_protocol::TerminalId(params.terminal_id.into()),
+                    meta: None,
+                })
+                .await?;
+            return Ok(Self::exit_status_from_client(response.exit_status));
+        }
+
         // Get terminal manager from tool handler
         let tool_handler = self.tool_handler.read().await;
         let terminal_manager = tool_handler.get_terminal_manager();
@@ -3785,13 +4326,27 @@ impl ClaudeAgent {
             })
     }
 
-    /// Handle terminal/kill ACP extension method
+    /// Kill the command of a terminal
+    ///
+    /// The request is sent to the client as `terminal/kill` if it declared the `terminal`
+    /// capability, and served by the agent's terminal manager otherwise.
     pub async fn handle_terminal_kill(
         &self,
         params: crate::terminal_manager::TerminalOutputParams,
     ) -> Result<(), agent_client_protocol::Error> {
         tracing::debug!("Processing terminal/kill request: {:?}", params);
 
+        if self.client_supports(|caps| caps.terminal).await {
+            self.client
+                .kill_terminal_command(agent_client_protocol::KillTerminalCommandRequest {
+                    session_id: SessionId(params.session_id.into()),
+                    terminal_id: agent_client_protocol::TerminalId(params.terminal_id.into()),
+                    meta: None,
+                })
+                .await?;
+            return Ok(());
+        }
+
         // Get terminal manager from tool handler
         let tool_handler = self.tool_handler.read().await;
         let terminal_manager = tool_handler.get_terminal_manager();
@@ -3806,6 +4361,35 @@ impl ClaudeAgent {
             })
     }
 
+    /// Whether the connected client declared the capability `declared` checks
+    ///
+    /// File and terminal requests go to the client when it did. Without a client, or
+    /// for capabilities it lacks, the agent serves them itself.
+    async fn client_supports(
+        &self,
+        declared: impl Fn(&agent_client_protocol::ClientCapabilities) -> bool,
+    ) -> bool {
+        self.client.is_connected()
+            && self
+                .client_capabilities
+                .read()
+                .await
+                .as_ref()
+                .is_some_and(declared)
+    }
+
+    /// Convert a terminal exit status reported by the client
+    fn exit_status_from_client(
+        status: agent_client_protocol::TerminalExitStatus,
+    ) -> crate::terminal_manager::ExitStatus {
+        crate::terminal_manager::ExitStatus {
+            exit_code: status
+                .exit_code
+                .map(|code| i32::try_from(code).unwrap_or(i32::MAX)),
+            signal: status.signal,
+        }
+    }
+
     /// Read file content with optional line offset and limit
     async fn read_file_with_options(
         &self,
@@ -4196,6 +4780,28 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_claude_error_to_acp_keeps_crash_details() {
+        let error = crate::AgentError::ProcessCrashed {
+            session_id: "session-1".to_string(),
+            exit_status: "exit status: 1".to_string(),
+            stderr_tail: vec!["Error: out of memory".to_string()],
+            restart_count: 1,
+            will_restart: true,
+        };
+
+        let acp_error = ClaudeAgent::claude_error_to_acp(&error);
+        assert_eq!(acp_error.code, -32000);
+        assert!(acp_error.message.contains("exited unexpectedly"));
+        let data = acp_error.data.expect("crash errors carry data");
+        assert_eq!(data["stderrTail"][0], "Error: out of memory");
+        assert_eq!(data["willRestart"], true);
+
+        let other = crate::AgentError::Internal("boom".to_string());
+        let acp_error = ClaudeAgent::claude_error_to_acp(&other);
+        assert_eq!(acp_error.code, -32603);
+    }
+
     #[tokio::test]
     async fn test_new_session() {
         let agent = create_test_agent().await;
@@ -4247,6 +4853,85 @@ mod tests {
         assert_eq!(meta.get("history_replayed").unwrap().as_u64().unwrap(), 0);
     }
 
+    #[tokio::test]
+    async fn test_claude_session_id_is_written_only_when_it_changes() {
+        let agent = create_test_agent().await;
+        let new_response = agent
+            .new_session(NewSessionRequest {
+                cwd: std::path::PathBuf::from("/tmp"),
+                mcp_servers: vec![],
+                meta: None,
+            })
+            .await
+            .unwrap();
+        let session_id = agent.parse_session_id(&new_response.session_id).unwrap();
+        let process_manager = agent.claude_client.process_manager();
+        let last_accessed = |agent: &ClaudeAgent| {
+            agent
+                .session_manager
+                .get_session(&session_id)
+                .unwrap()
+                .unwrap()
+                .last_accessed
+        };
+
+        process_manager.set_claude_session_id(session_id, "claude-abc".to_string());
+        agent.record_claude_session_id(&session_id);
+        let recorded = last_accessed(&agent);
+
+        // Later turns of the same conversation leave the stored session alone
+        agent.record_claude_session_id(&session_id);
+        assert_eq!(last_accessed(&agent), recorded);
+
+        process_manager.set_claude_session_id(session_id, "claude-def".to_string());
+        agent.record_claude_session_id(&session_id);
+        let session = agent
+            .session_manager
+            .get_session(&session_id)
+            .unwrap()
+            .unwrap();
+        assert_eq!(session.claude_session_id.as_deref(), Some("claude-def"));
+    }
+
+    #[tokio::test]
+    async fn test_load_session_resumes_claude_conversation() {
+        let agent = create_test_agent().await;
+        let new_response = agent
+            .new_session(NewSessionRequest {
+                cwd: std::path::PathBuf::from("/tmp"),
+                mcp_servers: vec![],
+                meta: None,
+            })
+            .await
+            .unwrap();
+        let session_id = agent.parse_session_id(&new_response.session_id).unwrap();
+        agent
+            .session_manager
+            .update_session(&session_id, |session| {
+                session.claude_session_id = Some("claude-abc".to_string());
+            })
+            .unwrap();
+
+        agent
+            .load_session(LoadSessionRequest {
+                session_id: new_response.session_id,
+                cwd: std::path::PathBuf::from("/tmp"),
+                mcp_servers: vec![],
+                meta: None,
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(
+            agent
+                .claude_client
+                .process_manager()
+                .claude_session_id(&session_id)
+                .as_deref(),
+            Some("claude-abc")
+        );
+    }
+
     #[tokio::test]
     async fn test_load_session_with_history_replay() {
         let agent = create_test_agent().await;
@@ -4517,34 +5202,602 @@ mod tests {
             meta: Some(serde_json::json!({"prompt": "Hello, world!"})),
         };
 
-        let response = agent.prompt(request).await.unwrap();
-        assert!(response.meta.is_some());
+        let response = agent.prompt(request).await.unwrap();
+        assert!(response.meta.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_cancel() {
+        let agent = create_test_agent().await;
+
+        let notification = CancelNotification {
+            session_id: SessionId("test_session".to_string().into()),
+            meta: Some(serde_json::json!({"reason": "user_request"})),
+        };
+
+        let result = agent.cancel(notification).await;
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_ext_method() {
+        let agent = create_test_agent().await;
+
+        let request = ExtRequest {
+            method: "test_method".to_string().into(),
+            params: Arc::from(RawValue::from_string("{}".to_string()).unwrap()),
+        };
+
+        let response = agent.ext_method(request).await.unwrap();
+        assert!(!response.get().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_audit_query_ext_method() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut config = AgentConfig::default();
+        config.audit.path = Some(temp_dir.path().join("audit.jsonl"));
+        let (agent, _notifications) = ClaudeAgent::new_with_policy_home(config, None)
+            .await
+            .unwrap();
+
+        // A Bash call reported by the claude CLI, then its result
+        let tool_call: SessionUpdate = serde_json::from_value(serde_json::json!({
+            "sessionUpdate": "tool_call",
+            "toolCallId": "toolu_1",
+            "title": "Run tests",
+            "rawInput": {"command": "API_TOKEN=abc123 cargo test"},
+            "_meta": {"tool_name": "Bash"}
+        }))
+        .unwrap();
+        let tool_result: SessionUpdate = serde_json::from_value(serde_json::json!({
+            "sessionUpdate": "tool_call_update",
+            "toolCallId": "toolu_1",
+            "status": "completed",
+            "content": [{"type": "content", "content": {"type": "text", "text": "ok"}}]
+        }))
+        .unwrap();
+        let cwd = std::path::PathBuf::from("/work");
+        agent
+            .audit_log
+            .record_tool_call_update("sess_a", Some(cwd.clone()), &tool_call);
+        agent
+            .audit_log
+            .record_tool_call_update("sess_a", Some(cwd), &tool_result);
+
+        let request = ExtRequest {
+            method: "claude_agent/audit/query".to_string().into(),
+            params: Arc::from(
+                RawValue::from_string(
+                    serde_json::json!({ "sessionId": "sess_a", "toolName": "Bash" }).to_string(),
+                )
+                .unwrap(),
+            ),
+        };
+        let response = agent.ext_method(request).await.unwrap();
+        let response: serde_json::Value = serde_json::from_str(response.get()).unwrap();
+        let records = response["records"].as_array().unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0]["event"], "tool_execution");
+        assert_eq!(records[0]["cwd"], "/work");
+        assert_eq!(records[0]["status"], "completed");
+        assert_eq!(records[0]["result_size"], 2);
+        assert_eq!(records[0]["exit_code"], 0);
+        assert_eq!(
+            records[0]["arguments"]["command"],
+            "API_TOKEN=[REDACTED] cargo test"
+        );
+        assert_eq!(response["truncated"], false);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_mcp_status_ext_method() {
+        let script = r#"
+            while IFS= read -r line; do
+              id=$(printf '%s' "$line" | sed -n 's/^{"id":\([0-9]*\),.*/\1/p')
+              case "$line" in
+                *'"method":"initialize"'*)
+                  printf '{"jsonrpc":"2.0","id":%s,"result":{}}\n' "$id" ;;
+                *'"method":"tools/list"'*)
+                  printf '{"jsonrpc":"2.0","id":%s,"result":{"tools":[{"name":"lookup"}]}}\n' "$id" ;;
+              esac
+            done
+        "#;
+        let mut config = AgentConfig::default();
+        config.mcp_servers = vec![
+            crate::config::McpServerConfig::Stdio(crate::config::StdioTransport {
+                name: "lookup".to_string(),
+                command: "sh".to_string(),
+                args: vec!["-c".to_string(), script.to_string()],
+                env: vec![],
+                cwd: None,
+            }),
+            crate::config::McpServerConfig::Stdio(crate::config::StdioTransport {
+                name: "missing".to_string(),
+                command: "nonexistent_command_12345".to_string(),
+                args: vec![],
+                env: vec![],
+                cwd: None,
+            }),
+        ];
+        let mock_handler = Arc::new(crate::user_prompt::MockPromptHandler::new(None));
+        let agent = ClaudeAgent::new_with_prompt_handler(config, mock_handler)
+            .await
+            .unwrap()
+            .0;
+
+        let request = ExtRequest {
+            method: "claude_agent/mcp/status".to_string().into(),
+            params: Arc::from(RawValue::from_string("{}".to_string()).unwrap()),
+        };
+        let response = agent.ext_method(request).await.unwrap();
+        let response: serde_json::Value = serde_json::from_str(response.get()).unwrap();
+        let servers = response["servers"].as_array().unwrap();
+        assert_eq!(servers.len(), 2);
+        assert_eq!(servers[0]["name"], "lookup");
+        assert_eq!(servers[0]["state"], "connected");
+        assert_eq!(servers[0]["transport"], "stdio");
+        assert_eq!(servers[0]["tools"], 1);
+        assert_eq!(servers[1]["name"], "missing");
+        assert_eq!(servers[1]["state"], "failed");
+        assert!(servers[1]["lastError"].is_string());
+
+        agent.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_session_usage_ext_method() {
+        let agent = create_test_agent().await;
+        let new_response = agent
+            .new_session(NewSessionRequest {
+                cwd: std::path::PathBuf::from("/tmp"),
+                mcp_servers: vec![],
+                meta: None,
+            })
+            .await
+            .unwrap();
+        let session_id = agent.parse_session_id(&new_response.session_id).unwrap();
+
+        let usage = crate::claude::TokenUsageInfo {
+            input_tokens: 1_000,
+            output_tokens: 200,
+            cache_creation_input_tokens: 300,
+            cache_read_input_tokens: 5_000,
+        };
+        let (report, exceeded) = agent
+            .record_turn_usage(&session_id, Some(&usage), Some(0.02))
+            .await;
+        assert!(!exceeded);
+        assert_eq!(report["turn_tokens"], 1_500);
+        assert_eq!(report["session"]["turns"], 1);
+
+        let request = ExtRequest {
+            method: "claude_agent/session/usage".to_string().into(),
+            params: Arc::from(
+                RawValue::from_string(
+                    serde_json::json!({ "sessionId": new_response.session_id.0 }).to_string(),
+                )
+                .unwrap(),
+            ),
+        };
+        let response = agent.ext_method(request).await.unwrap();
+        let response: serde_json::Value = serde_json::from_str(response.get()).unwrap();
+        assert_eq!(response["inputTokens"], 1_000);
+        assert_eq!(response["cacheReadInputTokens"], 5_000);
+        assert_eq!(response["totalTokens"], 1_500);
+        assert_eq!(response["totalCostUsd"], 0.02);
+        assert_eq!(response["turns"], 1);
+    }
+
+    #[tokio::test]
+    async fn test_record_turn_usage_enforces_token_limit() {
+        let agent = create_test_agent().await;
+        let session_id = agent
+            .session_manager
+            .create_session(std::path::PathBuf::from("/tmp"), None)
+            .unwrap();
+
+        let usage = crate::claude::TokenUsageInfo {
+            input_tokens: agent.config().max_tokens_per_turn,
+            output_tokens: 1,
+            ..Default::default()
+        };
+        let (_, exceeded) = agent
+            .record_turn_usage(&session_id, Some(&usage), None)
+            .await;
+        assert!(exceeded);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_streaming_turn_is_stopped_once_over_token_limit() {
+        use std::os::unix::fs::PermissionsExt;
+
+        // A stand-in claude that reports growing usage and keeps the turn open until it
+        // is interrupted
+        let temp_dir = tempfile::tempdir().unwrap();
+        let script = temp_dir.path().join("fake-claude");
+        std::fs::write(
+            &script,
+            r#"#!/bin/sh
+read prompt
+echo '{"type":"stream_event","event":{"type":"message_start","message":{"usage":{"input_tokens":40,"output_tokens":1}}}}'
+echo '{"type":"stream_event","event":{"type":"content_block_delta","delta":{"text":"Once upon"}}}'
+echo '{"type":"stream_event","event":{"type":"message_delta","usage":{"output_tokens":80}}}'
+read interrupt
+echo '{"type":"result","subtype":"error_during_execution","total_cost_usd":0.02,"usage":{"input_tokens":40,"output_tokens":80}}'
+exec sleep 30
+"#,
+        )
+        .unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let mut config = AgentConfig {
+            max_tokens_per_turn: 100,
+            ..Default::default()
+        };
+        config.claude.binary_path = script;
+        let (agent, _notifications) = ClaudeAgent::new_with_policy_home(config, None)
+            .await
+            .unwrap();
+        let new_response = agent
+            .new_session(NewSessionRequest {
+                cwd: std::path::PathBuf::from("/tmp"),
+                mcp_servers: vec![],
+                meta: None,
+            })
+            .await
+            .unwrap();
+        let session_id = agent.parse_session_id(&new_response.session_id).unwrap();
+        agent
+            .session_manager
+            .update_session(&session_id, |session| {
+                session.client_capabilities = Some(agent_client_protocol::ClientCapabilities {
+                    fs: Default::default(),
+                    terminal: false,
+                    meta: Some(serde_json::json!({"streaming": true})),
+                });
+            })
+            .unwrap();
+
+        let response = tokio::time::timeout(
+            std::time::Duration::from_secs(10),
+            agent.prompt(PromptRequest {
+                session_id: new_response.session_id,
+                prompt: vec![ContentBlock::Text(TextContent {
+                    text: "Tell me a story".to_string(),
+                    annotations: None,
+                    meta: None,
+                })],
+                meta: None,
+            }),
+        )
+        .await
+        .expect("the turn should stop without waiting for claude")
+        .unwrap();
+
+        assert_eq!(response.stop_reason, StopReason::MaxTokens);
+        let meta = response.meta.unwrap();
+        assert_eq!(meta["stopped_during_streaming"], true);
+        assert_eq!(meta["turn_tokens"], 120);
+        assert_eq!(meta["partial_response_length"], "Once upon".len());
+        assert_eq!(meta["usage"]["turn_cost_usd"], 0.02);
+        // Interrupting keeps the process and its conversation
+        assert!(
+            agent
+                .claude_client
+                .process_manager()
+                .has_session(&session_id)
+                .await
+        );
+
+        let session = agent
+            .session_manager
+            .get_session(&session_id)
+            .unwrap()
+            .unwrap();
+        assert_eq!(session.usage.total_tokens(), 120);
+        assert_eq!(session.usage.total_cost_usd, 0.02);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_non_streaming_turn_audits_tool_calls() {
+        use std::os::unix::fs::PermissionsExt;
+
+        // A stand-in claude that runs a failing command and answers without streaming
+        let temp_dir = tempfile::tempdir().unwrap();
+        let script = temp_dir.path().join("fake-claude");
+        std::fs::write(
+            &script,
+            r#"#!/bin/sh
+read prompt
+cat <<'EOF'
+{"type":"assistant","message":{"content":[{"type":"tool_use","id":"toolu_1","name":"Bash","input":{"command":"make"}}]}}
+{"type":"user","message":{"role":"user","content":[{"type":"tool_result","tool_use_id":"toolu_1","content":"Exit code 2\nmake: *** No targets.","is_error":true}]}}
+{"type":"result","subtype":"success","stop_reason":"end_turn"}
+EOF
+exec sleep 30
+"#,
+        )
+        .unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let mut config = AgentConfig::default();
+        config.claude.binary_path = script;
+        config.audit.path = Some(temp_dir.path().join("audit.jsonl"));
+        let (agent, _notifications) = ClaudeAgent::new_with_policy_home(config, None)
+            .await
+            .unwrap();
+        let new_response = agent
+            .new_session(NewSessionRequest {
+                cwd: std::path::PathBuf::from("/tmp"),
+                mcp_servers: vec![],
+                meta: None,
+            })
+            .await
+            .unwrap();
+
+        let response = tokio::time::timeout(
+            std::time::Duration::from_secs(10),
+            agent.prompt(PromptRequest {
+                session_id: new_response.session_id.clone(),
+                prompt: vec![ContentBlock::Text(TextContent {
+                    text: "Build it".to_string(),
+                    annotations: None,
+                    meta: None,
+                })],
+                meta: None,
+            }),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        assert_eq!(response.stop_reason, StopReason::EndTurn);
+
+        let response = agent
+            .audit_log
+            .query(&crate::audit::AuditQuery::default())
+            .await
+            .unwrap();
+        assert_eq!(response.records.len(), 1);
+        let record = &response.records[0];
+        assert_eq!(record.session_id, new_response.session_id.0.as_ref());
+        assert_eq!(record.tool_name, "Bash");
+        assert_eq!(record.cwd, Some(std::path::PathBuf::from("/tmp")));
+        assert_eq!(record.status.as_deref(), Some("failed"));
+        assert_eq!(record.exit_code, Some(2));
+        assert_eq!(
+            record.result_size,
+            Some("Exit code 2\nmake: *** No targets.".len())
+        );
+        assert_eq!(record.error.as_deref(), Some("Exit code 2"));
+    }
+
+    #[tokio::test]
+    async fn test_prompt_over_token_limit_is_refused_before_calling_claude() {
+        let config = AgentConfig {
+            max_tokens_per_turn: 10,
+            ..Default::default()
+        };
+        let (agent, _notifications) = ClaudeAgent::new_with_policy_home(config, None)
+            .await
+            .unwrap();
+        let new_response = agent
+            .new_session(NewSessionRequest {
+                cwd: std::path::PathBuf::from("/tmp"),
+                mcp_servers: vec![],
+                meta: None,
+            })
+            .await
+            .unwrap();
+
+        let response = agent
+            .prompt(PromptRequest {
+                session_id: new_response.session_id,
+                prompt: vec![ContentBlock::Text(TextContent {
+                    text: "a prompt estimated at well over ten tokens".repeat(4),
+                    annotations: None,
+                    meta: None,
+                })],
+                meta: None,
+            })
+            .await
+            .unwrap();
+        assert_eq!(response.stop_reason, StopReason::MaxTokens);
+        let meta = response.meta.unwrap();
+        assert_eq!(meta["max_tokens_per_turn"], 10);
+        assert!(meta["turn_tokens"].as_u64().unwrap() > 10);
+    }
+
+    #[tokio::test]
+    async fn test_session_budget_warns_and_refuses_prompts() {
+        let mut config = AgentConfig::default();
+        config.budget.max_session_cost_usd = Some(0.01);
+        let (agent, _notifications) = ClaudeAgent::new_with_policy_home(config, None)
+            .await
+            .unwrap();
+        let mut outgoing = agent.client.take_outgoing_receiver().unwrap();
+        let new_response = agent
+            .new_session(NewSessionRequest {
+                cwd: std::path::PathBuf::from("/tmp"),
+                mcp_servers: vec![],
+                meta: None,
+            })
+            .await
+            .unwrap();
+        let session_id = agent.parse_session_id(&new_response.session_id).unwrap();
+
+        // 60% of the budget triggers the 50% warning
+        let (_, exhausted) = agent
+            .record_turn_usage(&session_id, None, Some(0.006))
+            .await;
+        assert!(!exhausted);
+        let notification: serde_json::Value =
+            serde_json::from_str(&outgoing.try_recv().unwrap()).unwrap();
+        assert_eq!(notification["method"], "_claude_agent/budget_warning");
+        assert_eq!(notification["params"]["sessionId"], session_id.to_string());
+        assert_eq!(notification["params"]["warning"]["threshold"], 0.5);
+
+        let (report, exhausted) = agent
+            .record_turn_usage(&session_id, None, Some(0.012))
+            .await;
+        assert!(exhausted);
+        assert_eq!(report["budget_exhausted"]["scope"], "session");
+
+        // Further prompts are refused without calling Claude
+        let response = agent
+            .prompt(PromptRequest {
+                session_id: new_response.session_id,
+                prompt: vec![ContentBlock::Text(TextContent {
+                    text: "Hello".to_string(),
+                    annotations: None,
+                    meta: None,
+                })],
+                meta: None,
+            })
+            .await
+            .unwrap();
+        assert_eq!(response.stop_reason, StopReason::MaxTokens);
+        assert!(response.meta.unwrap()["budget_exhausted"].is_object());
+    }
+
+    #[tokio::test]
+    async fn test_reload_config_applies_live_fields() {
+        let agent = create_test_agent().await;
+        let session = agent
+            .new_session(NewSessionRequest {
+                cwd: std::path::PathBuf::from("/tmp"),
+                mcp_servers: vec![],
+                meta: None,
+            })
+            .await
+            .unwrap();
+
+        let config = AgentConfig {
+            max_turn_requests: 3,
+            notification_buffer_size: 10,
+            permission_policies: Some(vec![crate::permissions::PermissionPolicy {
+                tool_pattern: "*".to_string(),
+                default_action: crate::permissions::PolicyAction::Deny,
+                require_user_consent: false,
+                allow_always_option: false,
+                risk_level: crate::permissions::RiskLevel::High,
+                conditions: Vec::new(),
+            }]),
+            ..Default::default()
+        };
+
+        let reload = agent.reload_config(config).await.unwrap();
+        assert!(reload.applied.contains(&"max_turn_requests".to_string()));
+        assert!(reload.applied.contains(&"permission_policies".to_string()));
+        assert_eq!(reload.restart_required, ["notification_buffer_size"]);
+        assert!(!reload.tools_changed);
+
+        let config = agent.config();
+        assert_eq!(config.max_turn_requests, 3);
+        assert_eq!(
+            config.notification_buffer_size,
+            AgentConfig::default().notification_buffer_size
+        );
+        let evaluation = agent
+            .permission_engine
+            .evaluate_tool_call("fs_read", &serde_json::json!({}), None)
+            .await
+            .unwrap();
+        assert!(matches!(
+            evaluation,
+            crate::permissions::PolicyEvaluation::Denied { .. }
+        ));
+
+        // Sessions survive the reload, and an invalid config leaves everything in place
+        let session_id = agent.parse_session_id(&session.session_id).unwrap();
+        assert!(agent
+            .session_manager
+            .get_session(&session_id)
+            .unwrap()
+            .is_some());
+        let mut invalid = AgentConfig::default();
+        invalid.permission_store.expire_after_days = Some(0);
+        assert!(agent.reload_config(invalid).await.is_err());
+        assert_eq!(agent.config().max_turn_requests, 3);
     }
 
     #[tokio::test]
-    async fn test_cancel() {
+    async fn test_watch_config_reloads_a_config_file() {
         let agent = create_test_agent().await;
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("config.json");
+        let source = crate::config_watcher::ConfigFile::new(&path);
+        let (reload_tx, mut reload_rx) = tokio::sync::mpsc::unbounded_channel();
 
-        let notification = CancelNotification {
-            session_id: SessionId("test_session".to_string().into()),
-            meta: Some(serde_json::json!({"reason": "user_request"})),
+        let watch = agent.watch_config(&source, Duration::from_millis(10), |config, reload| {
+            let _ = reload_tx.send((config.max_turn_requests, reload.applied.clone()));
+        });
+        let change = async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            std::fs::write(&path, "not json").unwrap();
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            let config = AgentConfig {
+                max_turn_requests: 7,
+                ..Default::default()
+            };
+            std::fs::write(&path, serde_json::to_string(&config).unwrap()).unwrap();
+            reload_rx.recv().await
+        };
+        let reload = tokio::select! {
+            _ = watch => unreachable!("watching only stops when dropped"),
+            reload = tokio::time::timeout(Duration::from_secs(5), change) => reload,
         };
 
-        let result = agent.cancel(notification).await;
-        assert!(result.is_ok());
+        assert_eq!(
+            reload.unwrap(),
+            Some((7, vec!["max_turn_requests".to_string()]))
+        );
+        assert_eq!(agent.config().max_turn_requests, 7);
     }
 
     #[tokio::test]
-    async fn test_ext_method() {
+    async fn test_watch_config_reloads_for_a_session_policy_file() {
         let agent = create_test_agent().await;
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("config.json");
+        std::fs::write(
+            &path,
+            serde_json::to_string(&AgentConfig::default()).unwrap(),
+        )
+        .unwrap();
+        let source = crate::config_watcher::ConfigFile::new(&path);
+        let project = tempfile::tempdir().unwrap();
+        let (reload_tx, mut reload_rx) = tokio::sync::mpsc::unbounded_channel();
 
-        let request = ExtRequest {
-            method: "test_method".to_string().into(),
-            params: Arc::from(RawValue::from_string("{}".to_string()).unwrap()),
+        let watch = agent.watch_config(&source, Duration::from_millis(10), |_, reload| {
+            let _ = reload_tx.send(reload.clone());
+        });
+        let change = async {
+            // The session opens after watching started
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            agent
+                .session_manager
+                .create_session(project.path().to_path_buf(), None)
+                .unwrap();
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            std::fs::create_dir_all(project.path().join(".claude-agent")).unwrap();
+            std::fs::write(
+                project.path().join(".claude-agent/policy.toml"),
+                "[[rules]]\ntool_pattern = \"fs_write\"\ndefault_action = \"Deny\"\n",
+            )
+            .unwrap();
+            reload_rx.recv().await
+        };
+        let reload = tokio::select! {
+            _ = watch => unreachable!("watching only stops when dropped"),
+            reload = tokio::time::timeout(Duration::from_secs(5), change) => reload,
         };
 
-        let response = agent.ext_method(request).await.unwrap();
-        assert!(!response.get().is_empty());
+        // A reload loads the policy files again, dropping the cached project rules
+        assert!(reload.unwrap().is_some());
     }
 
     #[tokio::test]
@@ -4567,13 +5820,29 @@ mod tests {
     #[tokio::test]
     async fn test_agent_creation() {
         let config = AgentConfig::default();
-        let result = ClaudeAgent::new(config).await;
+        let result = ClaudeAgent::new_with_policy_home(config, None).await;
         assert!(result.is_ok());
 
         let (agent, _receiver) = result.unwrap();
         assert!(agent.capabilities.meta.is_some());
     }
 
+    #[tokio::test]
+    async fn test_agent_creation_skips_broken_home_policy() {
+        let home = tempfile::TempDir::new().unwrap();
+        std::fs::create_dir_all(home.path().join(".claude-agent")).unwrap();
+        std::fs::write(
+            home.path().join(".claude-agent/policy.toml"),
+            "[[rules]]\ndefault_action = \"Allow\"\n",
+        )
+        .unwrap();
+
+        let result =
+            ClaudeAgent::new_with_policy_home(AgentConfig::default(), Some(home.path().into()))
+                .await;
+        assert!(result.is_ok());
+    }
+
     #[tokio::test]
     async fn test_full_prompt_flow() {
         let agent = create_test_agent().await;
@@ -4808,7 +6077,9 @@ mod tests {
     async fn create_test_agent_with_notifications(
     ) -> (ClaudeAgent, broadcast::Receiver<SessionNotification>) {
         let config = AgentConfig::default();
-        ClaudeAgent::new(config).await.unwrap()
+        ClaudeAgent::new_with_policy_home(config, None)
+            .await
+            .unwrap()
     }
 
     #[tokio::test]
@@ -6864,50 +8135,70 @@ mod tests {
         assert_eq!(response.content, "Line 2\nLine 3"); // Should normalize to LF
     }
 
+    /// Connect a client declaring every file and terminal capability
+    async fn connect_client(agent: &ClaudeAgent) -> tokio::sync::mpsc::UnboundedReceiver<String> {
+        *agent.client_capabilities.write().await =
+            Some(agent_client_protocol::ClientCapabilities {
+                fs: agent_client_protocol::FileSystemCapability {
+                    read_text_file: true,
+                    write_text_file: true,
+                    meta: None,
+                },
+                terminal: true,
+                meta: None,
+            });
+        agent.client.take_outgoing_receiver().unwrap()
+    }
+
+    /// Answer the next request the agent sends to the client with `result`
+    async fn answer_client_request(
+        agent: &ClaudeAgent,
+        outgoing: &mut tokio::sync::mpsc::UnboundedReceiver<String>,
+        result: serde_json::Value,
+    ) -> serde_json::Value {
+        let request: serde_json::Value =
+            serde_json::from_str(&outgoing.recv().await.unwrap()).unwrap();
+        assert!(agent.client.handle_response(&serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": request["id"],
+            "result": result,
+        })));
+        request
+    }
+
     #[tokio::test]
-    async fn test_fs_read_text_file_ext_method_routing() {
+    async fn test_fs_read_text_file_goes_to_the_client() {
         let (agent, session_id) = setup_agent_with_session().await;
+        let mut outgoing = connect_client(&agent).await;
 
-        use std::io::Write;
-        use tempfile::NamedTempFile;
-
-        // Create a test file
-        let mut temp_file = NamedTempFile::new().unwrap();
-        temp_file.write_all(b"Test content for ext method").unwrap();
-        temp_file.flush().unwrap();
+        let params = ReadTextFileParams {
+            session_id: session_id.clone(),
+            path: "/project/src/main.rs".to_string(),
+            line: Some(2),
+            limit: None,
+        };
+        let (response, request) = tokio::join!(
+            agent.handle_read_text_file(params),
+            answer_client_request(
+                &agent,
+                &mut outgoing,
+                serde_json::json!({"content": "unsaved buffer"})
+            )
+        );
 
-        // Test through ext_method interface
-        let params = serde_json::json!({
-            "sessionId": session_id,
-            "path": temp_file.path().to_string_lossy(),
-            "line": null,
-            "limit": null
-        });
+        assert_eq!(request["method"], "fs/read_text_file");
+        assert_eq!(request["params"]["path"], "/project/src/main.rs");
+        assert_eq!(request["params"]["line"], 2);
+        assert_eq!(response.unwrap().content, "unsaved buffer");
 
-        println!("Parameters being sent: {}", params);
-        let params_raw = match agent_client_protocol::RawValue::from_string(params.to_string()) {
-            Ok(raw) => raw,
-            Err(e) => {
-                println!("Failed to create RawValue: {:?}", e);
-                panic!("RawValue creation failed");
-            }
-        };
+        // The client's own methods are not served as extension methods
         let ext_request = agent_client_protocol::ExtRequest {
             method: "fs/read_text_file".into(),
-            params: Arc::from(params_raw),
-        };
-
-        let result = match agent.ext_method(ext_request).await {
-            Ok(result) => result,
-            Err(e) => {
-                println!("ext_method failed with error: {:?}", e);
-                panic!("ext_method should have succeeded");
-            }
+            params: Arc::from(RawValue::from_string("{}".to_string()).unwrap()),
         };
-
-        // Parse the response
+        let result = agent.ext_method(ext_request).await.unwrap();
         let response: serde_json::Value = serde_json::from_str(result.get()).unwrap();
-        assert_eq!(response["content"], "Test content for ext method");
+        assert_eq!(response["result"], "Extension method not implemented");
     }
 
     #[tokio::test]
@@ -7090,37 +8381,29 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_fs_write_text_file_ext_method_routing() {
+    async fn test_fs_write_text_file_goes_to_the_client() {
         use tempfile::TempDir;
 
         let (agent, session_id) = setup_agent_with_session().await;
-
+        let mut outgoing = connect_client(&agent).await;
         let temp_dir = TempDir::new().unwrap();
-        let file_path = temp_dir.path().join("ext_method_test.txt");
-        let file_path_str = file_path.to_string_lossy().to_string();
-
-        // Test the ext_method routing for fs/write_text_file
-        let params = serde_json::json!({
-            "sessionId": session_id,
-            "path": file_path_str,
-            "content": "Test content via ext_method"
-        });
+        let file_path = temp_dir.path().join("client_write.txt");
 
-        let params_raw = agent_client_protocol::RawValue::from_string(params.to_string()).unwrap();
-        let ext_request = agent_client_protocol::ExtRequest {
-            method: "fs/write_text_file".into(),
-            params: Arc::from(params_raw),
+        let params = WriteTextFileParams {
+            session_id,
+            path: file_path.to_string_lossy().to_string(),
+            content: "Written by the client".to_string(),
         };
+        let (response, request) = tokio::join!(
+            agent.handle_write_text_file(params),
+            answer_client_request(&agent, &mut outgoing, serde_json::Value::Null)
+        );
 
-        let result = agent.ext_method(ext_request).await.unwrap();
-
-        // Parse the response - should be null for successful write
-        let response: serde_json::Value = serde_json::from_str(result.get()).unwrap();
-        assert_eq!(response, serde_json::Value::Null);
-
-        // Verify the file was actually written
-        let written_content = tokio::fs::read_to_string(&file_path).await.unwrap();
-        assert_eq!(written_content, "Test content via ext_method");
+        assert_eq!(request["method"], "fs/write_text_file");
+        assert_eq!(request["params"]["content"], "Written by the client");
+        assert_eq!(response.unwrap(), serde_json::Value::Null);
+        // The agent left the write to the client
+        assert!(!file_path.exists());
     }
 
     #[tokio::test]
@@ -7348,49 +8631,34 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_terminal_output_ext_method_routing() {
-        use crate::terminal_manager::TerminalCreateParams;
-        use tempfile::TempDir;
+    async fn test_terminal_output_goes_to_the_client() {
+        use crate::terminal_manager::TerminalOutputParams;
 
         let (agent, session_id) = setup_agent_with_session().await;
-        let temp_dir = TempDir::new().unwrap();
-
-        // Create terminal
-        let tool_handler = agent.tool_handler.read().await;
-        let terminal_manager = tool_handler.get_terminal_manager();
-
-        let create_params = TerminalCreateParams {
-            session_id: session_id.clone(),
-            command: "echo".to_string(),
-            args: Some(vec!["test".to_string()]),
-            env: None,
-            cwd: Some(temp_dir.path().to_string_lossy().to_string()),
-            output_byte_limit: None,
-        };
-
-        let terminal_id = terminal_manager
-            .create_terminal_with_command(&agent.session_manager, create_params)
-            .await
-            .unwrap();
-
-        // Test through ext_method interface
-        let params = serde_json::json!({
-            "sessionId": session_id,
-            "terminalId": terminal_id
-        });
-
-        let params_raw = agent_client_protocol::RawValue::from_string(params.to_string()).unwrap();
-        let ext_request = agent_client_protocol::ExtRequest {
-            method: "terminal/output".into(),
-            params: Arc::from(params_raw),
-        };
+        let mut outgoing = connect_client(&agent).await;
 
-        let result = agent.ext_method(ext_request).await.unwrap();
+        let params = TerminalOutputParams {
+            session_id,
+            terminal_id: "term_client".to_string(),
+        };
+        let (response, request) = tokio::join!(
+            agent.handle_terminal_output(params),
+            answer_client_request(
+                &agent,
+                &mut outgoing,
+                serde_json::json!({
+                    "output": "test\n",
+                    "truncated": false,
+                    "exitStatus": {"exitCode": 0, "signal": null}
+                })
+            )
+        );
 
-        // Parse the response
-        let response: serde_json::Value = serde_json::from_str(result.get()).unwrap();
-        assert!(response.get("output").is_some());
-        assert!(response.get("truncated").is_some());
+        assert_eq!(request["method"], "terminal/output");
+        assert_eq!(request["params"]["terminalId"], "term_client");
+        let response = response.unwrap();
+        assert_eq!(response.output, "test\n");
+        assert_eq!(response.exit_status.unwrap().exit_code, Some(0));
     }
 
     #[tokio::test]
@@ -7424,7 +8692,9 @@ mod tests {
     #[tokio::test]
     async fn test_user_message_chunks_sent_on_prompt() {
         let config = AgentConfig::default();
-        let (agent, _notification_receiver) = ClaudeAgent::new(config).await.unwrap();
+        let (agent, _notification_receiver) = ClaudeAgent::new_with_policy_home(config, None)
+            .await
+            .unwrap();
         let agent = Arc::new(agent);
 
         let init_request = InitializeRequest {
@@ -7922,6 +9192,261 @@ mod tests {
         );
     }
 
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_mcp_prompts_and_resources_expand_prompts() {
+        // Offers one prompt and one resource
+        let script = r#"
+            while IFS= read -r line; do
+              id=$(printf '%s' "$line" | sed -n 's/^{"id":\([0-9]*\),.*/\1/p')
+              case "$line" in
+                *'"method":"initialize"'*)
+                  printf '{"jsonrpc":"2.0","id":%s,"result":{"capabilities":{"resources":{},"prompts":{}}}}\n' "$id" ;;
+                *'"method":"tools/list"'*)
+                  printf '{"jsonrpc":"2.0","id":%s,"result":{"tools":[]}}\n' "$id" ;;
+                *'"method":"resources/list"'*)
+                  printf '{"jsonrpc":"2.0","id":%s,"result":{"resources":[{"uri":"memo://todo","name":"todo"}]}}\n' "$id" ;;
+                *'"method":"resources/read"'*)
+                  printf '{"jsonrpc":"2.0","id":%s,"result":{"contents":[{"uri":"memo://todo","mimeType":"text/plain","text":"ship it"}]}}\n' "$id" ;;
+                *'"method":"prompts/list"'*)
+                  printf '{"jsonrpc":"2.0","id":%s,"result":{"prompts":[{"name":"greet","arguments":[{"name":"who","required":true}]}]}}\n' "$id" ;;
+                *'"method":"prompts/get"'*)
+                  printf '{"jsonrpc":"2.0","id":%s,"result":{"messages":[{"role":"user","content":{"type":"text","text":"Say hello to Ada"}}]}}\n' "$id" ;;
+              esac
+            done
+        "#;
+        let mut config = AgentConfig::default();
+        config.mcp_servers = vec![crate::config::McpServerConfig::Stdio(
+            crate::config::StdioTransport {
+                name: "memo".to_string(),
+                command: "sh".to_string(),
+                args: vec!["-c".to_string(), script.to_string()],
+                env: vec![],
+                cwd: None,
+            },
+        )];
+        let mock_handler = Arc::new(crate::user_prompt::MockPromptHandler::new(None));
+        let agent = ClaudeAgent::new_with_prompt_handler(config, mock_handler)
+            .await
+            .unwrap()
+            .0;
+
+        let session_id = SessionId("test_session_mcp_prompts".to_string().into());
+        let commands = agent.get_available_commands_for_session(&session_id).await;
+        let greet = commands
+            .iter()
+            .find(|cmd| cmd.name == "memo:greet")
+            .expect("MCP prompt should be offered as a command");
+        assert_eq!(greet.meta.as_ref().unwrap()["source"], "mcp_prompt");
+        assert!(matches!(
+            &greet.input,
+            Some(agent_client_protocol::AvailableCommandInput::Unstructured { hint }) if hint == "who"
+        ));
+
+        let link = |uri: &str| {
+            ContentBlock::ResourceLink(agent_client_protocol::ResourceLink {
+                annotations: None,
+                description: None,
+                mime_type: None,
+                name: "link".to_string(),
+                size: None,
+                title: None,
+                uri: uri.to_string(),
+                meta: None,
+            })
+        };
+        let request = PromptRequest {
+            session_id: session_id.clone(),
+            prompt: vec![
+                ContentBlock::Text(TextContent {
+                    text: "/memo:greet Ada".to_string(),
+                    annotations: None,
+                    meta: None,
+                }),
+                link("memo://todo"),
+                link("https://example.com/other"),
+            ],
+            meta: None,
+        };
+        let expanded = agent.expand_mcp_content(request).await.unwrap();
+
+        assert!(
+            matches!(&expanded.prompt[0], ContentBlock::Text(text) if text.text == "Say hello to Ada")
+        );
+        match &expanded.prompt[1] {
+            ContentBlock::Resource(embedded) => match &embedded.resource {
+                agent_client_protocol::EmbeddedResourceResource::TextResourceContents(text) => {
+                    assert_eq!(text.text, "ship it");
+                    assert_eq!(text.mime_type.as_deref(), Some("text/plain"));
+                }
+                other => panic!("Expected text contents, got {:?}", other),
+            },
+            other => panic!("Expected an embedded resource, got {:?}", other),
+        }
+        // Links to resources no server lists stay links
+        assert!(matches!(&expanded.prompt[2], ContentBlock::ResourceLink(_)));
+
+        // A prompt command without its required argument is rejected
+        let request = PromptRequest {
+            session_id,
+            prompt: vec![ContentBlock::Text(TextContent {
+                text: "/memo:greet".to_string(),
+                annotations: None,
+                meta: None,
+            })],
+            meta: None,
+        };
+        let error = agent.expand_mcp_content(request).await.unwrap_err();
+        assert_eq!(error.code, -32602);
+
+        agent.shutdown().await.unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_mcp_prompt_roles_tool_names_and_resource_updates() {
+        // A tool and a prompt share a name, the prompt has an assistant turn, and the
+        // resource reports an update as soon as it is subscribed to
+        let script = r#"
+            while IFS= read -r line; do
+              id=$(printf '%s' "$line" | sed -n 's/^{"id":\([0-9]*\),.*/\1/p')
+              case "$line" in
+                *'"method":"initialize"'*)
+                  printf '{"jsonrpc":"2.0","id":%s,"result":{"capabilities":{"tools":{},"resources":{"subscribe":true},"prompts":{}}}}\n' "$id" ;;
+                *'"method":"tools/list"'*)
+                  printf '{"jsonrpc":"2.0","id":%s,"result":{"tools":[{"name":"greet"}]}}\n' "$id" ;;
+                *'"method":"resources/list"'*)
+                  printf '{"jsonrpc":"2.0","id":%s,"result":{"resources":[{"uri":"memo://todo","name":"todo"}]}}\n' "$id" ;;
+                *'"method":"resources/read"'*)
+                  printf '{"jsonrpc":"2.0","id":%s,"result":{"contents":[{"uri":"memo://todo","text":"ship it"}]}}\n' "$id" ;;
+                *'"method":"resources/subscribe"'*)
+                  printf '{"jsonrpc":"2.0","id":%s,"result":{}}\n' "$id"
+                  printf '{"jsonrpc":"2.0","method":"notifications/resources/updated","params":{"uri":"memo://todo"}}\n' ;;
+                *'"method":"prompts/list"'*)
+                  printf '{"jsonrpc":"2.0","id":%s,"result":{"prompts":[{"name":"greet"}]}}\n' "$id" ;;
+                *'"method":"prompts/get"'*)
+                  printf '{"jsonrpc":"2.0","id":%s,"result":{"messages":[{"role":"user","content":{"type":"text","text":"Say hello"}},{"role":"assistant","content":{"type":"text","text":"Hello!"}}]}}\n' "$id" ;;
+              esac
+            done
+        "#;
+        let mut config = AgentConfig::default();
+        config.mcp_servers = vec![crate::config::McpServerConfig::Stdio(
+            crate::config::StdioTransport {
+                name: "memo".to_string(),
+                command: "sh".to_string(),
+                args: vec!["-c".to_string(), script.to_string()],
+                env: vec![],
+                cwd: None,
+            },
+        )];
+        let mock_handler = Arc::new(crate::user_prompt::MockPromptHandler::new(None));
+        let agent = ClaudeAgent::new_with_prompt_handler(config, mock_handler)
+            .await
+            .unwrap()
+            .0;
+
+        let session_id = SessionId("test_session_mcp_updates".to_string().into());
+        let commands = agent.get_available_commands_for_session(&session_id).await;
+        let source = |name: &str| {
+            commands
+                .iter()
+                .find(|cmd| cmd.name == name)
+                .and_then(|cmd| cmd.meta.as_ref())
+                .map(|meta| meta["source"].clone())
+        };
+        assert_eq!(source("memo:greet"), Some(serde_json::json!("mcp_prompt")));
+        assert_eq!(
+            source("memo:tool:greet"),
+            Some(serde_json::json!("mcp_server"))
+        );
+
+        let text = |text: &str| {
+            ContentBlock::Text(TextContent {
+                text: text.to_string(),
+                annotations: None,
+                meta: None,
+            })
+        };
+        let texts = |prompt: &[ContentBlock]| -> Vec<String> {
+            prompt
+                .iter()
+                .map(|block| match block {
+                    ContentBlock::Text(text) => text.text.clone(),
+                    ContentBlock::Resource(_) => "<resource>".to_string(),
+                    other => panic!("Unexpected block {:?}", other),
+                })
+                .collect()
+        };
+        let expanded = agent
+            .expand_mcp_content(PromptRequest {
+                session_id: session_id.clone(),
+                prompt: vec![text("/memo:greet")],
+                meta: None,
+            })
+            .await
+            .unwrap();
+        assert_eq!(
+            texts(&expanded.prompt),
+            ["User: Say hello", "Assistant: Hello!"]
+        );
+
+        let expanded = agent
+            .expand_mcp_content(PromptRequest {
+                session_id: session_id.clone(),
+                prompt: vec![
+                    text("Remember this"),
+                    ContentBlock::ResourceLink(agent_client_protocol::ResourceLink {
+                        annotations: None,
+                        description: None,
+                        mime_type: None,
+                        name: "todo".to_string(),
+                        size: None,
+                        title: None,
+                        uri: "memo://todo".to_string(),
+                        meta: None,
+                    }),
+                ],
+                meta: None,
+            })
+            .await
+            .unwrap();
+        assert_eq!(texts(&expanded.prompt), ["Remember this", "<resource>"]);
+
+        // The update sent on subscribing reaches the session that linked the resource
+        tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            while !agent
+                .linked_resources
+                .lock()
+                .unwrap()
+                .updated
+                .contains_key(session_id.0.as_ref())
+            {
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("Resource update should arrive");
+
+        let other_session = SessionId("test_session_mcp_other".to_string().into());
+        for (session_id, expected) in [
+            (other_session, vec!["Next"]),
+            (session_id.clone(), vec!["Next", "<resource>"]),
+            (session_id, vec!["Next"]),
+        ] {
+            let expanded = agent
+                .expand_mcp_content(PromptRequest {
+                    session_id,
+                    prompt: vec![text("Next")],
+                    meta: None,
+                })
+                .await
+                .unwrap();
+            assert_eq!(texts(&expanded.prompt), expected);
+        }
+
+        agent.shutdown().await.unwrap();
+    }
+
     #[tokio::test]
     async fn test_editor_update_buffers_ext_method() {
         use std::collections::HashMap;
@@ -7964,7 +9489,7 @@ mod tests {
         let params = RawValue::from_string(params_json).unwrap();
 
         let request = ExtRequest {
-            method: "editor/update_buffers".to_string().into(),
+            method: "claude_agent/editor/update_buffers".to_string().into(),
             params: Arc::from(params),
         };
 
@@ -8027,12 +9552,12 @@ mod tests {
             unavailable_paths: vec![],
         };
 
-        // Send editor/update_buffers extension method
+        // Send _claude_agent/editor/update_buffers extension method
         let params_json = serde_json::to_string(&response).unwrap();
         let params = RawValue::from_string(params_json).unwrap();
 
         let request = ExtRequest {
-            method: "editor/update_buffers".to_string().into(),
+            method: "claude_agent/editor/update_buffers".to_string().into(),
             params: Arc::from(params),
         };
 
@@ -8091,7 +9616,8 @@ mod tests {
             meta: None,
         };
         let session_response = agent.new_session(new_session_request).await.unwrap();
-        let session_id: crate::session::SessionId = session_response.session_id.0.as_ref().parse().unwrap();
+        let session_id: crate::session::SessionId =
+            session_response.session_id.0.as_ref().parse().unwrap();
 
         // First prompt - should start with turn_request_count = 0, then increment to 1
         let prompt_request_1 = PromptRequest {
@@ -8108,8 +9634,16 @@ mod tests {
         assert_eq!(response_1.stop_reason, StopReason::EndTurn);
 
         // Check that turn_request_count is now 1
-        let session_1 = agent.session_manager.get_session(&session_id).unwrap().unwrap();
-        assert_eq!(session_1.get_turn_request_count(), 1, "First prompt should increment counter to 1");
+        let session_1 = agent
+            .session_manager
+            .get_session(&session_id)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            session_1.get_turn_request_count(),
+            1,
+            "First prompt should increment counter to 1"
+        );
 
         // Second prompt - should RESET to 0 then increment to 1
         let prompt_request_2 = PromptRequest {
@@ -8126,7 +9660,11 @@ mod tests {
         assert_eq!(response_2.stop_reason, StopReason::EndTurn);
 
         // Check that turn_request_count reset and is now 1 (not 2)
-        let session_2 = agent.session_manager.get_session(&session_id).unwrap().unwrap();
+        let session_2 = agent
+            .session_manager
+            .get_session(&session_id)
+            .unwrap()
+            .unwrap();
         assert_eq!(
             session_2.get_turn_request_count(),
             1,
@@ -8147,10 +9685,10 @@ mod tests {
         // Note: In current implementation, each prompt() call is a new turn (resets counters)
         // and only makes one LM request, so the limit won't be exceeded naturally.
 
-        let mut agent = create_test_agent().await;
+        let agent = create_test_agent().await;
 
         // Set limit to 0 to force immediate failure (any request exceeds limit)
-        agent.config.max_turn_requests = 0;
+        Arc::make_mut(&mut agent.config.write().unwrap()).max_turn_requests = 0;
 
         // Create a session with streaming capability
         let new_session_request = NewSessionRequest {
@@ -8159,20 +9697,24 @@ mod tests {
             meta: Some(serde_json::json!({"streaming": true})),
         };
         let session_response = agent.new_session(new_session_request).await.unwrap();
-        let session_id: crate::session::SessionId = session_response.session_id.0.as_ref().parse().unwrap();
+        let session_id: crate::session::SessionId =
+            session_response.session_id.0.as_ref().parse().unwrap();
 
         // Update session to enable streaming
-        agent.session_manager.update_session(&session_id, |session| {
-            session.client_capabilities = Some(agent_client_protocol::ClientCapabilities {
-                fs: agent_client_protocol::FileSystemCapability {
-                    read_text_file: true,
-                    write_text_file: true,
-                    meta: None,
-                },
-                terminal: true,
-                meta: Some(serde_json::json!({"streaming": true})),
-            });
-        }).unwrap();
+        agent
+            .session_manager
+            .update_session(&session_id, |session| {
+                session.client_capabilities = Some(agent_client_protocol::ClientCapabilities {
+                    fs: agent_client_protocol::FileSystemCapability {
+                        read_text_file: true,
+                        write_text_file: true,
+                        meta: None,
+                    },
+                    terminal: true,
+                    meta: Some(serde_json::json!({"streaming": true})),
+                });
+            })
+            .unwrap();
 
         // Prompt should be blocked immediately by streaming path check
         // Counter resets to 0, increments to 1, and 1 > 0 triggers limit
@@ -8195,7 +9737,10 @@ mod tests {
 
         // Verify metadata contains turn request info
         let meta = response.meta.unwrap();
-        assert_eq!(meta["turn_requests"], 1, "Should have reset to 0 then incremented to 1");
+        assert_eq!(
+            meta["turn_requests"], 1,
+            "Should have reset to 0 then incremented to 1"
+        );
         assert_eq!(meta["max_turn_requests"], 0);
     }
 }
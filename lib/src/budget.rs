@@ -0,0 +1,357 @@
+//! Spending budgets for sessions and the agent process
+//!
+//! Budgets are checked against the token usage and cost the claude CLI reports at the end
+//! of each turn, and token budgets also against the usage it reports while a turn streams.
+//! Once a session or the agent as a whole has used up a budget, the turn is stopped and
+//! further prompts are refused; warnings are raised as consumption crosses the configured
+//! thresholds.
+
+use crate::claude::TokenUsageInfo;
+use crate::config::BudgetConfig;
+use crate::session::SessionUsage;
+use serde::Serialize;
+use std::fmt;
+use std::sync::Mutex;
+
+/// What a budget applies to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BudgetScope {
+    /// A single session
+    Session,
+    /// All sessions of this agent process
+    Agent,
+}
+
+/// The quantity a budget limits
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BudgetResource {
+    /// Cost in USD
+    CostUsd,
+    /// Tokens, excluding cache reads
+    Tokens,
+}
+
+/// Consumption of a single budget
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BudgetStatus {
+    pub scope: BudgetScope,
+    pub resource: BudgetResource,
+    pub used: f64,
+    pub limit: f64,
+}
+
+impl BudgetStatus {
+    /// Fraction of the budget used so far
+    pub fn fraction(&self) -> f64 {
+        self.used / self.limit
+    }
+
+    /// Whether the budget is used up
+    pub fn is_exhausted(&self) -> bool {
+        self.used >= self.limit
+    }
+}
+
+impl fmt::Display for BudgetStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let scope = match self.scope {
+            BudgetScope::Session => "session",
+            BudgetScope::Agent => "agent",
+        };
+        let percent = self.fraction() * 100.0;
+        match self.resource {
+            BudgetResource::CostUsd => write!(
+                f,
+                "{} cost ${:.4} of ${:.2} ({:.0}%)",
+                scope, self.used, self.limit, percent
+            ),
+            BudgetResource::Tokens => write!(
+                f,
+                "{} tokens {} of {} ({:.0}%)",
+                scope, self.used, self.limit, percent
+            ),
+        }
+    }
+}
+
+/// A budget crossed one of the configured warning thresholds
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BudgetWarning {
+    pub status: BudgetStatus,
+    /// The highest threshold crossed by the turn
+    pub threshold: f64,
+}
+
+/// Tracks consumption against the configured budgets
+///
+/// Session usage is kept on each session; the tracker keeps the agent-wide totals for
+/// the lifetime of the process.
+#[derive(Debug)]
+pub struct BudgetTracker {
+    config: Mutex<BudgetConfig>,
+    agent_usage: Mutex<SessionUsage>,
+}
+
+impl BudgetTracker {
+    /// Create a tracker with no usage recorded
+    pub fn new(config: BudgetConfig) -> Self {
+        Self {
+            config: Mutex::new(config),
+            agent_usage: Mutex::new(SessionUsage::default()),
+        }
+    }
+
+    /// Replace the budget limits, keeping the usage recorded so far
+    pub fn set_config(&self, config: BudgetConfig) {
+        if let Ok(mut current) = self.config.lock() {
+            *current = config;
+        }
+    }
+
+    /// The budget limits currently applied
+    fn config(&self) -> BudgetConfig {
+        self.config
+            .lock()
+            .map(|config| config.clone())
+            .unwrap_or_default()
+    }
+
+    /// Usage of all sessions since the agent started
+    pub fn agent_usage(&self) -> SessionUsage {
+        self.agent_usage
+            .lock()
+            .map(|usage| usage.clone())
+            .unwrap_or_default()
+    }
+
+    /// The first exhausted budget for a session, if any
+    pub fn exhausted(&self, session_usage: &SessionUsage) -> Option<BudgetStatus> {
+        self.statuses(session_usage, &self.agent_usage())
+            .into_iter()
+            .find(BudgetStatus::is_exhausted)
+    }
+
+    /// The first budget a turn that is still streaming has used up, if any
+    ///
+    /// `turn` is the usage the turn reported so far on top of `session_usage`. Its cost is
+    /// only known once the turn ends, so only token budgets run out mid-turn.
+    pub fn exhausted_during_turn(
+        &self,
+        session_usage: &SessionUsage,
+        turn: &TokenUsageInfo,
+    ) -> Option<BudgetStatus> {
+        let with_turn = |usage: &SessionUsage| {
+            let mut usage = usage.clone();
+            usage.add_tokens(turn);
+            usage
+        };
+        self.statuses(&with_turn(session_usage), &with_turn(&self.agent_usage()))
+            .into_iter()
+            .find(BudgetStatus::is_exhausted)
+    }
+
+    /// Add a finished turn to the agent totals and return the warnings it triggered
+    ///
+    /// `before` and `after` are the session's usage around the turn.
+    pub fn record_turn(&self, before: &SessionUsage, after: &SessionUsage) -> Vec<BudgetWarning> {
+        let (agent_before, agent_after) = match self.agent_usage.lock() {
+            Ok(mut agent_usage) => {
+                let agent_before = agent_usage.clone();
+                agent_usage.input_tokens += after.input_tokens.saturating_sub(before.input_tokens);
+                agent_usage.output_tokens +=
+                    after.output_tokens.saturating_sub(before.output_tokens);
+                agent_usage.cache_creation_input_tokens += after
+                    .cache_creation_input_tokens
+                    .saturating_sub(before.cache_creation_input_tokens);
+                agent_usage.cache_read_input_tokens += after
+                    .cache_read_input_tokens
+                    .saturating_sub(before.cache_read_input_tokens);
+                agent_usage.total_cost_usd +=
+                    (after.total_cost_usd - before.total_cost_usd).max(0.0);
+                agent_usage.turns += after.turns.saturating_sub(before.turns);
+                (agent_before, agent_usage.clone())
+            }
+            Err(_) => return Vec::new(),
+        };
+
+        let config = self.config();
+        self.statuses(before, &agent_before)
+            .into_iter()
+            .zip(self.statuses(after, &agent_after))
+            .filter_map(|(previous, current)| {
+                config
+                    .warning_thresholds
+                    .iter()
+                    .copied()
+                    .filter(|threshold| {
+                        previous.fraction() < *threshold && current.fraction() >= *threshold
+                    })
+                    .reduce(f64::max)
+                    .map(|threshold| BudgetWarning {
+                        status: current,
+                        threshold,
+                    })
+            })
+            .collect()
+    }
+
+    /// Consumption of every configured budget, in a fixed order
+    fn statuses(
+        &self,
+        session_usage: &SessionUsage,
+        agent_usage: &SessionUsage,
+    ) -> Vec<BudgetStatus> {
+        let config = self.config();
+        let limits = [
+            (
+                BudgetScope::Session,
+                BudgetResource::CostUsd,
+                config.max_session_cost_usd,
+                session_usage.total_cost_usd,
+            ),
+            (
+                BudgetScope::Session,
+                BudgetResource::Tokens,
+                config.max_session_tokens.map(|limit| limit as f64),
+                session_usage.total_tokens() as f64,
+            ),
+            (
+                BudgetScope::Agent,
+                BudgetResource::CostUsd,
+                config.max_agent_cost_usd,
+                agent_usage.total_cost_usd,
+            ),
+            (
+                BudgetScope::Agent,
+                BudgetResource::Tokens,
+                config.max_agent_tokens.map(|limit| limit as f64),
+                agent_usage.total_tokens() as f64,
+            ),
+        ];
+
+        limits
+            .into_iter()
+            .filter_map(|(scope, resource, limit, used)| {
+                limit.map(|limit| BudgetStatus {
+                    scope,
+                    resource,
+                    used,
+                    limit,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usage_after(before: &SessionUsage, tokens: u64, total_cost_usd: f64) -> SessionUsage {
+        let mut after = before.clone();
+        after.record_turn(
+            Some(&TokenUsageInfo {
+                input_tokens: tokens,
+                ..Default::default()
+            }),
+            Some(total_cost_usd),
+        );
+        after
+    }
+
+    #[test]
+    fn test_unlimited_budget_never_exhausts() {
+        let tracker = BudgetTracker::new(BudgetConfig::default());
+        let before = SessionUsage::default();
+        let after = usage_after(&before, 1_000_000, 100.0);
+
+        assert!(tracker.record_turn(&before, &after).is_empty());
+        assert!(tracker.exhausted(&after).is_none());
+        assert_eq!(tracker.agent_usage().input_tokens, 1_000_000);
+    }
+
+    #[test]
+    fn test_session_cost_warnings_and_exhaustion() {
+        let tracker = BudgetTracker::new(BudgetConfig {
+            max_session_cost_usd: Some(1.0),
+            ..Default::default()
+        });
+
+        let start = SessionUsage::default();
+        let first = usage_after(&start, 10, 0.4);
+        assert!(tracker.record_turn(&start, &first).is_empty());
+
+        // Crossing 50% and 80% in one turn reports only the highest threshold
+        let second = usage_after(&first, 10, 0.85);
+        let warnings = tracker.record_turn(&first, &second);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].threshold, 0.8);
+        assert_eq!(warnings[0].status.scope, BudgetScope::Session);
+        assert!(tracker.exhausted(&second).is_none());
+
+        let third = usage_after(&second, 10, 1.2);
+        assert_eq!(tracker.record_turn(&second, &third)[0].threshold, 0.95);
+        let status = tracker.exhausted(&third).unwrap();
+        assert_eq!(status.resource, BudgetResource::CostUsd);
+        assert!(status.to_string().starts_with("session cost"));
+    }
+
+    #[test]
+    fn test_streaming_turn_exhausts_token_budgets() {
+        let tracker = BudgetTracker::new(BudgetConfig {
+            max_session_tokens: Some(1_000),
+            max_session_cost_usd: Some(0.5),
+            ..Default::default()
+        });
+        let session = usage_after(&SessionUsage::default(), 600, 0.4);
+        let turn = |tokens| TokenUsageInfo {
+            output_tokens: tokens,
+            ..Default::default()
+        };
+
+        assert!(tracker
+            .exhausted_during_turn(&session, &turn(300))
+            .is_none());
+        let status = tracker.exhausted_during_turn(&session, &turn(400)).unwrap();
+        assert_eq!(status.resource, BudgetResource::Tokens);
+        assert_eq!(status.used, 1_000.0);
+    }
+
+    #[test]
+    fn test_set_config_keeps_agent_usage() {
+        let tracker = BudgetTracker::new(BudgetConfig::default());
+        let before = SessionUsage::default();
+        let after = usage_after(&before, 10, 2.0);
+        tracker.record_turn(&before, &after);
+        assert!(tracker.exhausted(&after).is_none());
+
+        tracker.set_config(BudgetConfig {
+            max_agent_cost_usd: Some(1.0),
+            ..Default::default()
+        });
+        assert_eq!(tracker.agent_usage().total_cost_usd, 2.0);
+        assert_eq!(tracker.exhausted(&after).unwrap().scope, BudgetScope::Agent);
+    }
+
+    #[test]
+    fn test_agent_budget_spans_sessions() {
+        let tracker = BudgetTracker::new(BudgetConfig {
+            max_agent_tokens: Some(1_000),
+            ..Default::default()
+        });
+
+        let start = SessionUsage::default();
+        let session_a = usage_after(&start, 600, 0.0);
+        tracker.record_turn(&start, &session_a);
+        let session_b = usage_after(&start, 600, 0.0);
+        let warnings = tracker.record_turn(&start, &session_b);
+        assert_eq!(warnings[0].status.scope, BudgetScope::Agent);
+
+        // A fresh session is refused once the agent budget is used up
+        let status = tracker.exhausted(&SessionUsage::default()).unwrap();
+        assert_eq!(status.scope, BudgetScope::Agent);
+        assert_eq!(status.used, 1_200.0);
+    }
+}
@@ -8,16 +8,117 @@ use std::time::SystemTime;
 use tokio::sync::Mutex;
 
 use crate::{
+    audit::AuditLog,
     claude_process::{ClaudeProcess, ClaudeProcessManager},
     config::ClaudeConfig,
+    content_block_processor::{ContentProcessingSummary, ProcessedContent},
     error::Result,
-    protocol_translator::ProtocolTranslator,
+    protocol_translator::{
+        ControlRequest, ControlRequestKind, ProtocolTranslator, StreamUsage,
+        ToolPermissionDecision, ToolPermissionRequest,
+    },
     session::{MessageRole, SessionId},
 };
 
+/// Decides whether the claude CLI may run a tool
+///
+/// The CLI is spawned with `--permission-prompt-tool stdio`, so every tool call that
+/// needs approval is sent to the client as a control request and blocks until the
+/// handler returns a decision.
+#[async_trait::async_trait]
+pub trait ToolPermissionHandler: Send + Sync {
+    /// Decide whether the tool call described by `request` may run in `session_id`
+    async fn check_tool_permission(
+        &self,
+        session_id: &SessionId,
+        request: &ToolPermissionRequest,
+    ) -> ToolPermissionDecision;
+}
+
 /// Claude client wrapper with session management
 pub struct ClaudeClient {
     process_manager: Arc<ClaudeProcessManager>,
+    /// Handler answering tool permission checks; tool calls are denied when unset
+    permission_handler: std::sync::RwLock<Option<Arc<dyn ToolPermissionHandler>>>,
+    /// Log recording the tool calls claude runs; nothing is recorded when unset
+    audit_log: std::sync::RwLock<Option<Arc<AuditLog>>>,
+}
+
+/// ACP prompt content with the result of processing it
+///
+/// Content is decoded and validated once, by the agent's
+/// [`ContentBlockProcessor`](crate::content_block_processor::ContentBlockProcessor), and
+/// translated for claude from the processed result.
+#[derive(Debug)]
+pub struct ProcessedPrompt {
+    content: Vec<ContentBlock>,
+    summary: ContentProcessingSummary,
+}
+
+impl ProcessedPrompt {
+    /// Pair content with the summary of processing it
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the summary does not hold one processed block per content block.
+    pub fn new(content: Vec<ContentBlock>, summary: ContentProcessingSummary) -> Result<Self> {
+        if content.len() != summary.processed_contents.len() {
+            return Err(crate::error::AgentError::Internal(format!(
+                "Processed content count ({}) does not match content block count ({})",
+                summary.processed_contents.len(),
+                content.len()
+            )));
+        }
+        Ok(Self { content, summary })
+    }
+
+    /// A prompt consisting of plain text
+    pub fn text(prompt: &str) -> Self {
+        let processed = ProcessedContent::text(prompt);
+        let summary = ContentProcessingSummary {
+            combined_text: prompt.to_string(),
+            has_binary_content: false,
+            total_size_bytes: processed.size_bytes,
+            content_type_counts: [("text".to_string(), 1)].into_iter().collect(),
+            processed_contents: vec![processed],
+        };
+        Self {
+            content: vec![ClaudeClient::text_block(prompt)],
+            summary,
+        }
+    }
+
+    /// The content blocks of the prompt
+    pub fn content(&self) -> &[ContentBlock] {
+        &self.content
+    }
+
+    /// Check whether the prompt carries anything to send
+    fn is_empty(&self) -> bool {
+        self.content.is_empty()
+            || self
+                .content
+                .iter()
+                .all(|block| matches!(block, ContentBlock::Text(text) if text.text.is_empty()))
+    }
+
+    /// Put `text` in front of the prompt, in its first text block when it starts with one
+    fn prepend_text(&mut self, text: &str) {
+        match self.content.first_mut() {
+            Some(ContentBlock::Text(first)) => first.text.insert_str(0, text),
+            _ => {
+                self.content.insert(0, ClaudeClient::text_block(text));
+                self.summary
+                    .processed_contents
+                    .insert(0, ProcessedContent::text(text));
+            }
+        }
+    }
+
+    /// The prompt as a stream-json user message
+    fn to_stream_json(&self) -> Result<String> {
+        ProtocolTranslator::processed_content_to_stream_json(&self.content, &self.summary)
+    }
 }
 
 /// Session context for managing conversation history
@@ -48,10 +149,34 @@ pub struct MessageChunk {
     pub chunk_type: ChunkType,
     /// Tool call information (only present when chunk_type is ToolCall)
     pub tool_call: Option<ToolCallInfo>,
-    /// Token usage information (only present in Result messages)
+    /// Token usage of the turn so far (only present in Usage chunks), or of the whole
+    /// turn (only present in Result messages)
     pub token_usage: Option<TokenUsageInfo>,
+    /// Cumulative cost of the claude conversation in USD (only present in Result messages)
+    pub total_cost_usd: Option<f64>,
     /// Stop reason from Claude (only present in final chunk from result message)
     pub stop_reason: Option<String>,
+    /// ACP tool call notification (only present when chunk_type is ToolCall or ToolResult)
+    pub tool_call_update: Option<SessionUpdate>,
+    /// Error that ended the stream, such as a crash of the claude process (only present
+    /// in the last chunk)
+    pub error: Option<Arc<crate::error::AgentError>>,
+}
+
+impl MessageChunk {
+    /// Last chunk of a stream that ended with `error`
+    fn failure(error: crate::error::AgentError) -> Self {
+        Self {
+            content: String::new(),
+            chunk_type: ChunkType::Text,
+            tool_call: None,
+            token_usage: None,
+            total_cost_usd: None,
+            stop_reason: None,
+            tool_call_update: None,
+            error: Some(Arc::new(error)),
+        }
+    }
 }
 
 /// Tool call information extracted from Message::Tool
@@ -61,19 +186,105 @@ pub struct ToolCallInfo {
     pub parameters: serde_json::Value,
 }
 
-/// Token usage information extracted from Message metadata
-#[derive(Debug, Clone)]
+/// Token usage information reported in the `usage` of a stream-json result message
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub struct TokenUsageInfo {
+    #[serde(default)]
     pub input_tokens: u64,
+    #[serde(default)]
     pub output_tokens: u64,
+    /// Input tokens written to the prompt cache
+    #[serde(default)]
+    pub cache_creation_input_tokens: u64,
+    /// Input tokens read from the prompt cache
+    #[serde(default)]
+    pub cache_read_input_tokens: u64,
+}
+
+impl TokenUsageInfo {
+    /// Tokens processed by the turn
+    ///
+    /// Cache reads are excluded: they re-read context that earlier requests already
+    /// counted, and would otherwise dominate every turn of a long conversation.
+    pub fn total(&self) -> u64 {
+        self.input_tokens + self.cache_creation_input_tokens + self.output_tokens
+    }
+}
+
+impl std::ops::AddAssign for TokenUsageInfo {
+    fn add_assign(&mut self, other: Self) {
+        self.input_tokens += other.input_tokens;
+        self.output_tokens += other.output_tokens;
+        self.cache_creation_input_tokens += other.cache_creation_input_tokens;
+        self.cache_read_input_tokens += other.cache_read_input_tokens;
+    }
+}
+
+/// Token usage of a turn accumulated from its partial message events
+///
+/// A turn makes one model request per tool round trip. Each starts with a
+/// `message_start` and reports its growing usage in `message_delta` events.
+#[derive(Debug, Clone, Copy, Default)]
+struct StreamedUsage {
+    /// Usage of the model requests that have finished
+    finished: TokenUsageInfo,
+    /// Usage of the model request in progress
+    current: TokenUsageInfo,
+}
+
+impl StreamedUsage {
+    fn record(&mut self, usage: StreamUsage) {
+        match usage {
+            StreamUsage::Started(usage) => {
+                self.finished += self.current;
+                self.current = usage;
+            }
+            StreamUsage::Updated(usage) => {
+                // Counts are cumulative and a delta may leave out the input counts
+                let current = &mut self.current;
+                current.input_tokens = current.input_tokens.max(usage.input_tokens);
+                current.output_tokens = current.output_tokens.max(usage.output_tokens);
+                current.cache_creation_input_tokens = current
+                    .cache_creation_input_tokens
+                    .max(usage.cache_creation_input_tokens);
+                current.cache_read_input_tokens = current
+                    .cache_read_input_tokens
+                    .max(usage.cache_read_input_tokens);
+            }
+        }
+    }
+
+    /// Usage of the turn so far
+    fn total(&self) -> TokenUsageInfo {
+        let mut total = self.finished;
+        total += self.current;
+        total
+    }
+}
+
+/// Complete response to a non-streaming query
+#[derive(Debug, Clone, Default)]
+pub struct QueryResponse {
+    /// Response text
+    pub content: String,
+    /// Stop reason from Claude's result message
+    pub stop_reason: Option<String>,
+    /// Tokens used by the turn
+    pub token_usage: Option<TokenUsageInfo>,
+    /// Cumulative cost of the claude conversation in USD, including earlier turns
+    pub total_cost_usd: Option<f64>,
 }
 
 /// Types of message chunks in streaming responses
 #[derive(Debug, Clone)]
 pub enum ChunkType {
     Text,
+    /// A new tool call reported by Claude
     ToolCall,
+    /// A status or result update for an earlier tool call
     ToolResult,
+    /// The turn's token usage grew; carries no content
+    Usage,
 }
 
 impl ClaudeClient {
@@ -81,17 +292,79 @@ impl ClaudeClient {
     pub fn new() -> Result<Self> {
         Ok(Self {
             process_manager: Arc::new(ClaudeProcessManager::new()),
+            permission_handler: std::sync::RwLock::new(None),
+            audit_log: std::sync::RwLock::new(None),
         })
     }
 
     /// Create a new Claude client with custom configuration
-    pub fn new_with_config(_claude_config: &ClaudeConfig) -> Result<Self> {
-        tracing::info!("Created ClaudeClient with process manager");
+    pub fn new_with_config(claude_config: &ClaudeConfig) -> Result<Self> {
+        tracing::info!(
+            "Created ClaudeClient with process manager (binary: {}, model: {})",
+            claude_config.binary_path.display(),
+            claude_config.model
+        );
         Ok(Self {
-            process_manager: Arc::new(ClaudeProcessManager::new()),
+            process_manager: Arc::new(ClaudeProcessManager::with_config(claude_config.clone())),
+            permission_handler: std::sync::RwLock::new(None),
+            audit_log: std::sync::RwLock::new(None),
         })
     }
 
+    /// Set the handler that answers tool permission checks from the claude CLI
+    pub fn set_permission_handler(&self, handler: Arc<dyn ToolPermissionHandler>) {
+        let mut slot = self
+            .permission_handler
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        *slot = Some(handler);
+    }
+
+    fn permission_handler(&self) -> Option<Arc<dyn ToolPermissionHandler>> {
+        self.permission_handler
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+
+    /// Set the log recording the tool calls run by the claude CLI
+    pub fn set_audit_log(&self, audit_log: Arc<AuditLog>) {
+        let mut slot = self
+            .audit_log
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        *slot = Some(audit_log);
+    }
+
+    fn audit_log(&self) -> Option<Arc<AuditLog>> {
+        self.audit_log
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+
+    /// Audit a session update if it starts or finishes a tool call
+    fn audit_update(
+        audit_log: Option<&Arc<AuditLog>>,
+        process_manager: &ClaudeProcessManager,
+        session_id: &SessionId,
+        update: &SessionUpdate,
+    ) {
+        let Some(audit_log) = audit_log else {
+            return;
+        };
+        if matches!(
+            update,
+            SessionUpdate::ToolCall(_) | SessionUpdate::ToolCallUpdate(_)
+        ) {
+            audit_log.record_tool_call_update(
+                &session_id.to_string(),
+                process_manager.session_cwd(session_id),
+                update,
+            );
+        }
+    }
+
     /// Check if the client supports streaming
     pub fn supports_streaming(&self) -> bool {
         true
@@ -107,45 +380,124 @@ impl ClaudeClient {
         agent_client_protocol::SessionId(Arc::from(session_id.to_string().as_str()))
     }
 
-    /// Convert ContentBlock to MessageChunk
-    fn content_block_to_message_chunk(content: ContentBlock) -> MessageChunk {
-        match content {
-            ContentBlock::Text(text) => MessageChunk {
-                content: text.text,
-                chunk_type: ChunkType::Text,
-                tool_call: None,
-                token_usage: None,
-                stop_reason: None,
-            },
-            // Handle other ContentBlock variants if they exist
-            _ => MessageChunk {
-                content: String::new(),
-                chunk_type: ChunkType::Text,
-                tool_call: None,
-                token_usage: None,
-                stop_reason: None,
-            },
+    /// Convert an ACP session update from the translator to a MessageChunk
+    ///
+    /// Returns `None` for updates that have no chunk representation.
+    fn session_update_to_message_chunk(update: SessionUpdate) -> Option<MessageChunk> {
+        let chunk = |content: String, chunk_type: ChunkType| MessageChunk {
+            content,
+            chunk_type,
+            tool_call: None,
+            token_usage: None,
+            total_cost_usd: None,
+            stop_reason: None,
+            tool_call_update: None,
+            error: None,
+        };
+
+        match update {
+            SessionUpdate::AgentMessageChunk {
+                content: ContentBlock::Text(text),
+            } => Some(chunk(text.text, ChunkType::Text)),
+            SessionUpdate::ToolCall(tool_call) => {
+                let name = tool_call
+                    .meta
+                    .as_ref()
+                    .and_then(|meta| meta.get("tool_name"))
+                    .and_then(|name| name.as_str())
+                    .unwrap_or(&tool_call.title)
+                    .to_string();
+                Some(MessageChunk {
+                    tool_call: Some(ToolCallInfo {
+                        name,
+                        parameters: tool_call.raw_input.clone().unwrap_or_default(),
+                    }),
+                    tool_call_update: Some(SessionUpdate::ToolCall(tool_call)),
+                    ..chunk(String::new(), ChunkType::ToolCall)
+                })
+            }
+            update @ SessionUpdate::ToolCallUpdate(_) => Some(MessageChunk {
+                tool_call_update: Some(update),
+                ..chunk(String::new(), ChunkType::ToolResult)
+            }),
+            _ => None,
         }
     }
 
+    /// Wrap plain text in an ACP text content block
+    fn text_block(text: &str) -> ContentBlock {
+        ContentBlock::Text(TextContent {
+            text: text.to_string(),
+            annotations: None,
+            meta: None,
+        })
+    }
+
     /// Helper method to send prompt to process
     async fn send_prompt_to_process(
         &self,
         process: Arc<Mutex<ClaudeProcess>>,
-        prompt: &str,
+        prompt: &ProcessedPrompt,
     ) -> Result<()> {
-        let content = vec![ContentBlock::Text(TextContent {
-            text: prompt.to_string(),
-            annotations: None,
-            meta: None,
-        })];
-        let stream_json = ProtocolTranslator::acp_to_stream_json(content)?;
+        let stream_json = prompt.to_stream_json()?;
 
         let mut proc = process.lock().await;
         proc.write_line(&stream_json).await?;
         Ok(())
     }
 
+    /// Answer a control request from the claude CLI
+    ///
+    /// The process lock must not be held by the caller: the permission handler may
+    /// wait for the user, and the process is only locked again to write the response.
+    async fn respond_to_control_request(
+        process: &Arc<Mutex<ClaudeProcess>>,
+        permission_handler: Option<&Arc<dyn ToolPermissionHandler>>,
+        session_id: &SessionId,
+        request: ControlRequest,
+    ) -> Result<()> {
+        let response = match request.kind {
+            ControlRequestKind::CanUseTool(permission_request) => {
+                let decision = match permission_handler {
+                    Some(handler) => {
+                        handler
+                            .check_tool_permission(session_id, &permission_request)
+                            .await
+                    }
+                    None => {
+                        tracing::warn!(
+                            "No permission handler configured, denying tool '{}'",
+                            permission_request.tool_name
+                        );
+                        ToolPermissionDecision::Deny {
+                            message: "No permission handler is configured".to_string(),
+                            interrupt: false,
+                        }
+                    }
+                };
+                tracing::info!(
+                    "Permission decision for tool '{}': {:?}",
+                    permission_request.tool_name,
+                    decision
+                );
+                ProtocolTranslator::permission_decision_to_stream_json(
+                    &request.request_id,
+                    &decision,
+                )?
+            }
+            ControlRequestKind::Unsupported { subtype } => {
+                tracing::warn!("Unsupported control request subtype: {}", subtype);
+                ProtocolTranslator::control_error_to_stream_json(
+                    &request.request_id,
+                    &format!("Unsupported control request: {}", subtype),
+                )?
+            }
+        };
+
+        let mut proc = process.lock().await;
+        proc.write_line(&response).await
+    }
+
     /// Helper method to check if a line indicates end of stream
     fn is_end_of_stream(line: &str) -> bool {
         // Parse JSON and check type field properly
@@ -159,21 +511,46 @@ impl ClaudeClient {
 
     /// Execute a simple query without session context
     pub async fn query(&self, prompt: &str, session_id: &SessionId) -> Result<String> {
+        self.query_content(ProcessedPrompt::text(prompt), session_id)
+            .await
+    }
+
+    /// Execute a query with ACP content blocks (text, images, resources) without session context
+    pub async fn query_content(
+        &self,
+        prompt: ProcessedPrompt,
+        session_id: &SessionId,
+    ) -> Result<String> {
+        self.query_content_response(prompt, session_id)
+            .await
+            .map(|response| response.content)
+    }
+
+    /// Execute a query with ACP content blocks and return the text with the turn's result
+    pub async fn query_content_response(
+        &self,
+        prompt: ProcessedPrompt,
+        session_id: &SessionId,
+    ) -> Result<QueryResponse> {
         if prompt.is_empty() {
             return Err(crate::error::AgentError::Process(
                 "Empty prompt".to_string(),
             ));
         }
 
-        // Get the process for this session
+        // Get the process for this session once an interrupted turn has finished
+        self.process_manager.wait_for_turn_end(session_id).await;
         let process = self.process_manager.get_process(session_id).await?;
 
         // Send prompt to process
-        self.send_prompt_to_process(process.clone(), prompt).await?;
+        self.send_prompt_to_process(process.clone(), &prompt)
+            .await?;
 
         // Read response lines until we get a result
-        let mut response_text = String::new();
+        let mut response = QueryResponse::default();
         let acp_session_id = Self::to_acp_session_id(session_id);
+        let permission_handler = self.permission_handler();
+        let audit_log = self.audit_log();
         loop {
             let line = {
                 let mut proc = process.lock().await;
@@ -182,26 +559,61 @@ impl ClaudeClient {
 
             match line {
                 Some(line) => {
-                    if let Ok(Some(notification)) =
-                        ProtocolTranslator::stream_json_to_acp(&line, &acp_session_id)
+                    if let Some(claude_session_id) =
+                        ProtocolTranslator::parse_claude_session_id(&line)
                     {
+                        self.process_manager
+                            .set_claude_session_id(*session_id, claude_session_id);
+                    }
+                    if let Ok(Some(request)) = ProtocolTranslator::parse_control_request(&line) {
+                        Self::respond_to_control_request(
+                            &process,
+                            permission_handler.as_ref(),
+                            session_id,
+                            request,
+                        )
+                        .await?;
+                        continue;
+                    }
+                    let notifications = ProtocolTranslator::stream_json_to_acp_notifications(
+                        &line,
+                        &acp_session_id,
+                    )
+                    .unwrap_or_default();
+                    for notification in notifications {
+                        Self::audit_update(
+                            audit_log.as_ref(),
+                            &self.process_manager,
+                            session_id,
+                            &notification.update,
+                        );
                         if let SessionUpdate::AgentMessageChunk {
                             content: ContentBlock::Text(text),
                         } = notification.update
                         {
-                            response_text.push_str(&text.text);
+                            response.content.push_str(&text.text);
                         }
                     }
                     // Check if this is a result message (indicates end)
                     if Self::is_end_of_stream(&line) {
+                        if let Ok(Some(result)) = ProtocolTranslator::parse_result_message(&line) {
+                            response.stop_reason = result.stop_reason;
+                            response.token_usage = result.usage;
+                            response.total_cost_usd = result.total_cost_usd;
+                        }
                         break;
                     }
                 }
-                None => break,
+                None => {
+                    return Err(self
+                        .process_manager
+                        .handle_process_exit(session_id, &process)
+                        .await)
+                }
             }
         }
 
-        Ok(response_text)
+        Ok(response)
     }
 
     /// Execute a streaming query without session context
@@ -209,6 +621,16 @@ impl ClaudeClient {
         &self,
         prompt: &str,
         session_id: &SessionId,
+    ) -> Result<Pin<Box<dyn Stream<Item = MessageChunk> + Send>>> {
+        self.query_stream_content(ProcessedPrompt::text(prompt), session_id)
+            .await
+    }
+
+    /// Execute a streaming query with ACP content blocks without session context
+    pub async fn query_stream_content(
+        &self,
+        prompt: ProcessedPrompt,
+        session_id: &SessionId,
     ) -> Result<Pin<Box<dyn Stream<Item = MessageChunk> + Send>>> {
         if prompt.is_empty() {
             return Err(crate::error::AgentError::Process(
@@ -216,55 +638,132 @@ impl ClaudeClient {
             ));
         }
 
-        // Get the process for this session
+        // Get the process for this session once an interrupted turn has finished
+        self.process_manager.wait_for_turn_end(session_id).await;
         let process = self.process_manager.get_process(session_id).await?;
 
         // Send prompt to process
-        self.send_prompt_to_process(process.clone(), prompt).await?;
+        self.send_prompt_to_process(process.clone(), &prompt)
+            .await?;
 
         // Create a channel-based stream to avoid holding mutex across await
         let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
         let process_clone = process.clone();
         let acp_session_id = Self::to_acp_session_id(session_id);
+        let session_id = *session_id;
+        let permission_handler = self.permission_handler();
+        let audit_log = self.audit_log();
+        let process_manager = Arc::clone(&self.process_manager);
 
         // Spawn an async task to read from the process and send chunks
         tokio::task::spawn(async move {
+            let mut streamed_usage = StreamedUsage::default();
+            // A caller that stops listening, e.g. after interrupting the turn, leaves the
+            // rest of the turn to be read here, so the next turn starts at its own output
+            let mut receiver_dropped = false;
             loop {
                 let line = {
                     let mut proc = process_clone.lock().await;
                     match proc.read_line().await {
-                        Ok(Some(line)) => line,
-                        Ok(None) => break,
-                        Err(_) => break,
+                        Ok(Some(line)) => Some(line),
+                        Ok(None) => None,
+                        Err(e) => {
+                            tracing::error!("Failed to read from claude process: {}", e);
+                            None
+                        }
                     }
                 };
 
+                // The process closed stdout mid-turn, report the crash to the caller
+                let Some(line) = line else {
+                    let error = process_manager
+                        .handle_process_exit(&session_id, &process_clone)
+                        .await;
+                    let _ = tx.send(MessageChunk::failure(error));
+                    break;
+                };
+
+                // Remember claude's conversation id so later processes can resume it
+                if let Some(claude_session_id) = ProtocolTranslator::parse_claude_session_id(&line)
+                {
+                    process_manager.set_claude_session_id(session_id, claude_session_id);
+                }
+
+                // Permission checks block the tool until we answer them
+                if let Ok(Some(request)) = ProtocolTranslator::parse_control_request(&line) {
+                    if let Err(e) = Self::respond_to_control_request(
+                        &process_clone,
+                        permission_handler.as_ref(),
+                        &session_id,
+                        request,
+                    )
+                    .await
+                    {
+                        // Claude waits for the answer, so the turn cannot go on
+                        tracing::error!("Failed to answer control request: {}", e);
+                        let _ = tx.send(MessageChunk::failure(e));
+                        break;
+                    }
+                    continue;
+                }
+
+                // Report the turn's usage as it grows so limits can stop it early
+                if let Some(usage) = ProtocolTranslator::parse_stream_usage(&line) {
+                    streamed_usage.record(usage);
+                    let chunk = MessageChunk {
+                        content: String::new(),
+                        chunk_type: ChunkType::Usage,
+                        tool_call: None,
+                        token_usage: Some(streamed_usage.total()),
+                        total_cost_usd: None,
+                        stop_reason: None,
+                        tool_call_update: None,
+                        error: None,
+                    };
+                    if !receiver_dropped && tx.send(chunk).is_err() {
+                        receiver_dropped = true;
+                    }
+                    continue;
+                }
+
                 // Check if this is a result message (indicates end)
                 if Self::is_end_of_stream(&line) {
-
-                    // Parse the result message to extract stop_reason
+                    // Parse the result message to extract stop_reason, usage and cost
                     if let Ok(Some(result)) = ProtocolTranslator::parse_result_message(&line) {
-                        // Send a final chunk with the stop_reason
+                        // Send a final chunk with the turn's outcome
                         let final_chunk = MessageChunk {
                             content: String::new(),
                             chunk_type: ChunkType::Text,
                             tool_call: None,
-                            token_usage: None,
+                            token_usage: result.usage,
+                            total_cost_usd: result.total_cost_usd,
                             stop_reason: result.stop_reason,
+                            tool_call_update: None,
+                            error: None,
                         };
                         let _ = tx.send(final_chunk);
                     }
                     break;
                 }
 
-                // Translate to ACP notification
-                if let Ok(Some(notification)) =
-                    ProtocolTranslator::stream_json_to_acp(&line, &acp_session_id)
-                {
-                    if let SessionUpdate::AgentMessageChunk { content } = notification.update {
-                        let chunk = Self::content_block_to_message_chunk(content);
+                // Translate to ACP notifications
+                let notifications =
+                    ProtocolTranslator::stream_json_to_acp_notifications(&line, &acp_session_id)
+                        .unwrap_or_default();
+                for notification in notifications {
+                    Self::audit_update(
+                        audit_log.as_ref(),
+                        &process_manager,
+                        &session_id,
+                        &notification.update,
+                    );
+                    if receiver_dropped {
+                        continue;
+                    }
+                    if let Some(chunk) = Self::session_update_to_message_chunk(notification.update)
+                    {
                         if tx.send(chunk).is_err() {
-                            break;
+                            receiver_dropped = true;
                         }
                     }
                 }
@@ -282,6 +781,20 @@ impl ClaudeClient {
         prompt: &str,
         context: &SessionContext,
     ) -> Result<String> {
+        self.query_content_with_context(ProcessedPrompt::text(prompt), context)
+            .await
+            .map(|response| response.content)
+    }
+
+    /// Execute a query with ACP content blocks and full session context
+    ///
+    /// The conversation history is flattened into text and prepended to the first
+    /// text block of the prompt, so non-text blocks are forwarded unchanged.
+    pub async fn query_content_with_context(
+        &self,
+        mut prompt: ProcessedPrompt,
+        context: &SessionContext,
+    ) -> Result<QueryResponse> {
         if prompt.is_empty() {
             return Err(crate::error::AgentError::Process(
                 "Empty prompt".to_string(),
@@ -299,19 +812,24 @@ impl ClaudeClient {
             };
             full_conversation.push_str(&format!("{}: {}\n", role_str, message.content));
         }
-        full_conversation.push_str(&format!("User: {}", prompt));
+        full_conversation.push_str("User: ");
+
+        prompt.prepend_text(&full_conversation);
 
         // Use the process manager for the query
         tracing::info!(
-            "Sending request to Claude process (prompt length: {} chars)",
+            "Sending request to Claude process ({} content blocks, history length: {} chars)",
+            prompt.content().len(),
             full_conversation.len()
         );
 
-        let response = self.query(&full_conversation, &context.session_id).await?;
+        let response = self
+            .query_content_response(prompt, &context.session_id)
+            .await?;
 
         tracing::info!(
             "Received response from Claude process (content length: {} chars)",
-            response.len()
+            response.content.len()
         );
 
         Ok(response)
@@ -323,17 +841,21 @@ impl ClaudeClient {
         prompt: &str,
         context: &SessionContext,
     ) -> Result<Pin<Box<dyn Stream<Item = MessageChunk> + Send>>> {
-        if prompt.is_empty() {
-            return Err(crate::error::AgentError::Process(
-                "Empty prompt".to_string(),
-            ));
-        }
+        self.query_stream_content_with_context(ProcessedPrompt::text(prompt), context)
+            .await
+    }
 
+    /// Execute a streaming query with ACP content blocks and full session context
+    pub async fn query_stream_content_with_context(
+        &self,
+        prompt: ProcessedPrompt,
+        context: &SessionContext,
+    ) -> Result<Pin<Box<dyn Stream<Item = MessageChunk> + Send>>> {
         // Claude CLI maintains conversation state internally, so we just send
         // the new prompt without rebuilding the full conversation history.
         // The process manager ensures we're using the same CLI process for this
         // session, which maintains context across calls.
-        self.query_stream(prompt, &context.session_id).await
+        self.query_stream_content(prompt, &context.session_id).await
     }
 }
 
@@ -435,6 +957,76 @@ mod tests {
         assert!(client.supports_streaming());
     }
 
+    #[test]
+    fn test_processed_prompt_keeps_history_in_step_with_summary() {
+        let content = vec![ContentBlock::Image(agent_client_protocol::ImageContent {
+            data: "iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR42mP8/5+hHgAHggJ/PchI7wAAAABJRU5ErkJggg==".to_string(),
+            mime_type: "image/png".to_string(),
+            uri: None,
+            annotations: None,
+            meta: None,
+        })];
+        let summary = crate::content_block_processor::ContentBlockProcessor::default()
+            .process_content_blocks(&content)
+            .unwrap();
+        assert!(ProcessedPrompt::new(Vec::new(), summary).is_err());
+
+        let summary = crate::content_block_processor::ContentBlockProcessor::default()
+            .process_content_blocks(&content)
+            .unwrap();
+        let mut prompt = ProcessedPrompt::new(content, summary).unwrap();
+        prompt.prepend_text("User: ");
+
+        let parsed: serde_json::Value =
+            serde_json::from_str(&prompt.to_stream_json().unwrap()).unwrap();
+        let blocks = parsed["message"]["content"].as_array().unwrap();
+        assert_eq!(blocks[0]["text"], "User: ");
+        assert_eq!(blocks[1]["type"], "image");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_stream_fails_when_a_control_request_cannot_be_answered() {
+        use std::os::unix::fs::PermissionsExt;
+        use tokio_stream::StreamExt;
+
+        // A claude CLI that stops reading stdin and then asks for a permission
+        let temp_dir = tempfile::tempdir().unwrap();
+        let binary_path = temp_dir.path().join("fake-claude");
+        std::fs::write(
+            &binary_path,
+            r#"#!/bin/sh
+read prompt
+exec 0<&-
+echo '{"type":"control_request","request_id":"req_1","request":{"subtype":"can_use_tool","tool_name":"Write","input":{},"tool_use_id":"toolu_1"}}'
+sleep 1
+"#,
+        )
+        .unwrap();
+        std::fs::set_permissions(&binary_path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let client = ClaudeClient::new_with_config(&ClaudeConfig {
+            binary_path,
+            ..ClaudeConfig::default()
+        })
+        .unwrap();
+        let session_id = SessionId::new();
+
+        let mut stream = client
+            .query_stream_content(ProcessedPrompt::text("Write a file"), &session_id)
+            .await
+            .unwrap();
+        let chunk = tokio::time::timeout(std::time::Duration::from_secs(5), stream.next())
+            .await
+            .expect("the failure should end the stream")
+            .unwrap();
+        assert!(chunk.error.is_some());
+        assert!(stream.next().await.is_none());
+        let _ = client
+            .process_manager()
+            .terminate_session(&session_id)
+            .await;
+    }
+
     #[tokio::test]
     async fn test_session_context() {
         let session_id = SessionId::new();
@@ -517,7 +1109,10 @@ mod tests {
             chunk_type: ChunkType::Text,
             tool_call: None,
             token_usage: None,
+            total_cost_usd: None,
             stop_reason: None,
+            tool_call_update: None,
+            error: None,
         };
 
         let tool_call_chunk = MessageChunk {
@@ -528,7 +1123,10 @@ mod tests {
                 parameters: serde_json::json!({"arg": "value"}),
             }),
             token_usage: None,
+            total_cost_usd: None,
             stop_reason: None,
+            tool_call_update: None,
+            error: None,
         };
 
         let tool_result_chunk = MessageChunk {
@@ -536,7 +1134,10 @@ mod tests {
             chunk_type: ChunkType::ToolResult,
             tool_call: None,
             token_usage: None,
+            total_cost_usd: None,
             stop_reason: None,
+            tool_call_update: None,
+            error: None,
         };
 
         let result_chunk = MessageChunk {
@@ -546,8 +1147,12 @@ mod tests {
             token_usage: Some(TokenUsageInfo {
                 input_tokens: 100,
                 output_tokens: 200,
+                ..Default::default()
             }),
+            total_cost_usd: Some(0.01),
             stop_reason: None,
+            tool_call_update: None,
+            error: None,
         };
 
         assert!(matches!(text_chunk.chunk_type, ChunkType::Text));
@@ -569,6 +1174,26 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_streamed_usage_adds_up_model_requests() {
+        let usage = |input_tokens, output_tokens| TokenUsageInfo {
+            input_tokens,
+            output_tokens,
+            ..Default::default()
+        };
+        let mut streamed = StreamedUsage::default();
+
+        streamed.record(StreamUsage::Started(usage(100, 1)));
+        streamed.record(StreamUsage::Updated(usage(0, 20)));
+        streamed.record(StreamUsage::Updated(usage(0, 50)));
+        assert_eq!(streamed.total(), usage(100, 50));
+
+        // A tool round trip makes another model request
+        streamed.record(StreamUsage::Started(usage(300, 1)));
+        streamed.record(StreamUsage::Updated(usage(300, 10)));
+        assert_eq!(streamed.total(), usage(400, 60));
+    }
+
     #[test]
     fn test_session_context_token_tracking() {
         let session_id = SessionId::new();
@@ -613,24 +1238,41 @@ mod tests {
         let session2_id = SessionId::new();
 
         // Send first message to session 1 - should spawn process 1
-        let result1 = client.query("Hello from session 1, message 1", &session1_id).await;
-        if result1.is_err() {
-            eprintln!("Skipping test - claude not installed: {:?}", result1.unwrap_err());
+        let result1 = client
+            .query("Hello from session 1, message 1", &session1_id)
+            .await;
+        if let Err(e) = &result1 {
+            eprintln!("Skipping test - claude not installed: {:?}", e);
             return;
         }
         let response1_1 = result1.unwrap();
-        assert!(!response1_1.is_empty(), "Expected response from session 1 message 1");
+        assert!(
+            !response1_1.is_empty(),
+            "Expected response from session 1 message 1"
+        );
 
         // Verify session 1 has a process
         let process_manager = client.process_manager();
-        assert!(process_manager.has_session(&session1_id).await, "Session 1 should have a process");
+        assert!(
+            process_manager.has_session(&session1_id).await,
+            "Session 1 should have a process"
+        );
 
         // Send first message to session 2 - should spawn process 2
-        let response2_1 = client.query("Hello from session 2, message 1", &session2_id).await.unwrap();
-        assert!(!response2_1.is_empty(), "Expected response from session 2 message 1");
+        let response2_1 = client
+            .query("Hello from session 2, message 1", &session2_id)
+            .await
+            .unwrap();
+        assert!(
+            !response2_1.is_empty(),
+            "Expected response from session 2 message 1"
+        );
 
         // Verify session 2 has a process
-        assert!(process_manager.has_session(&session2_id).await, "Session 2 should have a process");
+        assert!(
+            process_manager.has_session(&session2_id).await,
+            "Session 2 should have a process"
+        );
 
         // At this point, we should have exactly 2 processes
         // We can't directly count processes from the public API, but we can verify both sessions exist
@@ -638,15 +1280,27 @@ mod tests {
         assert!(process_manager.has_session(&session2_id).await);
 
         // Send second message to session 1 - should REUSE process 1
-        let response1_2 = client.query("Session 1, message 2", &session1_id).await.unwrap();
-        assert!(!response1_2.is_empty(), "Expected response from session 1 message 2");
+        let response1_2 = client
+            .query("Session 1, message 2", &session1_id)
+            .await
+            .unwrap();
+        assert!(
+            !response1_2.is_empty(),
+            "Expected response from session 1 message 2"
+        );
 
         // Verify session 1 still has a process (same one)
         assert!(process_manager.has_session(&session1_id).await);
 
         // Send second message to session 2 - should REUSE process 2
-        let response2_2 = client.query("Session 2, message 2", &session2_id).await.unwrap();
-        assert!(!response2_2.is_empty(), "Expected response from session 2 message 2");
+        let response2_2 = client
+            .query("Session 2, message 2", &session2_id)
+            .await
+            .unwrap();
+        assert!(
+            !response2_2.is_empty(),
+            "Expected response from session 2 message 2"
+        );
 
         // Verify session 2 still has a process (same one)
         assert!(process_manager.has_session(&session2_id).await);
@@ -665,6 +1319,8 @@ mod tests {
         assert!(!process_manager.has_session(&session1_id).await);
         assert!(!process_manager.has_session(&session2_id).await);
 
-        tracing::info!("Successfully verified two sessions with multiple messages reuse processes correctly");
+        tracing::info!(
+            "Successfully verified two sessions with multiple messages reuse processes correctly"
+        );
     }
 }
@@ -23,16 +23,17 @@
 //!   --input-format stream-json \
 //!   --output-format stream-json \
 //!   --verbose \
-//!   --dangerously-skip-permissions \
-//!   --replay-user-messages
+//!   --permission-prompt-tool stdio \
+//!   --include-partial-messages
 //! ```
 //!
 //! - `-p`: Print mode (non-interactive)
 //! - `--input-format stream-json`: Accept newline-delimited JSON on stdin
 //! - `--output-format stream-json`: Emit newline-delimited JSON on stdout
 //! - `--verbose`: Required for stream-json output format
-//! - `--dangerously-skip-permissions`: ACP server handles permission checks
-//! - `--replay-user-messages`: Re-emit user messages for immediate acknowledgment
+//! - `--permission-prompt-tool stdio`: Send tool permission checks to the ACP server as
+//!   `control_request` lines on stdout, answered with `control_response` lines on stdin
+//! - `--include-partial-messages`: Emit partial messages for immediate streaming
 //!
 //! Messages are exchanged as newline-delimited JSON objects conforming to the
 //! JSON-RPC 2.0 specification for Agent Communication Protocol (ACP).
@@ -49,10 +50,10 @@
 //! # Usage Example
 //!
 //! ```no_run
-//! use claude_agent::claude_process::ClaudeProcessManager;
-//! use claude_agent::session::SessionId;
+//! use claude_agent_lib::claude_process::ClaudeProcessManager;
+//! use claude_agent_lib::session::SessionId;
 //!
-//! # async fn example() -> claude_agent::Result<()> {
+//! # async fn example() -> claude_agent_lib::Result<()> {
 //! let manager = ClaudeProcessManager::new();
 //! let session_id = SessionId::new();
 //!
@@ -96,30 +97,113 @@
 //! Processes are automatically cleaned up when terminated via the manager, but callers must ensure
 //! no `Arc<Mutex<ClaudeProcess>>` references are held when calling `terminate_session()`.
 
+use crate::config::ClaudeConfig;
+use crate::protocol_translator::ProtocolTranslator;
 use crate::session::SessionId;
 use crate::{AgentError, Result};
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::process::Stdio;
 use std::sync::{Arc, RwLock};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 use tokio::process::{Child, ChildStderr, ChildStdin, ChildStdout, Command};
-use tokio::sync::Mutex;
+use tokio::sync::{watch, Mutex};
 
 /// Claude CLI command-line arguments for stream-json communication
+///
+/// Launch settings from [`ClaudeConfig`] are appended after these, see [`cli_args`].
 const CLAUDE_CLI_ARGS: &[&str] = &[
     "-p", // print mode (non-interactive)
     "--input-format",
     "stream-json", // accept newline-delimited JSON on stdin
     "--output-format",
-    "stream-json",                    // emit newline-delimited JSON on stdout
-    "--verbose",                      // REQUIRED for stream-json output format
-    "--dangerously-skip-permissions", // ACP server handles permission checks
-    "--include-partial-messages",     // Emit partial messages for immediate streaming
+    "stream-json", // emit newline-delimited JSON on stdout
+    "--verbose",   // REQUIRED for stream-json output format
+    "--permission-prompt-tool",
+    "stdio", // ask the ACP server for tool permissions via control requests
+    "--include-partial-messages", // Emit partial messages for immediate streaming
 ];
 
+/// Number of stderr lines kept for crash reports
+const STDERR_TAIL_LINES: usize = 20;
+
+/// How long an interrupted turn may take to end before its process is killed
+const INTERRUPT_GRACE: Duration = Duration::from_secs(5);
+
+/// Stdin of a claude process, shared so a turn can be interrupted while its output is read
+type SharedStdin = Arc<Mutex<Option<ChildStdin>>>;
+
+/// Build the full claude CLI argument list for a launch configuration
+///
+/// When `resume_session_id` is given the CLI continues that claude conversation.
+fn cli_args(config: &ClaudeConfig, resume_session_id: Option<&str>) -> Vec<String> {
+    let mut args: Vec<String> = CLAUDE_CLI_ARGS.iter().map(|arg| arg.to_string()).collect();
+
+    if let Some(model) = config.configured_model() {
+        args.push("--model".to_string());
+        args.push(model.to_string());
+    }
+
+    if let Some(prompt) = &config.append_system_prompt {
+        args.push("--append-system-prompt".to_string());
+        args.push(prompt.clone());
+    }
+
+    if !config.allowed_tools.is_empty() {
+        args.push("--allowed-tools".to_string());
+        args.push(config.allowed_tools.join(","));
+    }
+
+    if let Some(claude_session_id) = resume_session_id {
+        args.push("--resume".to_string());
+        args.push(claude_session_id.to_string());
+    }
+
+    args.extend(config.extra_args.iter().cloned());
+    args
+}
+
+/// Launch and supervision state kept per session across process restarts
+#[derive(Debug, Default, Clone)]
+struct SessionLaunchState {
+    /// Working directory for the session's process
+    cwd: Option<PathBuf>,
+    /// Claude's own conversation id, used with `--resume` after a crash
+    claude_session_id: Option<String>,
+    /// Restarts since the last stable run, see [`crate::config::RestartPolicy::stable_run_ms`]
+    restart_count: u32,
+    /// When the last process exited unexpectedly
+    last_crash: Option<Instant>,
+    /// Handles of the current process, used to interrupt it while a turn holds its lock
+    control: Option<ProcessControl>,
+}
+
+/// Handles for reaching a running process without its lock
+#[derive(Debug, Clone)]
+struct ProcessControl {
+    /// OS process id, which is also the id of the process group claude runs in
+    pid: Option<u32>,
+    stdin: SharedStdin,
+    /// Whether a turn is running: set when a user message is written, cleared by its result
+    turn: watch::Receiver<bool>,
+}
+
 /// Manages multiple persistent claude CLI processes, one per session
 ///
+/// # Supervision
+///
+/// A process that exits while the session is still active is treated as a crash, either
+/// when a reader hits EOF (see [`Self::handle_process_exit`]) or when [`Self::get_process`]
+/// finds it dead. The next [`Self::get_process`] call respawns it after an exponential
+/// backoff until the configured [`crate::config::RestartPolicy`] limit is reached.
+///
+/// # Resuming
+///
+/// Once claude's own conversation id is known (see [`Self::set_claude_session_id`]),
+/// every new process for the session is started with `--resume`, so the model keeps its
+/// context across crashes and across `session/load`.
+///
 /// # Thread Safety
 ///
 /// This type is thread-safe and can be safely shared across threads using `Arc<ClaudeProcessManager>`.
@@ -138,13 +222,87 @@ const CLAUDE_CLI_ARGS: &[&str] = &[
 #[derive(Debug)]
 pub struct ClaudeProcessManager {
     processes: Arc<RwLock<HashMap<SessionId, Arc<Mutex<ClaudeProcess>>>>>,
+    /// Launch and restart state for each session, kept across process restarts
+    session_states: Arc<RwLock<HashMap<SessionId, SessionLaunchState>>>,
+    /// How new claude processes are launched
+    launch_config: ClaudeConfig,
+    /// How long an interrupted turn may take to end, see [`Self::interrupt_session`]
+    interrupt_grace: Duration,
 }
 
 impl ClaudeProcessManager {
     /// Create a new process manager
     pub fn new() -> Self {
+        Self::with_config(ClaudeConfig::default())
+    }
+
+    /// Create a new process manager that launches claude with the given configuration
+    pub fn with_config(launch_config: ClaudeConfig) -> Self {
         Self {
             processes: Arc::new(RwLock::new(HashMap::new())),
+            session_states: Arc::new(RwLock::new(HashMap::new())),
+            launch_config,
+            interrupt_grace: INTERRUPT_GRACE,
+        }
+    }
+
+    /// Record the working directory used when the session's process is spawned
+    ///
+    /// Has no effect on a process that is already running.
+    pub fn set_session_cwd(&self, session_id: SessionId, cwd: PathBuf) {
+        if let Ok(mut states) = self.session_states.write() {
+            states.entry(session_id).or_default().cwd = Some(cwd);
+        }
+    }
+
+    /// Get the working directory the session's process is spawned in, if one was recorded
+    pub fn session_cwd(&self, session_id: &SessionId) -> Option<PathBuf> {
+        self.session_states
+            .read()
+            .ok()
+            .and_then(|states| states.get(session_id)?.cwd.clone())
+    }
+
+    /// Record claude's own conversation id for a session
+    ///
+    /// The next process spawned for the session is started with `--resume` and this id.
+    /// A running process is not affected.
+    pub fn set_claude_session_id(&self, session_id: SessionId, claude_session_id: String) {
+        if let Ok(mut states) = self.session_states.write() {
+            states.entry(session_id).or_default().claude_session_id = Some(claude_session_id);
+        }
+    }
+
+    /// Get claude's own conversation id for a session, if one was recorded
+    pub fn claude_session_id(&self, session_id: &SessionId) -> Option<String> {
+        self.session_states
+            .read()
+            .ok()
+            .and_then(|states| states.get(session_id)?.claude_session_id.clone())
+    }
+
+    /// Get the number of restarts used by a session since its last stable run
+    pub fn restart_count(&self, session_id: &SessionId) -> u32 {
+        self.session_states
+            .read()
+            .ok()
+            .and_then(|states| states.get(session_id).map(|state| state.restart_count))
+            .unwrap_or(0)
+    }
+
+    /// Start the session's restart count over after a stable period
+    fn reset_restart_count(&self, session_id: &SessionId) {
+        if let Ok(mut states) = self.session_states.write() {
+            if let Some(state) = states.get_mut(session_id) {
+                if state.restart_count > 0 {
+                    tracing::debug!(
+                        "Claude process for session {} is stable, resetting its restart count",
+                        session_id
+                    );
+                }
+                state.restart_count = 0;
+                state.last_crash = None;
+            }
         }
     }
 
@@ -167,8 +325,26 @@ impl ClaudeProcessManager {
             return Ok(());
         }
 
-        // Spawn new process
-        let process = ClaudeProcess::spawn(session_id).map_err(|e| {
+        // Spawn new process in the session's working directory, resuming the claude
+        // conversation if one is known (restarts after a crash follow the restart policy)
+        let state = self
+            .session_states
+            .read()
+            .ok()
+            .and_then(|states| states.get(&session_id).cloned())
+            .unwrap_or_default();
+        let resume_session_id = if state.restart_count > 0 && !self.launch_config.restart.resume {
+            None
+        } else {
+            state.claude_session_id.as_deref()
+        };
+        let process = ClaudeProcess::spawn_with_config(
+            session_id,
+            &self.launch_config,
+            state.cwd.as_deref(),
+            resume_session_id,
+        )
+        .map_err(|e| {
             tracing::error!(
                 "Failed to spawn claude process for session {}: {}",
                 session_id,
@@ -177,6 +353,10 @@ impl ClaudeProcessManager {
             e
         })?;
 
+        if let Ok(mut states) = self.session_states.write() {
+            states.entry(session_id).or_default().control = Some(process.control());
+        }
+
         // Insert into map
         processes.insert(session_id, Arc::new(Mutex::new(process)));
 
@@ -186,22 +366,81 @@ impl ClaudeProcessManager {
 
     /// Get the process for a session, spawning one if it doesn't exist
     ///
+    /// A process that has exited since it was last used is reported as a crash and
+    /// respawned, subject to the restart policy.
+    ///
     /// # Errors
-    /// Returns error if spawning fails
+    /// Returns error if spawning fails or the session exceeded its restart limit
     pub async fn get_process(&self, session_id: &SessionId) -> Result<Arc<Mutex<ClaudeProcess>>> {
         // First try to get existing process
-        {
+        let existing = {
             let processes = self.processes.read().map_err(|_| {
                 AgentError::Internal("Failed to acquire read lock on processes".to_string())
             })?;
-            if let Some(process) = processes.get(session_id) {
+            processes.get(session_id).cloned().inspect(|_| {
                 tracing::debug!(
                     "Reusing existing Claude process for session {} (total active: {})",
                     session_id,
                     processes.len()
                 );
-                return Ok(process.clone());
+            })
+        };
+
+        let stable_run = self.launch_config.restart.stable_run();
+        if let Some(process) = existing {
+            // A process that is busy with I/O is in use and therefore alive
+            let (exited, stable) = match process.try_lock() {
+                Ok(mut proc) => (!proc.is_alive().await, proc.uptime() >= stable_run),
+                Err(_) => (false, false),
+            };
+            if !exited {
+                if stable {
+                    self.reset_restart_count(session_id);
+                }
+                return Ok(process);
             }
+
+            let error = self.handle_process_exit(session_id, &process).await;
+            tracing::warn!("Respawning claude process after crash: {}", error);
+        }
+
+        // Restarts after a crash are delayed and limited by the restart policy. A session
+        // that has not crashed for a stable period starts counting again.
+        let mut state = self
+            .session_states
+            .read()
+            .ok()
+            .and_then(|states| states.get(session_id).cloned())
+            .unwrap_or_default();
+        if state
+            .last_crash
+            .is_some_and(|last_crash| last_crash.elapsed() >= stable_run)
+        {
+            self.reset_restart_count(session_id);
+            state.restart_count = 0;
+        }
+        if state.restart_count > 0 {
+            let policy = &self.launch_config.restart;
+            if state.restart_count > policy.max_restarts {
+                return Err(AgentError::Process(format!(
+                    "Claude process for session {} crashed {} times, restart limit of {} reached",
+                    session_id, state.restart_count, policy.max_restarts
+                )));
+            }
+
+            if let Some(last_crash) = state.last_crash {
+                let backoff = policy.backoff(state.restart_count);
+                let elapsed = last_crash.elapsed();
+                if elapsed < backoff {
+                    tokio::time::sleep(backoff - elapsed).await;
+                }
+            }
+            tracing::info!(
+                "Restarting claude process for session {} (restart {} of {})",
+                session_id,
+                state.restart_count,
+                policy.max_restarts
+            );
         }
 
         // Process doesn't exist, spawn one
@@ -227,6 +466,214 @@ impl ClaudeProcessManager {
         })
     }
 
+    /// Handle a session process that exited unexpectedly
+    ///
+    /// Called when reading from `process` hits EOF. The process is removed from the
+    /// manager, its exit status and stderr tail are collected, and the session's restart
+    /// count is updated. The caller must not hold the process lock.
+    ///
+    /// # Returns
+    /// A [`AgentError::ProcessCrashed`] describing the crash, or a process error if the
+    /// process was terminated deliberately
+    pub async fn handle_process_exit(
+        &self,
+        session_id: &SessionId,
+        process: &Arc<Mutex<ClaudeProcess>>,
+    ) -> AgentError {
+        let is_current = match self.processes.write() {
+            Ok(mut processes) => {
+                let is_current = processes
+                    .get(session_id)
+                    .is_some_and(|current| Arc::ptr_eq(current, process));
+                if is_current {
+                    processes.remove(session_id);
+                }
+                is_current
+            }
+            Err(_) => false,
+        };
+
+        if !is_current {
+            return AgentError::Process(format!(
+                "Claude process for session {} was terminated",
+                session_id
+            ));
+        }
+
+        let (exit_status, stderr_tail, uptime) = {
+            let mut proc = process.lock().await;
+            let exit_status = proc.wait_for_exit().await;
+            let stderr_tail = proc.stderr_tail(STDERR_TAIL_LINES).await;
+            (exit_status, stderr_tail, proc.uptime())
+        };
+
+        let restart_count = match self.session_states.write() {
+            Ok(mut states) => {
+                let state = states.entry(*session_id).or_default();
+                if uptime >= self.launch_config.restart.stable_run() {
+                    state.restart_count = 0;
+                }
+                state.restart_count += 1;
+                state.last_crash = Some(Instant::now());
+                state.restart_count
+            }
+            Err(_) => u32::MAX,
+        };
+        let will_restart = restart_count <= self.launch_config.restart.max_restarts;
+
+        tracing::error!(
+            "Claude process for session {} exited unexpectedly ({}), will restart: {}, stderr: {:?}",
+            session_id,
+            exit_status,
+            will_restart,
+            stderr_tail
+        );
+
+        AgentError::ProcessCrashed {
+            session_id: session_id.to_string(),
+            exit_status,
+            stderr_tail,
+            restart_count,
+            will_restart,
+        }
+    }
+
+    /// Interrupt the turn running in a session's process
+    ///
+    /// Claude is asked to stop with an `interrupt` control request and ends the turn with
+    /// a result message, keeping the process and its conversation. A turn that has not
+    /// ended after a grace period is stopped by killing the process group, which also
+    /// stops the tools claude started. The process is then removed from the manager while
+    /// the session's launch state is kept, so the next turn spawns a new process that
+    /// resumes the claude conversation, and a turn still reading from the old process sees
+    /// it exit and gets a "was terminated" error instead of a crash.
+    ///
+    /// Returns once the turn has ended or the process was killed.
+    ///
+    /// # Returns
+    /// Whether the session had a running turn to interrupt
+    pub async fn interrupt_session(&self, session_id: &SessionId) -> bool {
+        let process = match self.processes.read() {
+            Ok(processes) => processes.get(session_id).cloned(),
+            Err(_) => None,
+        };
+        let control = self.session_states.read().ok().and_then(|states| {
+            states
+                .get(session_id)
+                .and_then(|state| state.control.clone())
+        });
+        let (Some(process), Some(mut control)) = (process, control) else {
+            return false;
+        };
+        if !*control.turn.borrow() {
+            return false;
+        }
+
+        let request_id = format!("interrupt_{}", ulid::Ulid::new());
+        let sent = match ProtocolTranslator::interrupt_request_to_stream_json(&request_id) {
+            Ok(line) => write_stdin(&control.stdin, &line).await,
+            Err(e) => Err(e),
+        };
+        let ended = match sent {
+            Ok(()) => {
+                let ended = tokio::time::timeout(
+                    self.interrupt_grace,
+                    control.turn.wait_for(|running| !running),
+                )
+                .await;
+                // A dropped sender means the process itself is gone
+                ended.is_ok()
+            }
+            Err(e) => {
+                tracing::warn!(
+                    "Failed to send interrupt to claude process for session {}: {}",
+                    session_id,
+                    e
+                );
+                false
+            }
+        };
+
+        if ended {
+            tracing::info!("Interrupted claude turn for session {}", session_id);
+        } else {
+            tracing::warn!(
+                "Claude turn for session {} did not stop when interrupted, killing its process",
+                session_id
+            );
+            self.kill_interrupted_process(session_id, process, control.pid);
+        }
+        true
+    }
+
+    /// Wait until the turn running in a session's process has ended
+    ///
+    /// A new prompt must not be written while an interrupted turn is still emitting its
+    /// last lines, or the next turn would read them as its own. Waits at most as long as
+    /// an interrupt does, see [`Self::interrupt_session`].
+    pub async fn wait_for_turn_end(&self, session_id: &SessionId) {
+        let turn = self.session_states.read().ok().and_then(|states| {
+            states
+                .get(session_id)
+                .and_then(|state| state.control.as_ref().map(|control| control.turn.clone()))
+        });
+        if let Some(mut turn) = turn {
+            let wait = turn.wait_for(|running| !running);
+            if tokio::time::timeout(self.interrupt_grace, wait)
+                .await
+                .is_err()
+            {
+                tracing::warn!(
+                    "Previous claude turn for session {} is still running",
+                    session_id
+                );
+            }
+        }
+    }
+
+    /// Remove an interrupted process from the manager and kill it
+    fn kill_interrupted_process(
+        &self,
+        session_id: &SessionId,
+        process: Arc<Mutex<ClaudeProcess>>,
+        pid: Option<u32>,
+    ) {
+        if let Ok(mut processes) = self.processes.write() {
+            if processes
+                .get(session_id)
+                .is_some_and(|current| Arc::ptr_eq(current, &process))
+            {
+                processes.remove(session_id);
+            }
+        }
+        if let Ok(mut states) = self.session_states.write() {
+            if let Some(state) = states.get_mut(session_id) {
+                state.control = None;
+            }
+        }
+
+        // The turn reading from the process holds its lock until it sees the exit
+        Self::kill_busy_process(process, pid);
+    }
+
+    #[cfg(unix)]
+    fn kill_busy_process(_process: Arc<Mutex<ClaudeProcess>>, pid: Option<u32>) {
+        use nix::sys::signal::{killpg, Signal};
+        use nix::unistd::Pid;
+
+        if let Some(pid) = pid {
+            if let Err(e) = killpg(Pid::from_raw(pid as i32), Signal::SIGKILL) {
+                tracing::warn!("Failed to kill claude process group {}: {}", pid, e);
+            }
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn kill_busy_process(process: Arc<Mutex<ClaudeProcess>>, _pid: Option<u32>) {
+        // Without signals the process can only be killed once the turn releases it
+        tokio::spawn(async move { process.lock().await.kill().await });
+    }
+
     /// Terminate a session's process
     ///
     /// # Errors
@@ -239,6 +686,9 @@ impl ClaudeProcessManager {
             })?;
             processes.remove(session_id)
         };
+        if let Ok(mut states) = self.session_states.write() {
+            states.remove(session_id);
+        }
 
         if let Some(process_arc) = process {
             // Take ownership and shutdown
@@ -274,18 +724,91 @@ impl Default for ClaudeProcessManager {
     }
 }
 
+/// Lines read from a process's stderr
+///
+/// A background task drains the pipe as the process writes to it, so a chatty process
+/// cannot fill the pipe and block. Only the last [`STDERR_TAIL_LINES`] lines are kept.
+#[derive(Debug, Default)]
+struct StderrBuffer {
+    lines: std::sync::Mutex<std::collections::VecDeque<String>>,
+    closed: std::sync::atomic::AtomicBool,
+    changed: tokio::sync::Notify,
+}
+
+impl StderrBuffer {
+    /// Start draining `stderr` into a new buffer
+    fn drain(session_id: SessionId, stderr: ChildStderr) -> Arc<Self> {
+        let buffer = Arc::new(Self::default());
+        let drained = Arc::clone(&buffer);
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(stderr).lines();
+            loop {
+                match lines.next_line().await {
+                    Ok(Some(line)) => {
+                        tracing::debug!("claude stderr for session {}: {}", session_id, line);
+                        drained.push(line);
+                    }
+                    Ok(None) => break,
+                    Err(e) => {
+                        tracing::warn!(
+                            "Failed to read claude stderr for session {}: {}",
+                            session_id,
+                            e
+                        );
+                        break;
+                    }
+                }
+            }
+            drained
+                .closed
+                .store(true, std::sync::atomic::Ordering::SeqCst);
+            drained.changed.notify_waiters();
+        });
+        buffer
+    }
+
+    fn push(&self, line: String) {
+        if let Ok(mut lines) = self.lines.lock() {
+            if lines.len() == STDERR_TAIL_LINES {
+                lines.pop_front();
+            }
+            lines.push_back(line);
+        }
+        self.changed.notify_waiters();
+    }
+
+    /// Wait for the next buffered line, `None` once stderr is closed and empty
+    async fn next_line(&self) -> Option<String> {
+        loop {
+            let changed = self.changed.notified();
+            tokio::pin!(changed);
+            changed.as_mut().enable();
+
+            if let Some(line) = self.lines.lock().ok()?.pop_front() {
+                return Some(line);
+            }
+            if self.closed.load(std::sync::atomic::Ordering::SeqCst) {
+                return None;
+            }
+            changed.await;
+        }
+    }
+}
+
 /// A persistent claude CLI process for stream-json communication
 #[derive(Debug)]
 pub struct ClaudeProcess {
     session_id: SessionId,
     child: Child,
-    stdin: ChildStdin,
+    stdin: SharedStdin,
     stdout: BufReader<ChildStdout>,
-    stderr: BufReader<ChildStderr>,
+    stderr: Arc<StderrBuffer>,
+    spawned_at: Instant,
+    turn: watch::Sender<bool>,
 }
 
 impl ClaudeProcess {
-    /// Spawn a new claude process with stream-json flags
+    /// Spawn a new claude process with stream-json flags and the default launch configuration
     ///
     /// # Errors
     /// Returns error if:
@@ -293,22 +816,55 @@ impl ClaudeProcess {
     /// - Process spawn fails
     /// - stdin/stdout/stderr not available
     pub fn spawn(session_id: SessionId) -> Result<Self> {
-        let mut cmd = Command::new("claude")
-            .args(CLAUDE_CLI_ARGS)
+        Self::spawn_with_config(session_id, &ClaudeConfig::default(), None, None)
+    }
+
+    /// Spawn a new claude process using the given launch configuration
+    ///
+    /// The binary, model, system prompt, allowed tools, extra arguments and environment
+    /// come from `config`. When `cwd` is given the process runs in that directory, and
+    /// when `resume_session_id` is given the claude conversation with that id is resumed.
+    ///
+    /// # Errors
+    /// Returns error if:
+    /// - claude binary not found
+    /// - Process spawn fails
+    /// - stdin/stdout/stderr not available
+    pub fn spawn_with_config(
+        session_id: SessionId,
+        config: &ClaudeConfig,
+        cwd: Option<&Path>,
+        resume_session_id: Option<&str>,
+    ) -> Result<Self> {
+        let mut command = Command::new(&config.binary_path);
+        command
+            .args(cli_args(config, resume_session_id))
+            .envs(
+                config
+                    .env
+                    .iter()
+                    .map(|variable| (&variable.name, &variable.value)),
+            )
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn()
-            .map_err(|e| {
-                if e.kind() == std::io::ErrorKind::NotFound {
-                    AgentError::Internal(
-                        "claude binary not found in PATH. Please ensure claude CLI is installed."
-                            .to_string(),
-                    )
-                } else {
-                    AgentError::Internal(format!("Failed to spawn claude process: {}", e))
-                }
-            })?;
+            .stderr(Stdio::piped());
+        if let Some(cwd) = cwd {
+            command.current_dir(cwd);
+        }
+        // Own process group, so killing an interrupted turn also stops claude's tools
+        #[cfg(unix)]
+        command.process_group(0);
+
+        let mut cmd = command.spawn().map_err(|e| {
+            if e.kind() == std::io::ErrorKind::NotFound {
+                AgentError::Internal(format!(
+                    "claude binary '{}' not found. Please ensure claude CLI is installed.",
+                    config.binary_path.display()
+                ))
+            } else {
+                AgentError::Internal(format!("Failed to spawn claude process: {}", e))
+            }
+        })?;
 
         let stdin = cmd.stdin.take().ok_or_else(|| {
             AgentError::Internal("Failed to capture claude process stdin".to_string())
@@ -331,31 +887,32 @@ impl ClaudeProcess {
         Ok(Self {
             session_id,
             child: cmd,
-            stdin,
+            stdin: Arc::new(Mutex::new(Some(stdin))),
             stdout: BufReader::new(stdout),
-            stderr: BufReader::new(stderr),
+            stderr: StderrBuffer::drain(session_id, stderr),
+            spawned_at: Instant::now(),
+            turn: watch::Sender::new(false),
         })
     }
 
+    /// Handles for reaching the process while another task holds its lock
+    fn control(&self) -> ProcessControl {
+        ProcessControl {
+            pid: self.id(),
+            stdin: Arc::clone(&self.stdin),
+            turn: self.turn.subscribe(),
+        }
+    }
+
     /// Write a line to the process stdin
     ///
     /// # Errors
     /// Returns error if write or flush fails
     pub async fn write_line(&mut self, line: &str) -> Result<()> {
-        self.stdin
-            .write_all(line.as_bytes())
-            .await
-            .map_err(|e| AgentError::Internal(format!("Failed to write to claude stdin: {}", e)))?;
-
-        self.stdin
-            .write_all(b"\n")
-            .await
-            .map_err(|e| AgentError::Internal(format!("Failed to write newline: {}", e)))?;
-
-        self.stdin
-            .flush()
-            .await
-            .map_err(|e| AgentError::Internal(format!("Failed to flush claude stdin: {}", e)))?;
+        write_stdin(&self.stdin, line).await?;
+        if message_type(line).as_deref() == Some("user") {
+            self.turn.send_replace(true);
+        }
 
         tracing::trace!("Wrote line to session {}: {}", self.session_id, line);
         Ok(())
@@ -380,34 +937,32 @@ impl ClaudeProcess {
 
         // Remove trailing newline
         let line = line.trim_end().to_string();
+        if message_type(&line).as_deref() == Some("result") {
+            self.turn.send_replace(false);
+        }
         tracing::trace!("Read line from session {}: {}", self.session_id, line);
         Ok(Some(line))
     }
 
     /// Read a line from the process stderr
     ///
+    /// Stderr is drained in the background; this returns the oldest line not read yet.
+    /// Lines beyond the last few the process wrote are dropped.
+    ///
     /// Returns None if EOF
     ///
     /// # Errors
     /// Returns error if read fails (but not on EOF)
     pub async fn read_stderr_line(&mut self) -> Result<Option<String>> {
-        let mut line = String::new();
-        let bytes_read = self.stderr.read_line(&mut line).await.map_err(|e| {
-            AgentError::Internal(format!("Failed to read from claude stderr: {}", e))
-        })?;
-
-        if bytes_read == 0 {
-            return Ok(None);
+        let line = self.stderr.next_line().await;
+        if let Some(line) = &line {
+            tracing::trace!(
+                "Read stderr line from session {}: {}",
+                self.session_id,
+                line
+            );
         }
-
-        // Remove trailing newline
-        let line = line.trim_end().to_string();
-        tracing::trace!(
-            "Read stderr line from session {}: {}",
-            self.session_id,
-            line
-        );
-        Ok(Some(line))
+        Ok(line)
     }
 
     /// Check if the process is still alive
@@ -433,6 +988,40 @@ impl ClaudeProcess {
         }
     }
 
+    /// How long the process has been running
+    pub fn uptime(&self) -> Duration {
+        self.spawned_at.elapsed()
+    }
+
+    /// Wait briefly for an exiting process and describe its exit status
+    ///
+    /// A process that does not exit within the wait is killed.
+    pub async fn wait_for_exit(&mut self) -> String {
+        match tokio::time::timeout(Duration::from_secs(2), self.child.wait()).await {
+            Ok(Ok(status)) => status.to_string(),
+            Ok(Err(e)) => format!("unknown exit status: {}", e),
+            Err(_) => {
+                let _ = self.child.kill().await;
+                "killed after closing stdout".to_string()
+            }
+        }
+    }
+
+    /// Read what is left on stderr and return its last `max_lines` lines
+    pub async fn stderr_tail(&mut self, max_lines: usize) -> Vec<String> {
+        let mut tail = std::collections::VecDeque::new();
+        let _ = tokio::time::timeout(Duration::from_secs(1), async {
+            while let Ok(Some(line)) = self.read_stderr_line().await {
+                if tail.len() == max_lines {
+                    tail.pop_front();
+                }
+                tail.push_back(line);
+            }
+        })
+        .await;
+        tail.into()
+    }
+
     /// Gracefully shutdown the process
     ///
     /// Attempts graceful termination first, then force kills if needed
@@ -445,8 +1034,8 @@ impl ClaudeProcess {
             self.session_id
         );
 
-        // Drop stdin to signal EOF to the process
-        drop(self.stdin);
+        // Close stdin to signal EOF to the process
+        self.stdin.lock().await.take();
 
         // Try to wait for graceful exit with timeout
         // Use try_wait in a loop to avoid blocking and retain access to child
@@ -514,6 +1103,57 @@ impl ClaudeProcess {
     pub fn session_id(&self) -> SessionId {
         self.session_id
     }
+
+    /// OS process id, if the process has not been reaped yet
+    pub fn id(&self) -> Option<u32> {
+        self.child.id()
+    }
+
+    /// Kill the process immediately and reap it
+    pub async fn kill(&mut self) {
+        if let Err(e) = self.child.kill().await {
+            tracing::warn!(
+                "Failed to kill claude process for session {}: {}",
+                self.session_id,
+                e
+            );
+        }
+    }
+}
+
+/// Write a line to a process stdin and flush it
+async fn write_stdin(stdin: &SharedStdin, line: &str) -> Result<()> {
+    let mut stdin = stdin.lock().await;
+    let stdin = stdin
+        .as_mut()
+        .ok_or_else(|| AgentError::Internal("claude stdin is closed".to_string()))?;
+
+    stdin
+        .write_all(line.as_bytes())
+        .await
+        .map_err(|e| AgentError::Internal(format!("Failed to write to claude stdin: {}", e)))?;
+
+    stdin
+        .write_all(b"\n")
+        .await
+        .map_err(|e| AgentError::Internal(format!("Failed to write newline: {}", e)))?;
+
+    stdin
+        .flush()
+        .await
+        .map_err(|e| AgentError::Internal(format!("Failed to flush claude stdin: {}", e)))
+}
+
+/// The `type` of a stream-json message
+fn message_type(line: &str) -> Option<String> {
+    #[derive(serde::Deserialize)]
+    struct Message {
+        r#type: String,
+    }
+
+    serde_json::from_str::<Message>(line)
+        .ok()
+        .map(|message| message.r#type)
 }
 
 #[cfg(test)]
@@ -534,12 +1174,9 @@ mod tests {
 
         // Spawn process
         let result = manager.spawn_for_session(session_id).await;
-        if result.is_err() {
+        if let Err(e) = &result {
             // Skip test if claude is not installed
-            eprintln!(
-                "Skipping test - claude not installed: {:?}",
-                result.unwrap_err()
-            );
+            eprintln!("Skipping test - claude not installed: {:?}", e);
             return;
         }
 
@@ -567,11 +1204,8 @@ mod tests {
 
         // Spawn first process
         let result = manager.spawn_for_session(session_id).await;
-        if result.is_err() {
-            eprintln!(
-                "Skipping test - claude not installed: {:?}",
-                result.unwrap_err()
-            );
+        if let Err(e) = &result {
+            eprintln!("Skipping test - claude not installed: {:?}", e);
             return;
         }
 
@@ -650,11 +1284,8 @@ mod tests {
         let session_id = SessionId::new();
         let result = ClaudeProcess::spawn(session_id);
 
-        if result.is_err() {
-            eprintln!(
-                "Skipping test - claude not installed: {:?}",
-                result.unwrap_err()
-            );
+        if let Err(e) = &result {
+            eprintln!("Skipping test - claude not installed: {:?}", e);
             return;
         }
 
@@ -852,4 +1483,392 @@ mod tests {
 
         tracing::info!("Concurrent access test completed successfully");
     }
+
+    #[test]
+    fn test_cli_args_from_config() {
+        let config = ClaudeConfig {
+            model: "opus".to_string(),
+            append_system_prompt: Some("Be brief.".to_string()),
+            allowed_tools: vec!["Read".to_string(), "Grep".to_string()],
+            extra_args: vec!["--add-dir".to_string(), "/shared".to_string()],
+            ..ClaudeConfig::default()
+        };
+
+        let args = cli_args(&config, None);
+
+        assert_eq!(&args[..CLAUDE_CLI_ARGS.len()], CLAUDE_CLI_ARGS);
+        assert_eq!(
+            &args[CLAUDE_CLI_ARGS.len()..],
+            [
+                "--model",
+                "opus",
+                "--append-system-prompt",
+                "Be brief.",
+                "--allowed-tools",
+                "Read,Grep",
+                "--add-dir",
+                "/shared"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cli_args_defaults_add_nothing() {
+        let args = cli_args(&ClaudeConfig::default(), None);
+        assert_eq!(args, CLAUDE_CLI_ARGS);
+
+        let args = cli_args(&ClaudeConfig::default(), Some("abc-123"));
+        assert_eq!(&args[CLAUDE_CLI_ARGS.len()..], ["--resume", "abc-123"]);
+    }
+
+    #[tokio::test]
+    async fn test_spawn_with_missing_binary() {
+        let config = ClaudeConfig {
+            binary_path: PathBuf::from("/nonexistent/claude"),
+            ..ClaudeConfig::default()
+        };
+
+        let result = ClaudeProcess::spawn_with_config(SessionId::new(), &config, None, None);
+
+        match result {
+            Err(AgentError::Internal(msg)) => assert!(msg.contains("/nonexistent/claude")),
+            other => panic!("Expected spawn failure, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_spawn_uses_env_and_cwd() {
+        use std::os::unix::fs::PermissionsExt;
+
+        // A stand-in binary that reports its working directory, environment and model
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let script = temp_dir.path().join("fake-claude");
+        std::fs::write(
+            &script,
+            "#!/bin/sh\nfor arg; do [ \"$prev\" = --model ] && model=$arg; prev=$arg; done\necho \"$(pwd) $CLAUDE_AGENT_TEST_VAR $model\"\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let config = ClaudeConfig {
+            model: "haiku".to_string(),
+            binary_path: script,
+            env: vec![crate::config::EnvVariable {
+                name: "CLAUDE_AGENT_TEST_VAR".to_string(),
+                value: "from-config".to_string(),
+            }],
+            ..ClaudeConfig::default()
+        };
+        let manager = ClaudeProcessManager::with_config(config);
+        let session_id = SessionId::new();
+        let cwd = temp_dir.path().canonicalize().unwrap();
+        manager.set_session_cwd(session_id, cwd.clone());
+
+        let process = manager.get_process(&session_id).await.unwrap();
+        let line = process.lock().await.read_line().await.unwrap();
+        assert_eq!(line, Some(format!("{} from-config haiku", cwd.display())));
+
+        drop(process);
+        let _ = manager.terminate_session(&session_id).await;
+    }
+
+    /// Write an executable stand-in for the claude binary
+    #[cfg(unix)]
+    fn fake_claude(dir: &Path, script: &str) -> PathBuf {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = dir.join("fake-claude");
+        std::fs::write(&path, format!("#!/bin/sh\n{}\n", script)).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_crash_is_reported_and_respawned_with_resume() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let config = ClaudeConfig {
+            binary_path: fake_claude(
+                temp_dir.path(),
+                "echo \"$*\"; echo 'Error: something broke' >&2; exit 3",
+            ),
+            restart: crate::config::RestartPolicy {
+                max_restarts: 1,
+                initial_backoff_ms: 10,
+                ..Default::default()
+            },
+            ..ClaudeConfig::default()
+        };
+        let manager = ClaudeProcessManager::with_config(config);
+        let session_id = SessionId::new();
+
+        // First process: not resumed, reports its conversation id and crashes
+        let process = manager.get_process(&session_id).await.unwrap();
+        let args = process.lock().await.read_line().await.unwrap().unwrap();
+        assert!(!args.contains("--resume"));
+        manager.set_claude_session_id(session_id, "claude-conversation-1".to_string());
+        assert_eq!(process.lock().await.read_line().await.unwrap(), None);
+
+        match manager.handle_process_exit(&session_id, &process).await {
+            AgentError::ProcessCrashed {
+                exit_status,
+                stderr_tail,
+                restart_count,
+                will_restart,
+                ..
+            } => {
+                assert!(exit_status.contains('3'));
+                assert_eq!(stderr_tail, vec!["Error: something broke"]);
+                assert_eq!(restart_count, 1);
+                assert!(will_restart);
+            }
+            other => panic!("Expected ProcessCrashed, got {:?}", other),
+        }
+        assert!(!manager.has_session(&session_id).await);
+
+        // Respawned process resumes the claude conversation
+        let process = manager.get_process(&session_id).await.unwrap();
+        let args = process.lock().await.read_line().await.unwrap().unwrap();
+        assert!(args.contains("--resume claude-conversation-1"));
+        assert_eq!(process.lock().await.read_line().await.unwrap(), None);
+
+        // Second crash exceeds the restart limit
+        match manager.handle_process_exit(&session_id, &process).await {
+            AgentError::ProcessCrashed { will_restart, .. } => assert!(!will_restart),
+            other => panic!("Expected ProcessCrashed, got {:?}", other),
+        }
+        let result = manager.get_process(&session_id).await;
+        assert!(matches!(result, Err(AgentError::Process(msg)) if msg.contains("restart limit")));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_known_claude_session_is_resumed() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let config = ClaudeConfig {
+            binary_path: fake_claude(temp_dir.path(), "echo \"$*\""),
+            ..ClaudeConfig::default()
+        };
+        let manager = ClaudeProcessManager::with_config(config);
+        let session_id = SessionId::new();
+        manager.set_claude_session_id(session_id, "claude-conversation-2".to_string());
+        assert_eq!(
+            manager.claude_session_id(&session_id).as_deref(),
+            Some("claude-conversation-2")
+        );
+
+        let process = manager.get_process(&session_id).await.unwrap();
+        let args = process.lock().await.read_line().await.unwrap().unwrap();
+        assert!(args.contains("--resume claude-conversation-2"));
+
+        let _ = manager.terminate_session(&session_id).await;
+        assert_eq!(manager.claude_session_id(&session_id), None);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_interrupt_asks_claude_to_end_the_turn() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let script = r#"while read -r line; do
+  case "$line" in
+    *'"subtype":"interrupt"'*) echo '{"type":"result","subtype":"error_during_execution"}' ;;
+    *'"type":"user"'*) echo '{"type":"assistant"}' ;;
+  esac
+done"#;
+        let config = ClaudeConfig {
+            binary_path: fake_claude(temp_dir.path(), script),
+            ..ClaudeConfig::default()
+        };
+        let manager = ClaudeProcessManager::with_config(config);
+        let session_id = SessionId::new();
+
+        let process = manager.get_process(&session_id).await.unwrap();
+        assert!(!manager.interrupt_session(&session_id).await);
+        {
+            let mut proc = process.lock().await;
+            proc.write_line(r#"{"type":"user"}"#).await.unwrap();
+            proc.read_line().await.unwrap();
+        }
+
+        // A turn waiting for output holds the process lock
+        let turn = tokio::spawn({
+            let process = Arc::clone(&process);
+            async move { process.lock().await.read_line().await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        assert!(manager.interrupt_session(&session_id).await);
+        let output = tokio::time::timeout(Duration::from_secs(5), turn)
+            .await
+            .expect("interrupted turn should end")
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert!(output.contains(r#""type":"result""#));
+
+        // The process and its conversation stay in place
+        let current = manager.get_process(&session_id).await.unwrap();
+        assert!(Arc::ptr_eq(&current, &process));
+        assert!(!manager.interrupt_session(&session_id).await);
+        let _ = manager.terminate_session(&session_id).await;
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_interrupt_kills_a_turn_that_does_not_stop() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let config = ClaudeConfig {
+            binary_path: fake_claude(temp_dir.path(), "echo \"$*\"; sleep 30 & echo $!; wait"),
+            ..ClaudeConfig::default()
+        };
+        let mut manager = ClaudeProcessManager::with_config(config);
+        manager.interrupt_grace = Duration::from_millis(100);
+        let session_id = SessionId::new();
+        manager.set_claude_session_id(session_id, "claude-conversation-3".to_string());
+
+        let process = manager.get_process(&session_id).await.unwrap();
+        let tool_pid = {
+            let mut proc = process.lock().await;
+            proc.read_line().await.unwrap();
+            let tool_pid: i32 = proc.read_line().await.unwrap().unwrap().parse().unwrap();
+            proc.write_line(r#"{"type":"user"}"#).await.unwrap();
+            tool_pid
+        };
+
+        let turn = tokio::spawn({
+            let process = Arc::clone(&process);
+            async move { process.lock().await.read_line().await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        assert!(manager.interrupt_session(&session_id).await);
+        let output = tokio::time::timeout(Duration::from_secs(5), turn)
+            .await
+            .expect("interrupted turn should see the process exit")
+            .unwrap()
+            .unwrap();
+        assert_eq!(output, None);
+        let error = manager.handle_process_exit(&session_id, &process).await;
+        assert!(matches!(error, AgentError::Process(_)));
+        assert_eq!(manager.restart_count(&session_id), 0);
+
+        // The whole process group was killed, including the tool claude started
+        let tool_stopped = async {
+            while std::fs::read_to_string(format!("/proc/{}/stat", tool_pid))
+                .is_ok_and(|stat| !stat.contains(") Z "))
+            {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(5), tool_stopped)
+            .await
+            .expect("tool process should be killed with claude");
+
+        let process = manager.get_process(&session_id).await.unwrap();
+        let args = process.lock().await.read_line().await.unwrap().unwrap();
+        assert!(args.contains("--resume claude-conversation-3"));
+        let _ = manager.terminate_session(&session_id).await;
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_dead_process_is_respawned_on_get() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let config = ClaudeConfig {
+            binary_path: fake_claude(temp_dir.path(), "exit 1"),
+            restart: crate::config::RestartPolicy {
+                initial_backoff_ms: 10,
+                ..Default::default()
+            },
+            ..ClaudeConfig::default()
+        };
+        let manager = ClaudeProcessManager::with_config(config);
+        let session_id = SessionId::new();
+
+        let first = manager.get_process(&session_id).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        let second = manager.get_process(&session_id).await.unwrap();
+        assert!(!Arc::ptr_eq(&first, &second));
+        assert_eq!(manager.restart_count(&session_id), 1);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_restart_limit_resets_after_stable_period() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let config = ClaudeConfig {
+            binary_path: fake_claude(temp_dir.path(), "exit 1"),
+            restart: crate::config::RestartPolicy {
+                max_restarts: 1,
+                initial_backoff_ms: 10,
+                stable_run_ms: 300,
+                ..Default::default()
+            },
+            ..ClaudeConfig::default()
+        };
+        let manager = ClaudeProcessManager::with_config(config);
+        let session_id = SessionId::new();
+
+        for _ in 0..2 {
+            let process = manager.get_process(&session_id).await.unwrap();
+            assert_eq!(process.lock().await.read_line().await.unwrap(), None);
+            manager.handle_process_exit(&session_id, &process).await;
+        }
+        assert!(manager.get_process(&session_id).await.is_err());
+
+        // A quiet period gives the session a fresh restart count
+        tokio::time::sleep(Duration::from_millis(400)).await;
+        assert!(manager.get_process(&session_id).await.is_ok());
+        assert_eq!(manager.restart_count(&session_id), 0);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_stderr_is_drained_while_running() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        // Far more than a pipe holds; a process nobody drains would block on stderr
+        let script = "i=0; while [ $i -lt 5000 ]; do \
+            echo \"warning $i: padding padding padding padding padding padding\" >&2; \
+            i=$((i+1)); done; echo ready; exec sleep 30";
+        let config = ClaudeConfig {
+            binary_path: fake_claude(temp_dir.path(), script),
+            ..ClaudeConfig::default()
+        };
+        let mut process =
+            ClaudeProcess::spawn_with_config(SessionId::new(), &config, None, None).unwrap();
+
+        let line = tokio::time::timeout(Duration::from_secs(10), process.read_line())
+            .await
+            .expect("process blocked writing to stderr")
+            .unwrap();
+        assert_eq!(line.as_deref(), Some("ready"));
+
+        let _ = process.child.kill().await;
+        let tail = process.stderr_tail(STDERR_TAIL_LINES).await;
+        assert_eq!(tail.len(), STDERR_TAIL_LINES);
+        assert!(tail.last().unwrap().starts_with("warning 4999:"));
+    }
+
+    #[tokio::test]
+    async fn test_exit_of_terminated_process_is_not_a_crash() {
+        let manager = ClaudeProcessManager::new();
+        let session_id = SessionId::new();
+        let process = match ClaudeProcess::spawn(session_id) {
+            Ok(process) => Arc::new(Mutex::new(process)),
+            Err(e) => {
+                eprintln!("Skipping test - claude not installed: {:?}", e);
+                return;
+            }
+        };
+
+        // The process is not managed, as if it had been terminated already
+        let error = manager.handle_process_exit(&session_id, &process).await;
+        assert!(matches!(error, AgentError::Process(msg) if msg.contains("terminated")));
+        assert_eq!(manager.restart_count(&session_id), 0);
+
+        let process = Arc::try_unwrap(process).unwrap().into_inner();
+        let _ = process.shutdown().await;
+    }
 }
@@ -0,0 +1,434 @@
+//! Requests from the agent to the ACP client
+//!
+//! ACP connections are bidirectional: besides answering the client's requests, the agent
+//! calls methods the client implements, such as `session/request_permission`,
+//! `fs/read_text_file` and `terminal/create`. [`ClientConnection`] gives each outbound
+//! request an id, queues it for the server to write, and completes it when the client's
+//! response with the same id arrives or the request times out. A request the agent stops
+//! waiting for is cancelled with `$/cancel_request`, so the client can close its dialog.
+
+use agent_client_protocol::{
+    Client, CreateTerminalRequest, CreateTerminalResponse, Error, ExtNotification, ExtRequest,
+    ExtResponse, KillTerminalCommandRequest, KillTerminalCommandResponse, ReadTextFileRequest,
+    ReadTextFileResponse, ReleaseTerminalRequest, ReleaseTerminalResponse,
+    RequestPermissionRequest, RequestPermissionResponse, SessionNotification,
+    TerminalOutputRequest, TerminalOutputResponse, WaitForTerminalExitRequest,
+    WaitForTerminalExitResponse, WriteTextFileRequest, WriteTextFileResponse, CLIENT_METHOD_NAMES,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
+
+type PendingResponse = oneshot::Sender<Result<Value, Error>>;
+
+/// Notification telling the client the agent no longer waits for a request
+const CANCEL_REQUEST_METHOD: &str = "$/cancel_request";
+
+/// The agent's side of the connection for calling methods on the client
+///
+/// Outbound messages are serialized JSON-RPC lines; the server takes the receiving end
+/// with [`ClientConnection::take_outgoing_receiver`] and writes them to the client, and
+/// passes every response it reads back to [`ClientConnection::handle_response`].
+#[derive(Debug)]
+pub struct ClientConnection {
+    next_id: AtomicU64,
+    pending: Mutex<HashMap<u64, PendingResponse>>,
+    outgoing: mpsc::UnboundedSender<String>,
+    outgoing_receiver: Mutex<Option<mpsc::UnboundedReceiver<String>>>,
+    closed: AtomicBool,
+    request_timeout: Duration,
+}
+
+impl ClientConnection {
+    /// Create a connection whose requests fail after `request_timeout` without a response
+    pub fn new(request_timeout: Duration) -> Self {
+        let (outgoing, outgoing_receiver) = mpsc::unbounded_channel();
+        Self {
+            next_id: AtomicU64::new(1),
+            pending: Mutex::new(HashMap::new()),
+            outgoing,
+            outgoing_receiver: Mutex::new(Some(outgoing_receiver)),
+            closed: AtomicBool::new(false),
+            request_timeout,
+        }
+    }
+
+    /// Take the stream of outbound messages; only the first caller gets it
+    pub fn take_outgoing_receiver(&self) -> Option<mpsc::UnboundedReceiver<String>> {
+        self.outgoing_receiver.lock().ok()?.take()
+    }
+
+    /// Whether a server is writing outbound messages to a client
+    pub fn is_connected(&self) -> bool {
+        !self.closed.load(Ordering::SeqCst)
+            && self
+                .outgoing_receiver
+                .lock()
+                .map(|receiver| receiver.is_none())
+                .unwrap_or(false)
+    }
+
+    /// Number of requests waiting for a response
+    pub fn pending_requests(&self) -> usize {
+        self.pending
+            .lock()
+            .map(|pending| pending.len())
+            .unwrap_or(0)
+    }
+
+    /// Send a request to the client and wait for its response
+    ///
+    /// # Errors
+    /// Returns the client's error response, or an internal error if no client is
+    /// connected, the connection closes, or the request times out
+    pub async fn request<P, R>(&self, method: &str, params: &P) -> Result<R, Error>
+    where
+        P: Serialize + ?Sized,
+        R: DeserializeOwned,
+    {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let message = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": method,
+            "params": params,
+        });
+
+        let (sender, receiver) = oneshot::channel();
+        if let Ok(mut pending) = self.pending.lock() {
+            pending.insert(id, sender);
+        }
+        // Forget the request however this call ends, including when the caller gives up
+        let _pending = PendingGuard {
+            connection: self,
+            id,
+        };
+        // Checked after registering: `close` marks the connection closed before failing
+        // the pending requests, so a request it misses sees the connection closed here
+        if !self.is_connected() {
+            return Err(Self::connection_error(method, "no client is connected"));
+        }
+        if self.outgoing.send(message.to_string()).is_err() {
+            return Err(Self::connection_error(method, "the connection is closed"));
+        }
+        tracing::debug!("Sent {} request {} to client", method, id);
+
+        let result = match tokio::time::timeout(self.request_timeout, receiver).await {
+            Ok(Ok(result)) => result,
+            Ok(Err(_)) => Err(Self::connection_error(method, "the connection is closed")),
+            Err(_) => {
+                tracing::warn!(
+                    "Client did not answer {} request {} within {:?}",
+                    method,
+                    id,
+                    self.request_timeout
+                );
+                Err(Self::connection_error(
+                    method,
+                    &format!("no response within {:?}", self.request_timeout),
+                ))
+            }
+        }?;
+
+        serde_json::from_value(result).map_err(|e| {
+            Error::invalid_params().with_data(format!("Invalid {} response: {}", method, e))
+        })
+    }
+
+    /// Send a notification to the client
+    ///
+    /// # Errors
+    /// Returns an internal error if no client is connected
+    pub fn notify<P: Serialize + ?Sized>(&self, method: &str, params: &P) -> Result<(), Error> {
+        if !self.is_connected() {
+            return Err(Self::connection_error(method, "no client is connected"));
+        }
+        let message = serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": method,
+            "params": params,
+        });
+        self.outgoing
+            .send(message.to_string())
+            .map_err(|_| Self::connection_error(method, "the connection is closed"))
+    }
+
+    /// Complete the request a response from the client belongs to
+    ///
+    /// Returns false if no request with the response's id is waiting, e.g. because it
+    /// already timed out.
+    pub fn handle_response(&self, response: &Value) -> bool {
+        let Some(id) = response.get("id").and_then(Value::as_u64) else {
+            tracing::warn!(
+                "Ignoring client response without a numeric id: {}",
+                response
+            );
+            return false;
+        };
+        let Some(sender) = self.remove_pending(id) else {
+            tracing::warn!("Ignoring client response to unknown request {}", id);
+            return false;
+        };
+
+        let result = match response.get("error") {
+            Some(error) => Err(serde_json::from_value(error.clone())
+                .unwrap_or_else(|_| Error::internal_error().with_data(error.clone()))),
+            None => Ok(response.get("result").cloned().unwrap_or(Value::Null)),
+        };
+        sender.send(result).is_ok()
+    }
+
+    /// Fail every pending request and refuse new ones
+    ///
+    /// Called when the client disconnects, so callers don't wait for their timeout.
+    pub fn close(&self) {
+        self.closed.store(true, Ordering::SeqCst);
+        let pending = self
+            .pending
+            .lock()
+            .map(|mut pending| std::mem::take(&mut *pending))
+            .unwrap_or_default();
+        for (_, sender) in pending {
+            let _ = sender.send(Err(
+                Error::internal_error().with_data("The client disconnected")
+            ));
+        }
+    }
+
+    fn remove_pending(&self, id: u64) -> Option<PendingResponse> {
+        self.pending.lock().ok()?.remove(&id)
+    }
+
+    fn connection_error(method: &str, reason: &str) -> Error {
+        Error::internal_error().with_data(format!("Client request {} failed: {}", method, reason))
+    }
+}
+
+/// Removes a request from the pending map when its caller stops waiting
+///
+/// A request still unanswered by then timed out or was abandoned, and is cancelled.
+struct PendingGuard<'a> {
+    connection: &'a ClientConnection,
+    id: u64,
+}
+
+impl Drop for PendingGuard<'_> {
+    fn drop(&mut self) {
+        if self.connection.remove_pending(self.id).is_some() && self.connection.is_connected() {
+            let params = serde_json::json!({ "requestId": self.id });
+            if let Err(e) = self.connection.notify(CANCEL_REQUEST_METHOD, &params) {
+                tracing::debug!("Failed to cancel client request {}: {:?}", self.id, e);
+            }
+        }
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl Client for ClientConnection {
+    async fn request_permission(
+        &self,
+        args: RequestPermissionRequest,
+    ) -> Result<RequestPermissionResponse, Error> {
+        self.request(CLIENT_METHOD_NAMES.session_request_permission, &args)
+            .await
+    }
+
+    async fn session_notification(&self, args: SessionNotification) -> Result<(), Error> {
+        self.notify(CLIENT_METHOD_NAMES.session_update, &args)
+    }
+
+    async fn write_text_file(
+        &self,
+        args: WriteTextFileRequest,
+    ) -> Result<WriteTextFileResponse, Error> {
+        // The response may be `null`, which the unit-like response type doesn't accept
+        let _: Value = self
+            .request(CLIENT_METHOD_NAMES.fs_write_text_file, &args)
+            .await?;
+        Ok(WriteTextFileResponse::default())
+    }
+
+    async fn read_text_file(
+        &self,
+        args: ReadTextFileRequest,
+    ) -> Result<ReadTextFileResponse, Error> {
+        self.request(CLIENT_METHOD_NAMES.fs_read_text_file, &args)
+            .await
+    }
+
+    async fn create_terminal(
+        &self,
+        args: CreateTerminalRequest,
+    ) -> Result<CreateTerminalResponse, Error> {
+        self.request(CLIENT_METHOD_NAMES.terminal_create, &args)
+            .await
+    }
+
+    async fn terminal_output(
+        &self,
+        args: TerminalOutputRequest,
+    ) -> Result<TerminalOutputResponse, Error> {
+        self.request(CLIENT_METHOD_NAMES.terminal_output, &args)
+            .await
+    }
+
+    async fn release_terminal(
+        &self,
+        args: ReleaseTerminalRequest,
+    ) -> Result<ReleaseTerminalResponse, Error> {
+        let _: Value = self
+            .request(CLIENT_METHOD_NAMES.terminal_release, &args)
+            .await?;
+        Ok(ReleaseTerminalResponse::default())
+    }
+
+    async fn wait_for_terminal_exit(
+        &self,
+        args: WaitForTerminalExitRequest,
+    ) -> Result<WaitForTerminalExitResponse, Error> {
+        self.request(CLIENT_METHOD_NAMES.terminal_wait_for_exit, &args)
+            .await
+    }
+
+    async fn kill_terminal_command(
+        &self,
+        args: KillTerminalCommandRequest,
+    ) -> Result<KillTerminalCommandResponse, Error> {
+        let _: Value = self
+            .request(CLIENT_METHOD_NAMES.terminal_kill, &args)
+            .await?;
+        Ok(KillTerminalCommandResponse::default())
+    }
+
+    async fn ext_method(&self, args: ExtRequest) -> Result<ExtResponse, Error> {
+        let result: Value = self.request(&args.method, &args.params).await?;
+        let raw = agent_client_protocol::RawValue::from_string(result.to_string())
+            .map_err(|e| Error::internal_error().with_data(e.to_string()))?;
+        Ok(ExtResponse::from(Arc::from(raw)))
+    }
+
+    async fn ext_notification(&self, args: ExtNotification) -> Result<(), Error> {
+        self.notify(&args.method, &args.params)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A connection whose outbound messages are collected by the test
+    fn connected(timeout: Duration) -> (Arc<ClientConnection>, mpsc::UnboundedReceiver<String>) {
+        let connection = Arc::new(ClientConnection::new(timeout));
+        let receiver = connection.take_outgoing_receiver().unwrap();
+        (connection, receiver)
+    }
+
+    async fn next_request(receiver: &mut mpsc::UnboundedReceiver<String>) -> Value {
+        serde_json::from_str(&receiver.recv().await.unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_response_is_matched_by_id() {
+        let (connection, mut outgoing) = connected(Duration::from_secs(5));
+
+        let request = connection.read_text_file(ReadTextFileRequest {
+            session_id: agent_client_protocol::SessionId("session-1".into()),
+            path: "/project/src/main.rs".into(),
+            line: None,
+            limit: None,
+            meta: None,
+        });
+        let client = async {
+            let message = next_request(&mut outgoing).await;
+            assert_eq!(message["method"], "fs/read_text_file");
+            assert_eq!(message["params"]["path"], "/project/src/main.rs");
+            assert!(connection.handle_response(&serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": message["id"],
+                "result": {"content": "fn main() {}"}
+            })));
+        };
+
+        let (response, _) = tokio::join!(request, client);
+        assert_eq!(response.unwrap().content, "fn main() {}");
+        assert_eq!(connection.pending_requests(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_error_response_is_returned() {
+        let (connection, mut outgoing) = connected(Duration::from_secs(5));
+
+        let params = serde_json::json!({"sessionId": "session-1", "terminalId": "term-1"});
+        let request = connection.request::<_, Value>("terminal/output", &params);
+        let client = async {
+            let message = next_request(&mut outgoing).await;
+            connection.handle_response(&serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": message["id"],
+                "error": {"code": -32602, "message": "Unknown terminal"}
+            }));
+        };
+
+        let (response, _) = tokio::join!(request, client);
+        let error = response.unwrap_err();
+        assert_eq!(error.code, -32602);
+        assert_eq!(error.message, "Unknown terminal");
+
+        // Answered requests are not cancelled
+        assert!(outgoing.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_unanswered_request_times_out() {
+        let (connection, mut outgoing) = connected(Duration::from_millis(20));
+
+        let error = connection
+            .request::<_, Value>("session/request_permission", &serde_json::json!({}))
+            .await
+            .unwrap_err();
+        assert!(error.data.unwrap().to_string().contains("no response"));
+        assert_eq!(connection.pending_requests(), 0);
+
+        // The client is told to stop waiting for an answer
+        let request = next_request(&mut outgoing).await;
+        let cancel = next_request(&mut outgoing).await;
+        assert_eq!(cancel["method"], "$/cancel_request");
+        assert_eq!(cancel["params"]["requestId"], request["id"]);
+
+        // A late response is ignored
+        assert!(!connection.handle_response(&serde_json::json!({"id": 1, "result": null})));
+    }
+
+    #[tokio::test]
+    async fn test_close_fails_pending_and_new_requests() {
+        let (connection, mut outgoing) = connected(Duration::from_secs(5));
+
+        let params = serde_json::json!({"sessionId": "session-1", "command": "ls"});
+        let request = connection.request::<_, Value>("terminal/create", &params);
+        let disconnect = async {
+            next_request(&mut outgoing).await;
+            connection.close();
+        };
+        let (response, _) = tokio::join!(request, disconnect);
+        assert!(response.is_err());
+
+        assert!(!connection.is_connected());
+        assert!(connection
+            .request::<_, Value>("terminal/create", &serde_json::json!({}))
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_requests_fail_without_a_server() {
+        let connection = ClientConnection::new(Duration::from_secs(5));
+        assert!(!connection.is_connected());
+        assert!(connection
+            .notify("session/update", &serde_json::json!({}))
+            .is_err());
+    }
+}
@@ -50,13 +50,270 @@ pub struct AgentConfig {
     /// Maximum language model requests per turn (default: 50) - triggers MaxTurnRequests stop reason
     #[serde(default = "default_max_turn_requests")]
     pub max_turn_requests: u64,
+    /// Directory for persisted session files (default: None, sessions are kept in memory only)
+    #[serde(default)]
+    pub session_storage_path: Option<std::path::PathBuf>,
+    /// Spending limits per session and for the whole agent process (default: unlimited)
+    #[serde(default)]
+    pub budget: BudgetConfig,
+    /// Permission policies replacing the built-in defaults (default: None, use the defaults)
+    #[serde(default)]
+    pub permission_policies: Option<Vec<crate::permissions::PermissionPolicy>>,
+    /// Global TOML or JSON policy file (default: None, use ~/.claude-agent/policy.toml or .json)
+    #[serde(default)]
+    pub policy_file: Option<std::path::PathBuf>,
+    /// Repositories whose policy files may also allow tools (default: none, repository
+    /// policy files can only deny tools or ask for them)
+    #[serde(default)]
+    pub trusted_projects: Vec<std::path::PathBuf>,
+    /// Where "always allow" and "always reject" answers are remembered
+    #[serde(default)]
+    pub permission_store: PermissionStoreConfig,
+    /// Append-only log of tool executions and permission decisions (default: off)
+    #[serde(default)]
+    pub audit: AuditConfig,
+    /// How terminal commands are run
+    #[serde(default)]
+    pub terminal: TerminalConfig,
+}
+
+/// Storage of "always allow" and "always reject" answers across restarts
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+#[serde(default)]
+pub struct PermissionStoreConfig {
+    /// Keep answers on disk so they survive restarts (default: true)
+    pub persist: bool,
+    /// Scope new answers are stored in, `project` or `user` (default: project)
+    pub scope: crate::permission_storage::PermissionScope,
+    /// Directory of user-scoped answers, whose `projects` subdirectory holds the answers
+    /// of each project (default: ~/.claude-agent/permissions)
+    pub user_dir: Option<std::path::PathBuf>,
+    /// Days after which a stored answer expires (default: None, answers never expire)
+    pub expire_after_days: Option<u64>,
+}
+
+impl Default for PermissionStoreConfig {
+    fn default() -> Self {
+        Self {
+            persist: true,
+            scope: Default::default(),
+            user_dir: None,
+            expire_after_days: None,
+        }
+    }
+}
+
+/// Audit log of tool executions and permission decisions
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+#[serde(default)]
+pub struct AuditConfig {
+    /// JSONL file records are appended to (default: None, nothing is logged)
+    pub path: Option<std::path::PathBuf>,
+    /// Size in bytes at which the file is rotated (default: 10 MiB)
+    pub max_file_bytes: u64,
+    /// Number of rotated files kept as `<path>.1`, `<path>.2`, ... (default: 5)
+    pub max_files: usize,
+    /// Argument names redacted in addition to the built-in secret names (default: none)
+    pub redact_keys: Vec<String>,
+}
+
+impl Default for AuditConfig {
+    fn default() -> Self {
+        Self {
+            path: None,
+            max_file_bytes: 10 * 1024 * 1024,
+            max_files: 5,
+            redact_keys: Vec::new(),
+        }
+    }
+}
+
+/// Shell and pseudo-terminal used to run terminal commands
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+#[serde(default)]
+pub struct TerminalConfig {
+    /// Shell commands are passed to with `-c` (default: sh, cmd on Windows)
+    pub shell: String,
+    /// Run commands in a pseudo-terminal so interactive programs work (default: false)
+    pub use_pty: bool,
+}
+
+impl Default for TerminalConfig {
+    fn default() -> Self {
+        Self {
+            shell: if cfg!(windows) { "cmd" } else { "sh" }.to_string(),
+            use_pty: false,
+        }
+    }
+}
+
+/// Spending limits checked against the usage and cost reported by the claude CLI
+///
+/// Tokens are counted without cache reads, like `max_tokens_per_turn`.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+#[serde(default)]
+pub struct BudgetConfig {
+    /// Maximum cost per session in USD (default: unlimited)
+    pub max_session_cost_usd: Option<f64>,
+    /// Maximum tokens per session (default: unlimited)
+    pub max_session_tokens: Option<u64>,
+    /// Maximum cost of all sessions of this agent process in USD (default: unlimited)
+    pub max_agent_cost_usd: Option<f64>,
+    /// Maximum tokens of all sessions of this agent process (default: unlimited)
+    pub max_agent_tokens: Option<u64>,
+    /// Fractions of a budget at which a warning is sent (default: 0.5, 0.8, 0.95)
+    pub warning_thresholds: Vec<f64>,
+}
+
+impl Default for BudgetConfig {
+    fn default() -> Self {
+        Self {
+            max_session_cost_usd: None,
+            max_session_tokens: None,
+            max_agent_cost_usd: None,
+            max_agent_tokens: None,
+            warning_thresholds: vec![0.5, 0.8, 0.95],
+        }
+    }
+}
+
+impl BudgetConfig {
+    /// Validate the budget limits and warning thresholds
+    pub fn validate(&self) -> crate::error::Result<()> {
+        for (name, limit) in [
+            ("max_session_cost_usd", self.max_session_cost_usd),
+            ("max_agent_cost_usd", self.max_agent_cost_usd),
+        ] {
+            if limit.is_some_and(|limit| !limit.is_finite() || limit <= 0.0) {
+                return Err(crate::error::AgentError::Config(format!(
+                    "Budget {} must be a positive number",
+                    name
+                )));
+            }
+        }
+
+        for (name, limit) in [
+            ("max_session_tokens", self.max_session_tokens),
+            ("max_agent_tokens", self.max_agent_tokens),
+        ] {
+            if limit == Some(0) {
+                return Err(crate::error::AgentError::Config(format!(
+                    "Budget {} must be greater than zero",
+                    name
+                )));
+            }
+        }
+
+        if let Some(threshold) = self
+            .warning_thresholds
+            .iter()
+            .find(|threshold| !(**threshold > 0.0 && **threshold < 1.0))
+        {
+            return Err(crate::error::AgentError::Config(format!(
+                "Budget warning threshold {} must be between 0 and 1",
+                threshold
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+/// Model of the default configuration, which leaves the choice to the claude CLI
+pub const DEFAULT_CLAUDE_MODEL: &str = "claude-sonnet-4-20250514";
+
+/// Default value for binary_path
+fn default_claude_binary_path() -> std::path::PathBuf {
+    std::path::PathBuf::from("claude")
 }
 
 /// Configuration for Claude SDK integration
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct ClaudeConfig {
+    /// Model passed to the claude CLI with `--model` (full name or alias), unless it is
+    /// the default model
     pub model: String,
     pub stream_format: StreamFormat,
+    /// Path to the claude CLI binary (default: `claude` looked up in PATH)
+    #[serde(default = "default_claude_binary_path")]
+    pub binary_path: std::path::PathBuf,
+    /// Text appended to the default system prompt with `--append-system-prompt`
+    #[serde(default)]
+    pub append_system_prompt: Option<String>,
+    /// Tools the CLI may use without asking, passed with `--allowed-tools`
+    #[serde(default)]
+    pub allowed_tools: Vec<String>,
+    /// Additional command-line arguments appended after the generated ones
+    #[serde(default)]
+    pub extra_args: Vec<String>,
+    /// Environment variables set for the claude process
+    #[serde(default)]
+    pub env: Vec<EnvVariable>,
+    /// How the claude process is restarted after it exits unexpectedly
+    #[serde(default)]
+    pub restart: RestartPolicy,
+}
+
+/// Restart policy for crashed claude CLI processes
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+#[serde(default)]
+pub struct RestartPolicy {
+    /// Maximum consecutive restarts per session before giving up (default: 3)
+    pub max_restarts: u32,
+    /// Delay before the first restart in milliseconds, doubled for each further restart (default: 500)
+    pub initial_backoff_ms: u64,
+    /// Upper bound for the restart delay in milliseconds (default: 30,000)
+    pub max_backoff_ms: u64,
+    /// Resume the previous claude conversation with `--resume` when restarting (default: true)
+    pub resume: bool,
+    /// A process running this long, or a session this long after its last crash, starts
+    /// a fresh restart count, in milliseconds (default: 60,000)
+    pub stable_run_ms: u64,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self {
+            max_restarts: 3,
+            initial_backoff_ms: 500,
+            max_backoff_ms: 30_000,
+            resume: true,
+            stable_run_ms: 60_000,
+        }
+    }
+}
+
+impl RestartPolicy {
+    /// Delay before restart number `restart` (1-based)
+    pub fn backoff(&self, restart: u32) -> std::time::Duration {
+        let factor = 2u64.saturating_pow(restart.saturating_sub(1));
+        std::time::Duration::from_millis(
+            self.initial_backoff_ms
+                .saturating_mul(factor)
+                .min(self.max_backoff_ms),
+        )
+    }
+
+    /// How long a process must run, or a session stay without crashes, before its
+    /// restart count starts over
+    pub fn stable_run(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.stable_run_ms)
+    }
+}
+
+impl Default for ClaudeConfig {
+    fn default() -> Self {
+        Self {
+            model: DEFAULT_CLAUDE_MODEL.to_string(),
+            stream_format: StreamFormat::StreamJson,
+            binary_path: default_claude_binary_path(),
+            append_system_prompt: None,
+            allowed_tools: Vec::new(),
+            extra_args: Vec::new(),
+            env: Vec::new(),
+            restart: RestartPolicy::default(),
+        }
+    }
 }
 
 /// Server configuration options  
@@ -64,6 +321,9 @@ pub struct ClaudeConfig {
 pub struct ServerConfig {
     pub port: Option<u16>,
     pub log_level: String,
+    /// Seconds to wait for the client to answer a request from the agent (default: 300)
+    #[serde(default = "default_client_request_timeout_secs")]
+    pub client_request_timeout_secs: u64,
 }
 
 /// Security configuration options
@@ -309,6 +569,11 @@ fn default_mcp_protocol_version() -> String {
     "2024-11-05".to_string()
 }
 
+fn default_client_request_timeout_secs() -> u64 {
+    // Permission requests wait for the user to decide
+    300
+}
+
 fn default_mcp_timeout() -> u64 {
     30
 }
@@ -337,13 +602,11 @@ pub enum StreamFormat {
 impl Default for AgentConfig {
     fn default() -> Self {
         Self {
-            claude: ClaudeConfig {
-                model: "claude-sonnet-4-20250514".to_string(),
-                stream_format: StreamFormat::StreamJson,
-            },
+            claude: ClaudeConfig::default(),
             server: ServerConfig {
                 port: None,
                 log_level: "info".to_string(),
+                client_request_timeout_secs: default_client_request_timeout_secs(),
             },
             security: SecurityConfig {
                 allowed_file_patterns: vec![
@@ -360,6 +623,14 @@ impl Default for AgentConfig {
             cancellation_buffer_size: default_cancellation_buffer_size(),
             max_tokens_per_turn: default_max_tokens_per_turn(),
             max_turn_requests: default_max_turn_requests(),
+            session_storage_path: None,
+            budget: BudgetConfig::default(),
+            permission_policies: None,
+            policy_file: None,
+            trusted_projects: Vec::new(),
+            permission_store: PermissionStoreConfig::default(),
+            audit: AuditConfig::default(),
+            terminal: TerminalConfig::default(),
         }
     }
 }
@@ -374,6 +645,27 @@ impl AgentConfig {
             ));
         }
 
+        self.claude.validate()?;
+        self.budget.validate()?;
+
+        if self.permission_store.expire_after_days == Some(0) {
+            return Err(crate::error::AgentError::Config(
+                "Permission store expire_after_days must be greater than zero".to_string(),
+            ));
+        }
+
+        if self.audit.max_file_bytes == 0 {
+            return Err(crate::error::AgentError::Config(
+                "Audit log max_file_bytes must be greater than zero".to_string(),
+            ));
+        }
+
+        if self.terminal.shell.trim().is_empty() {
+            return Err(crate::error::AgentError::Config(
+                "Terminal shell cannot be empty".to_string(),
+            ));
+        }
+
         // Validate log level
         if !["error", "warn", "info", "debug", "trace"].contains(&self.server.log_level.as_str()) {
             return Err(crate::error::AgentError::Config(format!(
@@ -397,12 +689,69 @@ impl AgentConfig {
         Ok(config)
     }
 
+    /// Create the session store selected by this configuration
+    pub fn create_session_store(&self) -> std::sync::Arc<dyn crate::session_store::SessionStore> {
+        match &self.session_storage_path {
+            Some(path) => {
+                std::sync::Arc::new(crate::session_store::FileSessionStore::new(path.clone()))
+            }
+            None => std::sync::Arc::new(crate::session_store::InMemorySessionStore::new()),
+        }
+    }
+
     /// Serialize configuration to JSON string
     pub fn to_json(&self) -> crate::error::Result<String> {
         Ok(serde_json::to_string_pretty(self)?)
     }
 }
 
+impl ClaudeConfig {
+    /// The model the user configured, if it isn't the default one
+    pub fn configured_model(&self) -> Option<&str> {
+        Some(self.model.as_str()).filter(|model| *model != DEFAULT_CLAUDE_MODEL)
+    }
+
+    /// Arguments that the agent manages itself and that must not be overridden
+    const RESERVED_ARGS: &'static [&'static str] = &[
+        "-p",
+        "--print",
+        "--input-format",
+        "--output-format",
+        "--permission-prompt-tool",
+        "--dangerously-skip-permissions",
+    ];
+
+    /// Validate the claude CLI launch settings
+    pub fn validate(&self) -> crate::error::Result<()> {
+        if self.binary_path.as_os_str().is_empty() {
+            return Err(crate::error::AgentError::Config(
+                "Claude binary path cannot be empty".to_string(),
+            ));
+        }
+
+        for arg in &self.extra_args {
+            let flag = arg.split('=').next().unwrap_or(arg);
+            if Self::RESERVED_ARGS.contains(&flag) {
+                return Err(crate::error::AgentError::Config(format!(
+                    "Claude extra argument '{}' conflicts with arguments managed by the agent",
+                    arg
+                )));
+            }
+        }
+
+        for variable in &self.env {
+            if variable.name.is_empty() || variable.name.contains('=') {
+                return Err(crate::error::AgentError::Config(format!(
+                    "Invalid claude environment variable name: '{}'",
+                    variable.name
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
 impl SecurityConfig {
     /// Convert SecurityConfig to ToolPermissions for tool call handler
     pub fn to_tool_permissions(&self) -> crate::tools::ToolPermissions {
@@ -582,6 +931,150 @@ mod tests {
         assert_eq!(config.max_prompt_length, sizes::messages::MAX_PROMPT_LENGTH);
     }
 
+    #[test]
+    fn test_claude_launch_config_deserialization() {
+        let json = r#"{
+            "model": "opus",
+            "stream_format": "StreamJson",
+            "binary_path": "/opt/claude/2.1.0/claude",
+            "append_system_prompt": "Follow the project style guide.",
+            "allowed_tools": ["Read", "Bash(git diff:*)"],
+            "extra_args": ["--add-dir", "/shared"],
+            "env": [{"name": "CLAUDE_CODE_USE_BEDROCK", "value": "1"}]
+        }"#;
+
+        let claude: ClaudeConfig = serde_json::from_str(json).unwrap();
+
+        assert_eq!(
+            claude.binary_path,
+            std::path::PathBuf::from("/opt/claude/2.1.0/claude")
+        );
+        assert_eq!(
+            claude.append_system_prompt.as_deref(),
+            Some("Follow the project style guide.")
+        );
+        assert_eq!(claude.allowed_tools, vec!["Read", "Bash(git diff:*)"]);
+        assert_eq!(claude.extra_args, vec!["--add-dir", "/shared"]);
+        assert_eq!(claude.env[0].name, "CLAUDE_CODE_USE_BEDROCK");
+        assert!(claude.validate().is_ok());
+    }
+
+    #[test]
+    fn test_claude_config_rejects_reserved_extra_args() {
+        let mut config = AgentConfig::default();
+        config.claude.extra_args = vec!["--output-format=text".to_string()];
+
+        let result = config.validate();
+        assert!(result.is_err());
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("conflicts with arguments managed by the agent"));
+    }
+
+    #[test]
+    fn test_claude_config_rejects_invalid_env_name() {
+        let mut config = AgentConfig::default();
+        config.claude.env = vec![EnvVariable {
+            name: "A=B".to_string(),
+            value: "1".to_string(),
+        }];
+
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_budget_config_validation() {
+        let mut config = AgentConfig::default();
+        assert!(config.budget.max_session_cost_usd.is_none());
+        assert_eq!(config.budget.warning_thresholds, vec![0.5, 0.8, 0.95]);
+
+        config.budget.max_session_cost_usd = Some(5.0);
+        config.budget.max_agent_tokens = Some(1_000_000);
+        assert!(config.validate().is_ok());
+
+        config.budget.max_agent_cost_usd = Some(-1.0);
+        assert!(config.validate().is_err());
+        config.budget.max_agent_cost_usd = None;
+
+        config.budget.max_session_tokens = Some(0);
+        assert!(config.validate().is_err());
+        config.budget.max_session_tokens = None;
+
+        config.budget.warning_thresholds = vec![0.5, 1.5];
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_permission_store_config() {
+        let config: AgentConfig = serde_json::from_value(serde_json::json!({
+            "claude": {"model": "claude-sonnet-4-20250514", "stream_format": "StreamJson"},
+            "server": {"port": null, "log_level": "info"},
+            "security": {
+                "allowed_file_patterns": [],
+                "forbidden_paths": [],
+                "require_permission_for": []
+            },
+            "mcp_servers": [],
+            "permission_store": {"scope": "user", "expire_after_days": 30}
+        }))
+        .unwrap();
+        assert!(config.permission_store.persist);
+        assert_eq!(
+            config.permission_store.scope,
+            crate::permission_storage::PermissionScope::User
+        );
+        assert!(config.validate().is_ok());
+
+        let mut config = AgentConfig::default();
+        config.permission_store.expire_after_days = Some(0);
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_audit_config() {
+        let config = AgentConfig::default();
+        assert!(config.audit.path.is_none());
+
+        let audit: AuditConfig =
+            serde_json::from_value(serde_json::json!({"path": "/var/log/agent/audit.jsonl"}))
+                .unwrap();
+        assert_eq!(audit.max_files, 5);
+        assert_eq!(audit.max_file_bytes, 10 * 1024 * 1024);
+
+        let mut config = AgentConfig::default();
+        config.audit.max_file_bytes = 0;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_terminal_config() {
+        let terminal: TerminalConfig =
+            serde_json::from_value(serde_json::json!({"use_pty": true})).unwrap();
+        assert!(terminal.use_pty);
+        assert_eq!(terminal.shell, TerminalConfig::default().shell);
+
+        let mut config = AgentConfig::default();
+        assert!(!config.terminal.use_pty);
+        config.terminal.shell = " ".to_string();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_restart_policy_backoff() {
+        let policy = RestartPolicy {
+            initial_backoff_ms: 100,
+            max_backoff_ms: 500,
+            ..RestartPolicy::default()
+        };
+
+        assert_eq!(policy.backoff(1), std::time::Duration::from_millis(100));
+        assert_eq!(policy.backoff(2), std::time::Duration::from_millis(200));
+        assert_eq!(policy.backoff(3), std::time::Duration::from_millis(400));
+        assert_eq!(policy.backoff(4), std::time::Duration::from_millis(500));
+        assert_eq!(policy.backoff(100), std::time::Duration::from_millis(500));
+    }
+
     #[test]
     fn test_round_trip_serialization() {
         let original = AgentConfig::default();
@@ -0,0 +1,294 @@
+//! Reloading the agent configuration while it runs
+//!
+//! [`ConfigWatcher`] polls the configuration, MCP and policy files for changes. When one
+//! changes, [`crate::agent::ClaudeAgent::watch_config`] builds a new [`AgentConfig`] from
+//! a [`ConfigSource`] and hands it to [`crate::agent::ClaudeAgent::reload_config`], which
+//! applies the fields that can change live and reports the ones that only take effect
+//! after a restart.
+
+use crate::config::AgentConfig;
+use crate::error::{AgentError, Result};
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+/// Fields of [`AgentConfig`] applied by a reload without restarting the agent
+///
+/// `server.log_level` is applied by the embedding application, which owns the log
+/// subscriber.
+pub const LIVE_FIELDS: &[&str] = &[
+    "max_prompt_length",
+    "max_tokens_per_turn",
+    "max_turn_requests",
+    "budget",
+    "permission_policies",
+    "policy_file",
+    "trusted_projects",
+    "mcp_servers",
+    "server.log_level",
+];
+
+/// Outcome of applying a new configuration
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ConfigReload {
+    /// Changed fields that are now in effect
+    pub applied: Vec<String>,
+    /// Changed fields that keep their old value until the agent restarts
+    pub restart_required: Vec<String>,
+    /// Whether the MCP tools changed, which updates every session's commands
+    pub tools_changed: bool,
+}
+
+/// Names of the fields that differ between two configurations
+///
+/// Fields of `server` are compared one by one and reported as `server.<field>`.
+pub fn changed_fields(old: &AgentConfig, new: &AgentConfig) -> Vec<String> {
+    let (Ok(serde_json::Value::Object(old)), Ok(serde_json::Value::Object(new))) =
+        (serde_json::to_value(old), serde_json::to_value(new))
+    else {
+        return Vec::new();
+    };
+
+    let mut changed = Vec::new();
+    for (field, value) in &new {
+        match (field.as_str(), value, old.get(field)) {
+            ("server", serde_json::Value::Object(server), Some(serde_json::Value::Object(old))) => {
+                changed.extend(
+                    server
+                        .iter()
+                        .filter(|(name, value)| old.get(name.as_str()) != Some(*value))
+                        .map(|(name, _)| format!("server.{}", name)),
+                );
+            }
+            (_, value, old_value) if old_value != Some(value) => changed.push(field.clone()),
+            _ => {}
+        }
+    }
+    changed
+}
+
+/// Where the agent configuration is built from
+///
+/// Implemented by [`ConfigFile`] for a single JSON file; applications that merge several
+/// files or command-line overrides implement it themselves.
+pub trait ConfigSource: Send + Sync {
+    /// Build the configuration from the current contents of its files
+    fn load(&self) -> Result<AgentConfig>;
+
+    /// Files the configuration is built from, whether or not they exist yet
+    ///
+    /// Policy files referenced by the configuration are added by the caller.
+    fn paths(&self) -> Vec<PathBuf>;
+}
+
+/// Configuration read from a single JSON file
+#[derive(Debug, Clone)]
+pub struct ConfigFile {
+    path: PathBuf,
+}
+
+impl ConfigFile {
+    /// Read the configuration from `path`
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl ConfigSource for ConfigFile {
+    fn load(&self) -> Result<AgentConfig> {
+        let contents = std::fs::read_to_string(&self.path).map_err(|e| {
+            AgentError::Config(format!("Failed to read {}: {}", self.path.display(), e))
+        })?;
+        let config: AgentConfig = serde_json::from_str(&contents).map_err(|e| {
+            AgentError::Config(format!("Failed to parse {}: {}", self.path.display(), e))
+        })?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn paths(&self) -> Vec<PathBuf> {
+        vec![self.path.clone()]
+    }
+}
+
+/// Files to watch for `config`: those of its source and the policy files it uses in
+/// `project_dirs`
+pub fn watched_paths(
+    source: &dyn ConfigSource,
+    config: &AgentConfig,
+    project_dirs: &[PathBuf],
+) -> Vec<PathBuf> {
+    let mut paths = source.paths();
+    paths.extend(crate::policy_file::watched_paths(config, project_dirs));
+    paths
+}
+
+/// Size and modification time of a file, `None` when it does not exist
+type FileSignature = Option<(u64, Option<SystemTime>)>;
+
+/// Polls a set of files and reports when any of them is created, changed or removed
+#[derive(Debug)]
+pub struct ConfigWatcher {
+    paths: Vec<PathBuf>,
+    signatures: Vec<FileSignature>,
+    interval: Duration,
+}
+
+impl ConfigWatcher {
+    /// Watch `paths`, checking them every `interval`
+    ///
+    /// The current state of the files is the baseline; only later changes are reported.
+    pub fn new(paths: Vec<PathBuf>, interval: Duration) -> Self {
+        let signatures = paths.iter().map(signature).collect();
+        Self {
+            paths,
+            signatures,
+            interval,
+        }
+    }
+
+    /// Replace the watched files
+    ///
+    /// Files already watched keep their baseline, so a change not yet polled is still
+    /// reported; the current state of new files is their baseline.
+    pub fn set_paths(&mut self, paths: Vec<PathBuf>) {
+        self.signatures = paths
+            .iter()
+            .map(
+                |path| match self.paths.iter().position(|known| known == path) {
+                    Some(index) => self.signatures[index],
+                    None => signature(path),
+                },
+            )
+            .collect();
+        self.paths = paths;
+    }
+
+    /// The watched files
+    pub fn paths(&self) -> &[PathBuf] {
+        &self.paths
+    }
+
+    /// Check the files once and return whether any changed since the last check
+    pub fn poll(&mut self) -> bool {
+        let signatures: Vec<FileSignature> = self.paths.iter().map(signature).collect();
+        let changed = signatures != self.signatures;
+        self.signatures = signatures;
+        changed
+    }
+
+    /// Wait until a watched file changes
+    ///
+    /// Returns once the files have stopped changing for one interval, so an editor
+    /// saving in several steps triggers a single reload.
+    pub async fn changed(&mut self) {
+        let paths = self.paths.clone();
+        self.changed_in(|| paths.clone()).await
+    }
+
+    /// Wait until a watched file changes, asking `paths` for the files before each check
+    ///
+    /// Lets the set of files follow state that changes while waiting, such as the
+    /// working directories of open sessions. See [`ConfigWatcher::set_paths`] and
+    /// [`ConfigWatcher::changed`].
+    pub async fn changed_in(&mut self, mut paths: impl FnMut() -> Vec<PathBuf>) {
+        loop {
+            tokio::time::sleep(self.interval).await;
+            self.set_paths(paths());
+            if self.poll() {
+                break;
+            }
+        }
+        loop {
+            tokio::time::sleep(self.interval).await;
+            if !self.poll() {
+                return;
+            }
+        }
+    }
+}
+
+fn signature(path: &PathBuf) -> FileSignature {
+    std::fs::metadata(path)
+        .ok()
+        .map(|metadata| (metadata.len(), metadata.modified().ok()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_changed_fields() {
+        let old = AgentConfig::default();
+        let mut new = old.clone();
+        assert!(changed_fields(&old, &new).is_empty());
+
+        new.max_turn_requests += 1;
+        new.server.log_level = "debug".to_string();
+        new.notification_buffer_size += 1;
+        let mut changed = changed_fields(&old, &new);
+        changed.sort();
+        assert_eq!(
+            changed,
+            [
+                "max_turn_requests",
+                "notification_buffer_size",
+                "server.log_level"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_poll_detects_created_changed_and_removed_files() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("config.json");
+        let mut watcher = ConfigWatcher::new(vec![path.clone()], Duration::from_millis(10));
+        assert!(!watcher.poll());
+
+        std::fs::write(&path, "{}").unwrap();
+        assert!(watcher.poll());
+        assert!(!watcher.poll());
+
+        std::fs::write(&path, r#"{"max_turn_requests": 5}"#).unwrap();
+        assert!(watcher.poll());
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(watcher.poll());
+        assert!(!watcher.poll());
+    }
+
+    #[test]
+    fn test_set_paths_keeps_the_baseline_of_watched_files() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let config = temp_dir.path().join("config.json");
+        let policy = temp_dir.path().join("policy.toml");
+        std::fs::write(&policy, "[[rules]]").unwrap();
+        let mut watcher = ConfigWatcher::new(vec![config.clone()], Duration::from_millis(10));
+
+        std::fs::write(&config, "{}").unwrap();
+        watcher.set_paths(vec![config, policy]);
+        assert!(watcher.poll());
+        assert!(!watcher.poll());
+    }
+
+    #[tokio::test]
+    async fn test_changed_waits_for_a_change() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("policy.toml");
+        let mut watcher = ConfigWatcher::new(vec![path.clone()], Duration::from_millis(10));
+
+        let writer = {
+            let path = path.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(Duration::from_millis(30)).await;
+                std::fs::write(path, "[[rules]]").unwrap();
+            })
+        };
+
+        tokio::time::timeout(Duration::from_secs(5), watcher.changed())
+            .await
+            .expect("change was not reported");
+        writer.await.unwrap();
+        assert!(!watcher.poll());
+    }
+}
@@ -182,6 +182,19 @@ pub struct ProcessedContent {
     pub size_bytes: usize,
 }
 
+impl ProcessedContent {
+    /// Processed form of a text block
+    pub fn text(text: &str) -> Self {
+        Self {
+            content_type: ProcessedContentType::Text,
+            text_representation: text.to_string(),
+            binary_data: None,
+            metadata: HashMap::new(),
+            size_bytes: text.len(),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum ProcessedContentType {
     Text,
@@ -658,18 +671,7 @@ impl ContentBlockProcessor {
         &self,
         text_content: &TextContent,
     ) -> Result<ProcessedContent, ContentBlockProcessorError> {
-        let metadata = HashMap::new();
-
-        let content_text = text_content.text.clone();
-        let size_bytes = content_text.len();
-
-        Ok(ProcessedContent {
-            content_type: ProcessedContentType::Text,
-            text_representation: content_text,
-            binary_data: None,
-            metadata,
-            size_bytes,
-        })
+        Ok(ProcessedContent::text(&text_content.text))
     }
 
     fn validate_uri(&self, uri: &str) -> Result<(), ContentBlockProcessorError> {
@@ -376,7 +376,7 @@ impl ConversationManager {
 
         // Process streaming chunks
         while let Some(chunk) = stream.next().await {
-            // Check for actual token usage in Result messages
+            // Check for actual token usage in Usage chunks and Result messages
             if let Some(usage_info) = chunk.token_usage {
                 actual_token_usage = Some(TokenUsage {
                     input_tokens: usage_info.input_tokens,
@@ -434,6 +434,9 @@ impl ConversationManager {
                     // Tool results are inputs, not outputs from LM
                     tracing::debug!("Tool result chunk (unexpected in LM output)");
                 }
+                ChunkType::Usage => {
+                    // Running usage; the usage it carries was taken above
+                }
             }
         }
 
@@ -172,7 +172,7 @@ impl EditorStateManager {
     /// # Client Integration
     ///
     /// Clients should proactively push editor state updates via the
-    /// `editor/update_buffers` extension method. The agent maintains a cache
+    /// `_claude_agent/editor/update_buffers` extension method. The agent maintains a cache
     /// of editor buffers that have been pushed by the client.
     pub async fn get_file_content(
         &self,
@@ -188,7 +188,7 @@ impl EditorStateManager {
         tracing::trace!("Editor buffer cache miss for: {}", path.display());
 
         // Return None to indicate editor buffer not available in cache
-        // Clients can proactively push updates via editor/update_buffers
+        // Clients can proactively push updates via _claude_agent/editor/update_buffers
         Ok(None)
     }
 
@@ -196,7 +196,7 @@ impl EditorStateManager {
     ///
     /// This method processes an `EditorBufferResponse` from the client and
     /// updates the internal cache with the provided buffers. This is typically
-    /// called when handling the `editor/update_buffers` extension method.
+    /// called when handling the `_claude_agent/editor/update_buffers` extension method.
     ///
     /// # Arguments
     ///
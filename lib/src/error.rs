@@ -108,6 +108,24 @@ pub enum McpError {
     #[error("MCP server tools list request failed: {0}")]
     ToolsListFailed(String),
 
+    /// Tool call arguments do not match the tool's input schema
+    ///
+    /// Occurs before a tools/call request is sent, when the arguments fail
+    /// validation against the inputSchema the server declared for the tool.
+    #[error("Invalid arguments for MCP tool '{0}': {1}")]
+    InvalidToolArguments(String, String),
+
+    /// Prompt arguments are missing or cannot be assigned
+    ///
+    /// Occurs before a prompts/get request is sent, when the text after an MCP
+    /// prompt command lacks an argument the prompt requires.
+    #[error("Invalid arguments for MCP prompt '{0}': {1}")]
+    InvalidPromptArguments(String, String),
+
+    /// No connected MCP server lists the requested resource
+    #[error("No MCP server provides resource '{0}'")]
+    ResourceNotFound(String),
+
     /// MCP server configuration is invalid
     ///
     /// Occurs when MCP server configuration contains invalid values,
@@ -147,13 +165,16 @@ pub enum McpError {
 impl ToJsonRpcError for McpError {
     fn to_json_rpc_code(&self) -> i32 {
         match self {
-            McpError::ProtocolError(_) => -32600,       // Invalid Request
-            McpError::SerializationFailed(_) => -32700, // Parse error
-            McpError::ServerError(_) => -32000,         // Server error
-            McpError::RequestTimeout => -32000,         // Server error
-            McpError::ConnectionClosed => -32000,       // Server error
-            McpError::ProcessCrashed => -32000,         // Server error
-            _ => -32603,                                // Internal error (default)
+            McpError::ProtocolError(_) => -32600,         // Invalid Request
+            McpError::SerializationFailed(_) => -32700,   // Parse error
+            McpError::InvalidToolArguments(..) => -32602, // Invalid params
+            McpError::InvalidPromptArguments(..) => -32602, // Invalid params
+            McpError::ResourceNotFound(_) => -32002,      // Resource not found
+            McpError::ServerError(_) => -32000,           // Server error
+            McpError::RequestTimeout => -32000,           // Server error
+            McpError::ConnectionClosed => -32000,         // Server error
+            McpError::ProcessCrashed => -32000,           // Server error
+            _ => -32603,                                  // Internal error (default)
         }
     }
 }
@@ -210,6 +231,18 @@ pub enum AgentError {
 
     #[error("Internal error: {0}")]
     Internal(String),
+
+    #[error("Claude process for session {session_id} exited unexpectedly ({exit_status})")]
+    ProcessCrashed {
+        session_id: String,
+        exit_status: String,
+        /// Last lines the process wrote to stderr
+        stderr_tail: Vec<String>,
+        /// Number of restarts used so far in this session
+        restart_count: u32,
+        /// Whether the process will be respawned on the next prompt
+        will_restart: bool,
+    },
 }
 
 impl ToJsonRpcError for AgentError {
@@ -226,9 +259,30 @@ impl ToJsonRpcError for AgentError {
             AgentError::ToolExecution(_) => -32000,  // Server error
             AgentError::Session(_) => -32000,        // Server error
             AgentError::Config(_) => -32000,         // Server error
+            AgentError::ProcessCrashed { .. } => -32000, // Server error
             _ => -32603,                             // Internal error (default)
         }
     }
+
+    fn to_error_data(&self) -> Option<Value> {
+        match self {
+            AgentError::ProcessCrashed {
+                session_id,
+                exit_status,
+                stderr_tail,
+                restart_count,
+                will_restart,
+            } => Some(serde_json::json!({
+                "error": "process_crashed",
+                "sessionId": session_id,
+                "exitStatus": exit_status,
+                "stderrTail": stderr_tail,
+                "restartCount": restart_count,
+                "willRestart": will_restart,
+            })),
+            _ => None,
+        }
+    }
 }
 
 impl AgentError {
@@ -417,4 +471,24 @@ mod tests {
             assert!(matches!(error, AgentError::Protocol(_)));
         }
     }
+
+    #[test]
+    fn test_process_crashed_error_data() {
+        let error = AgentError::ProcessCrashed {
+            session_id: "01ABC".to_string(),
+            exit_status: "exit status: 1".to_string(),
+            stderr_tail: vec!["Error: invalid API key".to_string()],
+            restart_count: 1,
+            will_restart: true,
+        };
+
+        let json_rpc = <AgentError as ToJsonRpcError>::to_json_rpc_error(&error);
+        assert_eq!(json_rpc.code, -32000);
+        assert!(json_rpc.message.contains("exited unexpectedly"));
+
+        let data = json_rpc.data.unwrap();
+        assert_eq!(data["error"], "process_crashed");
+        assert_eq!(data["stderrTail"][0], "Error: invalid API key");
+        assert_eq!(data["willRestart"], true);
+    }
 }
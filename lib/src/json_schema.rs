@@ -0,0 +1,188 @@
+//! Validation of JSON values against a JSON Schema
+//!
+//! MCP servers describe the arguments of their tools with a JSON Schema. This module
+//! checks arguments against it before a `tools/call` request is sent, so mistakes are
+//! reported to the model without a round trip to the server.
+//!
+//! Validation is done by the `jsonschema` crate, which supports every keyword of the
+//! drafts tools declare, including `patternProperties` and `$ref` to `$defs`. Remote
+//! references are not fetched: a schema that needs them, like any schema the crate
+//! cannot compile, is not checked and the server validates the arguments itself.
+
+use serde_json::Value;
+
+/// A place where a value does not match its schema
+#[derive(Debug, Clone, PartialEq)]
+pub struct SchemaViolation {
+    /// JSON pointer to the offending value, empty for the value itself
+    pub path: String,
+    /// What is wrong with the value
+    pub message: String,
+}
+
+impl std::fmt::Display for SchemaViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "{}: {}", self.path, self.message)
+        }
+    }
+}
+
+/// Check `value` against `schema` and return every violation found
+pub fn validate(schema: &Value, value: &Value) -> Vec<SchemaViolation> {
+    let validator = match jsonschema::validator_for(schema) {
+        Ok(validator) => validator,
+        Err(e) => {
+            // An unusable schema is the server's fault, not the value's
+            tracing::warn!("Not validating against an invalid JSON Schema: {}", e);
+            return Vec::new();
+        }
+    };
+    validator
+        .iter_errors(value)
+        .map(|error| SchemaViolation {
+            path: error.instance_path.to_string(),
+            message: error.to_string(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn read_file_schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "path": {"type": "string", "minLength": 1},
+                "encoding": {"enum": ["utf-8", "base64"]},
+                "lines": {
+                    "type": "array",
+                    "items": {"type": "integer", "minimum": 1},
+                    "maxItems": 2
+                }
+            },
+            "required": ["path"],
+            "additionalProperties": false
+        })
+    }
+
+    #[test]
+    fn test_valid_arguments() {
+        let schema = read_file_schema();
+        assert!(validate(&schema, &json!({"path": "/tmp/a.txt"})).is_empty());
+        assert!(validate(
+            &schema,
+            &json!({"path": "/tmp/a.txt", "encoding": "base64", "lines": [1, 20]})
+        )
+        .is_empty());
+
+        // Schemas without constraints accept anything
+        assert!(validate(&json!({}), &json!({"anything": [1, "two"]})).is_empty());
+        assert!(validate(&json!(true), &json!(null)).is_empty());
+    }
+
+    #[test]
+    fn test_violations_name_the_offending_value() {
+        let schema = read_file_schema();
+        let violations = validate(
+            &schema,
+            &json!({"encoding": "latin-1", "lines": [0, 2, 3], "mode": "r"}),
+        );
+        let mut paths: Vec<&str> = violations
+            .iter()
+            .map(|violation| violation.path.as_str())
+            .collect();
+        paths.sort_unstable();
+        assert_eq!(paths, ["", "", "/encoding", "/lines", "/lines/0"]);
+        assert!(violations
+            .iter()
+            .any(|violation| violation.to_string().contains("\"path\"")));
+        assert!(violations
+            .iter()
+            .any(|violation| violation.to_string().contains("'mode'")));
+
+        let violations = validate(&schema, &json!({"path": 42}));
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].path, "/path");
+        assert!(violations[0].message.contains("\"string\""));
+    }
+
+    #[test]
+    fn test_pattern_properties_are_not_additional() {
+        let schema = json!({
+            "type": "object",
+            "properties": {"name": {"type": "string"}},
+            "patternProperties": {"^x-": {"type": "string"}},
+            "additionalProperties": false
+        });
+        assert!(validate(&schema, &json!({"name": "a", "x-trace": "on"})).is_empty());
+
+        let violations = validate(&schema, &json!({"x-trace": 1}));
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].path, "/x-trace");
+        assert_eq!(validate(&schema, &json!({"other": "a"})).len(), 1);
+    }
+
+    #[test]
+    fn test_references_are_followed() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "from": {"$ref": "#/$defs/position"},
+                "to": {"$ref": "#/$defs/position"}
+            },
+            "$defs": {
+                "position": {
+                    "type": "object",
+                    "properties": {"line": {"type": "integer", "minimum": 0}},
+                    "required": ["line"]
+                }
+            }
+        });
+        assert!(validate(&schema, &json!({"from": {"line": 1}, "to": {"line": 4}})).is_empty());
+
+        let violations = validate(&schema, &json!({"from": {"line": -1}, "to": {}}));
+        let mut paths: Vec<&str> = violations
+            .iter()
+            .map(|violation| violation.path.as_str())
+            .collect();
+        paths.sort_unstable();
+        assert_eq!(paths, ["/from/line", "/to"]);
+    }
+
+    #[test]
+    fn test_unusable_schema_is_not_checked() {
+        let schema = json!({"type": "object", "properties": {"a": {"$ref": "#/$defs/missing"}}});
+        assert!(validate(&schema, &json!({"a": 1})).is_empty());
+    }
+
+    #[test]
+    fn test_numbers_and_combinators() {
+        let schema = json!({
+            "type": ["integer", "null"],
+            "exclusiveMinimum": 0,
+            "multipleOf": 5
+        });
+        assert!(validate(&schema, &json!(10)).is_empty());
+        assert!(validate(&schema, &json!(null)).is_empty());
+        assert_eq!(validate(&schema, &json!(0)).len(), 1);
+        assert_eq!(validate(&schema, &json!(7)).len(), 1);
+        assert!(!validate(&schema, &json!(2.5)).is_empty());
+
+        let schema = json!({
+            "anyOf": [{"type": "string", "pattern": "^[a-z]+$"}, {"type": "boolean"}]
+        });
+        assert!(validate(&schema, &json!("main")).is_empty());
+        assert!(validate(&schema, &json!(true)).is_empty());
+        assert_eq!(validate(&schema, &json!("Main")).len(), 1);
+
+        let schema = json!({"oneOf": [{"type": "number"}, {"type": "integer"}]});
+        assert_eq!(validate(&schema, &json!(1)).len(), 1);
+        assert!(validate(&schema, &json!(1.5)).is_empty());
+    }
+}
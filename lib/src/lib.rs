@@ -6,12 +6,16 @@
 
 pub mod acp_error_conversion;
 pub mod agent;
+pub mod audit;
 pub mod base64_processor;
 pub mod base64_validation;
+pub mod budget;
 pub mod capability_validation;
 pub mod claude;
 pub mod claude_process;
+pub mod client_connection;
 pub mod config;
+pub mod config_watcher;
 pub mod constants;
 pub mod content_block_processor;
 pub mod content_capability_validator;
@@ -19,6 +23,7 @@ pub mod content_security_validator;
 pub mod conversation_manager;
 pub mod editor_state;
 pub mod json_rpc_codes;
+pub mod json_schema;
 pub mod mime_type_validator;
 
 #[cfg(test)]
@@ -27,17 +32,21 @@ pub mod error;
 pub mod mcp;
 pub mod mcp_error_handling;
 pub mod path_validator;
+pub mod permission_conditions;
+pub mod permission_flow;
 #[cfg(test)]
 mod permission_interaction_tests;
 pub mod permission_storage;
 pub mod permissions;
 pub mod plan;
+pub mod policy_file;
 pub mod protocol_translator;
 pub mod request_validation;
 pub mod server;
 pub mod session;
 pub mod session_errors;
 pub mod session_loading;
+pub mod session_store;
 pub mod session_validation;
 pub mod size_validator;
 pub mod terminal_manager;
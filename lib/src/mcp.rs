@@ -6,1008 +6,2358 @@
 
 use crate::{config::McpServerConfig, error::McpError, tools::InternalToolRequest};
 use reqwest::Client;
+use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use std::collections::HashMap;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, PoisonError, Weak};
+use std::time::{Duration, Instant};
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, BufWriter};
-use tokio::process::{Child, Command};
-use tokio::sync::{mpsc, RwLock};
+use tokio::process::{Child, ChildStdin, ChildStdout, Command};
+use tokio::sync::{broadcast, mpsc, oneshot, watch, RwLock};
 
 /// Transport-specific connection details
-#[derive(Debug)]
+///
+/// Clones share the underlying process or HTTP session.
+#[derive(Debug, Clone)]
 pub enum TransportConnection {
     /// Stdio transport using child process
+    ///
+    /// The server's stdout is read by a task that hands each message to the
+    /// connection's [`MessageRouter`].
     Stdio {
         process: Arc<RwLock<Option<Child>>>,
-        stdin_writer: Arc<RwLock<Option<BufWriter<tokio::process::ChildStdin>>>>,
-        stdout_reader: Arc<RwLock<Option<BufReader<tokio::process::ChildStdout>>>>,
+        stdin_writer: Arc<RwLock<Option<BufWriter<ChildStdin>>>>,
     },
-    /// HTTP transport using reqwest client
+    /// Streamable HTTP transport
+    ///
+    /// Every message is POSTed to `url`, which answers with JSON or an event stream.
+    /// Messages the server initiates arrive on a GET event stream that is resumed with
+    /// `Last-Event-ID` when it breaks. The session is ended with a DELETE.
     Http {
         client: Arc<Client>,
         url: String,
         headers: Vec<crate::config::HttpHeader>,
         session_id: Arc<RwLock<Option<String>>>,
+        /// Protocol version agreed on by initialize, sent as `MCP-Protocol-Version`
+        protocol_version: Arc<RwLock<Option<String>>>,
     },
-    /// SSE transport using WebSocket connection
+    /// Legacy HTTP+SSE transport
+    ///
+    /// The server sends its messages on the event stream opened at `url`, whose
+    /// `endpoint` event names the URL client messages are POSTed to.
     Sse {
+        client: Arc<Client>,
         url: String,
+        endpoint: String,
         headers: Vec<crate::config::HttpHeader>,
-        message_tx: Arc<RwLock<Option<mpsc::UnboundedSender<String>>>>,
-        response_rx: Arc<RwLock<Option<mpsc::UnboundedReceiver<String>>>>,
     },
 }
 
-/// Represents a connection to an MCP server
-#[derive(Debug)]
-pub struct McpServerConnection {
-    /// Name of the MCP server
+/// Protocol version offered in initialize requests
+const PROTOCOL_VERSION: &str = "2025-03-26";
+
+/// Time a legacy SSE server has to name its message endpoint
+const SSE_ENDPOINT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Delay before a broken event stream is reopened, unless the server asks for another
+const EVENT_STREAM_RETRY: Duration = Duration::from_secs(1);
+
+/// Longest delay between two attempts to reopen an event stream
+const MAX_EVENT_STREAM_RETRY: Duration = Duration::from_secs(30);
+
+/// Time a server has to acknowledge the end of a session
+const SESSION_DELETE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Attempts to resume a POST response stream that broke before the response arrived
+const MAX_STREAM_RESUMPTIONS: usize = 3;
+
+/// Build the HTTP client for a server, sending `headers` with every request
+fn http_client(server_name: &str, headers: &[crate::config::HttpHeader]) -> crate::Result<Client> {
+    let mut header_map = reqwest::header::HeaderMap::new();
+    for header in headers {
+        if let (Ok(name), Ok(value)) = (
+            reqwest::header::HeaderName::from_bytes(header.name.as_bytes()),
+            reqwest::header::HeaderValue::from_str(&header.value),
+        ) {
+            header_map.insert(name, value);
+        }
+    }
+
+    Client::builder()
+        .default_headers(header_map)
+        .build()
+        .map_err(|e| {
+            crate::AgentError::ToolExecution(format!(
+                "Failed to create HTTP client for MCP server {}: {}",
+                server_name, e
+            ))
+        })
+}
+
+/// A tool as declared by an MCP server in its tools/list response
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct McpTool {
+    /// Name the server knows the tool by
     pub name: String,
-    /// List of tools available from this server
-    pub tools: Vec<String>,
-    /// Configuration used to create this connection
-    pub config: McpServerConfig,
-    /// Transport-specific connection details
-    pub transport: TransportConnection,
+    /// Human-readable name for display
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// What the tool does
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// JSON Schema of the tool's arguments
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input_schema: Option<Value>,
+    /// Hints about the tool's behavior
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub annotations: Option<McpToolAnnotations>,
 }
 
-/// Manages connections to multiple MCP servers
-#[derive(Debug)]
-pub struct McpServerManager {
-    /// Map of server name to connection
-    connections: Arc<RwLock<HashMap<String, McpServerConnection>>>,
+/// Behavior hints of an MCP tool
+///
+/// Hints come from the server and are not guaranteed to be accurate; they inform how a
+/// tool call is displayed, not whether it is allowed.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct McpToolAnnotations {
+    /// Human-readable name for display
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// The tool does not modify its environment
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub read_only_hint: Option<bool>,
+    /// The tool may perform destructive updates
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub destructive_hint: Option<bool>,
+    /// Calling the tool repeatedly with the same arguments has no additional effect
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idempotent_hint: Option<bool>,
+    /// The tool interacts with external entities, such as the web
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub open_world_hint: Option<bool>,
 }
 
-impl McpServerManager {
-    /// Create a new MCP server manager
-    pub fn new() -> Self {
+impl McpTool {
+    /// A tool known only by its name
+    pub fn named(name: impl Into<String>) -> Self {
         Self {
-            connections: Arc::new(RwLock::new(HashMap::new())),
+            name: name.into(),
+            title: None,
+            description: None,
+            input_schema: None,
+            annotations: None,
         }
     }
 
-    /// Connect to all configured MCP servers
-    pub async fn connect_servers(&mut self, configs: Vec<McpServerConfig>) -> crate::Result<()> {
-        for config in configs {
-            let config_name = config.name().to_string();
-            match self.connect_server(config).await {
-                Ok(connection) => {
-                    let connection_name = connection.name.clone();
-                    tracing::info!("Connected to MCP server: {}", connection_name);
-                    let mut connections = self.connections.write().await;
-                    connections.insert(connection_name, connection);
-                }
-                Err(e) => {
-                    tracing::error!("Failed to connect to MCP server {}: {}", config_name, e);
-                    // Continue with other servers instead of failing completely
-                }
-            }
+    /// Check arguments against the tool's input schema
+    ///
+    /// Tools without a schema accept any arguments.
+    pub fn validate_arguments(&self, arguments: &Value) -> Result<(), McpError> {
+        let Some(schema) = &self.input_schema else {
+            return Ok(());
+        };
+        // A missing arguments object is sent as an empty one
+        let empty = Value::Object(Default::default());
+        let arguments = if arguments.is_null() {
+            &empty
+        } else {
+            arguments
+        };
+
+        let violations = crate::json_schema::validate(schema, arguments);
+        if violations.is_empty() {
+            return Ok(());
         }
-        Ok(())
+        let details: Vec<String> = violations.iter().map(ToString::to_string).collect();
+        Err(McpError::InvalidToolArguments(
+            self.name.clone(),
+            details.join("; "),
+        ))
     }
 
-    /// Connect to a single MCP server
-    async fn connect_server(&self, config: McpServerConfig) -> crate::Result<McpServerConnection> {
-        // Only stdio transport is currently implemented in the connection logic
-        match &config {
-            McpServerConfig::Stdio(stdio_config) => {
-                tracing::info!(
-                    "Connecting to MCP server: {} ({})",
-                    stdio_config.name,
-                    stdio_config.command
-                );
-
-                // Start the MCP server process with environment variables
-                let mut command = Command::new(&stdio_config.command);
-                command.args(&stdio_config.args);
-
-                // Set working directory if provided
-                if let Some(cwd) = &stdio_config.cwd {
-                    command.current_dir(cwd);
-                }
-
-                // Set environment variables
-                for env_var in &stdio_config.env {
-                    command.env(&env_var.name, &env_var.value);
-                }
-
-                let mut child = command
-                    .stdin(std::process::Stdio::piped())
-                    .stdout(std::process::Stdio::piped())
-                    .stderr(std::process::Stdio::piped())
-                    .spawn()
-                    .map_err(|e| McpError::ProcessSpawnFailed(stdio_config.name.clone(), e))?;
+    /// Short description of the arguments, e.g. `path, recursive?`
+    ///
+    /// Lists the required properties first, then the optional ones marked with `?`.
+    pub fn input_hint(&self) -> Option<String> {
+        let schema = self.input_schema.as_ref()?;
+        let properties = schema.get("properties")?.as_object()?;
+        if properties.is_empty() {
+            return None;
+        }
+        let required: Vec<&str> = schema
+            .get("required")
+            .and_then(Value::as_array)
+            .map(|names| names.iter().filter_map(Value::as_str).collect())
+            .unwrap_or_default();
+
+        let hint: Vec<String> = required
+            .iter()
+            .filter(|name| properties.contains_key(**name))
+            .map(|name| name.to_string())
+            .chain(
+                properties
+                    .keys()
+                    .filter(|name| !required.contains(&name.as_str()))
+                    .map(|name| format!("{}?", name)),
+            )
+            .collect();
+        Some(hint.join(", "))
+    }
+}
 
-                // Get stdio handles
-                let stdin = child.stdin.take().ok_or(McpError::StdinNotAvailable)?;
-                let stdout = child.stdout.take().ok_or(McpError::StdoutNotAvailable)?;
+/// Parse the tools of a tools/list response
+///
+/// Tools whose optional fields are malformed are kept by name; entries without a name
+/// are skipped.
+pub fn parse_tool_list(response: &Value) -> Vec<McpTool> {
+    let Some(tool_list) = response
+        .get("result")
+        .and_then(|result| result.get("tools"))
+        .and_then(Value::as_array)
+    else {
+        return Vec::new();
+    };
+
+    tool_list.iter().filter_map(parse_tool).collect()
+}
 
-                let mut stdin_writer = BufWriter::new(stdin);
-                let mut stdout_reader = BufReader::new(stdout);
+/// Parse one entry of a tools/list response, keeping a malformed tool by its name
+fn parse_tool(tool: &Value) -> Option<McpTool> {
+    let name = tool.get("name").and_then(Value::as_str)?;
+    Some(serde_json::from_value(tool.clone()).unwrap_or_else(|e| {
+        tracing::warn!("Ignoring malformed metadata of MCP tool {}: {}", name, e);
+        McpTool::named(name)
+    }))
+}
 
-                // Initialize MCP protocol
-                let tools = self
-                    .initialize_mcp_connection(
-                        &mut stdin_writer,
-                        &mut stdout_reader,
-                        &stdio_config.name,
-                        stdio_config,
-                    )
-                    .await?;
+/// A resource as declared by an MCP server in its resources/list response
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct McpResource {
+    /// URI the resource is read by
+    pub uri: String,
+    /// Name of the resource
+    pub name: String,
+    /// Human-readable name for display
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// What the resource contains
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// MIME type of the contents, if known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+    /// Size of the contents in bytes, if known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+}
 
-                let transport = TransportConnection::Stdio {
-                    process: Arc::new(RwLock::new(Some(child))),
-                    stdin_writer: Arc::new(RwLock::new(Some(stdin_writer))),
-                    stdout_reader: Arc::new(RwLock::new(Some(stdout_reader))),
-                };
+/// A prompt template as declared by an MCP server in its prompts/list response
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct McpPrompt {
+    /// Name the server knows the prompt by
+    pub name: String,
+    /// Human-readable name for display
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// What the prompt is for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Arguments the template is filled with, in order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub arguments: Vec<McpPromptArgument>,
+}
 
-                let connection = McpServerConnection {
-                    name: stdio_config.name.clone(),
-                    tools,
-                    config,
-                    transport,
-                };
+/// An argument of an MCP prompt template
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct McpPromptArgument {
+    /// Name of the argument
+    pub name: String,
+    /// What the argument means
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// The prompt cannot be rendered without the argument
+    #[serde(default)]
+    pub required: bool,
+}
 
-                Ok(connection)
+impl McpPrompt {
+    /// Assign the words of a slash command's input to the prompt's arguments
+    ///
+    /// Words are assigned in the order the arguments are declared, and the last
+    /// argument receives the rest of the text. Arguments without a word are left out.
+    pub fn arguments_from_text(&self, text: &str) -> Result<Value, McpError> {
+        let mut values = serde_json::Map::new();
+        let mut rest = text.trim();
+        for (index, argument) in self.arguments.iter().enumerate() {
+            if rest.is_empty() {
+                break;
             }
-            McpServerConfig::Http(http_config) => {
-                tracing::info!(
-                    "Connecting to HTTP MCP server: {} ({})",
-                    http_config.name,
-                    http_config.url
-                );
+            let value = if index + 1 == self.arguments.len() {
+                std::mem::take(&mut rest)
+            } else {
+                let (word, remainder) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+                rest = remainder.trim_start();
+                word
+            };
+            values.insert(argument.name.clone(), Value::String(value.to_string()));
+        }
 
-                // Create HTTP client with headers
-                let client_builder = Client::builder();
-                let mut headers = reqwest::header::HeaderMap::new();
+        let missing: Vec<&str> = self
+            .arguments
+            .iter()
+            .filter(|argument| argument.required && !values.contains_key(&argument.name))
+            .map(|argument| argument.name.as_str())
+            .collect();
+        if !missing.is_empty() {
+            return Err(McpError::InvalidPromptArguments(
+                self.name.clone(),
+                format!("missing required arguments: {}", missing.join(", ")),
+            ));
+        }
+        Ok(Value::Object(values))
+    }
 
-                for header in &http_config.headers {
-                    if let (Ok(name), Ok(value)) = (
-                        reqwest::header::HeaderName::from_bytes(header.name.as_bytes()),
-                        reqwest::header::HeaderValue::from_str(&header.value),
-                    ) {
-                        headers.insert(name, value);
-                    }
+    /// Short description of the arguments, e.g. `language focus?`
+    pub fn input_hint(&self) -> Option<String> {
+        if self.arguments.is_empty() {
+            return None;
+        }
+        let hint: Vec<String> = self
+            .arguments
+            .iter()
+            .map(|argument| {
+                if argument.required {
+                    argument.name.clone()
+                } else {
+                    format!("{}?", argument.name)
                 }
+            })
+            .collect();
+        Some(hint.join(" "))
+    }
+}
 
-                let client = client_builder
-                    .default_headers(headers)
-                    .build()
-                    .map_err(|e| {
-                        crate::AgentError::ToolExecution(format!(
-                            "Failed to create HTTP client for MCP server {}: {}",
-                            http_config.name, e
-                        ))
-                    })?;
-
-                // Initialize MCP connection via HTTP
-                let session_id = Arc::new(RwLock::new(None));
-                let tools = self
-                    .initialize_http_mcp_connection(&client, http_config, Arc::clone(&session_id))
-                    .await?;
-
-                let transport = TransportConnection::Http {
-                    client: Arc::new(client),
-                    url: http_config.url.clone(),
-                    headers: http_config.headers.clone(),
-                    session_id,
-                };
+/// Parse the entries of a paginated list response, skipping malformed ones
+fn parse_entries<T: serde::de::DeserializeOwned>(entries: Vec<Value>, kind: &str) -> Vec<T> {
+    entries
+        .into_iter()
+        .filter_map(|entry| match serde_json::from_value(entry) {
+            Ok(parsed) => Some(parsed),
+            Err(e) => {
+                tracing::warn!("Ignoring malformed MCP {}: {}", kind, e);
+                None
+            }
+        })
+        .collect()
+}
 
-                let connection = McpServerConnection {
-                    name: http_config.name.clone(),
-                    tools,
-                    config,
-                    transport,
-                };
+/// Progress an MCP server reported for a running request
+#[derive(Debug, Clone, PartialEq)]
+pub struct McpProgress {
+    /// Progress so far, increasing with every notification
+    pub progress: f64,
+    /// Total amount of work, if the server knows it
+    pub total: Option<f64>,
+    /// Description of the current step
+    pub message: Option<String>,
+}
 
-                Ok(connection)
-            }
-            McpServerConfig::Sse(sse_config) => {
-                tracing::info!(
-                    "Connecting to SSE MCP server: {} ({})",
-                    sse_config.name,
-                    sse_config.url
-                );
+/// Upper bound on the pages fetched for one list, against servers that never stop paging
+const MAX_LIST_PAGES: usize = 100;
 
-                // Create SSE connection channels
-                let (message_tx, _message_rx) = mpsc::unbounded_channel();
-                let (response_tx, response_rx) = mpsc::unbounded_channel();
+/// Time a server has to list what changed after announcing it
+const LIST_REFRESH_TIMEOUT: Duration = Duration::from_secs(30);
 
-                // Initialize SSE connection
-                let tools = self
-                    .initialize_sse_mcp_connection(sse_config, response_tx)
-                    .await?;
+/// A list a server announces changes of with `notifications/<kind>/list_changed`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ListKind {
+    Tools,
+    Resources,
+    Prompts,
+}
 
-                let transport = TransportConnection::Sse {
-                    url: sse_config.url.clone(),
-                    headers: sse_config.headers.clone(),
-                    message_tx: Arc::new(RwLock::new(Some(message_tx))),
-                    response_rx: Arc::new(RwLock::new(Some(response_rx))),
-                };
+impl ListKind {
+    /// Method that lists the entries
+    fn method(self) -> &'static str {
+        match self {
+            ListKind::Tools => "tools/list",
+            ListKind::Resources => "resources/list",
+            ListKind::Prompts => "prompts/list",
+        }
+    }
 
-                let connection = McpServerConnection {
-                    name: sse_config.name.clone(),
-                    tools,
-                    config,
-                    transport,
-                };
+    /// Field of the list result holding the entries, also the name of the capability
+    fn field(self) -> &'static str {
+        match self {
+            ListKind::Tools => "tools",
+            ListKind::Resources => "resources",
+            ListKind::Prompts => "prompts",
+        }
+    }
+}
 
-                Ok(connection)
-            }
+/// Requests waiting for their response, keyed by the JSON text of their id
+type PendingRequests = Arc<std::sync::Mutex<HashMap<String, oneshot::Sender<Value>>>>;
+
+/// Progress listeners, keyed by the JSON text of their progress token
+type ProgressListeners = Arc<std::sync::Mutex<HashMap<String, mpsc::UnboundedSender<McpProgress>>>>;
+
+/// Reacts to server notifications that concern the manager rather than one request
+#[derive(Debug, Clone)]
+pub struct NotificationHandler {
+    /// Connections of the manager, to refresh the lists of a server
+    connections: Weak<RwLock<HashMap<String, McpServerConnection>>>,
+    /// Signalled whenever the stored tools or prompts of a server change
+    commands_changed: watch::Sender<()>,
+    /// URIs of subscribed resources the servers reported as updated
+    resource_updates: broadcast::Sender<String>,
+}
+
+impl Default for NotificationHandler {
+    /// A handler that belongs to no manager, so list changes are only logged
+    fn default() -> Self {
+        Self {
+            connections: Weak::new(),
+            commands_changed: watch::Sender::new(()),
+            resource_updates: broadcast::Sender::new(RESOURCE_UPDATE_CAPACITY),
         }
     }
+}
 
-    /// Initialize the MCP protocol connection
-    async fn initialize_mcp_connection(
-        &self,
-        writer: &mut BufWriter<tokio::process::ChildStdin>,
-        reader: &mut BufReader<tokio::process::ChildStdout>,
-        server_name: &str,
-        _config: &crate::config::StdioTransport,
-    ) -> crate::Result<Vec<String>> {
-        // Send initialize request
-        let initialize_request = json!({
-            "jsonrpc": "2.0",
-            "id": 1,
-            "method": "initialize",
-            "params": {
-                "protocolVersion": "2024-11-05",
-                "capabilities": {
-                    "tools": {}
-                },
-                "clientInfo": {
-                    "name": "claude-agent",
-                    "version": env!("CARGO_PKG_VERSION")
-                }
+/// Resource updates kept for subscribers that fall behind
+const RESOURCE_UPDATE_CAPACITY: usize = 64;
+
+impl NotificationHandler {
+    /// Fetch a list of a server again after it announced a change
+    fn list_changed(&self, server_name: &str, kind: ListKind) {
+        tracing::info!(
+            "MCP server {} changed its {} list",
+            server_name,
+            kind.field()
+        );
+        let Some(connections) = self.connections.upgrade() else {
+            return;
+        };
+        let commands_changed = self.commands_changed.clone();
+        let server_name = server_name.to_string();
+        tokio::spawn(async move {
+            if let Err(e) =
+                McpServerManager::refresh_list(&connections, &server_name, kind, &commands_changed)
+                    .await
+            {
+                tracing::warn!(
+                    "Failed to refresh the {} of MCP server {}: {}",
+                    kind.field(),
+                    server_name,
+                    e
+                );
             }
         });
+    }
 
-        let request_line = format!("{}\n", initialize_request);
-        writer
-            .write_all(request_line.as_bytes())
-            .await
-            .map_err(|e| {
-                crate::AgentError::ToolExecution(format!(
-                    "Failed to write initialize request to MCP server: {}",
-                    e
-                ))
-            })?;
-        writer.flush().await.map_err(|e| {
-            crate::AgentError::ToolExecution(format!(
-                "Failed to flush initialize request to MCP server: {}",
-                e
-            ))
-        })?;
+    /// Pass on the URI of a subscribed resource that changed
+    fn resource_updated(&self, server_name: &str, params: &Value) {
+        let Some(uri) = params.get("uri").and_then(Value::as_str) else {
+            tracing::warn!(
+                "Ignoring resource update without uri from MCP server {}",
+                server_name
+            );
+            return;
+        };
+        tracing::debug!("MCP server {} updated resource {}", server_name, uri);
+        // Nobody may be listening
+        let _ = self.resource_updates.send(uri.to_string());
+    }
+}
 
-        // Read initialize response
-        let mut response_line = String::new();
-        let bytes_read = reader
-            .read_line(&mut response_line)
-            .await
-            .map_err(McpError::IoError)?;
+/// Routes the messages an MCP server sends on one connection
+///
+/// Responses go to the request waiting for their id and progress notifications to
+/// the listener of their token. List changes and resource updates go to the
+/// [`NotificationHandler`] and log messages to tracing. Clones share their state.
+#[derive(Debug, Clone)]
+pub struct MessageRouter {
+    server_name: String,
+    next_id: Arc<AtomicU64>,
+    pending: PendingRequests,
+    progress: ProgressListeners,
+    notifications: NotificationHandler,
+    /// When the server last sent a message
+    last_message: Arc<std::sync::Mutex<Instant>>,
+    /// Set once the connection is closed
+    closed: watch::Sender<bool>,
+}
 
-        if bytes_read == 0 {
-            return Err(McpError::ConnectionClosed.into());
+impl MessageRouter {
+    /// Create a router for the connection to `server_name`
+    pub fn new(server_name: impl Into<String>, notifications: NotificationHandler) -> Self {
+        Self {
+            server_name: server_name.into(),
+            next_id: Arc::new(AtomicU64::new(1)),
+            pending: Arc::default(),
+            progress: Arc::default(),
+            notifications,
+            last_message: Arc::new(std::sync::Mutex::new(Instant::now())),
+            closed: watch::Sender::new(false),
         }
+    }
 
-        let response: Value =
-            serde_json::from_str(response_line.trim()).map_err(McpError::SerializationFailed)?;
+    /// Allocate an id for a request and a receiver for its response
+    fn register(&self) -> (Value, oneshot::Receiver<Value>) {
+        let id = json!(self.next_id.fetch_add(1, Ordering::Relaxed));
+        let (response_tx, response_rx) = oneshot::channel();
+        lock(&self.pending).insert(id.to_string(), response_tx);
+        (id, response_rx)
+    }
 
-        // Extract available tools from response
-        let _tools = self.extract_tools_from_initialize_response(&response)?;
+    /// Stop waiting for the response and progress of a request
+    fn forget(&self, id: &Value) {
+        lock(&self.pending).remove(&id.to_string());
+        lock(&self.progress).remove(&id.to_string());
+    }
 
-        // Send initialized notification
-        let initialized_notification = json!({
-            "jsonrpc": "2.0",
-            "method": "initialized"
-        });
+    /// Whether a request is still waiting for its response
+    fn is_pending(&self, id: &Value) -> bool {
+        lock(&self.pending).contains_key(&id.to_string())
+    }
 
-        let notification_line = format!("{}\n", initialized_notification);
-        writer
-            .write_all(notification_line.as_bytes())
-            .await
-            .map_err(|e| {
-                crate::AgentError::ToolExecution(format!(
-                    "Failed to send initialized notification to MCP server: {}",
-                    e
-                ))
-            })?;
-        writer.flush().await.map_err(|e| {
-            crate::AgentError::ToolExecution(format!(
-                "Failed to flush initialized notification to MCP server: {}",
-                e
-            ))
-        })?;
+    /// Whether any request is waiting for its response
+    fn has_pending(&self) -> bool {
+        !lock(&self.pending).is_empty()
+    }
 
-        // Request list of available tools
-        let tools_list_request = json!({
-            "jsonrpc": "2.0",
-            "id": 2,
-            "method": "tools/list"
-        });
+    /// Whether the server sent a message within the last `period`
+    fn heard_within(&self, period: Duration) -> bool {
+        lock(&self.last_message).elapsed() < period
+    }
 
-        let tools_request_line = format!("{}\n", tools_list_request);
-        writer
-            .write_all(tools_request_line.as_bytes())
-            .await
-            .map_err(|e| {
-                crate::AgentError::ToolExecution(format!(
-                    "Failed to write tools/list request to MCP server: {}",
-                    e
-                ))
-            })?;
-        writer.flush().await.map_err(|e| {
-            crate::AgentError::ToolExecution(format!(
-                "Failed to flush tools/list request to MCP server: {}",
-                e
-            ))
-        })?;
+    /// Forward progress notifications with `token` to `listener`
+    fn watch_progress(&self, token: &Value, listener: mpsc::UnboundedSender<McpProgress>) {
+        lock(&self.progress).insert(token.to_string(), listener);
+    }
 
-        // Read tools list response
-        let mut tools_response_line = String::new();
-        let tools_bytes_read = reader
-            .read_line(&mut tools_response_line)
-            .await
-            .map_err(|e| {
-                crate::AgentError::ToolExecution(format!(
-                    "Failed to read tools/list response from MCP server: {}",
+    /// Handle a JSON-RPC message, or batch of messages, read from the server
+    ///
+    /// Returns the replies to send for requests the server made of the client.
+    pub fn dispatch_text(&self, text: &str) -> Vec<Value> {
+        match serde_json::from_str(text) {
+            Ok(Value::Array(batch)) => batch
+                .into_iter()
+                .filter_map(|message| self.dispatch(message))
+                .collect(),
+            Ok(message) => self.dispatch(message).into_iter().collect(),
+            Err(e) => {
+                tracing::warn!(
+                    "Ignoring invalid JSON from MCP server {}: {}",
+                    self.server_name,
                     e
-                ))
-            })?;
-
-        if tools_bytes_read == 0 {
-            return Err(crate::AgentError::ToolExecution(
-                "MCP server closed connection during tools/list request".to_string(),
-            ));
+                );
+                Vec::new()
+            }
         }
+    }
 
-        let tools_response: Value =
-            serde_json::from_str(tools_response_line.trim()).map_err(|e| {
-                crate::AgentError::ToolExecution(format!(
-                    "Invalid JSON from MCP server tools/list response: {}",
-                    e
-                ))
-            })?;
-
-        let final_tools = self.extract_tools_from_list_response(&tools_response)?;
+    /// Handle a JSON-RPC message read from the server
+    ///
+    /// Returns the reply to send if the message is a request the server made of the
+    /// client.
+    pub fn dispatch(&self, message: Value) -> Option<Value> {
+        *lock(&self.last_message) = Instant::now();
+        let id = message.get("id").filter(|id| !id.is_null()).cloned();
+        match (message.get("method").and_then(Value::as_str), id) {
+            (Some(method), Some(id)) => Some(self.answer(method, id)),
+            (Some(method), None) => {
+                self.notify(method, message.get("params").unwrap_or(&Value::Null));
+                None
+            }
+            (None, Some(id)) => {
+                match lock(&self.pending).remove(&id.to_string()) {
+                    Some(waiting) => {
+                        let _ = waiting.send(message);
+                    }
+                    None => tracing::debug!(
+                        "Ignoring response to unknown request {} from MCP server {}",
+                        id,
+                        self.server_name
+                    ),
+                }
+                None
+            }
+            (None, None) => {
+                tracing::warn!(
+                    "Ignoring message without id or method from MCP server {}: {}",
+                    self.server_name,
+                    message
+                );
+                None
+            }
+        }
+    }
 
-        tracing::info!(
-            "MCP server {} provides {} tools: {:?}",
-            server_name,
-            final_tools.len(),
-            final_tools
-        );
+    /// Reply to a request from the server; only `ping` is supported
+    fn answer(&self, method: &str, id: Value) -> Value {
+        match method {
+            "ping" => json!({"jsonrpc": "2.0", "id": id, "result": {}}),
+            _ => {
+                tracing::debug!(
+                    "Rejecting unsupported request {} from MCP server {}",
+                    method,
+                    self.server_name
+                );
+                json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "error": {
+                        "code": crate::json_rpc_codes::METHOD_NOT_FOUND,
+                        "message": format!("Method not supported: {}", method)
+                    }
+                })
+            }
+        }
+    }
 
-        Ok(final_tools)
+    /// Handle a notification from the server
+    fn notify(&self, method: &str, params: &Value) {
+        match method {
+            "notifications/tools/list_changed" => self
+                .notifications
+                .list_changed(&self.server_name, ListKind::Tools),
+            "notifications/resources/list_changed" => self
+                .notifications
+                .list_changed(&self.server_name, ListKind::Resources),
+            "notifications/prompts/list_changed" => self
+                .notifications
+                .list_changed(&self.server_name, ListKind::Prompts),
+            "notifications/resources/updated" => self
+                .notifications
+                .resource_updated(&self.server_name, params),
+            "notifications/progress" => self.report_progress(params),
+            "notifications/message" => log_server_message(&self.server_name, params),
+            _ => tracing::debug!(
+                "Ignoring notification {} from MCP server {}",
+                method,
+                self.server_name
+            ),
+        }
     }
 
-    /// Initialize HTTP MCP connection using the MCP Streamable HTTP transport protocol.
-    ///
-    /// Implements the three-step initialization handshake:
-    /// 1. Send initialize request and parse response (JSON or SSE)
-    /// 2. Send initialized notification (expects HTTP 202)
-    /// 3. Request tools list and extract tool names
-    ///
-    /// # Arguments
-    /// * `client` - HTTP client to use for requests
-    /// * `config` - HTTP transport configuration including URL and headers
-    /// * `session_id` - Arc-wrapped session ID storage for subsequent requests
-    ///
-    /// # Returns
-    /// List of available tool names from the MCP server
-    ///
-    /// # Errors
-    /// Returns error if:
-    /// - Connection fails
-    /// - Server returns non-success status
-    /// - Response parsing fails
-    /// - Protocol negotiation fails
-    async fn initialize_http_mcp_connection(
-        &self,
-        client: &Client,
-        config: &crate::config::HttpTransport,
-        session_id: Arc<RwLock<Option<String>>>,
-    ) -> crate::Result<Vec<String>> {
-        tracing::info!("Initializing HTTP MCP protocol for {}", config.name);
+    /// Hand a progress notification to the listener of its token
+    fn report_progress(&self, params: &Value) {
+        let (Some(token), Some(progress)) = (
+            params.get("progressToken"),
+            params.get("progress").and_then(Value::as_f64),
+        ) else {
+            tracing::warn!(
+                "Ignoring malformed progress notification from MCP server {}: {}",
+                self.server_name,
+                params
+            );
+            return;
+        };
 
-        // Step 1: Send initialize request via HTTP POST
-        let initialize_request = json!({
-            "jsonrpc": "2.0",
-            "id": 1,
-            "method": "initialize",
-            "params": {
-                "protocolVersion": "2024-11-05",
-                "capabilities": {
-                    "tools": {}
-                },
-                "clientInfo": {
-                    "name": "claude-agent",
-                    "version": env!("CARGO_PKG_VERSION")
+        if let Some(listener) = lock(&self.progress).get(&token.to_string()) {
+            let _ = listener.send(McpProgress {
+                progress,
+                total: params.get("total").and_then(Value::as_f64),
+                message: params
+                    .get("message")
+                    .and_then(Value::as_str)
+                    .map(str::to_string),
+            });
+        }
+    }
+
+    /// Mark the connection closed and fail the requests still waiting for a response
+    pub fn close(&self) {
+        self.closed.send_replace(true);
+        lock(&self.pending).clear();
+        lock(&self.progress).clear();
+    }
+
+    /// Whether the connection was closed
+    pub fn is_closed(&self) -> bool {
+        *self.closed.borrow()
+    }
+
+    /// Whether `other` routes the messages of the same connection
+    fn is_same_connection(&self, other: &MessageRouter) -> bool {
+        Arc::ptr_eq(&self.pending, &other.pending)
+    }
+
+    /// Wait until the connection is closed
+    pub async fn wait_closed(&self) {
+        let mut closed = self.closed.subscribe();
+        let _ = closed.wait_for(|closed| *closed).await;
+    }
+}
+
+/// Lock a mutex whose data stays consistent even if a holder panicked
+fn lock<T>(mutex: &std::sync::Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Forward a `notifications/message` log entry of a server to tracing
+fn log_server_message(server_name: &str, params: &Value) {
+    let level = params
+        .get("level")
+        .and_then(Value::as_str)
+        .unwrap_or("info");
+    let logger = params
+        .get("logger")
+        .and_then(Value::as_str)
+        .unwrap_or(server_name);
+    let data = match params.get("data") {
+        Some(Value::String(text)) => text.clone(),
+        Some(data) => data.to_string(),
+        None => String::new(),
+    };
+
+    match level {
+        "debug" => tracing::debug!(mcp_server = server_name, logger, "{}", data),
+        "warning" => tracing::warn!(mcp_server = server_name, logger, "{}", data),
+        "error" | "critical" | "alert" | "emergency" => {
+            tracing::error!(mcp_server = server_name, logger, "{}", data)
+        }
+        _ => tracing::info!(mcp_server = server_name, logger, "{}", data),
+    }
+}
+
+/// An event of a `text/event-stream` body
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct SseEvent {
+    /// Event type, `None` for the default `message` type
+    pub(crate) event: Option<String>,
+    /// Data lines of the event, joined by line breaks
+    pub(crate) data: String,
+}
+
+impl SseEvent {
+    /// Whether the event carries a JSON-RPC message
+    pub(crate) fn is_message(&self) -> bool {
+        matches!(self.event.as_deref(), None | Some("message"))
+    }
+}
+
+/// Splits a `text/event-stream` body into its events
+#[derive(Debug, Default)]
+pub(crate) struct SseDecoder {
+    /// Bytes of the current, incomplete line
+    buffer: Vec<u8>,
+    /// Type of the current event
+    event: Option<String>,
+    /// Data lines of the current event
+    data: Vec<String>,
+    /// Id of the last event that set one, to resume the stream after it
+    last_event_id: Option<String>,
+    /// Reconnection delay the server asked for
+    retry: Option<Duration>,
+}
+
+impl SseDecoder {
+    /// Longest line kept, so a stream without line breaks can't exhaust memory
+    const MAX_LINE_LENGTH: usize = 1024 * 1024;
+
+    /// Add received bytes and return each event they complete
+    pub(crate) fn feed(&mut self, bytes: &[u8]) -> Vec<SseEvent> {
+        self.buffer.extend_from_slice(bytes);
+        let mut events = Vec::new();
+
+        while let Some(end) = self.buffer.iter().position(|byte| *byte == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end_matches(['\n', '\r']);
+
+            if line.is_empty() {
+                events.extend(self.finish());
+                continue;
+            }
+            // Lines starting with a colon are comments
+            let (field, value) = line.split_once(':').unwrap_or((line, ""));
+            let value = value.strip_prefix(' ').unwrap_or(value);
+            match field {
+                "data" => self.data.push(value.to_string()),
+                "event" => self.event = Some(value.to_string()),
+                // An empty id means events can no longer be resumed after
+                "id" if !value.contains('\0') => {
+                    self.last_event_id = Some(value.to_string()).filter(|id| !id.is_empty())
+                }
+                "retry" => {
+                    if let Ok(millis) = value.parse() {
+                        self.retry = Some(Duration::from_millis(millis));
+                    }
                 }
+                _ => {}
             }
-        });
+        }
 
-        let response = client
-            .post(&config.url)
-            .header("Accept", "application/json, text/event-stream")
-            .header("Content-Type", "application/json")
-            .json(&initialize_request)
-            .send()
-            .await
-            .map_err(|e| {
-                crate::AgentError::ToolExecution(format!(
-                    "Failed to send initialize request to HTTP MCP server: {}",
-                    e
-                ))
-            })?;
+        if self.buffer.len() > Self::MAX_LINE_LENGTH {
+            tracing::error!("SSE line exceeded maximum length, discarding it");
+            self.buffer.clear();
+        }
+        events
+    }
 
-        if !response.status().is_success() {
-            return Err(crate::AgentError::ToolExecution(format!(
-                "Initialize request failed with status: {}",
-                response.status()
-            )));
+    /// End the current event and return it, if it has data
+    pub(crate) fn finish(&mut self) -> Option<SseEvent> {
+        let event = self.event.take();
+        if self.data.is_empty() {
+            return None;
         }
+        let data = self.data.join("\n");
+        self.data.clear();
+        Some(SseEvent { event, data })
+    }
+}
 
-        // Extract session ID if present
-        if let Some(session_id_header) = response.headers().get("Mcp-Session-Id") {
-            if let Ok(session_id_str) = session_id_header.to_str() {
-                let mut session_id_write = session_id.write().await;
-                *session_id_write = Some(session_id_str.to_string());
-                tracing::debug!("Stored session ID: {}", session_id_str);
-            }
+impl TransportConnection {
+    /// Streamable HTTP transport for the MCP endpoint at `url`
+    ///
+    /// No request is made until the first message is sent.
+    pub fn http(client: Client, url: &str, headers: Vec<crate::config::HttpHeader>) -> Self {
+        TransportConnection::Http {
+            client: Arc::new(client),
+            url: url.to_string(),
+            headers,
+            session_id: Arc::default(),
+            protocol_version: Arc::default(),
         }
+    }
 
-        // Parse response body - handle both JSON and SSE formats
-        let content_type = response
-            .headers()
-            .get("Content-Type")
-            .and_then(|v| v.to_str().ok())
-            .unwrap_or("application/json");
+    /// Legacy HTTP+SSE transport for the event stream at `url`
+    ///
+    /// Opens the stream and waits for the server to name its message endpoint, then
+    /// starts a task that routes the messages of the stream. The session lives as long
+    /// as the stream, so the connection is closed when the stream ends.
+    pub async fn sse(
+        client: Client,
+        url: &str,
+        headers: Vec<crate::config::HttpHeader>,
+        router: &MessageRouter,
+    ) -> crate::Result<Self> {
+        use futures::StreamExt;
 
-        let initialize_response: Value = if content_type.contains("text/event-stream") {
-            // Handle SSE response format
-            let body = response.text().await.map_err(|e| {
-                crate::AgentError::ToolExecution(format!(
-                    "Failed to read SSE response from HTTP MCP server: {}",
-                    e
-                ))
-            })?;
+        let stream_error = |e: String| {
+            crate::AgentError::ToolExecution(format!(
+                "Failed to open event stream of MCP server {}: {}",
+                router.server_name, e
+            ))
+        };
+        let response = client
+            .get(url)
+            .header("Accept", "text/event-stream")
+            .send()
+            .await
+            .map_err(|e| stream_error(e.to_string()))?;
+        if !response.status().is_success() {
+            return Err(stream_error(format!("status {}", response.status())));
+        }
 
-            // Parse SSE format - look for data: lines
-            let mut json_data = String::new();
-            for line in body.lines() {
-                if let Some(data) = line.strip_prefix("data: ") {
-                    json_data = data.to_string();
-                    break;
+        let mut body = response.bytes_stream();
+        let mut decoder = SseDecoder::default();
+        let endpoint = tokio::time::timeout(SSE_ENDPOINT_TIMEOUT, async {
+            while let Some(chunk) = body.next().await {
+                let chunk = chunk.map_err(|e| stream_error(e.to_string()))?;
+                for event in decoder.feed(&chunk) {
+                    if event.event.as_deref() == Some("endpoint") {
+                        return Ok(event.data);
+                    }
+                    tracing::debug!(
+                        "Ignoring event before the endpoint of MCP server {}",
+                        router.server_name
+                    );
                 }
             }
+            Err(stream_error(
+                "stream ended before the endpoint event".to_string(),
+            ))
+        })
+        .await
+        .map_err(|_| stream_error("no endpoint event received".to_string()))??;
+
+        // The endpoint may be relative to the stream's URL
+        let endpoint = url::Url::parse(url)
+            .and_then(|base| base.join(endpoint.trim()))
+            .map_err(|e| {
+                McpError::ProtocolError(format!("Invalid endpoint '{}': {}", endpoint, e))
+            })?
+            .to_string();
+        tracing::debug!(
+            "MCP server {} accepts messages at {}",
+            router.server_name,
+            endpoint
+        );
 
-            if json_data.is_empty() {
-                return Err(crate::AgentError::ToolExecution(
-                    "No data in SSE response from HTTP MCP server".to_string(),
-                ));
+        let client = Arc::new(client);
+        let stream_router = router.clone();
+        let reply_client = Arc::clone(&client);
+        let reply_endpoint = endpoint.clone();
+        tokio::spawn(async move {
+            let router = stream_router;
+            let read = async {
+                while let Some(chunk) = body.next().await {
+                    let Ok(chunk) = chunk else {
+                        break;
+                    };
+                    for event in decoder.feed(&chunk) {
+                        if !event.is_message() {
+                            continue;
+                        }
+                        for reply in router.dispatch_text(&event.data) {
+                            let result = reply_client
+                                .post(&reply_endpoint)
+                                .json(&reply)
+                                .send()
+                                .await
+                                .and_then(reqwest::Response::error_for_status);
+                            if let Err(e) = result {
+                                tracing::warn!(
+                                    "Failed to answer request of MCP server {}: {}",
+                                    router.server_name,
+                                    e
+                                );
+                            }
+                        }
+                    }
+                }
+                tracing::info!("Event stream of MCP server {} ended", router.server_name);
+            };
+            tokio::select! {
+                _ = read => router.close(),
+                _ = router.wait_closed() => {}
             }
+        });
 
-            serde_json::from_str(&json_data).map_err(|e| {
-                crate::AgentError::ToolExecution(format!(
-                    "Invalid JSON in SSE response from HTTP MCP server: {}",
-                    e
-                ))
-            })?
-        } else {
-            // Handle JSON response format
-            response.json().await.map_err(|e| {
-                crate::AgentError::ToolExecution(format!(
-                    "Failed to parse initialize response from HTTP MCP server: {}",
-                    e
-                ))
-            })?
-        };
+        Ok(TransportConnection::Sse {
+            client,
+            url: url.to_string(),
+            endpoint,
+            headers,
+        })
+    }
 
-        // Validate initialize response
-        if let Some(error) = initialize_response.get("error") {
-            return Err(crate::AgentError::ToolExecution(format!(
-                "HTTP MCP server returned error: {}",
-                error
-            )));
+    /// The session of a Streamable HTTP transport
+    fn http_session(&self) -> Option<HttpSession> {
+        match self {
+            TransportConnection::Http {
+                client,
+                url,
+                session_id,
+                protocol_version,
+                ..
+            } => Some(HttpSession::new(client, url, session_id, protocol_version)),
+            _ => None,
         }
+    }
 
-        // Step 2: Send initialized notification
-        let initialized_notification = json!({
-            "jsonrpc": "2.0",
-            "method": "initialized"
-        });
+    /// Stdio transport for a spawned server process
+    ///
+    /// Starts a task that reads the server's stdout, routes each message and answers
+    /// the server's requests, and a task that logs its stderr.
+    pub fn stdio(
+        mut process: Child,
+        writer: BufWriter<ChildStdin>,
+        reader: BufReader<ChildStdout>,
+        router: &MessageRouter,
+    ) -> Self {
+        if let Some(stderr) = process.stderr.take() {
+            let server_name = router.server_name.clone();
+            tokio::spawn(async move {
+                let mut lines = BufReader::new(stderr).lines();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    tracing::debug!(mcp_server = %server_name, "{}", line);
+                }
+            });
+        }
 
-        let mut notify_request = client
-            .post(&config.url)
-            .header("Accept", "application/json, text/event-stream")
-            .header("Content-Type", "application/json");
+        let stdin_writer = Arc::new(RwLock::new(Some(writer)));
+        tokio::spawn(read_stdio_messages(
+            reader,
+            Arc::clone(&stdin_writer),
+            router.clone(),
+        ));
 
-        // Include session ID if present
-        if let Some(session_id_value) = session_id.read().await.as_ref() {
-            notify_request = notify_request.header("Mcp-Session-Id", session_id_value);
+        TransportConnection::Stdio {
+            process: Arc::new(RwLock::new(Some(process))),
+            stdin_writer,
         }
+    }
+}
 
-        let notify_response = notify_request
-            .json(&initialized_notification)
-            .send()
-            .await
-            .map_err(|e| {
-                crate::AgentError::ToolExecution(format!(
-                    "Failed to send initialized notification to HTTP MCP server: {}",
+/// Read messages from a server's stdout until it closes
+async fn read_stdio_messages(
+    mut reader: BufReader<ChildStdout>,
+    writer: Arc<RwLock<Option<BufWriter<ChildStdin>>>>,
+    router: MessageRouter,
+) {
+    let mut line = String::new();
+    loop {
+        line.clear();
+        match reader.read_line(&mut line).await {
+            Ok(0) => break,
+            Ok(_) => {}
+            Err(e) => {
+                tracing::warn!(
+                    "Failed to read from MCP server {}: {}",
+                    router.server_name,
                     e
-                ))
-            })?;
+                );
+                break;
+            }
+        }
+        if line.trim().is_empty() {
+            continue;
+        }
 
-        // Expect 202 Accepted for notification
-        if notify_response.status() != reqwest::StatusCode::ACCEPTED {
-            tracing::warn!(
-                "Initialized notification returned status: {} (expected 202 Accepted)",
-                notify_response.status()
-            );
+        for reply in router.dispatch_text(line.trim()) {
+            if let Some(writer) = writer.write().await.as_mut() {
+                if let Err(e) = write_message(writer, &reply).await {
+                    tracing::warn!(
+                        "Failed to answer request of MCP server {}: {}",
+                        router.server_name,
+                        e
+                    );
+                }
+            }
         }
+    }
 
-        // Step 3: Request list of available tools
-        let tools_list_request = json!({
-            "jsonrpc": "2.0",
-            "id": 2,
-            "method": "tools/list"
-        });
+    tracing::debug!("MCP server {} closed its output", router.server_name);
+    router.close();
+}
 
-        let mut tools_request = client
-            .post(&config.url)
-            .header("Accept", "application/json, text/event-stream")
-            .header("Content-Type", "application/json");
+/// Write a message as one line and flush it
+async fn write_message(writer: &mut BufWriter<ChildStdin>, message: &Value) -> std::io::Result<()> {
+    writer
+        .write_all(format!("{}\n", message).as_bytes())
+        .await?;
+    writer.flush().await
+}
+
+/// The parts of a Streamable HTTP connection needed to reach its server
+#[derive(Debug, Clone)]
+struct HttpSession {
+    client: Arc<Client>,
+    url: String,
+    session_id: Arc<RwLock<Option<String>>>,
+    protocol_version: Arc<RwLock<Option<String>>>,
+}
+
+impl HttpSession {
+    fn new(
+        client: &Arc<Client>,
+        url: &str,
+        session_id: &Arc<RwLock<Option<String>>>,
+        protocol_version: &Arc<RwLock<Option<String>>>,
+    ) -> Self {
+        Self {
+            client: Arc::clone(client),
+            url: url.to_string(),
+            session_id: Arc::clone(session_id),
+            protocol_version: Arc::clone(protocol_version),
+        }
+    }
 
-        // Include session ID if present
-        if let Some(session_id_value) = session_id.read().await.as_ref() {
-            tools_request = tools_request.header("Mcp-Session-Id", session_id_value);
+    /// Start a request to the MCP endpoint carrying the session's headers
+    async fn request(&self, method: reqwest::Method) -> reqwest::RequestBuilder {
+        let mut request = self.client.request(method, &self.url);
+        if let Some(session_id) = self.session_id.read().await.as_ref() {
+            request = request.header("Mcp-Session-Id", session_id);
         }
+        if let Some(version) = self.protocol_version.read().await.as_ref() {
+            request = request.header("MCP-Protocol-Version", version);
+        }
+        request
+    }
 
-        let tools_response = tools_request
-            .json(&tools_list_request)
+    /// POST a message, accepting a JSON or event stream response
+    async fn post(&self, message: &Value) -> reqwest::Result<reqwest::Response> {
+        self.request(reqwest::Method::POST)
+            .await
+            .header("Accept", "application/json, text/event-stream")
+            .header("Content-Type", "application/json")
+            .json(message)
             .send()
             .await
-            .map_err(|e| {
-                crate::AgentError::ToolExecution(format!(
-                    "Failed to send tools/list request to HTTP MCP server: {}",
-                    e
-                ))
-            })?;
+    }
 
-        if !tools_response.status().is_success() {
-            return Err(crate::AgentError::ToolExecution(format!(
-                "Tools list request failed with status: {}",
-                tools_response.status()
-            )));
+    /// Open the event stream of the session, resuming after `last_event_id`
+    async fn open_event_stream(
+        &self,
+        last_event_id: Option<&str>,
+    ) -> reqwest::Result<reqwest::Response> {
+        let mut request = self
+            .request(reqwest::Method::GET)
+            .await
+            .header("Accept", "text/event-stream");
+        if let Some(last_event_id) = last_event_id {
+            request = request.header("Last-Event-ID", last_event_id);
         }
+        request.send().await
+    }
 
-        // Parse tools response - handle both JSON and SSE formats
-        let tools_content_type = tools_response
+    /// Keep the session id a server assigns, to send it with every later request
+    async fn remember_session_id(&self, response: &reqwest::Response) {
+        let Some(session_id) = response
             .headers()
-            .get("Content-Type")
-            .and_then(|v| v.to_str().ok())
-            .unwrap_or("application/json");
-
-        let tools_response_json: Value = if tools_content_type.contains("text/event-stream") {
-            // Handle SSE response format
-            let body = tools_response.text().await.map_err(|e| {
-                crate::AgentError::ToolExecution(format!(
-                    "Failed to read tools SSE response from HTTP MCP server: {}",
-                    e
-                ))
-            })?;
+            .get("Mcp-Session-Id")
+            .and_then(|value| value.to_str().ok())
+        else {
+            return;
+        };
+        let mut stored = self.session_id.write().await;
+        if stored.as_deref() != Some(session_id) {
+            tracing::debug!("Stored session ID: {}", session_id);
+            *stored = Some(session_id.to_string());
+        }
+    }
 
-            // Parse SSE format - look for data: lines
-            let mut json_data = String::new();
-            for line in body.lines() {
-                if let Some(data) = line.strip_prefix("data: ") {
-                    json_data = data.to_string();
-                    break;
-                }
-            }
+    /// Whether `status` tells that the server no longer knows the session
+    async fn is_expired(&self, status: reqwest::StatusCode) -> bool {
+        status == reqwest::StatusCode::NOT_FOUND && self.session_id.read().await.is_some()
+    }
 
-            if json_data.is_empty() {
-                return Err(crate::AgentError::ToolExecution(
-                    "No data in tools SSE response from HTTP MCP server".to_string(),
-                ));
+    /// POST replies to requests the server made
+    async fn answer(&self, router: &MessageRouter, replies: Vec<Value>) {
+        for reply in replies {
+            let result = self
+                .post(&reply)
+                .await
+                .and_then(reqwest::Response::error_for_status);
+            if let Err(e) = result {
+                tracing::warn!(
+                    "Failed to answer request of MCP server {}: {}",
+                    router.server_name,
+                    e
+                );
             }
+        }
+    }
+}
 
-            serde_json::from_str(&json_data).map_err(|e| {
-                crate::AgentError::ToolExecution(format!(
-                    "Invalid JSON in tools SSE response from HTTP MCP server: {}",
-                    e
-                ))
-            })?
-        } else {
-            // Handle JSON response format
-            tools_response.json().await.map_err(|e| {
-                crate::AgentError::ToolExecution(format!(
-                    "Failed to parse tools/list response from HTTP MCP server: {}",
+/// Route the messages of an event stream until it ends
+///
+/// With `until_answered`, stops as soon as the response to that request arrived, since
+/// the server may keep the stream open after answering.
+async fn route_event_stream(
+    session: &HttpSession,
+    router: &MessageRouter,
+    response: reqwest::Response,
+    decoder: &mut SseDecoder,
+    until_answered: Option<&Value>,
+) {
+    use futures::StreamExt;
+
+    let mut body = response.bytes_stream();
+    while let Some(chunk) = body.next().await {
+        let chunk = match chunk {
+            Ok(chunk) => chunk,
+            Err(e) => {
+                tracing::debug!(
+                    "Event stream of MCP server {} broke: {}",
+                    router.server_name,
                     e
-                ))
-            })?
+                );
+                return;
+            }
         };
+        for event in decoder.feed(&chunk) {
+            if event.is_message() {
+                session
+                    .answer(router, router.dispatch_text(&event.data))
+                    .await;
+            }
+        }
+        if until_answered.is_some_and(|id| !router.is_pending(id)) {
+            return;
+        }
+    }
+    if let Some(event) = decoder.finish().filter(SseEvent::is_message) {
+        session
+            .answer(router, router.dispatch_text(&event.data))
+            .await;
+    }
+}
 
-        let final_tools = self.extract_tools_from_list_response(&tools_response_json)?;
+/// Route the messages a Streamable HTTP server initiates on its GET event stream
+///
+/// The stream is reopened after the last event received whenever it ends, until the
+/// connection is closed. Servers that offer no stream answer 405.
+async fn read_http_event_stream(session: HttpSession, router: MessageRouter) {
+    let mut decoder = SseDecoder::default();
+    let mut delay = EVENT_STREAM_RETRY;
+    loop {
+        match session
+            .open_event_stream(decoder.last_event_id.as_deref())
+            .await
+        {
+            Ok(response) if response.status() == reqwest::StatusCode::METHOD_NOT_ALLOWED => {
+                tracing::debug!("MCP server {} offers no event stream", router.server_name);
+                return;
+            }
+            Ok(response) if session.is_expired(response.status()).await => {
+                tracing::warn!("Session of MCP server {} expired", router.server_name);
+                router.close();
+                return;
+            }
+            Ok(response) if response.status().is_success() => {
+                route_event_stream(&session, &router, response, &mut decoder, None).await;
+                delay = decoder.retry.unwrap_or(EVENT_STREAM_RETRY);
+            }
+            Ok(response) => tracing::debug!(
+                "Event stream of MCP server {} failed with status {}",
+                router.server_name,
+                response.status()
+            ),
+            Err(e) => tracing::debug!(
+                "Failed to open event stream of MCP server {}: {}",
+                router.server_name,
+                e
+            ),
+        }
 
-        tracing::info!(
-            "HTTP MCP server {} provides {} tools: {:?}",
-            config.name,
-            final_tools.len(),
-            final_tools
+        tracing::debug!(
+            "Reopening event stream of MCP server {} in {:?}",
+            router.server_name,
+            delay
         );
+        tokio::time::sleep(delay).await;
+        delay = (delay * 2).min(MAX_EVENT_STREAM_RETRY);
+    }
+}
+
+/// Represents a connection to an MCP server
+///
+/// Clones share the transport and router, so requests can be sent on a clone without
+/// holding the manager's lock.
+#[derive(Debug, Clone)]
+pub struct McpServerConnection {
+    /// Name of the MCP server
+    pub name: String,
+    /// Tools available from this server
+    pub tools: Vec<McpTool>,
+    /// Resources the server lists
+    pub resources: Vec<McpResource>,
+    /// Prompt templates the server offers
+    pub prompts: Vec<McpPrompt>,
+    /// Capabilities the server declared when initialized
+    pub capabilities: Value,
+    /// Configuration used to create this connection
+    pub config: McpServerConfig,
+    /// Transport-specific connection details
+    pub transport: TransportConnection,
+    /// Routes the messages the server sends
+    pub router: MessageRouter,
+}
 
-        Ok(final_tools)
+impl McpServerConnection {
+    /// Send a request and wait for the server's response message
+    pub async fn request(&self, method: &str, params: Option<Value>) -> crate::Result<Value> {
+        self.request_with_progress(method, params, None).await
     }
 
-    /// Initialize SSE MCP connection using persistent event stream.
-    ///
-    /// SSE transport uses a persistent GET connection for receiving server events
-    /// and POST requests for sending client requests (initialize, tools/call, etc).
-    ///
-    /// # Arguments
-    /// * `config` - SSE transport configuration including URL and headers
-    /// * `response_tx` - Channel for sending responses from the event stream
-    ///
-    /// # Returns
-    /// List of available tool names from the MCP server
+    /// Send a request, forwarding the progress the server reports for it to `progress`
     ///
-    /// # Errors
-    /// Returns error if:
-    /// - Connection fails
-    /// - Server returns non-success status
-    /// - Response parsing fails
-    /// - Protocol negotiation fails
-    async fn initialize_sse_mcp_connection(
+    /// With a listener, the request's id is sent as its progress token.
+    pub async fn request_with_progress(
         &self,
-        config: &crate::config::SseTransport,
-        response_tx: mpsc::UnboundedSender<String>,
-    ) -> crate::Result<Vec<String>> {
-        tracing::info!("Initializing SSE MCP protocol for {}", config.name);
+        method: &str,
+        mut params: Option<Value>,
+        progress: Option<mpsc::UnboundedSender<McpProgress>>,
+    ) -> crate::Result<Value> {
+        let (id, response) = self.router.register();
+        if self.router.is_closed() {
+            self.router.forget(&id);
+            return Err(McpError::ConnectionClosed.into());
+        }
+        if let Some(listener) = progress {
+            self.router.watch_progress(&id, listener);
+            params.get_or_insert_with(|| json!({}))["_meta"]["progressToken"] = id.clone();
+        }
 
-        // Create HTTP client with headers
-        let mut headers = reqwest::header::HeaderMap::new();
-        for header in &config.headers {
-            if let (Ok(name), Ok(value)) = (
-                reqwest::header::HeaderName::from_bytes(header.name.as_bytes()),
-                reqwest::header::HeaderValue::from_str(&header.value),
-            ) {
-                headers.insert(name, value);
-            }
+        let mut message = json!({"jsonrpc": "2.0", "id": id, "method": method});
+        if let Some(params) = params {
+            message["params"] = params;
         }
 
-        let client = Client::builder()
-            .default_headers(headers.clone())
-            .build()
-            .map_err(|e| {
-                crate::AgentError::ToolExecution(format!(
-                    "Failed to create HTTP client for SSE MCP server {}: {}",
-                    config.name, e
-                ))
-            })?;
+        let result = match self.send(&message, Some(&id)).await {
+            Ok(()) => response
+                .await
+                .map_err(|_| McpError::ConnectionClosed.into()),
+            Err(e) => Err(e),
+        };
+        self.router.forget(&id);
+        result
+    }
 
-        // Step 1: Send initialize request via POST
-        let initialize_request = json!({
-            "jsonrpc": "2.0",
-            "id": 1,
-            "method": "initialize",
-            "params": {
-                "protocolVersion": "2024-11-05",
-                "capabilities": {
-                    "tools": {}
-                },
-                "clientInfo": {
-                    "name": "claude-agent",
-                    "version": env!("CARGO_PKG_VERSION")
-                }
+    /// Perform the initialize handshake and return the server's initialize response
+    ///
+    /// The transport already routes what the server sends, so notifications sent during
+    /// the handshake are handled like any other. Streamable HTTP connections then open
+    /// the event stream for messages the server initiates.
+    pub async fn initialize(&self) -> crate::Result<Value> {
+        let params = json!({
+            "protocolVersion": PROTOCOL_VERSION,
+            "capabilities": {
+                "tools": {}
+            },
+            "clientInfo": {
+                "name": "claude-agent",
+                "version": env!("CARGO_PKG_VERSION")
             }
         });
-
-        let response = client
-            .post(&config.url)
-            .header("Accept", "text/event-stream")
-            .header("Content-Type", "application/json")
-            .json(&initialize_request)
-            .send()
-            .await
-            .map_err(|e| {
-                crate::AgentError::ToolExecution(format!(
-                    "Failed to send initialize request to SSE MCP server: {}",
-                    e
-                ))
-            })?;
-
-        if !response.status().is_success() {
-            return Err(crate::AgentError::ToolExecution(format!(
-                "Initialize request failed with status: {}",
-                response.status()
-            )));
-        }
-
-        // Parse SSE response for initialize
-        let body = response.text().await.map_err(|e| {
-            crate::AgentError::ToolExecution(format!(
-                "Failed to read SSE response from MCP server: {}",
-                e
-            ))
-        })?;
-
-        let initialize_response = Self::parse_sse_response(&body)?;
-
-        // Validate initialize response
-        if let Some(error) = initialize_response.get("error") {
+        let response = self.request("initialize", Some(params)).await?;
+        if let Some(error) = response.get("error") {
             return Err(crate::AgentError::ToolExecution(format!(
-                "SSE MCP server returned error: {}",
+                "MCP server returned error: {}",
                 error
             )));
         }
 
-        // Step 2: Send initialized notification
-        let initialized_notification = json!({
-            "jsonrpc": "2.0",
-            "method": "initialized"
-        });
-
-        let notify_response = client
-            .post(&config.url)
-            .header("Accept", "text/event-stream")
-            .header("Content-Type", "application/json")
-            .json(&initialized_notification)
-            .send()
-            .await
-            .map_err(|e| {
-                crate::AgentError::ToolExecution(format!(
-                    "Failed to send initialized notification to SSE MCP server: {}",
-                    e
-                ))
-            })?;
-
-        if notify_response.status() != reqwest::StatusCode::ACCEPTED {
-            tracing::warn!(
-                "Initialized notification returned status: {} (expected 202 Accepted)",
-                notify_response.status()
-            );
+        let session = self.transport.http_session();
+        if let Some(session) = &session {
+            let version = response
+                .get("result")
+                .and_then(|result| result.get("protocolVersion"))
+                .and_then(Value::as_str);
+            *session.protocol_version.write().await = version.map(str::to_string);
         }
 
-        // Step 3: Request list of available tools
-        let tools_list_request = json!({
-            "jsonrpc": "2.0",
-            "id": 2,
-            "method": "tools/list"
-        });
-
-        let tools_response = client
-            .post(&config.url)
-            .header("Accept", "text/event-stream")
-            .header("Content-Type", "application/json")
-            .json(&tools_list_request)
-            .send()
-            .await
-            .map_err(|e| {
-                crate::AgentError::ToolExecution(format!(
-                    "Failed to send tools/list request to SSE MCP server: {}",
-                    e
-                ))
-            })?;
+        self.notify("notifications/initialized", None).await?;
 
-        if !tools_response.status().is_success() {
-            return Err(crate::AgentError::ToolExecution(format!(
-                "Tools list request failed with status: {}",
-                tools_response.status()
-            )));
+        if let Some(session) = session {
+            let router = self.router.clone();
+            tokio::spawn(async move {
+                tokio::select! {
+                    _ = read_http_event_stream(session, router.clone()) => {}
+                    _ = router.wait_closed() => {}
+                }
+            });
         }
 
-        let tools_body = tools_response.text().await.map_err(|e| {
-            crate::AgentError::ToolExecution(format!(
-                "Failed to read tools SSE response from MCP server: {}",
-                e
-            ))
-        })?;
-
-        let tools_response_json = Self::parse_sse_response(&tools_body)?;
-        let final_tools = self.extract_tools_from_list_response(&tools_response_json)?;
-
-        tracing::info!(
-            "SSE MCP server {} provides {} tools: {:?}",
-            config.name,
-            final_tools.len(),
-            final_tools
-        );
+        Ok(response)
+    }
 
-        // Spawn background task to maintain SSE event stream for server-initiated events
-        let event_url = config.url.clone();
-        let event_client = client.clone();
-        tokio::spawn(async move {
-            if let Err(e) =
-                Self::handle_sse_event_stream(event_client, &event_url, response_tx).await
-            {
-                tracing::error!("SSE event stream error: {}", e);
+    /// Request every page of a paginated list such as `resources/list`
+    ///
+    /// Returns the entries found in the `field` of the results.
+    pub async fn list_all(&self, method: &str, field: &str) -> crate::Result<Vec<Value>> {
+        let mut entries = Vec::new();
+        let mut cursor: Option<Value> = None;
+        for _ in 0..MAX_LIST_PAGES {
+            let params = cursor.take().map(|cursor| json!({ "cursor": cursor }));
+            let response = self.request(method, params).await?;
+            if let Some(error) = response.get("error") {
+                return Err(McpError::ServerError(error.clone()).into());
             }
-        });
+            let result = response.get("result").ok_or(McpError::MissingResult)?;
+            if let Some(Value::Array(page)) = result.get(field) {
+                entries.extend(page.iter().cloned());
+            }
+            match result.get("nextCursor") {
+                Some(next) if !next.is_null() => cursor = Some(next.clone()),
+                _ => return Ok(entries),
+            }
+        }
+        tracing::warn!(
+            "MCP server {} returned more than {} pages of {}",
+            self.name,
+            MAX_LIST_PAGES,
+            field
+        );
+        Ok(entries)
+    }
 
-        Ok(final_tools)
+    /// Whether the server declared the capability named `name`
+    pub fn supports(&self, name: &str) -> bool {
+        self.capabilities
+            .get(name)
+            .is_some_and(|capability| !capability.is_null())
     }
 
-    /// Parse SSE response body to extract JSON data
-    ///
-    /// SSE format specification (https://html.spec.whatwg.org/multipage/server-sent-events.html):
-    /// - Lines starting with "data: " contain the actual message data
-    /// - Multiple data lines can be sent for a single event
-    /// - Empty line indicates end of event
-    ///
-    /// This implementation extracts only the first "data: " line for simplicity.
+    /// Replace a stored list with freshly listed entries
     ///
-    /// # Example SSE stream format
-    /// ```text
-    /// data: {"jsonrpc":"2.0","id":1,"result":{"tools":[]}}
-    ///
-    /// data: {"jsonrpc":"2.0","method":"notification"}
-    ///
-    /// ```
-    fn parse_sse_response(body: &str) -> crate::Result<Value> {
-        let mut json_data = String::new();
-        for line in body.lines() {
-            if let Some(data) = line.strip_prefix("data: ") {
-                json_data = data.to_string();
-                break;
-            }
+    /// Returns whether the list changed.
+    fn store_list(&mut self, kind: ListKind, entries: Vec<Value>) -> bool {
+        fn replace<T: PartialEq>(stored: &mut Vec<T>, entries: Vec<T>) -> bool {
+            let changed = *stored != entries;
+            *stored = entries;
+            changed
         }
 
-        if json_data.is_empty() {
-            return Err(crate::AgentError::ToolExecution(
-                "No data in SSE response from MCP server".to_string(),
-            ));
+        match kind {
+            ListKind::Tools => replace(
+                &mut self.tools,
+                entries.iter().filter_map(parse_tool).collect(),
+            ),
+            ListKind::Resources => replace(&mut self.resources, parse_entries(entries, "resource")),
+            ListKind::Prompts => replace(&mut self.prompts, parse_entries(entries, "prompt")),
         }
+    }
 
-        serde_json::from_str(&json_data).map_err(|e| {
-            crate::AgentError::ToolExecution(format!(
-                "Invalid JSON in SSE response from MCP server: {}",
-                e
-            ))
-        })
+    /// Send a notification, which the server doesn't answer
+    pub async fn notify(&self, method: &str, params: Option<Value>) -> crate::Result<()> {
+        let mut message = json!({"jsonrpc": "2.0", "method": method});
+        if let Some(params) = params {
+            message["params"] = params;
+        }
+        self.send(&message, None).await
     }
 
-    /// Handle persistent SSE event stream for server-initiated events
-    ///
-    /// This runs in a background task and maintains a persistent connection to the SSE endpoint.
-    /// It buffers incoming bytes and processes complete SSE messages line by line.
-    ///
-    /// The buffer size is limited to prevent memory exhaustion attacks.
+    /// Write a message to the server
     ///
-    /// # Example SSE stream
-    /// ```text
-    /// data: {"jsonrpc":"2.0","method":"tools/list_changed"}
-    ///
-    /// data: {"jsonrpc":"2.0","method":"progress","params":{"progressToken":"token1"}}
+    /// Over Streamable HTTP, the messages the server streams back in the POST response
+    /// are routed as they arrive, until the response to request `id` is among them.
+    async fn send(&self, message: &Value, id: Option<&Value>) -> crate::Result<()> {
+        match &self.transport {
+            TransportConnection::Stdio { stdin_writer, .. } => {
+                let mut writer_guard = stdin_writer.write().await;
+                let writer = writer_guard.as_mut().ok_or(McpError::StdinNotAvailable)?;
+                write_message(writer, message).await.map_err(|e| {
+                    // A broken pipe means the server is gone
+                    self.router.close();
+                    McpError::IoError(e)
+                })?;
+                Ok(())
+            }
+            TransportConnection::Http {
+                client,
+                url,
+                session_id,
+                protocol_version,
+                ..
+            } => {
+                let session = HttpSession::new(client, url, session_id, protocol_version);
+                let response = session
+                    .post(message)
+                    .await
+                    .map_err(|e| self.send_error(e))?;
+                let status = response.status();
+                if session.is_expired(status).await {
+                    tracing::warn!("Session of MCP server {} expired", self.name);
+                    self.router.close();
+                    return Err(McpError::ConnectionClosed.into());
+                }
+                if !status.is_success() {
+                    return Err(crate::AgentError::ToolExecution(format!(
+                        "MCP server {} answered with status {}",
+                        self.name, status
+                    )));
+                }
+                session.remember_session_id(&response).await;
+                self.route_http_response(&session, response, id).await
+            }
+            TransportConnection::Sse {
+                client, endpoint, ..
+            } => {
+                // Responses arrive on the event stream, the POST is only acknowledged
+                let response = client
+                    .post(endpoint)
+                    .json(message)
+                    .send()
+                    .await
+                    .map_err(|e| self.send_error(e))?;
+                if !response.status().is_success() {
+                    return Err(crate::AgentError::ToolExecution(format!(
+                        "MCP server {} answered with status {}",
+                        self.name,
+                        response.status()
+                    )));
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Error for a message that could not be delivered to the server
+    fn send_error(&self, e: reqwest::Error) -> crate::AgentError {
+        crate::AgentError::ToolExecution(format!(
+            "Failed to send request to MCP server {}: {}",
+            self.name, e
+        ))
+    }
+
+    /// Route the messages in the body of a response to a POST
     ///
-    /// ```
-    async fn handle_sse_event_stream(
-        client: Client,
-        url: &str,
-        response_tx: mpsc::UnboundedSender<String>,
+    /// An event stream that breaks before the response to request `id` arrived is
+    /// resumed with a GET after the last event received.
+    async fn route_http_response(
+        &self,
+        session: &HttpSession,
+        response: reqwest::Response,
+        id: Option<&Value>,
     ) -> crate::Result<()> {
-        use futures::StreamExt;
-
-        const MAX_BUFFER_SIZE: usize = 1024 * 1024; // 1MB limit
+        let is_event_stream = response
+            .headers()
+            .get("Content-Type")
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.contains("text/event-stream"));
+        if !is_event_stream {
+            let body = response.text().await.map_err(|e| {
+                McpError::ProtocolError(format!(
+                    "Failed to read response from MCP server {}: {}",
+                    self.name, e
+                ))
+            })?;
+            if !body.trim().is_empty() {
+                session
+                    .answer(&self.router, self.router.dispatch_text(&body))
+                    .await;
+            }
+            return Ok(());
+        }
 
+        let mut decoder = SseDecoder::default();
+        let mut response = response;
+        let mut resumptions = 0;
         loop {
-            tracing::debug!("Establishing SSE event stream connection");
+            let until_answered = id.filter(|id| self.router.is_pending(id));
+            route_event_stream(
+                session,
+                &self.router,
+                response,
+                &mut decoder,
+                until_answered,
+            )
+            .await;
+            let Some(id) = id.filter(|id| self.router.is_pending(id)) else {
+                return Ok(());
+            };
 
-            let response = client
-                .get(url)
-                .header("Accept", "text/event-stream")
-                .send()
-                .await
-                .map_err(|e| {
-                    crate::AgentError::ToolExecution(format!(
-                        "Failed to establish SSE event stream: {}",
-                        e
-                    ))
-                })?;
+            let Some(last_event_id) = decoder.last_event_id.clone() else {
+                return Err(McpError::ProtocolError(format!(
+                    "Event stream of MCP server {} ended before the response to request {}",
+                    self.name, id
+                ))
+                .into());
+            };
+            if resumptions == MAX_STREAM_RESUMPTIONS {
+                return Err(McpError::ProtocolError(format!(
+                    "Event stream of MCP server {} broke {} times before the response to request {}",
+                    self.name, resumptions, id
+                ))
+                .into());
+            }
+            resumptions += 1;
+            tokio::time::sleep(decoder.retry.unwrap_or_default()).await;
 
+            tracing::debug!(
+                "Resuming event stream of MCP server {} after event {}",
+                self.name,
+                last_event_id
+            );
+            response = session
+                .open_event_stream(Some(&last_event_id))
+                .await
+                .map_err(|e| self.send_error(e))?;
             if !response.status().is_success() {
                 return Err(crate::AgentError::ToolExecution(format!(
-                    "SSE event stream failed with status: {}",
+                    "MCP server {} could not resume its event stream: status {}",
+                    self.name,
                     response.status()
                 )));
             }
+        }
+    }
+}
 
-            let mut bytes_stream = response.bytes_stream();
-            let mut buffer = String::new();
+/// How connected MCP servers are checked and reconnected
+#[derive(Debug, Clone, PartialEq)]
+pub struct McpHealthOptions {
+    /// Time between two pings of a server
+    pub ping_interval: Duration,
+    /// Time a server has to answer a ping before it is considered lost
+    pub ping_timeout: Duration,
+    /// Delay before the first reconnection attempt, doubled for every further attempt
+    pub initial_backoff: Duration,
+    /// Longest delay between two reconnection attempts
+    pub max_backoff: Duration,
+    /// Attempts after which a lost server is given up
+    pub max_reconnect_attempts: u32,
+}
 
-            while let Some(chunk_result) = bytes_stream.next().await {
-                match chunk_result {
-                    Ok(chunk) => {
-                        let text = String::from_utf8_lossy(&chunk);
+impl Default for McpHealthOptions {
+    fn default() -> Self {
+        Self {
+            ping_interval: Duration::from_secs(30),
+            ping_timeout: Duration::from_secs(10),
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+            max_reconnect_attempts: 10,
+        }
+    }
+}
 
-                        // Prevent unbounded buffer growth
-                        if buffer.len() + text.len() > MAX_BUFFER_SIZE {
-                            tracing::error!("SSE buffer exceeded maximum size, resetting");
-                            buffer.clear();
-                            continue;
-                        }
+impl McpHealthOptions {
+    /// Delay before reconnection attempt number `attempt`, counting from 1
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+}
 
-                        buffer.push_str(&text);
+/// Connection state of an MCP server
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum McpServerState {
+    /// The server is connected and answers pings
+    Connected,
+    /// The connection was lost and is being re-established
+    Reconnecting,
+    /// The server could not be connected, or was given up after losing it
+    Failed,
+}
 
-                        // Process complete lines from buffer
-                        while let Some(newline_pos) = buffer.find('\n') {
-                            let line = buffer[..newline_pos].to_string();
-                            buffer = buffer[newline_pos + 1..].to_string();
+/// Health of an MCP server, as reported by the `_claude_agent/mcp/status` extension
+/// method
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct McpServerStatus {
+    /// Name of the server
+    pub name: String,
+    /// Transport type, `stdio`, `http` or `sse`
+    pub transport: String,
+    /// Connection state
+    pub state: McpServerState,
+    /// Number of tools the server currently provides
+    pub tools: usize,
+    /// Reconnection attempts made since the connection was lost
+    pub reconnect_attempts: u32,
+    /// Round trip time of the last answered ping, in milliseconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_ping_ms: Option<u64>,
+    /// Why the server was last lost or failed to connect
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+}
 
-                            if let Some(data) = line.strip_prefix("data: ") {
-                                if response_tx.send(data.to_string()).is_err() {
-                                    tracing::warn!("SSE response channel closed");
-                                    return Ok(());
-                                }
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        tracing::error!("Error reading SSE stream: {}", e);
-                        break;
-                    }
-                }
-            }
+impl McpServerStatus {
+    fn new(config: &McpServerConfig, state: McpServerState) -> Self {
+        Self {
+            name: config.name().to_string(),
+            transport: config.transport_type().to_string(),
+            state,
+            tools: 0,
+            reconnect_attempts: 0,
+            last_ping_ms: None,
+            last_error: None,
+        }
+    }
+}
+
+/// Health statuses of the servers, keyed by server name
+type ServerStatuses = Arc<std::sync::Mutex<HashMap<String, McpServerStatus>>>;
+
+/// Configurations of the servers whose first connection failed and is being retried
+type RetriedServers = Arc<std::sync::Mutex<HashMap<String, McpServerConfig>>>;
 
-            // Connection closed, wait before reconnecting
-            tracing::info!("SSE connection closed, reconnecting in 5 seconds");
-            tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
+/// Outcome of a health check
+enum HealthCheck {
+    /// The server answered a ping with the given round trip, or was heard from lately
+    Alive(Option<Duration>),
+    /// The connection was lost, for the given reason
+    Lost(String),
+    /// The connection was closed on purpose or replaced
+    Retired,
+}
+
+/// Manages connections to multiple MCP servers
+///
+/// Every connected server is pinged periodically. A server whose process exits, whose
+/// pipe breaks or that stops answering pings is reconnected with exponential backoff,
+/// which runs `initialize` and the list requests again. A server that fails its first
+/// connection is retried the same way.
+#[derive(Debug)]
+pub struct McpServerManager {
+    /// Map of server name to connection
+    connections: Arc<RwLock<HashMap<String, McpServerConnection>>>,
+    /// Health of every configured server
+    statuses: ServerStatuses,
+    /// Servers whose first connection is being retried
+    retrying: RetriedServers,
+    /// How servers are checked and reconnected
+    health_options: McpHealthOptions,
+    /// Signalled when a server's notification changed its tools or prompts
+    commands_changed: watch::Sender<()>,
+    /// URIs of subscribed resources the servers reported as updated
+    resource_updates: broadcast::Sender<String>,
+}
+
+impl McpServerManager {
+    /// Create a new MCP server manager
+    pub fn new() -> Self {
+        Self::with_health_options(McpHealthOptions::default())
+    }
+
+    /// Create a manager that checks and reconnects servers as `health_options` say
+    pub fn with_health_options(health_options: McpHealthOptions) -> Self {
+        Self {
+            connections: Arc::new(RwLock::new(HashMap::new())),
+            statuses: Arc::default(),
+            retrying: Arc::default(),
+            health_options,
+            commands_changed: watch::Sender::new(()),
+            resource_updates: broadcast::Sender::new(RESOURCE_UPDATE_CAPACITY),
         }
     }
 
-    /// Extract tools from initialize response
+    /// Health of every configured server, sorted by name
+    pub async fn server_statuses(&self) -> Vec<McpServerStatus> {
+        let connections = self.connections.read().await;
+        let mut statuses: Vec<McpServerStatus> = lock(&self.statuses)
+            .values()
+            .cloned()
+            .map(|mut status| {
+                status.tools = connections
+                    .get(&status.name)
+                    .map_or(0, |connection| connection.tools.len());
+                status
+            })
+            .collect();
+        statuses.sort_by(|a, b| a.name.cmp(&b.name));
+        statuses
+    }
+
+    /// Watch for tool and prompt changes announced by the servers
     ///
-    /// The MCP initialize response may include server capabilities, but does not
-    /// directly list available tools. Tools are obtained via the separate tools/list
-    /// request. This function validates the response structure and extracts any
-    /// capability information for future use.
+    /// The receiver is notified after a server sent `notifications/tools/list_changed`
+    /// or `notifications/prompts/list_changed` and its new list differs from the old
+    /// one. Changes made by `sync_servers` are reported by its return value instead.
+    pub fn subscribe_command_changes(&self) -> watch::Receiver<()> {
+        self.commands_changed.subscribe()
+    }
+
+    /// Receive the URIs of resources that changed after `subscribe_resource`
+    pub fn subscribe_resource_updates(&self) -> broadcast::Receiver<String> {
+        self.resource_updates.subscribe()
+    }
+
+    /// Handler for the notifications of this manager's connections
+    fn notification_handler(&self) -> NotificationHandler {
+        NotificationHandler {
+            connections: Arc::downgrade(&self.connections),
+            commands_changed: self.commands_changed.clone(),
+            resource_updates: self.resource_updates.clone(),
+        }
+    }
+
+    /// Fetch a list of a server again and store it
     ///
-    /// According to the MCP specification, capabilities.tools is metadata about
-    /// tool support (like listChanged notification support), not the actual tool list.
-    fn extract_tools_from_initialize_response(
-        &self,
-        response: &Value,
-    ) -> crate::Result<Vec<String>> {
-        // Validate response structure
-        if let Some(result) = response.get("result") {
-            if let Some(capabilities) = result.get("capabilities") {
-                if let Some(tools_capability) = capabilities.get("tools") {
-                    tracing::debug!(
-                        "Server supports tools capability: {}",
-                        serde_json::to_string(tools_capability).unwrap_or_default()
-                    );
+    /// Signals `commands_changed` if the tools or prompts differ from the stored ones.
+    /// The list is requested without holding the lock, and dropped if the connection
+    /// was replaced meanwhile.
+    async fn refresh_list(
+        connections: &RwLock<HashMap<String, McpServerConnection>>,
+        server_name: &str,
+        kind: ListKind,
+        commands_changed: &watch::Sender<()>,
+    ) -> crate::Result<()> {
+        let Some(connection) = connections.read().await.get(server_name).cloned() else {
+            return Ok(());
+        };
+        let entries = tokio::time::timeout(
+            LIST_REFRESH_TIMEOUT,
+            connection.list_all(kind.method(), kind.field()),
+        )
+        .await
+        .map_err(|_| {
+            McpError::ProtocolError(format!(
+                "MCP server {} did not list its {} within {:?}",
+                server_name,
+                kind.field(),
+                LIST_REFRESH_TIMEOUT
+            ))
+        })??;
+
+        let mut connections = connections.write().await;
+        if let Some(connection) = connections
+            .get_mut(server_name)
+            .filter(|current| current.router.is_same_connection(&connection.router))
+        {
+            if connection.store_list(kind, entries) {
+                tracing::info!("MCP server {} updated its {}", server_name, kind.field());
+                // Resources are not offered as commands
+                if kind != ListKind::Resources {
+                    commands_changed.send_replace(());
                 }
             }
         }
-
-        // According to MCP specification, tools are obtained from tools/list request,
-        // not from the initialize response. Return empty list here.
-        Ok(Vec::new())
+        Ok(())
     }
 
-    /// Extract tools from tools/list response
-    fn extract_tools_from_list_response(&self, response: &Value) -> crate::Result<Vec<String>> {
-        let mut tools = Vec::new();
-
-        if let Some(result) = response.get("result") {
-            if let Some(tools_array) = result.get("tools") {
-                if let Some(tool_list) = tools_array.as_array() {
-                    for tool in tool_list {
-                        if let Some(name) = tool.get("name").and_then(|n| n.as_str()) {
-                            tools.push(name.to_string());
-                        }
-                    }
+    /// Fetch the resources and prompts a server declared capabilities for
+    ///
+    /// A server that fails to list them stays connected with its tools.
+    async fn load_resources_and_prompts(connection: &mut McpServerConnection) {
+        for kind in [ListKind::Resources, ListKind::Prompts] {
+            if !connection.supports(kind.field()) {
+                continue;
+            }
+            match connection.list_all(kind.method(), kind.field()).await {
+                Ok(entries) => {
+                    connection.store_list(kind, entries);
                 }
+                Err(e) => tracing::warn!(
+                    "Failed to list the {} of MCP server {}: {}",
+                    kind.field(),
+                    connection.name,
+                    e
+                ),
             }
         }
+        tracing::info!(
+            "MCP server {} provides {} resources and {} prompts",
+            connection.name,
+            connection.resources.len(),
+            connection.prompts.len()
+        );
+    }
 
-        // If no tools found, log warning but don't fail
-        if tools.is_empty() {
-            tracing::warn!("No tools found in MCP tools/list response");
+    /// Connect to all configured MCP servers
+    pub async fn connect_servers(&mut self, configs: Vec<McpServerConfig>) -> crate::Result<()> {
+        for config in configs {
+            // Continue with other servers instead of failing completely
+            self.connect_and_add(config).await;
         }
+        Ok(())
+    }
 
-        Ok(tools)
+    /// Connect to a server and start supervising it, or record why it failed and retry
+    async fn connect_and_add(&self, config: McpServerConfig) {
+        let config_name = config.name().to_string();
+        lock(&self.retrying).remove(&config_name);
+        match self.connect_server(config.clone()).await {
+            Ok(connection) => {
+                tracing::info!("Connected to MCP server: {}", connection.name);
+                lock(&self.statuses).insert(
+                    config_name.clone(),
+                    McpServerStatus::new(&config, McpServerState::Connected),
+                );
+                let router = connection.router.clone();
+                self.connections
+                    .write()
+                    .await
+                    .insert(connection.name.clone(), connection);
+                self.supervise(config_name, router);
+            }
+            Err(e) => {
+                tracing::error!("Failed to connect to MCP server {}: {}", config_name, e);
+                let mut status = McpServerStatus::new(&config, McpServerState::Failed);
+                status.last_error = Some(e.to_string());
+                lock(&self.statuses).insert(config_name.clone(), status);
+                lock(&self.retrying).insert(config_name, config.clone());
+                tokio::spawn(Self::retry_connect(self.downgrade(), config, e.to_string()));
+            }
+        }
+    }
+
+    /// Retry the first connection to a server with exponential backoff
+    ///
+    /// Stops when the server is removed or redefined meanwhile.
+    async fn retry_connect(manager: WeakManager, config: McpServerConfig, mut reason: String) {
+        let server_name = config.name().to_string();
+        let options = manager.health_options.clone();
+        for attempt in 1..=options.max_reconnect_attempts {
+            tokio::time::sleep(options.backoff(attempt)).await;
+            let Some(manager) = manager.upgrade() else {
+                return;
+            };
+            if !manager.is_retrying(&config) {
+                return;
+            }
+            if let Some(status) = lock(&manager.statuses).get_mut(&server_name) {
+                status.state = McpServerState::Reconnecting;
+                status.reconnect_attempts = attempt;
+                status.last_error = Some(reason.clone());
+            }
+
+            tracing::info!(
+                "Connecting to MCP server {} again (attempt {})",
+                server_name,
+                attempt
+            );
+            match manager.connect_server(config.clone()).await {
+                Ok(connection) => {
+                    manager.add_retried_connection(&config, connection).await;
+                    return;
+                }
+                Err(e) => {
+                    tracing::warn!("Failed to connect to MCP server {}: {}", server_name, e);
+                    reason = e.to_string();
+                }
+            }
+        }
+
+        let Some(manager) = manager.upgrade() else {
+            return;
+        };
+        if !manager.is_retrying(&config) {
+            return;
+        }
+        tracing::error!(
+            "Giving up on MCP server {} after {} connection attempts",
+            server_name,
+            options.max_reconnect_attempts
+        );
+        lock(&manager.retrying).remove(&server_name);
+        let mut statuses = lock(&manager.statuses);
+        if let Some(status) = statuses.get_mut(&server_name) {
+            status.state = McpServerState::Failed;
+            status.last_error = Some(reason);
+        }
+    }
+
+    /// Whether the first connection to the server defined by `config` is being retried
+    fn is_retrying(&self, config: &McpServerConfig) -> bool {
+        lock(&self.retrying)
+            .get(config.name())
+            .is_some_and(|retried| definition(retried) == definition(config))
+    }
+
+    /// Add a connection whose first attempt failed and start supervising it
+    ///
+    /// The connection is closed instead if the server was removed or redefined meanwhile.
+    async fn add_retried_connection(
+        &self,
+        config: &McpServerConfig,
+        connection: McpServerConnection,
+    ) {
+        let server_name = connection.name.clone();
+        let mut connections = self.connections.write().await;
+        if !self.is_retrying(config) || connections.contains_key(&server_name) {
+            Self::close_connection(&server_name, &connection).await;
+            return;
+        }
+
+        tracing::info!("Connected to MCP server: {}", server_name);
+        lock(&self.retrying).remove(&server_name);
+        self.supervise(server_name.clone(), connection.router.clone());
+        if !connection.tools.is_empty() || !connection.prompts.is_empty() {
+            self.commands_changed.send_replace(());
+        }
+        connections.insert(server_name.clone(), connection);
+        if let Some(status) = lock(&self.statuses).get_mut(&server_name) {
+            status.state = McpServerState::Connected;
+            status.reconnect_attempts = 0;
+        }
+    }
+
+    /// Start the task that pings a connection and reconnects it when it is lost
+    fn supervise(&self, server_name: String, router: MessageRouter) {
+        let manager = self.downgrade();
+        tokio::spawn(async move {
+            let reason = loop {
+                tokio::select! {
+                    _ = router.wait_closed() => {}
+                    _ = tokio::time::sleep(manager.health_options.ping_interval) => {}
+                }
+                let Some(manager) = manager.upgrade() else {
+                    return;
+                };
+                match manager.check_health(&server_name, &router).await {
+                    HealthCheck::Alive(Some(round_trip)) => {
+                        if let Some(status) = lock(&manager.statuses).get_mut(&server_name) {
+                            status.last_ping_ms = Some(round_trip.as_millis() as u64);
+                        }
+                    }
+                    HealthCheck::Alive(None) => {}
+                    HealthCheck::Lost(reason) => break reason,
+                    HealthCheck::Retired => return,
+                }
+            };
+            Self::reconnect(manager, server_name, router, reason).await;
+        });
+    }
+
+    /// Check whether the connection routed by `router` is still alive
+    ///
+    /// A server that is answering requests or sent a message during the last ping
+    /// interval is not pinged, so a busy server is not mistaken for a lost one.
+    async fn check_health(&self, server_name: &str, router: &MessageRouter) -> HealthCheck {
+        let connection = {
+            let connections = self.connections.read().await;
+            match connections
+                .get(server_name)
+                .filter(|connection| connection.router.is_same_connection(router))
+            {
+                Some(connection) => connection.clone(),
+                None => return HealthCheck::Retired,
+            }
+        };
+
+        let lost = |reason: String| {
+            tracing::warn!("Lost connection to MCP server {}: {}", server_name, reason);
+            HealthCheck::Lost(reason)
+        };
+        if let TransportConnection::Stdio { process, .. } = &connection.transport {
+            if let Some(Ok(Some(status))) = process.write().await.as_mut().map(Child::try_wait) {
+                return lost(format!("server process exited with {}", status));
+            }
+        }
+        if router.is_closed() {
+            return lost("connection closed".to_string());
+        }
+        if router.has_pending() || router.heard_within(self.health_options.ping_interval) {
+            return HealthCheck::Alive(None);
+        }
+
+        let started = Instant::now();
+        match tokio::time::timeout(
+            self.health_options.ping_timeout,
+            connection.request("ping", None),
+        )
+        .await
+        {
+            // Any answer, even an error, shows the server is there
+            Ok(Ok(_)) => HealthCheck::Alive(Some(started.elapsed())),
+            Ok(Err(e)) => lost(format!("ping failed: {}", e)),
+            Err(_) => lost(format!(
+                "no answer to ping within {:?}",
+                self.health_options.ping_timeout
+            )),
+        }
+    }
+
+    /// Re-establish a lost connection, retrying with exponential backoff
+    ///
+    /// The lost connection stays in place, closed, so tool calls fail right away until
+    /// it is replaced. It is removed when the server is given up.
+    async fn reconnect(
+        manager: WeakManager,
+        server_name: String,
+        lost_router: MessageRouter,
+        mut reason: String,
+    ) {
+        let options = manager.health_options.clone();
+        for attempt in 1..=options.max_reconnect_attempts {
+            let config = {
+                let Some(manager) = manager.upgrade() else {
+                    return;
+                };
+                let Some(config) = manager
+                    .lost_connection_config(&server_name, &lost_router)
+                    .await
+                else {
+                    return;
+                };
+                if let Some(status) = lock(&manager.statuses).get_mut(&server_name) {
+                    status.state = McpServerState::Reconnecting;
+                    status.reconnect_attempts = attempt;
+                    status.last_error = Some(reason.clone());
+                }
+                config
+            };
+
+            tokio::time::sleep(options.backoff(attempt)).await;
+            let Some(manager) = manager.upgrade() else {
+                return;
+            };
+            tracing::info!(
+                "Reconnecting to MCP server {} (attempt {})",
+                server_name,
+                attempt
+            );
+            match manager.connect_server(config).await {
+                Ok(connection) => {
+                    manager
+                        .replace_lost_connection(&lost_router, connection)
+                        .await;
+                    return;
+                }
+                Err(e) => {
+                    tracing::warn!("Failed to reconnect to MCP server {}: {}", server_name, e);
+                    reason = e.to_string();
+                }
+            }
+        }
+
+        let Some(manager) = manager.upgrade() else {
+            return;
+        };
+        tracing::error!(
+            "Giving up on MCP server {} after {} reconnection attempts",
+            server_name,
+            options.max_reconnect_attempts
+        );
+        let mut connections = manager.connections.write().await;
+        if connections
+            .get(&server_name)
+            .is_some_and(|connection| connection.router.is_same_connection(&lost_router))
+        {
+            if let Some(connection) = connections.remove(&server_name) {
+                Self::close_connection(&server_name, &connection).await;
+                if !connection.tools.is_empty() || !connection.prompts.is_empty() {
+                    manager.commands_changed.send_replace(());
+                }
+            }
+            if let Some(status) = lock(&manager.statuses).get_mut(&server_name) {
+                status.state = McpServerState::Failed;
+                status.last_error = Some(reason);
+            }
+        }
+    }
+
+    /// Close a lost connection and return its configuration, unless it was retired
+    async fn lost_connection_config(
+        &self,
+        server_name: &str,
+        lost_router: &MessageRouter,
+    ) -> Option<McpServerConfig> {
+        let connections = self.connections.read().await;
+        let connection = connections
+            .get(server_name)
+            .filter(|connection| connection.router.is_same_connection(lost_router))?;
+        // Stops a hung process and fails calls still waiting on it
+        Self::close_connection(server_name, connection).await;
+        Some(connection.config.clone())
+    }
+
+    /// Put a new connection in the place of a lost one
+    ///
+    /// The new connection is closed instead if the lost one was retired meanwhile.
+    async fn replace_lost_connection(
+        &self,
+        lost_router: &MessageRouter,
+        connection: McpServerConnection,
+    ) {
+        let server_name = connection.name.clone();
+        let mut connections = self.connections.write().await;
+        let still_lost = connections
+            .get(&server_name)
+            .is_some_and(|current| current.router.is_same_connection(lost_router));
+        if !still_lost {
+            Self::close_connection(&server_name, &connection).await;
+            return;
+        }
+
+        tracing::info!("Reconnected to MCP server {}", server_name);
+        self.supervise(server_name.clone(), connection.router.clone());
+        if let Some(lost) = connections.insert(server_name.clone(), connection) {
+            let current = &connections[&server_name];
+            if lost.tools != current.tools || lost.prompts != current.prompts {
+                self.commands_changed.send_replace(());
+            }
+        }
+        if let Some(status) = lock(&self.statuses).get_mut(&server_name) {
+            status.state = McpServerState::Connected;
+            status.reconnect_attempts = 0;
+        }
+    }
+
+    /// State shared with background tasks, without keeping the manager alive
+    fn downgrade(&self) -> WeakManager {
+        WeakManager {
+            connections: Arc::downgrade(&self.connections),
+            statuses: Arc::downgrade(&self.statuses),
+            retrying: Arc::downgrade(&self.retrying),
+            health_options: self.health_options.clone(),
+            commands_changed: self.commands_changed.clone(),
+            resource_updates: self.resource_updates.clone(),
+        }
+    }
+
+    /// Connect to a single MCP server and fetch what it offers
+    async fn connect_server(&self, config: McpServerConfig) -> crate::Result<McpServerConnection> {
+        let mut connection = self.open_connection(config).await?;
+        Self::load_resources_and_prompts(&mut connection).await;
+        Ok(connection)
+    }
+
+    /// Connect to a single MCP server, initialize it and list its tools
+    async fn open_connection(&self, config: McpServerConfig) -> crate::Result<McpServerConnection> {
+        match &config {
+            McpServerConfig::Stdio(stdio_config) => {
+                tracing::info!(
+                    "Connecting to MCP server: {} ({})",
+                    stdio_config.name,
+                    stdio_config.command
+                );
+
+                // Start the MCP server process with environment variables
+                let mut command = Command::new(&stdio_config.command);
+                command.args(&stdio_config.args);
+
+                // Set working directory if provided
+                if let Some(cwd) = &stdio_config.cwd {
+                    command.current_dir(cwd);
+                }
+
+                // Set environment variables
+                for env_var in &stdio_config.env {
+                    command.env(&env_var.name, &env_var.value);
+                }
+
+                let mut child = command
+                    .stdin(std::process::Stdio::piped())
+                    .stdout(std::process::Stdio::piped())
+                    .stderr(std::process::Stdio::piped())
+                    .kill_on_drop(true)
+                    .spawn()
+                    .map_err(|e| McpError::ProcessSpawnFailed(stdio_config.name.clone(), e))?;
+
+                // Get stdio handles
+                let stdin = child.stdin.take().ok_or(McpError::StdinNotAvailable)?;
+                let stdout = child.stdout.take().ok_or(McpError::StdoutNotAvailable)?;
+
+                let router = MessageRouter::new(&stdio_config.name, self.notification_handler());
+                let transport = TransportConnection::stdio(
+                    child,
+                    BufWriter::new(stdin),
+                    BufReader::new(stdout),
+                    &router,
+                );
+
+                self.initialize_connection(config.clone(), transport, router)
+                    .await
+            }
+            McpServerConfig::Http(http_config) => {
+                tracing::info!(
+                    "Connecting to HTTP MCP server: {} ({})",
+                    http_config.name,
+                    http_config.url
+                );
+
+                let client = http_client(&http_config.name, &http_config.headers)?;
+                let transport = TransportConnection::http(
+                    client,
+                    &http_config.url,
+                    http_config.headers.clone(),
+                );
+                let router = MessageRouter::new(&http_config.name, self.notification_handler());
+                self.initialize_connection(config.clone(), transport, router)
+                    .await
+            }
+            McpServerConfig::Sse(sse_config) => {
+                tracing::info!(
+                    "Connecting to SSE MCP server: {} ({})",
+                    sse_config.name,
+                    sse_config.url
+                );
+
+                // Server events are routed from the moment the stream is open
+                let client = http_client(&sse_config.name, &sse_config.headers)?;
+                let router = MessageRouter::new(&sse_config.name, self.notification_handler());
+                let transport = TransportConnection::sse(
+                    client,
+                    &sse_config.url,
+                    sse_config.headers.clone(),
+                    &router,
+                )
+                .await?;
+                self.initialize_connection(config.clone(), transport, router)
+                    .await
+            }
+        }
+    }
+
+    /// Wrap a transport in a connection and initialize the MCP protocol on it
+    async fn initialize_connection(
+        &self,
+        config: McpServerConfig,
+        transport: TransportConnection,
+        router: MessageRouter,
+    ) -> crate::Result<McpServerConnection> {
+        let mut connection = McpServerConnection {
+            name: config.name().to_string(),
+            tools: Vec::new(),
+            resources: Vec::new(),
+            prompts: Vec::new(),
+            capabilities: json!({}),
+            config,
+            transport,
+            router,
+        };
+
+        let result = self.initialize_mcp_connection(&connection).await;
+        match result {
+            Ok((tools, capabilities)) => {
+                connection.tools = tools;
+                connection.capabilities = capabilities;
+                Ok(connection)
+            }
+            Err(e) => {
+                // Stops the event stream of SSE servers
+                connection.router.close();
+                Err(e)
+            }
+        }
+    }
+
+    /// Initialize the MCP protocol on a connection and list the server's tools
+    ///
+    /// Returns the tools and the capabilities the server declared.
+    async fn initialize_mcp_connection(
+        &self,
+        connection: &McpServerConnection,
+    ) -> crate::Result<(Vec<McpTool>, Value)> {
+        let response = connection.initialize().await?;
+
+        // Extract available tools from response
+        let _tools = self.extract_tools_from_initialize_response(&response)?;
+
+        // Request list of available tools
+        let tools_response = connection.request("tools/list", None).await?;
+        let final_tools = self.extract_tools_from_list_response(&tools_response)?;
+
+        tracing::info!(
+            "MCP server {} provides {} tools: {:?}",
+            connection.name,
+            final_tools.len(),
+            final_tools
+                .iter()
+                .map(|tool| &tool.name)
+                .collect::<Vec<_>>()
+        );
+
+        Ok((final_tools, server_capabilities(&response)))
+    }
+
+    /// Extract tools from initialize response
+    ///
+    /// The MCP initialize response may include server capabilities, but does not
+    /// directly list available tools. Tools are obtained via the separate tools/list
+    /// request. This function validates the response structure and extracts any
+    /// capability information for future use.
+    ///
+    /// According to the MCP specification, capabilities.tools is metadata about
+    /// tool support (like listChanged notification support), not the actual tool list.
+    fn extract_tools_from_initialize_response(
+        &self,
+        response: &Value,
+    ) -> crate::Result<Vec<String>> {
+        // Validate response structure
+        if let Some(result) = response.get("result") {
+            if let Some(capabilities) = result.get("capabilities") {
+                if let Some(tools_capability) = capabilities.get("tools") {
+                    tracing::debug!(
+                        "Server supports tools capability: {}",
+                        serde_json::to_string(tools_capability).unwrap_or_default()
+                    );
+                }
+            }
+        }
+
+        // According to MCP specification, tools are obtained from tools/list request,
+        // not from the initialize response. Return empty list here.
+        Ok(Vec::new())
+    }
+
+    /// Extract tools from tools/list response
+    fn extract_tools_from_list_response(&self, response: &Value) -> crate::Result<Vec<McpTool>> {
+        let tools = parse_tool_list(response);
+
+        // If no tools found, log warning but don't fail
+        if tools.is_empty() {
+            tracing::warn!("No tools found in MCP tools/list response");
+        }
+
+        Ok(tools)
     }
 
     /// Execute a tool call on the specified MCP server
@@ -1015,29 +2365,577 @@ impl McpServerManager {
         &self,
         server_name: &str,
         tool_call: &InternalToolRequest,
+    ) -> crate::Result<String> {
+        self.execute_tool_call_with_progress(server_name, tool_call, None)
+            .await
+    }
+
+    /// Execute a tool call, forwarding the progress the server reports to `progress`
+    ///
+    /// The listener is dropped when the call finishes.
+    pub async fn execute_tool_call_with_progress(
+        &self,
+        server_name: &str,
+        tool_call: &InternalToolRequest,
+        progress: Option<mpsc::UnboundedSender<McpProgress>>,
     ) -> crate::Result<String> {
         let connections = self.connections.read().await;
         let connection = connections.get(server_name).ok_or_else(|| {
             McpError::InvalidConfiguration(format!("MCP server '{}' not found", server_name))
         })?;
 
-        // Send tool call to the server
-        let response_content = self.send_tool_call_to_server(connection, tool_call).await?;
+        // Reject arguments the server would reject anyway, without a round trip
+        let tool_name = tool_call.name.split(':').nth(1).unwrap_or(&tool_call.name);
+        if let Some(tool) = connection.tools.iter().find(|tool| tool.name == tool_name) {
+            tool.validate_arguments(&tool_call.arguments)?;
+        }
+
+        // Send tool call to the server
+        let response_content = self
+            .send_tool_call_to_server(connection, tool_call, progress)
+            .await?;
+
+        // Convert MCP response to string result
+        self.process_tool_call_response(&response_content)
+    }
+
+    /// Send a tool call request to an MCP server
+    async fn send_tool_call_to_server(
+        &self,
+        connection: &McpServerConnection,
+        tool_call: &InternalToolRequest,
+        progress: Option<mpsc::UnboundedSender<McpProgress>>,
+    ) -> crate::Result<Value> {
+        let params = json!({
+            "name": tool_call.name.split(':').nth(1).unwrap_or(&tool_call.name),
+            "arguments": tool_call.arguments
+        });
+
+        tracing::info!(
+            "Sending tool call to MCP server {}: {}",
+            connection.name,
+            tool_call.name
+        );
+
+        connection
+            .request_with_progress("tools/call", Some(params), progress)
+            .await
+    }
+
+    /// Process MCP tool call response into string result
+    fn process_tool_call_response(&self, response: &Value) -> crate::Result<String> {
+        if let Some(result) = response.get("result") {
+            // Try to extract content array first (standard MCP format)
+            if let Some(content) = result.get("content") {
+                if let Some(content_array) = content.as_array() {
+                    let mut result_text = String::new();
+                    for item in content_array {
+                        if let Some(text) = item.get("text").and_then(|t| t.as_str()) {
+                            result_text.push_str(text);
+                            result_text.push('\n');
+                        }
+                    }
+                    return Ok(result_text.trim().to_string());
+                }
+            }
+
+            // Fallback: handle different result types intelligently
+            match result {
+                Value::String(s) => {
+                    tracing::debug!("MCP response result is a simple string");
+                    Ok(s.clone())
+                }
+                Value::Number(n) => {
+                    tracing::debug!("MCP response result is a number");
+                    Ok(n.to_string())
+                }
+                Value::Bool(b) => {
+                    tracing::debug!("MCP response result is a boolean");
+                    Ok(b.to_string())
+                }
+                Value::Null => {
+                    tracing::debug!("MCP response result is null");
+                    Ok(String::new())
+                }
+                Value::Object(_) | Value::Array(_) => {
+                    tracing::debug!("MCP response result is a complex type, formatting as JSON");
+                    serde_json::to_string_pretty(result).map_err(|e| {
+                        crate::AgentError::ToolExecution(format!(
+                            "Failed to serialize MCP result to JSON: {}",
+                            e
+                        ))
+                    })
+                }
+            }
+        } else if let Some(error) = response.get("error") {
+            Err(McpError::ServerError(error.clone()).into())
+        } else {
+            Err(McpError::MissingResult.into())
+        }
+    }
+
+    /// List all available tools from all connected MCP servers
+    pub async fn list_available_tools(&self) -> Vec<String> {
+        let connections = self.connections.read().await;
+        let mut all_tools = Vec::new();
+
+        for connection in connections.values() {
+            for tool in &connection.tools {
+                all_tools.push(format!("{}:{}", connection.name, tool.name));
+            }
+        }
+
+        all_tools
+    }
+
+    /// Definitions of all tools from all connected MCP servers, keyed by `server:tool` name
+    ///
+    /// Sorted by name.
+    pub async fn list_tool_definitions(&self) -> Vec<(String, McpTool)> {
+        let connections = self.connections.read().await;
+        let mut definitions: Vec<(String, McpTool)> = connections
+            .values()
+            .flat_map(|connection| {
+                connection
+                    .tools
+                    .iter()
+                    .map(|tool| (format!("{}:{}", connection.name, tool.name), tool.clone()))
+            })
+            .collect();
+        definitions.sort_by(|a, b| a.0.cmp(&b.0));
+        definitions
+    }
+
+    /// Definition of a tool named `server:tool`, or `mcp__server__tool` as the claude CLI
+    /// names MCP tools
+    pub async fn tool_definition(&self, tool_name: &str) -> Option<McpTool> {
+        let (server_name, tool) = match tool_name.strip_prefix("mcp__") {
+            Some(name) => name.split_once("__")?,
+            None => tool_name.split_once(':')?,
+        };
+        let connections = self.connections.read().await;
+        connections
+            .get(server_name)?
+            .tools
+            .iter()
+            .find(|candidate| candidate.name == tool)
+            .cloned()
+    }
+
+    /// Prompts of all connected MCP servers, keyed by `server:prompt` name
+    ///
+    /// Sorted by name.
+    pub async fn list_prompt_definitions(&self) -> Vec<(String, McpPrompt)> {
+        let connections = self.connections.read().await;
+        let mut definitions: Vec<(String, McpPrompt)> = connections
+            .values()
+            .flat_map(|connection| {
+                connection.prompts.iter().map(|prompt| {
+                    (
+                        format!("{}:{}", connection.name, prompt.name),
+                        prompt.clone(),
+                    )
+                })
+            })
+            .collect();
+        definitions.sort_by(|a, b| a.0.cmp(&b.0));
+        definitions
+    }
+
+    /// Definition of a prompt named `server:prompt`
+    pub async fn prompt_definition(&self, prompt_name: &str) -> Option<McpPrompt> {
+        let (server_name, prompt) = prompt_name.split_once(':')?;
+        let connections = self.connections.read().await;
+        connections
+            .get(server_name)?
+            .prompts
+            .iter()
+            .find(|candidate| candidate.name == prompt)
+            .cloned()
+    }
+
+    /// Render the prompt named `server:prompt` with `arguments`
+    ///
+    /// Returns the messages of the prompts/get result, each with a `role` and a
+    /// `content` block.
+    pub async fn get_prompt(
+        &self,
+        prompt_name: &str,
+        arguments: Value,
+    ) -> crate::Result<Vec<Value>> {
+        let (server_name, prompt) = prompt_name.split_once(':').ok_or_else(|| {
+            McpError::InvalidConfiguration(format!("'{}' is not a server:prompt name", prompt_name))
+        })?;
+        let connections = self.connections.read().await;
+        let connection = connections.get(server_name).ok_or_else(|| {
+            McpError::InvalidConfiguration(format!("MCP server '{}' not found", server_name))
+        })?;
+
+        let response = connection
+            .request(
+                "prompts/get",
+                Some(json!({"name": prompt, "arguments": arguments})),
+            )
+            .await?;
+        let result = response_result(response)?;
+        match result.get("messages") {
+            Some(Value::Array(messages)) => Ok(messages.clone()),
+            _ => Err(McpError::ProtocolError(format!(
+                "prompts/get result of MCP server {} has no messages",
+                server_name
+            ))
+            .into()),
+        }
+    }
+
+    /// Resources of all connected MCP servers with the name of their server
+    ///
+    /// Sorted by server name and URI.
+    pub async fn list_resources(&self) -> Vec<(String, McpResource)> {
+        let connections = self.connections.read().await;
+        let mut resources: Vec<(String, McpResource)> = connections
+            .values()
+            .flat_map(|connection| {
+                connection
+                    .resources
+                    .iter()
+                    .map(|resource| (connection.name.clone(), resource.clone()))
+            })
+            .collect();
+        resources.sort_by(|a, b| (&a.0, &a.1.uri).cmp(&(&b.0, &b.1.uri)));
+        resources
+    }
+
+    /// Name of the server that lists the resource at `uri`
+    pub async fn resource_server(&self, uri: &str) -> Option<String> {
+        let connections = self.connections.read().await;
+        connections
+            .values()
+            .filter(|connection| {
+                connection
+                    .resources
+                    .iter()
+                    .any(|resource| resource.uri == uri)
+            })
+            .map(|connection| connection.name.clone())
+            .min()
+    }
+
+    /// Read the resource at `uri` from the server that lists it
+    ///
+    /// Returns the `contents` of the resources/read result, each with a `uri` and
+    /// either `text` or a base64 `blob`.
+    pub async fn read_resource(&self, uri: &str) -> crate::Result<Vec<Value>> {
+        let response = self.resource_request("resources/read", uri).await?;
+        let result = response_result(response)?;
+        match result.get("contents") {
+            Some(Value::Array(contents)) => Ok(contents.clone()),
+            _ => Err(McpError::ProtocolError(format!(
+                "resources/read result for {} has no contents",
+                uri
+            ))
+            .into()),
+        }
+    }
+
+    /// Ask the server of the resource at `uri` to report changes to it
+    ///
+    /// Updates arrive through `subscribe_resource_updates`. Fails if the server does
+    /// not support subscriptions.
+    pub async fn subscribe_resource(&self, uri: &str) -> crate::Result<()> {
+        response_result(self.resource_request("resources/subscribe", uri).await?)?;
+        Ok(())
+    }
+
+    /// Stop the updates requested with `subscribe_resource`
+    pub async fn unsubscribe_resource(&self, uri: &str) -> crate::Result<()> {
+        response_result(self.resource_request("resources/unsubscribe", uri).await?)?;
+        Ok(())
+    }
+
+    /// Send a request about the resource at `uri` to the server that lists it
+    async fn resource_request(&self, method: &str, uri: &str) -> crate::Result<Value> {
+        let server_name = self
+            .resource_server(uri)
+            .await
+            .ok_or_else(|| McpError::ResourceNotFound(uri.to_string()))?;
+        let connections = self.connections.read().await;
+        let connection = connections
+            .get(&server_name)
+            .ok_or_else(|| McpError::ResourceNotFound(uri.to_string()))?;
+        if method != "resources/read"
+            && connection.capabilities["resources"]["subscribe"] != json!(true)
+        {
+            return Err(McpError::ProtocolError(format!(
+                "MCP server {} does not support resource subscriptions",
+                server_name
+            ))
+            .into());
+        }
+        connection
+            .request(method, Some(json!({ "uri": uri })))
+            .await
+    }
+
+    /// Shutdown all MCP server connections
+    pub async fn shutdown(&self) -> crate::Result<()> {
+        let mut connections = self.connections.write().await;
+
+        for (name, connection) in connections.iter() {
+            tracing::info!("Shutting down MCP server: {}", name);
+            Self::close_connection(name, connection).await;
+        }
+
+        connections.clear();
+        lock(&self.statuses).clear();
+        lock(&self.retrying).clear();
+        Ok(())
+    }
+
+    /// Bring the connected servers in line with a new list of server definitions
+    ///
+    /// Servers that were removed or whose definition changed are shut down, and new or
+    /// changed servers are connected. Servers with unchanged definitions keep running.
+    ///
+    /// # Returns
+    /// Whether the list of available tools or prompts changed
+    pub async fn sync_servers(&self, configs: Vec<McpServerConfig>) -> crate::Result<bool> {
+        let commands_before = (
+            self.list_tool_definitions().await,
+            self.list_prompt_definitions().await,
+        );
+
+        let to_connect = {
+            let mut connections = self.connections.write().await;
+            let wanted: HashMap<String, serde_json::Value> = configs
+                .iter()
+                .map(|config| (config.name().to_string(), definition(config)))
+                .collect();
+
+            let stale: Vec<String> = connections
+                .iter()
+                .filter(|(name, connection)| {
+                    wanted.get(name.as_str()) != Some(&definition(&connection.config))
+                })
+                .map(|(name, _)| name.clone())
+                .collect();
+            for name in stale {
+                if let Some(connection) = connections.remove(&name) {
+                    tracing::info!("Disconnecting MCP server: {}", name);
+                    Self::close_connection(&name, &connection).await;
+                }
+            }
+            lock(&self.statuses).retain(|name, _| wanted.contains_key(name));
+            lock(&self.retrying)
+                .retain(|name, config| wanted.get(name) == Some(&definition(config)));
+
+            configs
+                .into_iter()
+                .filter(|config| {
+                    !connections.contains_key(config.name()) && !self.is_retrying(config)
+                })
+                .collect::<Vec<_>>()
+        };
+
+        for config in to_connect {
+            self.connect_and_add(config).await;
+        }
+
+        let commands_after = (
+            self.list_tool_definitions().await,
+            self.list_prompt_definitions().await,
+        );
+        Ok(commands_after != commands_before)
+    }
+
+    /// Close the transport of a server connection
+    async fn close_connection(name: &str, connection: &McpServerConnection) {
+        // Fails requests still waiting and stops the SSE event stream
+        connection.router.close();
+
+        match &connection.transport {
+            TransportConnection::Stdio {
+                process,
+                stdin_writer,
+            } => {
+                // Close stdin first; the reader task ends with the process
+                {
+                    let mut writer_guard = stdin_writer.write().await;
+                    *writer_guard = None;
+                }
+
+                // Kill and wait for the process
+                let mut process_guard = process.write().await;
+                if let Some(mut proc) = process_guard.take() {
+                    let _ = proc.kill().await;
+                    let _ = proc.wait().await;
+                }
+            }
+            TransportConnection::Http {
+                client,
+                url,
+                session_id,
+                protocol_version,
+                ..
+            } => {
+                let session = HttpSession::new(client, url, session_id, protocol_version);
+                if session.session_id.read().await.is_some() {
+                    // Servers that don't let clients end sessions answer 405
+                    let request = session.request(reqwest::Method::DELETE).await;
+                    match request.timeout(SESSION_DELETE_TIMEOUT).send().await {
+                        Ok(response) => tracing::debug!(
+                            "Ended session of MCP server {}: status {}",
+                            name,
+                            response.status()
+                        ),
+                        Err(e) => {
+                            tracing::debug!("Failed to end session of MCP server {}: {}", name, e)
+                        }
+                    }
+                }
+                tracing::debug!("HTTP MCP server connection closed: {}", name);
+            }
+            TransportConnection::Sse { .. } => {
+                // The session ends with the event stream, which the router stopped
+                tracing::debug!("SSE MCP server connection closed: {}", name);
+            }
+        }
+    }
+}
+
+/// The result of a response, or the error the server answered with
+fn response_result(response: Value) -> crate::Result<Value> {
+    if let Some(error) = response.get("error") {
+        return Err(McpError::ServerError(error.clone()).into());
+    }
+    response
+        .get("result")
+        .cloned()
+        .ok_or_else(|| McpError::MissingResult.into())
+}
+
+/// Capabilities declared in an initialize response, empty if there are none
+pub(crate) fn server_capabilities(response: &Value) -> Value {
+    response
+        .get("result")
+        .and_then(|result| result.get("capabilities"))
+        .filter(|capabilities| capabilities.is_object())
+        .cloned()
+        .unwrap_or_else(|| json!({}))
+}
+
+/// Server definition in a comparable form
+fn definition(config: &McpServerConfig) -> serde_json::Value {
+    serde_json::to_value(config).unwrap_or_default()
+}
+
+/// A manager's state as held by its background tasks
+#[derive(Debug, Clone)]
+struct WeakManager {
+    connections: Weak<RwLock<HashMap<String, McpServerConnection>>>,
+    statuses: Weak<std::sync::Mutex<HashMap<String, McpServerStatus>>>,
+    retrying: Weak<std::sync::Mutex<HashMap<String, McpServerConfig>>>,
+    health_options: McpHealthOptions,
+    commands_changed: watch::Sender<()>,
+    resource_updates: broadcast::Sender<String>,
+}
+
+impl WeakManager {
+    /// A manager sharing the state, if the manager still exists
+    fn upgrade(&self) -> Option<McpServerManager> {
+        Some(McpServerManager {
+            connections: self.connections.upgrade()?,
+            statuses: self.statuses.upgrade()?,
+            retrying: self.retrying.upgrade()?,
+            health_options: self.health_options.clone(),
+            commands_changed: self.commands_changed.clone(),
+            resource_updates: self.resource_updates.clone(),
+        })
+    }
+}
+
+impl Default for McpServerManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_mcp_manager_creation() {
+        let manager = McpServerManager::new();
+        let tools = manager.list_available_tools().await;
+        assert!(tools.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_mcp_manager_connect_empty_servers() {
+        let mut manager = McpServerManager::new();
+        let result = manager.connect_servers(vec![]).await;
+        assert!(result.is_ok());
+
+        let tools = manager.list_available_tools().await;
+        assert!(tools.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_sync_servers_skips_unreachable_servers() {
+        let manager = McpServerManager::new();
+
+        let config = McpServerConfig::Stdio(crate::config::StdioTransport {
+            name: "invalid_server".to_string(),
+            command: "nonexistent_command_12345".to_string(),
+            args: vec![],
+            env: vec![],
+            cwd: None,
+        });
 
-        // Convert MCP response to string result
-        self.process_tool_call_response(&response_content)
+        let tools_changed = manager.sync_servers(vec![config]).await.unwrap();
+        assert!(!tools_changed);
+        assert!(manager.connections.read().await.is_empty());
+
+        let tools_changed = manager.sync_servers(vec![]).await.unwrap();
+        assert!(!tools_changed);
     }
 
-    /// Send a tool call request to an MCP server
-    async fn send_tool_call_to_server(
-        &self,
-        connection: &McpServerConnection,
-        tool_call: &InternalToolRequest,
-    ) -> crate::Result<Value> {
-        // Create MCP tool call request
+    #[tokio::test]
+    async fn test_mcp_manager_connect_invalid_server() {
+        let mut manager = McpServerManager::new();
+
+        let invalid_config = McpServerConfig::Stdio(crate::config::StdioTransport {
+            name: "invalid_server".to_string(),
+            command: "nonexistent_command_12345".to_string(),
+            args: vec![],
+            env: vec![],
+            cwd: None,
+        });
+
+        // Should succeed but log errors for individual server failures
+        let result = manager.connect_servers(vec![invalid_config]).await;
+        assert!(result.is_ok());
+
+        // No tools should be available since server failed to start
+        let tools = manager.list_available_tools().await;
+        assert!(tools.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_tool_name_extraction() {
+        let _manager = McpServerManager::new();
+
+        let tool_call = InternalToolRequest {
+            id: "test-123".to_string(),
+            name: "server:read_file".to_string(),
+            arguments: json!({}),
+        };
+
+        // Test that we extract the tool name correctly in the request
         let mcp_request = json!({
             "jsonrpc": "2.0",
-            "id": tool_call.id.clone(),
+            "id": tool_call.id,
             "method": "tools/call",
             "params": {
                 "name": tool_call.name.split(':').nth(1).unwrap_or(&tool_call.name),
@@ -1045,361 +2943,693 @@ impl McpServerManager {
             }
         });
 
-        tracing::info!(
-            "Sending tool call to MCP server {}: {}",
-            connection.name,
-            tool_call.name
+        assert_eq!(mcp_request["params"]["name"].as_str().unwrap(), "read_file");
+    }
+
+    #[test]
+    fn test_extract_tools_from_list_response() {
+        let manager = McpServerManager::new();
+
+        let response = json!({
+            "jsonrpc": "2.0",
+            "id": 2,
+            "result": {
+                "tools": [
+                    {
+                        "name": "read_file",
+                        "description": "Read a file"
+                    },
+                    {
+                        "name": "write_file",
+                        "description": "Write a file"
+                    }
+                ]
+            }
+        });
+
+        let tools = manager.extract_tools_from_list_response(&response).unwrap();
+        assert_eq!(tools.len(), 2);
+        assert_eq!(tools[0].name, "read_file");
+        assert_eq!(tools[0].description.as_deref(), Some("Read a file"));
+        assert_eq!(tools[1].name, "write_file");
+    }
+
+    #[test]
+    fn test_parse_tool_list_keeps_schema_and_annotations() {
+        let response = json!({
+            "result": {
+                "tools": [
+                    {
+                        "name": "query",
+                        "title": "Run query",
+                        "inputSchema": {
+                            "type": "object",
+                            "properties": {"sql": {"type": "string"}, "limit": {"type": "integer"}},
+                            "required": ["sql"]
+                        },
+                        "annotations": {"readOnlyHint": true, "openWorldHint": false}
+                    },
+                    {"name": "broken", "annotations": "not an object"},
+                    {"description": "no name"}
+                ]
+            }
+        });
+
+        let tools = parse_tool_list(&response);
+        assert_eq!(tools.len(), 2);
+
+        let query = &tools[0];
+        assert_eq!(query.title.as_deref(), Some("Run query"));
+        let annotations = query.annotations.as_ref().unwrap();
+        assert_eq!(annotations.read_only_hint, Some(true));
+        assert_eq!(annotations.destructive_hint, None);
+        assert_eq!(query.input_hint().as_deref(), Some("sql, limit?"));
+
+        assert_eq!(tools[1], McpTool::named("broken"));
+        assert_eq!(tools[1].input_hint(), None);
+    }
+
+    #[test]
+    fn test_prompt_arguments_from_text() {
+        let prompt: McpPrompt = serde_json::from_value(json!({
+            "name": "review",
+            "arguments": [
+                {"name": "language", "required": true},
+                {"name": "focus", "description": "What to look at"}
+            ]
+        }))
+        .unwrap();
+        assert_eq!(prompt.input_hint().as_deref(), Some("language focus?"));
+
+        // The last argument takes the rest of the text
+        assert_eq!(
+            prompt
+                .arguments_from_text("  rust   error handling and tests ")
+                .unwrap(),
+            json!({"language": "rust", "focus": "error handling and tests"})
+        );
+        assert_eq!(
+            prompt.arguments_from_text("go").unwrap(),
+            json!({"language": "go"})
         );
 
-        match &connection.transport {
-            TransportConnection::Stdio {
-                stdin_writer,
-                stdout_reader,
-                ..
-            } => {
-                // Get writer and reader
-                let mut writer_guard = stdin_writer.write().await;
-                let writer = writer_guard.as_mut().ok_or(McpError::StdinNotAvailable)?;
+        let error = prompt.arguments_from_text("   ").unwrap_err();
+        assert!(matches!(error, McpError::InvalidPromptArguments(ref name, _) if name == "review"));
+        assert!(error.to_string().contains("language"));
 
-                let mut reader_guard = stdout_reader.write().await;
-                let reader = reader_guard.as_mut().ok_or(McpError::StdoutNotAvailable)?;
+        let bare: McpPrompt = serde_json::from_value(json!({"name": "summary"})).unwrap();
+        assert_eq!(bare.input_hint(), None);
+        assert_eq!(bare.arguments_from_text("ignored").unwrap(), json!({}));
+    }
 
-                // Send request
-                let request_line = format!("{}\n", mcp_request);
-                writer
-                    .write_all(request_line.as_bytes())
-                    .await
-                    .map_err(McpError::IoError)?;
-                writer.flush().await.map_err(McpError::IoError)?;
+    #[tokio::test]
+    async fn test_tool_arguments_are_validated_before_sending() {
+        let tool: McpTool = serde_json::from_value(json!({
+            "name": "read_file",
+            "description": "Read a file",
+            "inputSchema": {
+                "type": "object",
+                "properties": {"path": {"type": "string"}},
+                "required": ["path"]
+            }
+        }))
+        .unwrap();
+        assert!(tool.validate_arguments(&json!({"path": "/tmp/a"})).is_ok());
 
-                // Read response
-                let mut response_line = String::new();
-                let bytes_read = reader
-                    .read_line(&mut response_line)
-                    .await
-                    .map_err(McpError::IoError)?;
+        let manager = McpServerManager::new();
+        manager.connections.write().await.insert(
+            "files".to_string(),
+            McpServerConnection {
+                name: "files".to_string(),
+                tools: vec![tool],
+                resources: Vec::new(),
+                prompts: Vec::new(),
+                capabilities: json!({}),
+                config: McpServerConfig::Http(crate::config::HttpTransport {
+                    transport_type: "http".to_string(),
+                    name: "files".to_string(),
+                    url: "http://127.0.0.1:9/mcp".to_string(),
+                    headers: vec![],
+                }),
+                transport: TransportConnection::Http {
+                    client: Arc::new(Client::new()),
+                    url: "http://127.0.0.1:9/mcp".to_string(),
+                    headers: vec![],
+                    session_id: Arc::new(RwLock::new(None)),
+                    protocol_version: Arc::default(),
+                },
+                router: MessageRouter::new("files", NotificationHandler::default()),
+            },
+        );
 
-                if bytes_read == 0 {
-                    return Err(McpError::ConnectionClosed.into());
-                }
+        let definition = manager.tool_definition("files:read_file").await.unwrap();
+        assert_eq!(definition.description.as_deref(), Some("Read a file"));
+        assert!(manager
+            .tool_definition("mcp__files__read_file")
+            .await
+            .is_some());
+        assert!(manager.tool_definition("files:missing").await.is_none());
+        assert_eq!(
+            manager.list_tool_definitions().await[0].0,
+            "files:read_file"
+        );
+
+        let request = InternalToolRequest {
+            id: "call-1".to_string(),
+            name: "files:read_file".to_string(),
+            arguments: json!({"path": 42}),
+        };
+        let error = manager
+            .execute_tool_call("files", &request)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            crate::AgentError::Mcp(McpError::InvalidToolArguments(..))
+        ));
+        assert!(error
+            .to_string()
+            .contains("/path: 42 is not of type \"string\""));
+    }
+
+    #[test]
+    fn test_sse_decoder_splits_events() {
+        let mut decoder = SseDecoder::default();
+        assert!(decoder
+            .feed(b": keep-alive\n\nevent: message\ndata: {\"a\"")
+            .is_empty());
+        let events = decoder.feed(b":1}\r\n\ndata: first\ndata:second\n\ndata: \xc3");
+        let data: Vec<&str> = events.iter().map(|event| event.data.as_str()).collect();
+        assert_eq!(data, ["{\"a\":1}", "first\nsecond"]);
+        assert!(events.iter().all(SseEvent::is_message));
+        // A character split across chunks is decoded once the line is complete
+        assert!(decoder.feed(b"\xa9").is_empty());
+        assert_eq!(decoder.feed(b"\n").len(), 0);
+        assert_eq!(
+            decoder.finish().map(|event| event.data),
+            Some("\u{e9}".to_string())
+        );
+        assert_eq!(decoder.finish(), None);
+    }
 
-                let response: Value = serde_json::from_str(response_line.trim())
-                    .map_err(McpError::SerializationFailed)?;
+    #[test]
+    fn test_sse_decoder_tracks_event_ids_types_and_retry() {
+        let mut decoder = SseDecoder::default();
+        let events = decoder.feed(
+            b"event: endpoint\ndata: /messages?session=1\n\n\
+              id: 7\nretry: 2500\ndata: {}\n\n\
+              event: message\n\n\
+              id\nretry: soon\ndata: {}\n\n",
+        );
 
-                Ok(response)
+        assert_eq!(
+            events[0],
+            SseEvent {
+                event: Some("endpoint".to_string()),
+                data: "/messages?session=1".to_string(),
             }
-            TransportConnection::Http {
-                client,
-                url,
-                session_id,
-                ..
-            } => {
-                // Send HTTP request with session ID if available
-                let mut request = client
-                    .post(url)
-                    .header("Accept", "application/json, text/event-stream")
-                    .header("Content-Type", "application/json");
-
-                // Include session ID if present
-                if let Some(session_id_value) = session_id.read().await.as_ref() {
-                    request = request.header("Mcp-Session-Id", session_id_value);
-                }
+        );
+        assert!(!events[0].is_message());
+        // Events without data are not dispatched, and their type is dropped with them
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[2].event, None);
+        // An empty id resets the last event id, an invalid retry is ignored
+        assert_eq!(decoder.last_event_id, None);
+        assert_eq!(decoder.retry, Some(Duration::from_millis(2500)));
+
+        decoder.feed(b"id: 8\ndata: {}\n\n");
+        assert_eq!(decoder.last_event_id.as_deref(), Some("8"));
+    }
 
-                let response = request.json(&mcp_request).send().await.map_err(|e| {
-                    crate::AgentError::ToolExecution(format!(
-                        "Failed to send HTTP tool call request to MCP server: {}",
-                        e
-                    ))
-                })?;
+    #[tokio::test]
+    async fn test_router_dispatches_responses_notifications_and_requests() {
+        let router = MessageRouter::new("test", NotificationHandler::default());
+        let (id, response) = router.register();
+        let (progress_tx, mut progress_rx) = mpsc::unbounded_channel();
+        router.watch_progress(&id, progress_tx);
 
-                let response_json: Value = response.json().await.map_err(|e| {
-                    McpError::ProtocolError(format!(
-                        "Failed to parse HTTP tool call response from MCP server: {}",
-                        e
-                    ))
-                })?;
+        let progress = json!({
+            "jsonrpc": "2.0",
+            "method": "notifications/progress",
+            "params": {"progressToken": id, "progress": 5, "total": 10, "message": "Indexing"}
+        });
+        assert!(router.dispatch(progress).is_none());
+        assert_eq!(
+            progress_rx.recv().await,
+            Some(McpProgress {
+                progress: 5.0,
+                total: Some(10.0),
+                message: Some("Indexing".to_string()),
+            })
+        );
 
-                Ok(response_json)
-            }
-            TransportConnection::Sse { url, headers, .. } => {
-                // Create HTTP client with headers
-                let mut header_map = reqwest::header::HeaderMap::new();
-                for header in headers {
-                    if let (Ok(name), Ok(value)) = (
-                        reqwest::header::HeaderName::from_bytes(header.name.as_bytes()),
-                        reqwest::header::HeaderValue::from_str(&header.value),
-                    ) {
-                        header_map.insert(name, value);
-                    }
-                }
+        let log = json!({
+            "jsonrpc": "2.0",
+            "method": "notifications/message",
+            "params": {"level": "warning", "data": {"disk": "full"}}
+        });
+        assert!(router.dispatch(log).is_none());
 
-                let client = Client::builder()
-                    .default_headers(header_map)
-                    .build()
-                    .map_err(|e| {
-                        crate::AgentError::ToolExecution(format!(
-                            "Failed to create HTTP client for SSE tool call: {}",
-                            e
-                        ))
-                    })?;
+        let replies = router.dispatch_text(
+            r#"[{"jsonrpc":"2.0","id":"s1","method":"ping"},
+                {"jsonrpc":"2.0","id":"s2","method":"sampling/createMessage"}]"#,
+        );
+        assert_eq!(
+            replies[0],
+            json!({"jsonrpc": "2.0", "id": "s1", "result": {}})
+        );
+        assert_eq!(
+            replies[1]["error"]["code"],
+            crate::json_rpc_codes::METHOD_NOT_FOUND
+        );
 
-                // Send tool call request via POST
-                let response = client
-                    .post(url)
-                    .header("Accept", "text/event-stream")
-                    .header("Content-Type", "application/json")
-                    .json(&mcp_request)
-                    .send()
-                    .await
-                    .map_err(|e| {
-                        crate::AgentError::ToolExecution(format!(
-                            "Failed to send SSE tool call request to MCP server: {}",
-                            e
-                        ))
-                    })?;
+        assert!(router.is_pending(&id));
+        router.dispatch(json!({"jsonrpc": "2.0", "id": id, "result": {"ok": true}}));
+        assert!(!router.is_pending(&id));
+        assert_eq!(response.await.unwrap()["result"]["ok"], true);
 
-                if !response.status().is_success() {
-                    return Err(crate::AgentError::ToolExecution(format!(
-                        "SSE tool call request failed with status: {}",
-                        response.status()
-                    )));
-                }
+        // Closing fails the requests still waiting
+        let (_, response) = router.register();
+        router.close();
+        assert!(router.is_closed());
+        assert!(response.await.is_err());
+    }
 
-                // Parse SSE response
-                let body = response.text().await.map_err(|e| {
-                    crate::AgentError::ToolExecution(format!(
-                        "Failed to read SSE tool call response from MCP server: {}",
-                        e
-                    ))
-                })?;
+    /// Stdio server that reports progress, pings the client and changes its tools
+    /// while answering a tool call
+    #[cfg(unix)]
+    const NOTIFYING_SERVER: &str = r#"
+        listed=0
+        while IFS= read -r line; do
+          id=$(printf '%s' "$line" | sed -n 's/^{"id":\([0-9]*\),.*/\1/p')
+          case "$line" in
+            *'"method":"initialize"'*)
+              printf '{"jsonrpc":"2.0","method":"notifications/message","params":{"level":"info","data":"starting"}}\n'
+              printf '{"jsonrpc":"2.0","id":%s,"result":{"capabilities":{"tools":{"listChanged":true}}}}\n' "$id" ;;
+            *'"method":"tools/list"'*)
+              listed=$((listed + 1))
+              if [ "$listed" = 1 ]; then tools='[{"name":"slow"}]'; else tools='[{"name":"slow"},{"name":"fast"}]'; fi
+              printf '{"jsonrpc":"2.0","id":%s,"result":{"tools":%s}}\n' "$id" "$tools" ;;
+            *'"method":"tools/call"'*)
+              printf '{"jsonrpc":"2.0","method":"notifications/progress","params":{"progressToken":%s,"progress":1,"total":2,"message":"halfway"}}\n' "$id"
+              printf '{"jsonrpc":"2.0","id":"server-1","method":"ping"}\n'
+              printf '{"jsonrpc":"2.0","method":"notifications/tools/list_changed"}\n'
+              printf '{"jsonrpc":"2.0","id":%s,"result":{"content":[{"type":"text","text":"done"}]}}\n' "$id" ;;
+            *'"id":"server-1"'*)
+              echo "pong received" >&2 ;;
+          esac
+        done
+    "#;
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_stdio_server_notifications_are_routed() {
+        let mut manager = McpServerManager::new();
+        let mut tool_changes = manager.subscribe_command_changes();
+        manager
+            .connect_servers(vec![McpServerConfig::Stdio(
+                crate::config::StdioTransport {
+                    name: "notifier".to_string(),
+                    command: "sh".to_string(),
+                    args: vec!["-c".to_string(), NOTIFYING_SERVER.to_string()],
+                    env: vec![],
+                    cwd: None,
+                },
+            )])
+            .await
+            .unwrap();
+        assert_eq!(manager.list_available_tools().await, ["notifier:slow"]);
 
-                let response_json = Self::parse_sse_response(&body)?;
-                Ok(response_json)
-            }
-        }
+        let (progress_tx, mut progress_rx) = mpsc::unbounded_channel();
+        let request = InternalToolRequest {
+            id: "call-1".to_string(),
+            name: "notifier:slow".to_string(),
+            arguments: json!({}),
+        };
+        let result = manager
+            .execute_tool_call_with_progress("notifier", &request, Some(progress_tx))
+            .await
+            .unwrap();
+        assert_eq!(result, "done");
+        assert_eq!(
+            progress_rx.recv().await,
+            Some(McpProgress {
+                progress: 1.0,
+                total: Some(2.0),
+                message: Some("halfway".to_string()),
+            })
+        );
+        // The listener is dropped once the call is answered
+        assert_eq!(progress_rx.recv().await, None);
+
+        tokio::time::timeout(std::time::Duration::from_secs(5), tool_changes.changed())
+            .await
+            .expect("Tool list should be refreshed")
+            .unwrap();
+        let mut tools = manager.list_available_tools().await;
+        tools.sort();
+        assert_eq!(tools, ["notifier:fast", "notifier:slow"]);
+
+        manager.shutdown().await.unwrap();
     }
 
-    /// Process MCP tool call response into string result
-    fn process_tool_call_response(&self, response: &Value) -> crate::Result<String> {
-        if let Some(result) = response.get("result") {
-            // Try to extract content array first (standard MCP format)
-            if let Some(content) = result.get("content") {
-                if let Some(content_array) = content.as_array() {
-                    let mut result_text = String::new();
-                    for item in content_array {
-                        if let Some(text) = item.get("text").and_then(|t| t.as_str()) {
-                            result_text.push_str(text);
-                            result_text.push('\n');
-                        }
-                    }
-                    return Ok(result_text.trim().to_string());
-                }
-            }
+    /// Stdio server with two pages of resources, a prompt, and resource subscriptions
+    #[cfg(unix)]
+    const CATALOG_SERVER: &str = r#"
+        while IFS= read -r line; do
+          id=$(printf '%s' "$line" | sed -n 's/^{"id":\([0-9]*\),.*/\1/p')
+          case "$line" in
+            *'"method":"initialize"'*)
+              printf '{"jsonrpc":"2.0","id":%s,"result":{"capabilities":{"tools":{},"resources":{"subscribe":true},"prompts":{}}}}\n' "$id" ;;
+            *'"method":"tools/list"'*)
+              printf '{"jsonrpc":"2.0","id":%s,"result":{"tools":[]}}\n' "$id" ;;
+            *'"cursor":"page-2"'*)
+              printf '{"jsonrpc":"2.0","id":%s,"result":{"resources":[{"uri":"file:///notes/b.md","name":"b.md"}]}}\n' "$id" ;;
+            *'"method":"resources/list"'*)
+              printf '{"jsonrpc":"2.0","id":%s,"result":{"resources":[{"uri":"file:///notes/a.md","name":"a.md","mimeType":"text/markdown"},{"name":"no uri"}],"nextCursor":"page-2"}}\n' "$id" ;;
+            *'"method":"resources/read"'*)
+              printf '{"jsonrpc":"2.0","id":%s,"result":{"contents":[{"uri":"file:///notes/a.md","mimeType":"text/markdown","text":"Note A"}]}}\n' "$id" ;;
+            *'"method":"resources/subscribe"'*)
+              printf '{"jsonrpc":"2.0","id":%s,"result":{}}\n' "$id"
+              printf '{"jsonrpc":"2.0","method":"notifications/resources/updated","params":{"uri":"file:///notes/a.md"}}\n' ;;
+            *'"method":"prompts/list"'*)
+              printf '{"jsonrpc":"2.0","id":%s,"result":{"prompts":[{"name":"review","description":"Review code","arguments":[{"name":"language","required":true}]}]}}\n' "$id" ;;
+            *'"method":"prompts/get"'*)
+              printf '{"jsonrpc":"2.0","id":%s,"result":{"messages":[{"role":"user","content":{"type":"text","text":"Review this rust code"}}]}}\n' "$id" ;;
+          esac
+        done
+    "#;
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_stdio_server_resources_and_prompts() {
+        let mut manager = McpServerManager::new();
+        manager
+            .connect_servers(vec![McpServerConfig::Stdio(
+                crate::config::StdioTransport {
+                    name: "notes".to_string(),
+                    command: "sh".to_string(),
+                    args: vec!["-c".to_string(), CATALOG_SERVER.to_string()],
+                    env: vec![],
+                    cwd: None,
+                },
+            )])
+            .await
+            .unwrap();
 
-            // Fallback: handle different result types intelligently
-            match result {
-                Value::String(s) => {
-                    tracing::debug!("MCP response result is a simple string");
-                    Ok(s.clone())
-                }
-                Value::Number(n) => {
-                    tracing::debug!("MCP response result is a number");
-                    Ok(n.to_string())
-                }
-                Value::Bool(b) => {
-                    tracing::debug!("MCP response result is a boolean");
-                    Ok(b.to_string())
-                }
-                Value::Null => {
-                    tracing::debug!("MCP response result is null");
-                    Ok(String::new())
-                }
-                Value::Object(_) | Value::Array(_) => {
-                    tracing::debug!("MCP response result is a complex type, formatting as JSON");
-                    serde_json::to_string_pretty(result).map_err(|e| {
-                        crate::AgentError::ToolExecution(format!(
-                            "Failed to serialize MCP result to JSON: {}",
-                            e
-                        ))
-                    })
-                }
-            }
-        } else if let Some(error) = response.get("error") {
-            Err(McpError::ServerError(error.clone()).into())
-        } else {
-            Err(McpError::MissingResult.into())
-        }
+        // Both pages are listed and the entry without a uri is skipped
+        let resources = manager.list_resources().await;
+        let uris: Vec<&str> = resources
+            .iter()
+            .map(|(_, resource)| resource.uri.as_str())
+            .collect();
+        assert_eq!(uris, ["file:///notes/a.md", "file:///notes/b.md"]);
+        assert_eq!(resources[0].1.mime_type.as_deref(), Some("text/markdown"));
+        assert_eq!(
+            manager
+                .resource_server("file:///notes/b.md")
+                .await
+                .as_deref(),
+            Some("notes")
+        );
+        assert_eq!(manager.resource_server("file:///elsewhere").await, None);
+
+        let contents = manager.read_resource("file:///notes/a.md").await.unwrap();
+        assert_eq!(contents[0]["text"], "Note A");
+        assert!(matches!(
+            manager.read_resource("file:///elsewhere").await,
+            Err(crate::AgentError::Mcp(McpError::ResourceNotFound(_)))
+        ));
+
+        let mut updates = manager.subscribe_resource_updates();
+        manager
+            .subscribe_resource("file:///notes/a.md")
+            .await
+            .unwrap();
+        let updated = tokio::time::timeout(std::time::Duration::from_secs(5), updates.recv())
+            .await
+            .expect("Resource update should arrive")
+            .unwrap();
+        assert_eq!(updated, "file:///notes/a.md");
+
+        let prompts = manager.list_prompt_definitions().await;
+        assert_eq!(prompts.len(), 1);
+        assert_eq!(prompts[0].0, "notes:review");
+        assert_eq!(
+            manager.prompt_definition("notes:review").await,
+            Some(prompts[0].1.clone())
+        );
+        let messages = manager
+            .get_prompt("notes:review", json!({"language": "rust"}))
+            .await
+            .unwrap();
+        assert_eq!(messages[0]["content"]["text"], "Review this rust code");
+
+        manager.shutdown().await.unwrap();
     }
 
-    /// List all available tools from all connected MCP servers
-    pub async fn list_available_tools(&self) -> Vec<String> {
-        let connections = self.connections.read().await;
-        let mut all_tools = Vec::new();
+    #[test]
+    fn test_reconnect_backoff_doubles_up_to_the_maximum() {
+        let options = McpHealthOptions {
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(3),
+            ..McpHealthOptions::default()
+        };
+        let delays: Vec<u128> = (1..=5)
+            .map(|attempt| options.backoff(attempt).as_millis())
+            .collect();
+        assert_eq!(delays, [500, 1000, 2000, 3000, 3000]);
+        assert_eq!(options.backoff(u32::MAX), Duration::from_secs(3));
+    }
+
+    /// Stdio server whose `crash` tool ends its process, whose `pid` tool answers with
+    /// its process id and whose `slow` tool takes a second, during which it answers
+    /// nothing else; it refuses to start again once `$MARKER` exists, if set
+    #[cfg(unix)]
+    const CRASHING_SERVER: &str = r#"
+        if [ -n "$MARKER" ]; then
+          if [ -e "$MARKER" ]; then exit 1; fi
+          touch "$MARKER"
+        fi
+        while IFS= read -r line; do
+          id=$(printf '%s' "$line" | sed -n 's/^{"id":\([0-9]*\),.*/\1/p')
+          case "$line" in
+            *'"method":"initialize"'*)
+              printf '{"jsonrpc":"2.0","id":%s,"result":{"capabilities":{"tools":{}}}}\n' "$id" ;;
+            *'"method":"tools/list"'*)
+              printf '{"jsonrpc":"2.0","id":%s,"result":{"tools":[{"name":"crash"},{"name":"pid"}]}}\n' "$id" ;;
+            *'"method":"ping"'*)
+              printf '{"jsonrpc":"2.0","id":%s,"result":{}}\n' "$id" ;;
+            *'"name":"crash"'*)
+              exit 1 ;;
+            *'"name":"slow"'*)
+              sleep 1
+              printf '{"jsonrpc":"2.0","id":%s,"result":{"content":[{"type":"text","text":"done"}]}}\n' "$id" ;;
+            *'"method":"tools/call"'*)
+              printf '{"jsonrpc":"2.0","id":%s,"result":{"content":[{"type":"text","text":"%s"}]}}\n' "$id" "$$" ;;
+          esac
+        done
+    "#;
+
+    #[cfg(unix)]
+    fn crashing_server_config(marker: Option<&std::path::Path>) -> McpServerConfig {
+        McpServerConfig::Stdio(crate::config::StdioTransport {
+            name: "flaky".to_string(),
+            command: "sh".to_string(),
+            args: vec!["-c".to_string(), CRASHING_SERVER.to_string()],
+            env: marker
+                .map(|marker| crate::config::EnvVariable {
+                    name: "MARKER".to_string(),
+                    value: marker.display().to_string(),
+                })
+                .into_iter()
+                .collect(),
+            cwd: None,
+        })
+    }
 
-        for connection in connections.values() {
-            for tool in &connection.tools {
-                all_tools.push(format!("{}:{}", connection.name, tool));
-            }
+    #[cfg(unix)]
+    fn fast_health_options() -> McpHealthOptions {
+        McpHealthOptions {
+            ping_interval: Duration::from_millis(50),
+            ping_timeout: Duration::from_secs(2),
+            initial_backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_millis(40),
+            max_reconnect_attempts: 3,
         }
-
-        all_tools
     }
 
-    /// Shutdown all MCP server connections
-    pub async fn shutdown(&self) -> crate::Result<()> {
-        let mut connections = self.connections.write().await;
+    #[cfg(unix)]
+    fn tool_request(name: &str) -> InternalToolRequest {
+        InternalToolRequest {
+            id: "call-1".to_string(),
+            name: format!("flaky:{}", name),
+            arguments: json!({}),
+        }
+    }
 
-        for (name, connection) in connections.iter_mut() {
-            tracing::info!("Shutting down MCP server: {}", name);
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_crashed_stdio_server_is_reconnected() {
+        let mut manager = McpServerManager::with_health_options(fast_health_options());
+        manager
+            .connect_servers(vec![crashing_server_config(None)])
+            .await
+            .unwrap();
+        let statuses = manager.server_statuses().await;
+        assert_eq!(statuses[0].state, McpServerState::Connected);
+        assert_eq!(statuses[0].transport, "stdio");
+        assert_eq!(statuses[0].tools, 2);
 
-            match &connection.transport {
-                TransportConnection::Stdio {
-                    process,
-                    stdin_writer,
-                    stdout_reader,
-                } => {
-                    // Close stdio handles first
-                    {
-                        let mut writer_guard = stdin_writer.write().await;
-                        *writer_guard = None;
-                    }
-                    {
-                        let mut reader_guard = stdout_reader.write().await;
-                        *reader_guard = None;
-                    }
+        let first_pid = manager
+            .execute_tool_call("flaky", &tool_request("pid"))
+            .await
+            .unwrap();
+        assert!(manager
+            .execute_tool_call("flaky", &tool_request("crash"))
+            .await
+            .is_err());
 
-                    // Kill and wait for the process
-                    let mut process_guard = process.write().await;
-                    if let Some(mut proc) = process_guard.take() {
-                        let _ = proc.kill().await;
-                        let _ = proc.wait().await;
-                    }
-                }
-                TransportConnection::Http { .. } => {
-                    // HTTP connections don't need explicit cleanup
-                    tracing::debug!("HTTP MCP server connection closed: {}", name);
-                }
-                TransportConnection::Sse {
-                    message_tx,
-                    response_rx,
-                    ..
-                } => {
-                    // Close SSE channels
-                    {
-                        let mut tx_guard = message_tx.write().await;
-                        *tx_guard = None;
-                    }
-                    {
-                        let mut rx_guard = response_rx.write().await;
-                        *rx_guard = None;
-                    }
-                    tracing::debug!("SSE MCP server connection closed: {}", name);
+        // Calls fail until the server is back, then reach a new process
+        let second_pid = tokio::time::timeout(Duration::from_secs(10), async {
+            loop {
+                match manager
+                    .execute_tool_call("flaky", &tool_request("pid"))
+                    .await
+                {
+                    Ok(pid) => break pid,
+                    Err(_) => tokio::time::sleep(Duration::from_millis(20)).await,
                 }
             }
-        }
+        })
+        .await
+        .expect("Server should be reconnected");
+        assert_ne!(first_pid, second_pid);
 
-        connections.clear();
-        Ok(())
-    }
-}
+        let statuses = manager.server_statuses().await;
+        assert_eq!(statuses[0].state, McpServerState::Connected);
+        assert_eq!(statuses[0].reconnect_attempts, 0);
+        assert!(statuses[0].last_error.is_some());
 
-impl Default for McpServerManager {
-    fn default() -> Self {
-        Self::new()
+        manager.shutdown().await.unwrap();
+        assert!(manager.server_statuses().await.is_empty());
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
 
+    #[cfg(unix)]
     #[tokio::test]
-    async fn test_mcp_manager_creation() {
-        let manager = McpServerManager::new();
-        let tools = manager.list_available_tools().await;
-        assert!(tools.is_empty());
-    }
+    async fn test_server_that_cannot_restart_is_given_up() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let marker = temp_dir.path().join("started");
+        let mut manager = McpServerManager::with_health_options(fast_health_options());
+        let mut command_changes = manager.subscribe_command_changes();
+        manager
+            .connect_servers(vec![crashing_server_config(Some(&marker))])
+            .await
+            .unwrap();
+        assert_eq!(manager.list_available_tools().await.len(), 2);
 
-    #[tokio::test]
-    async fn test_mcp_manager_connect_empty_servers() {
-        let mut manager = McpServerManager::new();
-        let result = manager.connect_servers(vec![]).await;
-        assert!(result.is_ok());
+        assert!(manager
+            .execute_tool_call("flaky", &tool_request("crash"))
+            .await
+            .is_err());
 
-        let tools = manager.list_available_tools().await;
-        assert!(tools.is_empty());
+        tokio::time::timeout(Duration::from_secs(10), command_changes.changed())
+            .await
+            .expect("Lost tools should be reported")
+            .unwrap();
+        assert!(manager.list_available_tools().await.is_empty());
+        let statuses = manager.server_statuses().await;
+        assert_eq!(statuses[0].state, McpServerState::Failed);
+        assert_eq!(statuses[0].reconnect_attempts, 3);
+        assert_eq!(statuses[0].tools, 0);
+        assert!(statuses[0].last_error.is_some());
+        assert_eq!(
+            serde_json::to_value(&statuses[0]).unwrap()["state"],
+            "failed"
+        );
     }
 
+    #[cfg(unix)]
     #[tokio::test]
-    async fn test_mcp_manager_connect_invalid_server() {
-        let mut manager = McpServerManager::new();
-
-        let invalid_config = McpServerConfig::Stdio(crate::config::StdioTransport {
-            name: "invalid_server".to_string(),
-            command: "nonexistent_command_12345".to_string(),
-            args: vec![],
-            env: vec![],
-            cwd: None,
-        });
+    async fn test_server_that_fails_its_first_connection_is_retried() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let marker = temp_dir.path().join("failed-once");
+        let mut manager = McpServerManager::with_health_options(fast_health_options());
+        let mut command_changes = manager.subscribe_command_changes();
+        manager
+            .connect_servers(vec![McpServerConfig::Stdio(
+                crate::config::StdioTransport {
+                    name: "flaky".to_string(),
+                    command: "sh".to_string(),
+                    args: vec![
+                        "-c".to_string(),
+                        format!(
+                            r#"if [ ! -e "$FAILED" ]; then touch "$FAILED"; exit 1; fi {}"#,
+                            CRASHING_SERVER
+                        ),
+                    ],
+                    env: vec![crate::config::EnvVariable {
+                        name: "FAILED".to_string(),
+                        value: marker.display().to_string(),
+                    }],
+                    cwd: None,
+                },
+            )])
+            .await
+            .unwrap();
+        let statuses = manager.server_statuses().await;
+        assert_eq!(statuses[0].state, McpServerState::Failed);
+        assert!(manager.list_available_tools().await.is_empty());
 
-        // Should succeed but log errors for individual server failures
-        let result = manager.connect_servers(vec![invalid_config]).await;
-        assert!(result.is_ok());
+        tokio::time::timeout(Duration::from_secs(10), command_changes.changed())
+            .await
+            .expect("Tools of the retried server should be reported")
+            .unwrap();
+        assert_eq!(manager.list_available_tools().await.len(), 2);
+        let statuses = manager.server_statuses().await;
+        assert_eq!(statuses[0].state, McpServerState::Connected);
+        assert_eq!(statuses[0].reconnect_attempts, 0);
+        assert!(manager
+            .execute_tool_call("flaky", &tool_request("pid"))
+            .await
+            .is_ok());
 
-        // No tools should be available since server failed to start
-        let tools = manager.list_available_tools().await;
-        assert!(tools.is_empty());
+        manager.shutdown().await.unwrap();
     }
 
+    #[cfg(unix)]
     #[tokio::test]
-    async fn test_tool_name_extraction() {
-        let _manager = McpServerManager::new();
-
-        let tool_call = InternalToolRequest {
-            id: "test-123".to_string(),
-            name: "server:read_file".to_string(),
-            arguments: json!({}),
-        };
-
-        // Test that we extract the tool name correctly in the request
-        let mcp_request = json!({
-            "jsonrpc": "2.0",
-            "id": tool_call.id,
-            "method": "tools/call",
-            "params": {
-                "name": tool_call.name.split(':').nth(1).unwrap_or(&tool_call.name),
-                "arguments": tool_call.arguments
-            }
+    async fn test_busy_server_is_not_pinged() {
+        let mut manager = McpServerManager::with_health_options(McpHealthOptions {
+            ping_timeout: Duration::from_millis(100),
+            ..fast_health_options()
         });
+        manager
+            .connect_servers(vec![crashing_server_config(None)])
+            .await
+            .unwrap();
+        let first_pid = manager
+            .execute_tool_call("flaky", &tool_request("pid"))
+            .await
+            .unwrap();
 
-        assert_eq!(mcp_request["params"]["name"].as_str().unwrap(), "read_file");
-    }
-
-    #[test]
-    fn test_extract_tools_from_list_response() {
-        let manager = McpServerManager::new();
+        // The server can't answer pings during the call, which outlasts the ping timeout
+        let result = manager
+            .execute_tool_call("flaky", &tool_request("slow"))
+            .await
+            .unwrap();
+        assert_eq!(result, "done");
 
-        let response = json!({
-            "jsonrpc": "2.0",
-            "id": 2,
-            "result": {
-                "tools": [
-                    {
-                        "name": "read_file",
-                        "description": "Read a file"
-                    },
-                    {
-                        "name": "write_file",
-                        "description": "Write a file"
-                    }
-                ]
-            }
-        });
+        let second_pid = manager
+            .execute_tool_call("flaky", &tool_request("pid"))
+            .await
+            .unwrap();
+        assert_eq!(first_pid, second_pid);
+        let statuses = manager.server_statuses().await;
+        assert_eq!(statuses[0].state, McpServerState::Connected);
+        assert!(statuses[0].last_error.is_none());
 
-        let tools = manager.extract_tools_from_list_response(&response).unwrap();
-        assert_eq!(tools.len(), 2);
-        assert!(tools.contains(&"read_file".to_string()));
-        assert!(tools.contains(&"write_file".to_string()));
+        manager.shutdown().await.unwrap();
     }
 
     #[test]
@@ -1902,6 +4132,7 @@ mod tests {
                 url: "http://localhost:8080".to_string(),
                 headers: vec![],
                 session_id: session_id.clone(),
+                protocol_version: Arc::default(),
             };
 
             match transport {
@@ -1914,4 +4145,443 @@ mod tests {
             }
         });
     }
+
+    /// A request received by a stand-in HTTP server
+    #[derive(Debug, Clone)]
+    struct ReceivedRequest {
+        method: String,
+        target: String,
+        headers: HashMap<String, String>,
+        body: String,
+    }
+
+    impl ReceivedRequest {
+        fn header(&self, name: &str) -> Option<&str> {
+            self.headers.get(&name.to_lowercase()).map(String::as_str)
+        }
+
+        fn message(&self) -> Value {
+            serde_json::from_str(&self.body).unwrap_or_default()
+        }
+    }
+
+    type RequestLog = Arc<std::sync::Mutex<Vec<ReceivedRequest>>>;
+
+    /// Serve HTTP on a local port, one request per connection, and return its base URL
+    ///
+    /// Every request is recorded in `requests` before `respond` writes the response.
+    async fn serve_http<F, R>(requests: RequestLog, respond: F) -> String
+    where
+        F: Fn(ReceivedRequest, tokio::net::TcpStream) -> R + Send + Sync + 'static,
+        R: std::future::Future<Output = ()> + Send + 'static,
+    {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let respond = Arc::new(respond);
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let requests = Arc::clone(&requests);
+                let respond = Arc::clone(&respond);
+                tokio::spawn(async move {
+                    if let Some(request) = read_request(&mut stream).await {
+                        lock(&requests).push(request.clone());
+                        respond(request, stream).await;
+                    }
+                });
+            }
+        });
+        format!("http://{}", address)
+    }
+
+    async fn read_request(stream: &mut tokio::net::TcpStream) -> Option<ReceivedRequest> {
+        use tokio::io::AsyncReadExt;
+
+        let mut reader = BufReader::new(stream);
+        let mut line = String::new();
+        reader.read_line(&mut line).await.ok()?;
+        let mut parts = line.split_whitespace();
+        let method = parts.next()?.to_string();
+        let target = parts.next()?.to_string();
+
+        let mut headers = HashMap::new();
+        loop {
+            line.clear();
+            reader.read_line(&mut line).await.ok()?;
+            match line.trim_end().split_once(':') {
+                Some((name, value)) => {
+                    headers.insert(name.to_lowercase(), value.trim().to_string());
+                }
+                None => break,
+            }
+        }
+
+        let length = headers
+            .get("content-length")
+            .and_then(|length| length.parse().ok())
+            .unwrap_or(0);
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).await.ok()?;
+        Some(ReceivedRequest {
+            method,
+            target,
+            headers,
+            body: String::from_utf8(body).ok()?,
+        })
+    }
+
+    async fn write_response(
+        stream: &mut tokio::net::TcpStream,
+        status: &str,
+        headers: &[(&str, &str)],
+        body: &str,
+    ) {
+        let mut response = format!(
+            "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+            status,
+            body.len()
+        );
+        for (name, value) in headers {
+            response.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        response.push_str("\r\n");
+        response.push_str(body);
+        let _ = stream.write_all(response.as_bytes()).await;
+    }
+
+    /// Start an event stream response, which ends when the connection is closed
+    async fn start_event_stream(stream: &mut tokio::net::TcpStream) {
+        let _ = stream
+            .write_all(
+                b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nConnection: close\r\n\r\n",
+            )
+            .await;
+    }
+
+    async fn write_event(stream: &mut tokio::net::TcpStream, id: Option<&str>, message: &Value) {
+        let id = id.map(|id| format!("id: {}\n", id)).unwrap_or_default();
+        let _ = stream
+            .write_all(format!("{}data: {}\n\n", id, message).as_bytes())
+            .await;
+    }
+
+    /// Keep a response open until the client disconnects
+    async fn hold_open(stream: &mut tokio::net::TcpStream) {
+        use tokio::io::AsyncReadExt;
+
+        let mut buffer = [0; 64];
+        while matches!(stream.read(&mut buffer).await, Ok(read) if read > 0) {}
+    }
+
+    fn initialize_result(id: &Value) -> Value {
+        json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "result": {
+                "protocolVersion": "2025-03-26",
+                "capabilities": {"tools": {}},
+                "serverInfo": {"name": "stand-in", "version": "1.0"}
+            }
+        })
+    }
+
+    fn tools_result(id: &Value) -> Value {
+        json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "result": {"tools": [{"name": "echo", "inputSchema": {"type": "object"}}]}
+        })
+    }
+
+    fn call_result(id: &Value) -> Value {
+        json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "result": {"content": [{"type": "text", "text": "echoed"}]}
+        })
+    }
+
+    /// Wait until a request matching `matches` was received
+    async fn wait_for_request(
+        requests: &RequestLog,
+        matches: impl Fn(&ReceivedRequest) -> bool,
+    ) -> ReceivedRequest {
+        for _ in 0..250 {
+            if let Some(request) = lock(requests).iter().find(|request| matches(request)) {
+                return request.clone();
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("Expected request was not received");
+    }
+
+    /// Streamable HTTP server answering in JSON and in event streams
+    ///
+    /// The response to `tools/call` breaks off after its first event, so the client
+    /// has to resume the stream. The GET stream pings the client.
+    async fn streamable_http_server(
+        requests: RequestLog,
+        request: ReceivedRequest,
+        mut stream: tokio::net::TcpStream,
+    ) {
+        let message = request.message();
+        let id = &message["id"];
+        match (request.method.as_str(), message["method"].as_str()) {
+            ("POST", Some("initialize")) => {
+                let body = initialize_result(id).to_string();
+                let headers = [
+                    ("Content-Type", "application/json"),
+                    ("Mcp-Session-Id", "session-1"),
+                ];
+                write_response(&mut stream, "200 OK", &headers, &body).await;
+            }
+            ("POST", Some("tools/list")) => {
+                start_event_stream(&mut stream).await;
+                let log = json!({
+                    "jsonrpc": "2.0",
+                    "method": "notifications/message",
+                    "params": {"level": "info", "data": "listing"}
+                });
+                write_event(&mut stream, None, &log).await;
+                write_event(&mut stream, Some("1"), &tools_result(id)).await;
+            }
+            ("POST", Some("tools/call")) => {
+                start_event_stream(&mut stream).await;
+                let log = json!({
+                    "jsonrpc": "2.0",
+                    "method": "notifications/message",
+                    "params": {"level": "info", "data": "calling"}
+                });
+                write_event(&mut stream, Some("5"), &log).await;
+            }
+            ("GET", _) if request.header("Last-Event-ID") == Some("5") => {
+                let call = lock(&requests)
+                    .iter()
+                    .map(ReceivedRequest::message)
+                    .find(|message| message["method"] == "tools/call")
+                    .unwrap();
+                start_event_stream(&mut stream).await;
+                write_event(&mut stream, Some("6"), &call_result(&call["id"])).await;
+            }
+            ("GET", _) => {
+                start_event_stream(&mut stream).await;
+                let ping = json!({"jsonrpc": "2.0", "id": "server-1", "method": "ping"});
+                write_event(&mut stream, None, &ping).await;
+                hold_open(&mut stream).await;
+            }
+            ("DELETE", _) => write_response(&mut stream, "200 OK", &[], "").await,
+            // Notifications and replies to the server's requests
+            _ => write_response(&mut stream, "202 Accepted", &[], "").await,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_streamable_http_server() {
+        let requests = RequestLog::default();
+        let server_requests = Arc::clone(&requests);
+        let url = serve_http(Arc::clone(&requests), move |request, stream| {
+            streamable_http_server(Arc::clone(&server_requests), request, stream)
+        })
+        .await;
+
+        let mut manager = McpServerManager::new();
+        manager
+            .connect_servers(vec![McpServerConfig::Http(crate::config::HttpTransport {
+                transport_type: "http".to_string(),
+                name: "remote".to_string(),
+                url: format!("{}/mcp", url),
+                headers: vec![crate::config::HttpHeader {
+                    name: "Authorization".to_string(),
+                    value: "Bearer token".to_string(),
+                }],
+            })])
+            .await
+            .unwrap();
+        let tools = manager.list_tool_definitions().await;
+        assert_eq!(tools[0].0, "remote:echo");
+
+        // The broken response stream is resumed after the last event received
+        let result = manager
+            .execute_tool_call(
+                "remote",
+                &InternalToolRequest {
+                    id: "call-1".to_string(),
+                    name: "remote:echo".to_string(),
+                    arguments: json!({}),
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(result, "echoed");
+
+        // The server's ping on the GET stream is answered within the session
+        let reply =
+            wait_for_request(&requests, |request| request.message()["id"] == "server-1").await;
+        assert_eq!(reply.message()["result"], json!({}));
+
+        manager.shutdown().await.unwrap();
+        wait_for_request(&requests, |request| request.method == "DELETE").await;
+
+        let requests = lock(&requests).clone();
+        let initialize = &requests[0];
+        assert_eq!(initialize.message()["method"], "initialize");
+        assert_eq!(
+            initialize.header("Accept"),
+            Some("application/json, text/event-stream")
+        );
+        assert_eq!(initialize.header("Mcp-Session-Id"), None);
+        for request in &requests {
+            assert_eq!(request.target, "/mcp");
+            assert_eq!(request.header("Authorization"), Some("Bearer token"));
+        }
+        for request in &requests[1..] {
+            assert_eq!(request.header("Mcp-Session-Id"), Some("session-1"));
+            assert_eq!(request.header("MCP-Protocol-Version"), Some("2025-03-26"));
+        }
+        assert!(requests
+            .iter()
+            .any(|request| request.method == "GET" && request.header("Last-Event-ID").is_none()));
+    }
+
+    #[tokio::test]
+    async fn test_streamable_http_session_expiry_closes_the_connection() {
+        let requests = RequestLog::default();
+        let server_requests = Arc::clone(&requests);
+        let url = serve_http(Arc::clone(&requests), move |request, mut stream| {
+            let requests = Arc::clone(&server_requests);
+            async move {
+                if request.message()["method"] == "tools/call" {
+                    write_response(&mut stream, "404 Not Found", &[], "").await;
+                } else {
+                    streamable_http_server(requests, request, stream).await;
+                }
+            }
+        })
+        .await;
+
+        let mut manager = McpServerManager::new();
+        manager
+            .connect_servers(vec![McpServerConfig::Http(crate::config::HttpTransport {
+                transport_type: "http".to_string(),
+                name: "remote".to_string(),
+                url: format!("{}/mcp", url),
+                headers: vec![],
+            })])
+            .await
+            .unwrap();
+
+        let error = manager
+            .execute_tool_call(
+                "remote",
+                &InternalToolRequest {
+                    id: "call-1".to_string(),
+                    name: "remote:echo".to_string(),
+                    arguments: json!({}),
+                },
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            crate::AgentError::Mcp(McpError::ConnectionClosed)
+        ));
+        let connections = manager.connections.read().await;
+        assert!(connections["remote"].router.is_closed());
+    }
+
+    /// Legacy HTTP+SSE server, answering the messages POSTed to its endpoint on the
+    /// event stream
+    async fn legacy_sse_server(
+        events: Arc<std::sync::Mutex<Option<mpsc::UnboundedSender<Value>>>>,
+        request: ReceivedRequest,
+        mut stream: tokio::net::TcpStream,
+    ) {
+        if request.method == "GET" {
+            let (events_tx, mut events_rx) = mpsc::unbounded_channel();
+            *lock(&events) = Some(events_tx);
+            start_event_stream(&mut stream).await;
+            let _ = stream
+                .write_all(b"event: endpoint\ndata: /messages?session=abc\n\n")
+                .await;
+            while let Some(message) = events_rx.recv().await {
+                write_event(&mut stream, None, &message).await;
+            }
+            return;
+        }
+
+        let message = request.message();
+        let id = &message["id"];
+        let answer = match message["method"].as_str() {
+            Some("initialize") => Some(initialize_result(id)),
+            Some("tools/list") => Some(tools_result(id)),
+            Some("tools/call") => Some(call_result(id)),
+            _ => None,
+        };
+        write_response(&mut stream, "202 Accepted", &[], "").await;
+        if let (Some(answer), Some(events)) = (answer, lock(&events).as_ref()) {
+            let _ = events.send(answer);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_legacy_sse_server() {
+        let requests = RequestLog::default();
+        let events = Arc::default();
+        let url = serve_http(Arc::clone(&requests), move |request, stream| {
+            legacy_sse_server(Arc::clone(&events), request, stream)
+        })
+        .await;
+
+        let mut manager = McpServerManager::new();
+        manager
+            .connect_servers(vec![McpServerConfig::Sse(crate::config::SseTransport {
+                transport_type: "sse".to_string(),
+                name: "legacy".to_string(),
+                url: format!("{}/sse", url),
+                headers: vec![],
+            })])
+            .await
+            .unwrap();
+        {
+            let connections = manager.connections.read().await;
+            match &connections["legacy"].transport {
+                TransportConnection::Sse { endpoint, .. } => {
+                    assert_eq!(endpoint, &format!("{}/messages?session=abc", url))
+                }
+                _ => panic!("Expected SSE transport"),
+            }
+        }
+
+        let result = manager
+            .execute_tool_call(
+                "legacy",
+                &InternalToolRequest {
+                    id: "call-1".to_string(),
+                    name: "legacy:echo".to_string(),
+                    arguments: json!({}),
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(result, "echoed");
+
+        let requests = lock(&requests).clone();
+        assert_eq!(requests[0].method, "GET");
+        assert_eq!(requests[0].target, "/sse");
+        assert_eq!(requests[0].header("Accept"), Some("text/event-stream"));
+        let posted: Vec<Value> = requests[1..]
+            .iter()
+            .inspect(|request| assert_eq!(request.target, "/messages?session=abc"))
+            .map(|request| request.message()["method"].clone())
+            .collect();
+        assert_eq!(
+            posted,
+            [
+                "initialize",
+                "notifications/initialized",
+                "tools/list",
+                "tools/call"
+            ]
+        );
+        manager.shutdown().await.unwrap();
+    }
 }
@@ -5,7 +5,10 @@
 
 use crate::{
     config::McpServerConfig,
-    mcp::{McpServerConnection, TransportConnection},
+    mcp::{
+        parse_tool_list, McpServerConnection, McpTool, MessageRouter, NotificationHandler,
+        TransportConnection,
+    },
     session_errors::{SessionSetupError, SessionSetupResult},
     session_validation::validate_mcp_server_config,
 };
@@ -18,7 +21,7 @@ use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader, BufWriter};
 use tokio::process::Command;
-use tokio::sync::{mpsc, RwLock};
+use tokio::sync::RwLock;
 use tokio::time::timeout;
 
 /// Enhanced MCP server connection manager with comprehensive error handling
@@ -278,17 +281,18 @@ impl EnhancedMcpServerManager {
             }
         };
 
-        let transport = TransportConnection::Stdio {
-            process: Arc::new(RwLock::new(Some(child))),
-            stdin_writer: Arc::new(RwLock::new(Some(stdin_writer))),
-            stdout_reader: Arc::new(RwLock::new(Some(stdout_reader))),
-        };
+        let router = MessageRouter::new(&stdio_config.name, NotificationHandler::default());
+        let transport = TransportConnection::stdio(child, stdin_writer, stdout_reader, &router);
 
         let connection = McpServerConnection {
             name: stdio_config.name.clone(),
             tools,
+            resources: Vec::new(),
+            prompts: Vec::new(),
+            capabilities: json!({}),
             config,
             transport,
+            router,
         };
 
         let connection_time = start_time.elapsed();
@@ -323,59 +327,26 @@ impl EnhancedMcpServerManager {
             }
         })?;
 
-        // Build HTTP client with headers
-        let mut headers = reqwest::header::HeaderMap::new();
-        for header in &http_config.headers {
-            let name =
-                reqwest::header::HeaderName::from_bytes(header.name.as_bytes()).map_err(|_| {
-                    SessionSetupError::McpServerConnectionFailed {
-                        server_name: http_config.name.clone(),
-                        error: format!("Invalid header name: {}", header.name),
-                        transport_type: "http".to_string(),
-                    }
-                })?;
-
-            let value = reqwest::header::HeaderValue::from_str(&header.value).map_err(|_| {
-                SessionSetupError::McpServerConnectionFailed {
-                    server_name: http_config.name.clone(),
-                    error: format!("Invalid header value: {}", header.value),
-                    transport_type: "http".to_string(),
-                }
-            })?;
-
-            headers.insert(name, value);
-        }
-
-        let client = Client::builder()
-            .timeout(Duration::from_millis(self.connection_timeout_ms))
-            .default_headers(headers)
-            .build()
-            .map_err(|e| SessionSetupError::McpServerConnectionFailed {
-                server_name: http_config.name.clone(),
-                error: format!("Failed to create HTTP client: {}", e),
-                transport_type: "http".to_string(),
-            })?;
-
-        // Test connection and initialize protocol
-        let session_id = Arc::new(RwLock::new(None));
-        let tools = self
-            .initialize_http_mcp_protocol_enhanced(&client, http_config, Arc::clone(&session_id))
-            .await?;
-
-        let transport = TransportConnection::Http {
-            client: Arc::new(client),
-            url: parsed_url.to_string(),
-            headers: http_config.headers.clone(),
-            session_id,
-        };
-
-        let connection = McpServerConnection {
+        let client = self.http_client_enhanced(&http_config.name, &http_config.headers, "http")?;
+        let transport =
+            TransportConnection::http(client, parsed_url.as_str(), http_config.headers.clone());
+        let router = MessageRouter::new(&http_config.name, NotificationHandler::default());
+        let mut connection = McpServerConnection {
             name: http_config.name.clone(),
-            tools,
+            tools: Vec::new(),
+            resources: Vec::new(),
+            prompts: Vec::new(),
+            capabilities: json!({}),
             config,
             transport,
+            router,
         };
 
+        // Test connection and initialize protocol
+        connection.tools = self
+            .initialize_remote_mcp_protocol_enhanced(&connection, "http")
+            .await?;
+
         Ok(connection)
     }
 
@@ -399,39 +370,92 @@ impl EnhancedMcpServerManager {
             transport_type: "sse".to_string(),
         })?;
 
-        // Create SSE connection channels
-        let (message_tx, _message_rx) = mpsc::unbounded_channel();
-        let (response_tx, response_rx) = mpsc::unbounded_channel();
-
-        // Initialize SSE connection
-        let tools = self
-            .initialize_sse_mcp_protocol_enhanced(sse_config, response_tx)
-            .await?;
-
-        let transport = TransportConnection::Sse {
-            url: sse_config.url.clone(),
-            headers: sse_config.headers.clone(),
-            message_tx: Arc::new(RwLock::new(Some(message_tx))),
-            response_rx: Arc::new(RwLock::new(Some(response_rx))),
-        };
+        // Open the event stream; server events are routed from then on
+        let client = self.http_client_enhanced(&sse_config.name, &sse_config.headers, "sse")?;
+        let router = MessageRouter::new(&sse_config.name, NotificationHandler::default());
+        let transport = timeout(
+            Duration::from_millis(self.connection_timeout_ms),
+            TransportConnection::sse(client, &sse_config.url, sse_config.headers.clone(), &router),
+        )
+        .await
+        .map_err(|_| SessionSetupError::McpServerConnectionFailed {
+            server_name: sse_config.name.clone(),
+            error: format!(
+                "No endpoint event received within {}ms",
+                self.connection_timeout_ms
+            ),
+            transport_type: "sse".to_string(),
+        })?
+        .map_err(|e| SessionSetupError::McpServerConnectionFailed {
+            server_name: sse_config.name.clone(),
+            error: e.to_string(),
+            transport_type: "sse".to_string(),
+        })?;
 
-        let connection = McpServerConnection {
+        let mut connection = McpServerConnection {
             name: sse_config.name.clone(),
-            tools,
+            tools: Vec::new(),
+            resources: Vec::new(),
+            prompts: Vec::new(),
+            capabilities: json!({}),
             config,
             transport,
+            router,
         };
 
+        match self
+            .initialize_remote_mcp_protocol_enhanced(&connection, "sse")
+            .await
+        {
+            Ok(tools) => connection.tools = tools,
+            Err(e) => {
+                // Stops the event stream
+                connection.router.close();
+                return Err(e);
+            }
+        }
+
         Ok(connection)
     }
 
+    /// Build the HTTP client for a server, rejecting headers that can't be sent
+    fn http_client_enhanced(
+        &self,
+        server_name: &str,
+        headers: &[crate::config::HttpHeader],
+        transport_type: &str,
+    ) -> SessionSetupResult<Client> {
+        let connection_failed = |error: String| SessionSetupError::McpServerConnectionFailed {
+            server_name: server_name.to_string(),
+            error,
+            transport_type: transport_type.to_string(),
+        };
+
+        let mut header_map = reqwest::header::HeaderMap::new();
+        for header in headers {
+            let name = reqwest::header::HeaderName::from_bytes(header.name.as_bytes())
+                .map_err(|_| connection_failed(format!("Invalid header name: {}", header.name)))?;
+            let value = reqwest::header::HeaderValue::from_str(&header.value).map_err(|_| {
+                connection_failed(format!("Invalid header value: {}", header.value))
+            })?;
+            header_map.insert(name, value);
+        }
+
+        // Event streams stay open, so only connecting is limited in time
+        Client::builder()
+            .connect_timeout(Duration::from_millis(self.connection_timeout_ms))
+            .default_headers(header_map)
+            .build()
+            .map_err(|e| connection_failed(format!("Failed to create HTTP client: {}", e)))
+    }
+
     /// Initialize MCP protocol with comprehensive error handling
     async fn initialize_mcp_protocol_enhanced(
         &self,
         writer: &mut BufWriter<tokio::process::ChildStdin>,
         reader: &mut BufReader<tokio::process::ChildStdout>,
         server_name: &str,
-    ) -> SessionSetupResult<Vec<String>> {
+    ) -> SessionSetupResult<Vec<McpTool>> {
         // Send initialize request
         let initialize_request = json!({
             "jsonrpc": "2.0",
@@ -577,665 +601,63 @@ impl EnhancedMcpServerManager {
                 }
             })?;
 
-        // Extract tool names from response
-        let tools = if let Some(result) = tools_response.get("result") {
-            if let Some(tools_array) = result.get("tools").and_then(|t| t.as_array()) {
-                tools_array
-                    .iter()
-                    .filter_map(|tool| tool.get("name").and_then(|name| name.as_str()))
-                    .map(|name| name.to_string())
-                    .collect()
-            } else {
-                Vec::new()
-            }
-        } else {
-            Vec::new()
-        };
+        // Extract tool definitions from response
+        let tools = parse_tool_list(&tools_response);
 
         tracing::info!("MCP server {} reported {} tools", server_name, tools.len());
         Ok(tools)
     }
 
-    /// Initialize HTTP MCP protocol with comprehensive error handling
-    async fn initialize_http_mcp_protocol_enhanced(
+    /// Initialize MCP protocol on an HTTP or SSE connection with comprehensive error handling
+    ///
+    /// The connection's transport takes care of sessions and of responses streamed as
+    /// server-sent events. Returns the tools available from the server.
+    async fn initialize_remote_mcp_protocol_enhanced(
         &self,
-        client: &Client,
-        http_config: &crate::config::HttpTransport,
-        session_id: Arc<RwLock<Option<String>>>,
-    ) -> SessionSetupResult<Vec<String>> {
-        tracing::info!("Initializing HTTP MCP protocol for {}", http_config.name);
-
-        // Step 1: Send initialize request
-        let initialize_request = json!({
-            "jsonrpc": "2.0",
-            "id": 1,
-            "method": "initialize",
-            "params": {
-                "protocolVersion": "2024-11-05",
-                "capabilities": {
-                    "roots": {
-                        "listChanged": true
-                    },
-                    "sampling": {}
-                },
-                "clientInfo": {
-                    "name": "claude-agent",
-                    "version": env!("CARGO_PKG_VERSION")
-                }
-            }
-        });
-
-        let response = client
-            .post(&http_config.url)
-            .header("Accept", "application/json, text/event-stream")
-            .header("Content-Type", "application/json")
-            .json(&initialize_request)
-            .send()
-            .await
-            .map_err(|e| SessionSetupError::McpServerConnectionFailed {
-                server_name: http_config.name.clone(),
-                error: format!("Failed to send initialize request: {}", e),
-                transport_type: "http".to_string(),
-            })?;
-
-        if !response.status().is_success() {
-            return Err(SessionSetupError::McpServerConnectionFailed {
-                server_name: http_config.name.clone(),
-                error: format!(
-                    "Initialize request failed with status: {}",
-                    response.status()
-                ),
-                transport_type: "http".to_string(),
-            });
-        }
-
-        // Extract session ID if present
-        if let Some(session_id_header) = response.headers().get("Mcp-Session-Id") {
-            if let Ok(session_id_str) = session_id_header.to_str() {
-                let mut session_id_write = session_id.write().await;
-                *session_id_write = Some(session_id_str.to_string());
-                tracing::debug!("Stored session ID: {}", session_id_str);
-            }
-        }
-
-        // Parse response body
-        let content_type = response
-            .headers()
-            .get("Content-Type")
-            .and_then(|v| v.to_str().ok())
-            .unwrap_or("application/json");
-
-        let initialize_response: Value = if content_type.contains("text/event-stream") {
-            // Handle SSE response
-            let body = response.text().await.map_err(|e| {
-                SessionSetupError::McpServerConnectionFailed {
-                    server_name: http_config.name.clone(),
-                    error: format!("Failed to read SSE response: {}", e),
-                    transport_type: "http".to_string(),
-                }
-            })?;
-
-            // Parse SSE format - look for data: lines
-            let mut json_data = String::new();
-            for line in body.lines() {
-                if let Some(data) = line.strip_prefix("data: ") {
-                    json_data = data.to_string();
-                    break;
-                }
-            }
-
-            if json_data.is_empty() {
-                return Err(SessionSetupError::McpServerProtocolNegotiationFailed {
-                    server_name: http_config.name.clone(),
-                    expected_version: "2024-11-05".to_string(),
-                    actual_version: Some("No data in SSE response".to_string()),
-                });
-            }
-
-            serde_json::from_str(&json_data).map_err(|e| {
-                SessionSetupError::McpServerProtocolNegotiationFailed {
-                    server_name: http_config.name.clone(),
-                    expected_version: "2024-11-05".to_string(),
-                    actual_version: Some(format!("Invalid JSON in SSE: {}", e)),
-                }
-            })?
-        } else {
-            // Handle JSON response
-            response
-                .json()
-                .await
-                .map_err(|e| SessionSetupError::McpServerConnectionFailed {
-                    server_name: http_config.name.clone(),
-                    error: format!("Failed to parse initialize response: {}", e),
-                    transport_type: "http".to_string(),
-                })?
+        connection: &McpServerConnection,
+        transport_type: &str,
+    ) -> SessionSetupResult<Vec<McpTool>> {
+        tracing::info!(
+            "Initializing {} MCP protocol for {}",
+            transport_type.to_uppercase(),
+            connection.name
+        );
+        let protocol_timeout = Duration::from_millis(self.protocol_timeout_ms);
+        let connection_failed = |error: String| SessionSetupError::McpServerConnectionFailed {
+            server_name: connection.name.clone(),
+            error,
+            transport_type: transport_type.to_string(),
         };
 
-        // Validate initialize response
-        if let Some(error) = initialize_response.get("error") {
-            return Err(SessionSetupError::McpServerProtocolNegotiationFailed {
-                server_name: http_config.name.clone(),
-                expected_version: "2024-11-05".to_string(),
-                actual_version: Some(format!("Server error: {}", error)),
-            });
-        }
-
-        // Step 2: Send initialized notification
-        let initialized_notification = json!({
-            "jsonrpc": "2.0",
-            "method": "notifications/initialized"
-        });
-
-        let mut notify_request = client
-            .post(&http_config.url)
-            .header("Accept", "application/json, text/event-stream")
-            .header("Content-Type", "application/json");
-
-        // Include session ID if present
-        if let Some(session_id_value) = session_id.read().await.as_ref() {
-            notify_request = notify_request.header("Mcp-Session-Id", session_id_value);
-        }
-
-        let notif_response = notify_request
-            .json(&initialized_notification)
-            .send()
+        timeout(protocol_timeout, connection.initialize())
             .await
-            .map_err(|e| SessionSetupError::McpServerConnectionFailed {
-                server_name: http_config.name.clone(),
-                error: format!("Failed to send initialized notification: {}", e),
-                transport_type: "http".to_string(),
-            })?;
-
-        // Expect 202 Accepted for notification
-        if notif_response.status() != reqwest::StatusCode::ACCEPTED {
-            tracing::warn!(
-                "Initialized notification returned status: {}",
-                notif_response.status()
-            );
-        }
-
-        // Step 3: Request tools list
-        let tools_request = json!({
-            "jsonrpc": "2.0",
-            "id": 2,
-            "method": "tools/list"
-        });
-
-        let mut tools_request_builder = client
-            .post(&http_config.url)
-            .header("Accept", "application/json, text/event-stream")
-            .header("Content-Type", "application/json");
-
-        // Include session ID if present
-        if let Some(session_id_value) = session_id.read().await.as_ref() {
-            tools_request_builder =
-                tools_request_builder.header("Mcp-Session-Id", session_id_value);
-        }
+            .map_err(|_| {
+                connection_failed(format!(
+                    "Initialize request timed out after {}ms",
+                    self.protocol_timeout_ms
+                ))
+            })?
+            .map_err(|e| connection_failed(format!("Initialization failed: {}", e)))?;
 
-        let tools_response = tools_request_builder
-            .json(&tools_request)
-            .send()
+        let tools_response = timeout(protocol_timeout, connection.request("tools/list", None))
             .await
-            .map_err(|e| SessionSetupError::McpServerConnectionFailed {
-                server_name: http_config.name.clone(),
-                error: format!("Failed to send tools/list request: {}", e),
-                transport_type: "http".to_string(),
-            })?;
-
-        if !tools_response.status().is_success() {
-            return Err(SessionSetupError::McpServerConnectionFailed {
-                server_name: http_config.name.clone(),
-                error: format!(
-                    "Tools list request failed with status: {}",
-                    tools_response.status()
-                ),
-                transport_type: "http".to_string(),
-            });
-        }
-
-        // Parse tools response
-        let tools_content_type = tools_response
-            .headers()
-            .get("Content-Type")
-            .and_then(|v| v.to_str().ok())
-            .unwrap_or("application/json");
-
-        let tools_result: Value = if tools_content_type.contains("text/event-stream") {
-            // Handle SSE response
-            let body = tools_response.text().await.map_err(|e| {
-                SessionSetupError::McpServerConnectionFailed {
-                    server_name: http_config.name.clone(),
-                    error: format!("Failed to read tools SSE response: {}", e),
-                    transport_type: "http".to_string(),
-                }
-            })?;
-
-            // Parse SSE format - look for data: lines
-            let mut json_data = String::new();
-            for line in body.lines() {
-                if let Some(data) = line.strip_prefix("data: ") {
-                    json_data = data.to_string();
-                    break;
-                }
-            }
-
-            if json_data.is_empty() {
-                return Err(SessionSetupError::McpServerConnectionFailed {
-                    server_name: http_config.name.clone(),
-                    error: "No data in tools SSE response".to_string(),
-                    transport_type: "http".to_string(),
-                });
-            }
-
-            serde_json::from_str(&json_data).map_err(|e| {
-                SessionSetupError::McpServerConnectionFailed {
-                    server_name: http_config.name.clone(),
-                    error: format!("Invalid JSON in tools SSE: {}", e),
-                    transport_type: "http".to_string(),
-                }
+            .map_err(|_| {
+                connection_failed(format!(
+                    "Tools list request timed out after {}ms",
+                    self.protocol_timeout_ms
+                ))
             })?
-        } else {
-            // Handle JSON response
-            tools_response.json().await.map_err(|e| {
-                SessionSetupError::McpServerConnectionFailed {
-                    server_name: http_config.name.clone(),
-                    error: format!("Failed to parse tools response: {}", e),
-                    transport_type: "http".to_string(),
-                }
-            })?
-        };
-
-        // Extract tool names from response
-        let tools = if let Some(result) = tools_result.get("result") {
-            if let Some(tools_array) = result.get("tools").and_then(|t| t.as_array()) {
-                tools_array
-                    .iter()
-                    .filter_map(|tool| tool.get("name").and_then(|name| name.as_str()))
-                    .map(|name| name.to_string())
-                    .collect()
-            } else {
-                Vec::new()
-            }
-        } else {
-            Vec::new()
-        };
+            .map_err(|e| connection_failed(format!("Failed to list tools: {}", e)))?;
 
+        let tools =
+            self.extract_tools_from_list_response_enhanced(&connection.name, &tools_response)?;
         tracing::info!(
             "MCP server {} reported {} tools",
-            http_config.name,
+            connection.name,
             tools.len()
         );
-        Ok(tools)
-    }
-
-    /// Initialize SSE MCP protocol with comprehensive error handling
-    ///
-    /// SSE transport uses a persistent GET connection for receiving server events
-    /// and POST requests for sending client requests (initialize, tools/call, etc).
-    ///
-    /// # Arguments
-    /// * `sse_config` - SSE transport configuration including URL and headers
-    /// * `response_tx` - Channel for sending responses from the event stream
-    ///
-    /// # Returns
-    /// List of available tool names from the MCP server
-    ///
-    /// # Errors
-    /// Returns SessionSetupError if:
-    /// - Connection fails
-    /// - Server returns non-success status
-    /// - Response parsing fails
-    /// - Protocol negotiation fails
-    async fn initialize_sse_mcp_protocol_enhanced(
-        &self,
-        sse_config: &crate::config::SseTransport,
-        response_tx: mpsc::UnboundedSender<String>,
-    ) -> SessionSetupResult<Vec<String>> {
-        tracing::info!("Initializing SSE MCP protocol for {}", sse_config.name);
-
-        // Create HTTP client with headers
-        let mut headers = reqwest::header::HeaderMap::new();
-        for header in &sse_config.headers {
-            if let (Ok(name), Ok(value)) = (
-                reqwest::header::HeaderName::from_bytes(header.name.as_bytes()),
-                reqwest::header::HeaderValue::from_str(&header.value),
-            ) {
-                headers.insert(name, value);
-            }
-        }
-
-        let client = Client::builder()
-            .default_headers(headers.clone())
-            .build()
-            .map_err(|e| SessionSetupError::McpServerConnectionFailed {
-                server_name: sse_config.name.clone(),
-                error: format!("Failed to create HTTP client: {}", e),
-                transport_type: "sse".to_string(),
-            })?;
 
-        // Step 1: Send initialize request via POST
-        let initialize_request = json!({
-            "jsonrpc": "2.0",
-            "id": 1,
-            "method": "initialize",
-            "params": {
-                "protocolVersion": "2024-11-05",
-                "capabilities": {
-                    "tools": {}
-                },
-                "clientInfo": {
-                    "name": "claude-agent",
-                    "version": env!("CARGO_PKG_VERSION")
-                }
-            }
-        });
-
-        let response = timeout(
-            Duration::from_millis(self.protocol_timeout_ms),
-            client
-                .post(&sse_config.url)
-                .header("Accept", "text/event-stream")
-                .header("Content-Type", "application/json")
-                .json(&initialize_request)
-                .send(),
-        )
-        .await
-        .map_err(|_| SessionSetupError::McpServerConnectionFailed {
-            server_name: sse_config.name.clone(),
-            error: format!(
-                "Initialize request timed out after {}ms",
-                self.protocol_timeout_ms
-            ),
-            transport_type: "sse".to_string(),
-        })?
-        .map_err(|e| SessionSetupError::McpServerConnectionFailed {
-            server_name: sse_config.name.clone(),
-            error: format!("Failed to send initialize request: {}", e),
-            transport_type: "sse".to_string(),
-        })?;
-
-        if !response.status().is_success() {
-            return Err(SessionSetupError::McpServerConnectionFailed {
-                server_name: sse_config.name.clone(),
-                error: format!(
-                    "Initialize request failed with status: {}",
-                    response.status()
-                ),
-                transport_type: "sse".to_string(),
-            });
-        }
-
-        // Parse SSE response for initialize
-        let body =
-            response
-                .text()
-                .await
-                .map_err(|e| SessionSetupError::McpServerConnectionFailed {
-                    server_name: sse_config.name.clone(),
-                    error: format!("Failed to read SSE response: {}", e),
-                    transport_type: "sse".to_string(),
-                })?;
-
-        let initialize_response = Self::parse_sse_response_enhanced(&sse_config.name, &body)?;
-
-        // Validate initialize response
-        if let Some(error) = initialize_response.get("error") {
-            return Err(SessionSetupError::McpServerConnectionFailed {
-                server_name: sse_config.name.clone(),
-                error: format!("Server returned error: {}", error),
-                transport_type: "sse".to_string(),
-            });
-        }
-
-        // Step 2: Send initialized notification
-        let initialized_notification = json!({
-            "jsonrpc": "2.0",
-            "method": "initialized"
-        });
-
-        let notify_response = timeout(
-            Duration::from_millis(self.protocol_timeout_ms),
-            client
-                .post(&sse_config.url)
-                .header("Accept", "text/event-stream")
-                .header("Content-Type", "application/json")
-                .json(&initialized_notification)
-                .send(),
-        )
-        .await
-        .map_err(|_| SessionSetupError::McpServerConnectionFailed {
-            server_name: sse_config.name.clone(),
-            error: format!(
-                "Initialized notification timed out after {}ms",
-                self.protocol_timeout_ms
-            ),
-            transport_type: "sse".to_string(),
-        })?
-        .map_err(|e| SessionSetupError::McpServerConnectionFailed {
-            server_name: sse_config.name.clone(),
-            error: format!("Failed to send initialized notification: {}", e),
-            transport_type: "sse".to_string(),
-        })?;
-
-        if notify_response.status() != reqwest::StatusCode::ACCEPTED {
-            tracing::warn!(
-                "Initialized notification returned status: {} (expected 202 Accepted)",
-                notify_response.status()
-            );
-        }
-
-        // Step 3: Request list of available tools
-        let tools_list_request = json!({
-            "jsonrpc": "2.0",
-            "id": 2,
-            "method": "tools/list"
-        });
-
-        let tools_response = timeout(
-            Duration::from_millis(self.protocol_timeout_ms),
-            client
-                .post(&sse_config.url)
-                .header("Accept", "text/event-stream")
-                .header("Content-Type", "application/json")
-                .json(&tools_list_request)
-                .send(),
-        )
-        .await
-        .map_err(|_| SessionSetupError::McpServerConnectionFailed {
-            server_name: sse_config.name.clone(),
-            error: format!(
-                "Tools list request timed out after {}ms",
-                self.protocol_timeout_ms
-            ),
-            transport_type: "sse".to_string(),
-        })?
-        .map_err(|e| SessionSetupError::McpServerConnectionFailed {
-            server_name: sse_config.name.clone(),
-            error: format!("Failed to send tools/list request: {}", e),
-            transport_type: "sse".to_string(),
-        })?;
-
-        if !tools_response.status().is_success() {
-            return Err(SessionSetupError::McpServerConnectionFailed {
-                server_name: sse_config.name.clone(),
-                error: format!(
-                    "Tools list request failed with status: {}",
-                    tools_response.status()
-                ),
-                transport_type: "sse".to_string(),
-            });
-        }
-
-        let tools_body = tools_response.text().await.map_err(|e| {
-            SessionSetupError::McpServerConnectionFailed {
-                server_name: sse_config.name.clone(),
-                error: format!("Failed to read tools SSE response: {}", e),
-                transport_type: "sse".to_string(),
-            }
-        })?;
-
-        let tools_response_json = Self::parse_sse_response_enhanced(&sse_config.name, &tools_body)?;
-        let final_tools =
-            self.extract_tools_from_list_response_enhanced(&sse_config.name, &tools_response_json)?;
-
-        tracing::info!(
-            "SSE MCP server {} provides {} tools: {:?}",
-            sse_config.name,
-            final_tools.len(),
-            final_tools
-        );
-
-        // Spawn background task to maintain SSE event stream for server-initiated events
-        let event_url = sse_config.url.clone();
-        let event_client = client.clone();
-        let server_name = sse_config.name.clone();
-        tokio::spawn(async move {
-            if let Err(e) = Self::handle_sse_event_stream_enhanced(
-                &server_name,
-                event_client,
-                &event_url,
-                response_tx,
-            )
-            .await
-            {
-                tracing::error!("SSE event stream error for {}: {}", server_name, e);
-            }
-        });
-
-        Ok(final_tools)
-    }
-
-    /// Parse SSE response body to extract JSON data
-    ///
-    /// SSE format specification (https://html.spec.whatwg.org/multipage/server-sent-events.html):
-    /// - Lines starting with "data: " contain the actual message data
-    /// - Multiple data lines can be sent for a single event
-    /// - Empty line indicates end of event
-    ///
-    /// This implementation extracts only the first "data: " line for simplicity.
-    ///
-    /// # Example SSE stream format
-    /// ```text
-    /// data: {"jsonrpc":"2.0","id":1,"result":{"tools":[]}}
-    ///
-    /// data: {"jsonrpc":"2.0","method":"notification"}
-    ///
-    /// ```
-    fn parse_sse_response_enhanced(server_name: &str, body: &str) -> SessionSetupResult<Value> {
-        let mut json_data = String::new();
-        for line in body.lines() {
-            if let Some(data) = line.strip_prefix("data: ") {
-                json_data = data.to_string();
-                break;
-            }
-        }
-
-        if json_data.is_empty() {
-            return Err(SessionSetupError::McpServerConnectionFailed {
-                server_name: server_name.to_string(),
-                error: "No data in SSE response".to_string(),
-                transport_type: "sse".to_string(),
-            });
-        }
-
-        serde_json::from_str(&json_data).map_err(|e| SessionSetupError::McpServerConnectionFailed {
-            server_name: server_name.to_string(),
-            error: format!("Invalid JSON in SSE response: {}", e),
-            transport_type: "sse".to_string(),
-        })
-    }
-
-    /// Handle persistent SSE event stream for server-initiated events
-    ///
-    /// This runs in a background task and maintains a persistent connection to the SSE endpoint.
-    /// It buffers incoming bytes and processes complete SSE messages line by line.
-    ///
-    /// The buffer size is limited to prevent memory exhaustion attacks.
-    async fn handle_sse_event_stream_enhanced(
-        server_name: &str,
-        client: Client,
-        url: &str,
-        response_tx: mpsc::UnboundedSender<String>,
-    ) -> SessionSetupResult<()> {
-        use futures::StreamExt;
-
-        const MAX_BUFFER_SIZE: usize = 1024 * 1024; // 1MB limit
-
-        loop {
-            tracing::debug!(
-                "Establishing SSE event stream connection for {}",
-                server_name
-            );
-
-            let response = client
-                .get(url)
-                .header("Accept", "text/event-stream")
-                .send()
-                .await
-                .map_err(|e| SessionSetupError::McpServerConnectionFailed {
-                    server_name: server_name.to_string(),
-                    error: format!("Failed to establish SSE event stream: {}", e),
-                    transport_type: "sse".to_string(),
-                })?;
-
-            if !response.status().is_success() {
-                return Err(SessionSetupError::McpServerConnectionFailed {
-                    server_name: server_name.to_string(),
-                    error: format!("SSE event stream failed with status: {}", response.status()),
-                    transport_type: "sse".to_string(),
-                });
-            }
-
-            let mut bytes_stream = response.bytes_stream();
-            let mut buffer = String::new();
-
-            while let Some(chunk_result) = bytes_stream.next().await {
-                match chunk_result {
-                    Ok(chunk) => {
-                        let text = String::from_utf8_lossy(&chunk);
-
-                        // Prevent unbounded buffer growth
-                        if buffer.len() + text.len() > MAX_BUFFER_SIZE {
-                            tracing::error!(
-                                "SSE buffer exceeded maximum size for {}, resetting",
-                                server_name
-                            );
-                            buffer.clear();
-                            continue;
-                        }
-
-                        buffer.push_str(&text);
-
-                        // Process complete lines from buffer
-                        while let Some(newline_pos) = buffer.find('\n') {
-                            let line = buffer[..newline_pos].to_string();
-                            buffer = buffer[newline_pos + 1..].to_string();
-
-                            if let Some(data) = line.strip_prefix("data: ") {
-                                if response_tx.send(data.to_string()).is_err() {
-                                    tracing::warn!(
-                                        "SSE response channel closed for {}",
-                                        server_name
-                                    );
-                                    return Ok(());
-                                }
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        tracing::error!("Error reading SSE stream for {}: {}", server_name, e);
-                        break;
-                    }
-                }
-            }
-
-            // Connection closed, wait before reconnecting
-            tracing::info!(
-                "SSE connection closed for {}, reconnecting in 5 seconds",
-                server_name
-            );
-            tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
-        }
+        Ok(tools)
     }
 
     /// Extract tools from tools/list response
@@ -1243,20 +665,8 @@ impl EnhancedMcpServerManager {
         &self,
         server_name: &str,
         response: &Value,
-    ) -> SessionSetupResult<Vec<String>> {
-        let mut tools = Vec::new();
-
-        if let Some(result) = response.get("result") {
-            if let Some(tools_array) = result.get("tools") {
-                if let Some(tool_list) = tools_array.as_array() {
-                    for tool in tool_list {
-                        if let Some(name) = tool.get("name").and_then(|n| n.as_str()) {
-                            tools.push(name.to_string());
-                        }
-                    }
-                }
-            }
-        }
+    ) -> SessionSetupResult<Vec<McpTool>> {
+        let tools = parse_tool_list(response);
 
         // If no tools found, log warning but don't fail
         if tools.is_empty() {
@@ -1273,6 +683,7 @@ impl EnhancedMcpServerManager {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::mcp::SseDecoder;
 
     #[tokio::test]
     async fn test_enhanced_manager_creation() {
@@ -1347,47 +758,58 @@ mod tests {
 
     #[test]
     fn test_parse_sse_response_valid() {
-        let sse_body = "data: {\"jsonrpc\":\"2.0\",\"id\":1,\"result\":{\"tools\":[]}}\n\n";
-        let result = EnhancedMcpServerManager::parse_sse_response_enhanced("test_server", sse_body);
-        assert!(result.is_ok());
-        let json = result.unwrap();
+        let mut decoder = SseDecoder::default();
+        // The event is only complete at the blank line that ends it
+        assert!(decoder
+            .feed(b"data: {\"jsonrpc\":\"2.0\",\"id\":1,\"result\":{\"tools\":[]}}\n")
+            .is_empty());
+        let events = decoder.feed(b"\n");
+        assert_eq!(events.len(), 1);
+        assert!(events[0].is_message());
+        let json: Value = serde_json::from_str(&events[0].data).unwrap();
         assert_eq!(json.get("jsonrpc").and_then(|v| v.as_str()), Some("2.0"));
         assert_eq!(json.get("id").and_then(|v| v.as_i64()), Some(1));
     }
 
     #[test]
     fn test_parse_sse_response_multiple_data_lines() {
-        let sse_body = "data: {\"jsonrpc\":\"2.0\",\"id\":1}\ndata: {\"other\":\"data\"}\n\n";
-        let result = EnhancedMcpServerManager::parse_sse_response_enhanced("test_server", sse_body);
-        assert!(result.is_ok());
-        let json = result.unwrap();
-        // Should parse only the first data line
-        assert_eq!(json.get("jsonrpc").and_then(|v| v.as_str()), Some("2.0"));
-        assert!(json.get("other").is_none());
+        let mut decoder = SseDecoder::default();
+        let events =
+            decoder.feed(b"data: {\"jsonrpc\":\"2.0\",\"id\":1}\ndata: {\"other\":\"data\"}\n\n");
+        // Data lines of one event are joined, they are not separate messages
+        assert_eq!(events.len(), 1);
+        assert_eq!(
+            events[0].data,
+            "{\"jsonrpc\":\"2.0\",\"id\":1}\n{\"other\":\"data\"}"
+        );
+        assert!(serde_json::from_str::<Value>(&events[0].data).is_err());
     }
 
     #[test]
     fn test_parse_sse_response_no_data() {
-        let sse_body = "event: message\n\n";
-        let result = EnhancedMcpServerManager::parse_sse_response_enhanced("test_server", sse_body);
-        assert!(result.is_err());
-        if let Err(SessionSetupError::McpServerConnectionFailed { error, .. }) = result {
-            assert!(error.contains("No data in SSE response"));
-        } else {
-            panic!("Expected McpServerConnectionFailed error");
-        }
+        let mut decoder = SseDecoder::default();
+        assert!(decoder.feed(b"event: message\n\n").is_empty());
+        assert_eq!(decoder.finish(), None);
     }
 
     #[test]
     fn test_parse_sse_response_invalid_json() {
-        let sse_body = "data: not valid json\n\n";
-        let result = EnhancedMcpServerManager::parse_sse_response_enhanced("test_server", sse_body);
-        assert!(result.is_err());
-        if let Err(SessionSetupError::McpServerConnectionFailed { error, .. }) = result {
-            assert!(error.contains("Invalid JSON in SSE response"));
-        } else {
-            panic!("Expected McpServerConnectionFailed error");
-        }
+        let mut decoder = SseDecoder::default();
+        let events = decoder.feed(b"data: not valid json\n\n");
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].data, "not valid json");
+        assert!(serde_json::from_str::<Value>(&events[0].data).is_err());
+    }
+
+    #[test]
+    fn test_parse_sse_response_partial_event() {
+        let mut decoder = SseDecoder::default();
+        // A stream that ends inside an event still yields the data read so far
+        assert!(decoder
+            .feed(b"data: {\"jsonrpc\":\"2.0\",\"id\":2}\ndata: {\"a")
+            .is_empty());
+        let event = decoder.finish().unwrap();
+        assert_eq!(event.data, "{\"jsonrpc\":\"2.0\",\"id\":2}");
     }
 
     #[test]
@@ -1408,8 +830,8 @@ mod tests {
         assert!(result.is_ok());
         let tools = result.unwrap();
         assert_eq!(tools.len(), 2);
-        assert_eq!(tools[0], "tool1");
-        assert_eq!(tools[1], "tool2");
+        assert_eq!(tools[0].name, "tool1");
+        assert_eq!(tools[1].name, "tool2");
     }
 
     #[test]
@@ -1467,7 +889,7 @@ mod tests {
         let tools = result.unwrap();
         // Should skip the tool without a name
         assert_eq!(tools.len(), 2);
-        assert_eq!(tools[0], "tool1");
-        assert_eq!(tools[1], "tool2");
+        assert_eq!(tools[0].name, "tool1");
+        assert_eq!(tools[1].name, "tool2");
     }
 }
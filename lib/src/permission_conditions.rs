@@ -0,0 +1,364 @@
+//! Argument conditions for permission policies and stored decisions
+//!
+//! A tool pattern alone matches every call of a tool, so allowing `fs_write` allows
+//! writes anywhere. Conditions narrow a policy or an "always" decision to calls whose
+//! arguments match:
+//! - [`ArgumentCondition::Path`]: a path argument matches a glob; relative globs and
+//!   paths are resolved against the session's working directory
+//! - [`ArgumentCondition::Command`]: a command argument matches a pattern such as
+//!   `cargo test*`
+//! - [`ArgumentCondition::Host`]: the host of a URL argument matches a pattern such as
+//!   `*.github.com`
+//! - [`ArgumentCondition::Argument`]: the value at a JSON pointer, for MCP and other
+//!   tools with their own argument schema
+//!
+//! Arguments are found at a JSON pointer, or at the argument names used by the ACP and
+//! claude CLI tools when no pointer is given. A condition whose argument is missing
+//! does not match.
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::{Component, Path, PathBuf};
+
+/// Arguments holding a path when a path condition has no pointer
+const PATH_POINTERS: &[&str] = &["/path", "/file_path", "/notebook_path"];
+
+/// Argument holding a command when a command condition has no pointer
+const COMMAND_POINTER: &str = "/command";
+
+/// Argument holding the command's arguments, appended to the command when present
+const COMMAND_ARGS_POINTER: &str = "/args";
+
+/// Argument holding a URL when a host condition has no pointer
+const URL_POINTER: &str = "/url";
+
+/// Shell syntax that runs further commands or redirects output
+const SHELL_OPERATORS: &[&str] = &[";", "&", "|", "`", "$(", ">", "<", "\n"];
+
+/// A condition on the arguments of a tool call
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ArgumentCondition {
+    /// A path argument matches a glob (`*`, `**` and `?`)
+    Path {
+        glob: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pointer: Option<String>,
+    },
+    /// A command argument matches a pattern where `*` matches anything
+    ///
+    /// Commands chaining further commands or redirecting output never match, so
+    /// `cargo test*` does not match `cargo test && rm -rf ~`.
+    Command {
+        pattern: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pointer: Option<String>,
+    },
+    /// The host of a URL argument matches a pattern where `*` matches anything
+    Host {
+        pattern: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pointer: Option<String>,
+    },
+    /// The argument at a JSON pointer exists, equals a value or matches a pattern
+    Argument {
+        pointer: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        equals: Option<Value>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pattern: Option<String>,
+    },
+}
+
+impl ArgumentCondition {
+    /// Check that the condition can match anything, describing the problem if not
+    pub fn validate(&self) -> Result<(), String> {
+        let (pattern, pointer) = match self {
+            ArgumentCondition::Path { glob, pointer } => (Some(glob), pointer.as_ref()),
+            ArgumentCondition::Command { pattern, pointer }
+            | ArgumentCondition::Host { pattern, pointer } => (Some(pattern), pointer.as_ref()),
+            ArgumentCondition::Argument {
+                pointer, pattern, ..
+            } => (pattern.as_ref(), Some(pointer)),
+        };
+
+        if pattern.is_some_and(|pattern| pattern.trim().is_empty()) {
+            return Err("pattern must not be empty".to_string());
+        }
+        if let Some(pointer) = pointer.filter(|pointer| !pointer.starts_with('/')) {
+            return Err(format!("JSON pointer '{}' must start with '/'", pointer));
+        }
+        Ok(())
+    }
+
+    /// Whether the arguments of a tool call satisfy the condition
+    ///
+    /// # Arguments
+    /// * `args` - Arguments of the tool call
+    /// * `cwd` - Working directory of the session, used to resolve relative paths
+    pub fn matches(&self, args: &Value, cwd: Option<&Path>) -> bool {
+        match self {
+            ArgumentCondition::Path { glob, pointer } => {
+                let path = match pointer {
+                    Some(pointer) => args.pointer(pointer).and_then(Value::as_str),
+                    None => PATH_POINTERS
+                        .iter()
+                        .find_map(|pointer| args.pointer(pointer).and_then(Value::as_str)),
+                };
+                match (path.and_then(|path| resolve(path, cwd)), resolve(glob, cwd)) {
+                    (Some(path), Some(glob)) => glob_matches(&glob, &path),
+                    _ => false,
+                }
+            }
+            ArgumentCondition::Command { pattern, pointer } => {
+                let Some(command) = command_line(args, pointer.as_deref()) else {
+                    return false;
+                };
+                let command = command.trim();
+                if SHELL_OPERATORS
+                    .iter()
+                    .any(|operator| command.contains(operator) && !pattern.contains(operator))
+                {
+                    return false;
+                }
+                wildcard_matches(pattern, command)
+            }
+            ArgumentCondition::Host { pattern, pointer } => args
+                .pointer(pointer.as_deref().unwrap_or(URL_POINTER))
+                .and_then(Value::as_str)
+                .and_then(|url| url::Url::parse(url).ok())
+                .and_then(|url| url.host_str().map(str::to_ascii_lowercase))
+                .is_some_and(|host| wildcard_matches(&pattern.to_ascii_lowercase(), &host)),
+            ArgumentCondition::Argument {
+                pointer,
+                equals,
+                pattern,
+            } => {
+                let Some(value) = args.pointer(pointer) else {
+                    return false;
+                };
+                let equals_matches = equals.as_ref().is_none_or(|expected| expected == value);
+                let pattern_matches = pattern.as_ref().is_none_or(|pattern| {
+                    let text = match value {
+                        Value::String(text) => text.clone(),
+                        other => other.to_string(),
+                    };
+                    wildcard_matches(pattern, &text)
+                });
+                equals_matches && pattern_matches
+            }
+        }
+    }
+}
+
+/// Whether the arguments satisfy every condition
+pub fn all_match(conditions: &[ArgumentCondition], args: &Value, cwd: Option<&Path>) -> bool {
+    conditions
+        .iter()
+        .all(|condition| condition.matches(args, cwd))
+}
+
+/// The command and its arguments as one line
+fn command_line(args: &Value, pointer: Option<&str>) -> Option<String> {
+    if let Some(pointer) = pointer {
+        return args
+            .pointer(pointer)
+            .and_then(Value::as_str)
+            .map(str::to_string);
+    }
+
+    let command = args.pointer(COMMAND_POINTER).and_then(Value::as_str)?;
+    let arguments = args
+        .pointer(COMMAND_ARGS_POINTER)
+        .and_then(Value::as_array)
+        .map(|arguments| {
+            arguments
+                .iter()
+                .filter_map(Value::as_str)
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    Some(
+        std::iter::once(command)
+            .chain(arguments)
+            .collect::<Vec<_>>()
+            .join(" "),
+    )
+}
+
+/// Make a path absolute against the working directory and remove `.` and `..`
+///
+/// Relative paths cannot be resolved without a working directory.
+fn resolve(path: &str, cwd: Option<&Path>) -> Option<String> {
+    let path = Path::new(path);
+    let absolute = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        cwd?.join(path)
+    };
+
+    let mut normalized = PathBuf::new();
+    for component in absolute.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    Some(normalized.to_string_lossy().into_owned())
+}
+
+/// Match a path against a glob where `*` and `?` stay within one path segment
+fn glob_matches(glob: &str, path: &str) -> bool {
+    let mut pattern = String::from("^");
+    let mut chars = glob.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    pattern.push_str("(?:.*/)?");
+                } else {
+                    pattern.push_str(".*");
+                }
+            }
+            '*' => pattern.push_str("[^/]*"),
+            '?' => pattern.push_str("[^/]"),
+            other => pattern.push_str(&regex::escape(&other.to_string())),
+        }
+    }
+    pattern.push('$');
+    Regex::new(&pattern).is_ok_and(|regex| regex.is_match(path))
+}
+
+/// Match text against a pattern where `*` matches anything and `?` one character
+fn wildcard_matches(pattern: &str, text: &str) -> bool {
+    let pattern = pattern
+        .chars()
+        .map(|c| match c {
+            '*' => "(?s:.*)".to_string(),
+            '?' => "(?s:.)".to_string(),
+            other => regex::escape(&other.to_string()),
+        })
+        .collect::<String>();
+    Regex::new(&format!("^{}$", pattern)).is_ok_and(|regex| regex.is_match(text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_path_glob_under_cwd() {
+        let condition = ArgumentCondition::Path {
+            glob: "src/**/*.rs".to_string(),
+            pointer: None,
+        };
+        let cwd = Some(Path::new("/work/repo"));
+
+        assert!(condition.matches(&json!({"path": "/work/repo/src/lib.rs"}), cwd));
+        assert!(condition.matches(&json!({"file_path": "src/a/b/mod.rs"}), cwd));
+        assert!(!condition.matches(&json!({"path": "/work/repo/README.md"}), cwd));
+        assert!(!condition.matches(&json!({"path": "/work/other/src/lib.rs"}), cwd));
+        // Traversal out of the project is resolved before matching
+        assert!(!condition.matches(&json!({"path": "src/../../other/src/lib.rs"}), cwd));
+        // Relative globs need a working directory
+        assert!(!condition.matches(&json!({"path": "/work/repo/src/lib.rs"}), None));
+        assert!(!condition.matches(&json!({"content": "no path"}), cwd));
+    }
+
+    #[test]
+    fn test_command_prefix() {
+        let condition = ArgumentCondition::Command {
+            pattern: "cargo test*".to_string(),
+            pointer: None,
+        };
+
+        assert!(condition.matches(&json!({"command": "cargo test"}), None));
+        assert!(condition.matches(&json!({"command": "cargo test --workspace"}), None));
+        assert!(condition.matches(&json!({"command": "cargo", "args": ["test", "-q"]}), None));
+        assert!(!condition.matches(&json!({"command": "cargo build"}), None));
+        assert!(!condition.matches(&json!({"command": "cargo test && rm -rf ~"}), None));
+        assert!(!condition.matches(&json!({"command": "cargo test > /etc/passwd"}), None));
+    }
+
+    #[test]
+    fn test_host_pattern() {
+        let condition = ArgumentCondition::Host {
+            pattern: "*.github.com".to_string(),
+            pointer: None,
+        };
+
+        assert!(condition.matches(&json!({"url": "https://api.GitHub.com/repos"}), None));
+        assert!(!condition.matches(&json!({"url": "https://github.com.evil.io/"}), None));
+        assert!(!condition.matches(&json!({"url": "not a url"}), None));
+    }
+
+    #[test]
+    fn test_json_pointer_argument() {
+        let args = json!({"query": {"table": "users", "limit": 10}});
+        let equals = ArgumentCondition::Argument {
+            pointer: "/query/table".to_string(),
+            equals: Some(json!("users")),
+            pattern: None,
+        };
+        let pattern = ArgumentCondition::Argument {
+            pointer: "/query/limit".to_string(),
+            equals: None,
+            pattern: Some("1?".to_string()),
+        };
+        let missing = ArgumentCondition::Argument {
+            pointer: "/query/where".to_string(),
+            equals: None,
+            pattern: None,
+        };
+
+        assert!(all_match(&[equals.clone(), pattern], &args, None));
+        assert!(!missing.matches(&args, None));
+        assert!(!equals.matches(&json!({"query": {"table": "orders"}}), None));
+    }
+
+    #[test]
+    fn test_condition_validation() {
+        let valid = ArgumentCondition::Argument {
+            pointer: "/table".to_string(),
+            equals: None,
+            pattern: None,
+        };
+        assert!(valid.validate().is_ok());
+
+        let empty_glob = ArgumentCondition::Path {
+            glob: " ".to_string(),
+            pointer: None,
+        };
+        assert!(empty_glob.validate().is_err());
+
+        let bad_pointer = ArgumentCondition::Host {
+            pattern: "example.com".to_string(),
+            pointer: Some("url".to_string()),
+        };
+        assert!(bad_pointer.validate().unwrap_err().contains("'url'"));
+    }
+
+    #[test]
+    fn test_condition_serialization() {
+        let condition: ArgumentCondition =
+            serde_json::from_value(json!({"type": "command", "pattern": "npm run *"})).unwrap();
+        assert_eq!(
+            condition,
+            ArgumentCondition::Command {
+                pattern: "npm run *".to_string(),
+                pointer: None
+            }
+        );
+        assert_eq!(
+            serde_json::to_value(&condition).unwrap(),
+            json!({"type": "command", "pattern": "npm run *"})
+        );
+    }
+}
@@ -0,0 +1,844 @@
+//! Permission decision flow shared by ACP permission requests and the claude CLI
+//!
+//! A tool call is evaluated in this order:
+//! 1. Cancelled sessions never run tools
+//! 2. The [`PermissionPolicyEngine`] allows, denies or asks for consent
+//! 3. Tools listed in `require_permission_for` always ask for consent
+//! 4. Stored "always" preferences for the session's project, then for the user,
+//!    answer the consent request without prompting
+//! 5. Otherwise the [`UserPromptHandler`] asks the user, and "always" answers are stored
+//!
+//! The user is offered the [`ToolCallHandler`]'s options for the tool call, limited to
+//! the kinds of answer the policy allows. A question the user cancels, or that times
+//! out, ends the prompt turn.
+//!
+//! The same flow backs `session/request_permission` and the `can_use_tool` control
+//! requests the claude CLI sends for every Bash, Edit, Write and other tool call. Every
+//! decision is written to the [`AuditLog`].
+
+use crate::agent::CancellationManager;
+use crate::audit::{AuditEvent, AuditLog, AuditRecord};
+use crate::claude::ToolPermissionHandler;
+use crate::permission_conditions::ArgumentCondition;
+use crate::permission_storage::PermissionStorage;
+use crate::permissions::{PermissionPolicyEngine, PolicyEvaluation};
+use crate::protocol_translator::{ToolPermissionDecision, ToolPermissionRequest};
+use crate::session::{SessionId, SessionManager};
+use crate::tools::{
+    EnhancedPermissionRequest, InternalToolRequest, PermissionOption, PermissionOptionKind,
+    PermissionOutcome, ToolCallHandler,
+};
+use crate::user_prompt::UserPromptHandler;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+
+/// Evaluates tool calls against policies, stored preferences and the user
+#[derive(Clone)]
+pub struct PermissionFlow {
+    permission_engine: Arc<PermissionPolicyEngine>,
+    permission_storage: Arc<PermissionStorage>,
+    user_prompt_handler: Arc<dyn UserPromptHandler>,
+    tool_handler: Arc<RwLock<ToolCallHandler>>,
+    cancellation_manager: Arc<CancellationManager>,
+    session_manager: Arc<SessionManager>,
+    require_permission_for: Vec<String>,
+    audit_log: Arc<AuditLog>,
+    prompt_timeout: Duration,
+}
+
+impl PermissionFlow {
+    /// Create a permission flow from the agent's permission components
+    ///
+    /// The user has `prompt_timeout` to answer a permission question.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        permission_engine: Arc<PermissionPolicyEngine>,
+        permission_storage: Arc<PermissionStorage>,
+        user_prompt_handler: Arc<dyn UserPromptHandler>,
+        tool_handler: Arc<RwLock<ToolCallHandler>>,
+        cancellation_manager: Arc<CancellationManager>,
+        session_manager: Arc<SessionManager>,
+        require_permission_for: Vec<String>,
+        audit_log: Arc<AuditLog>,
+        prompt_timeout: Duration,
+    ) -> Self {
+        Self {
+            permission_engine,
+            permission_storage,
+            user_prompt_handler,
+            tool_handler,
+            cancellation_manager,
+            session_manager,
+            require_permission_for,
+            audit_log,
+            prompt_timeout,
+        }
+    }
+
+    /// Decide whether a tool call may run
+    ///
+    /// # Arguments
+    /// * `session_id` - Session the tool call belongs to
+    /// * `tool_call_id` - Id of the tool call, shown to the client when asking the user
+    /// * `tool_name` - Name of the tool being called
+    /// * `tool_args` - Arguments of the tool call
+    /// * `requested_options` - Options offered to the user; policy options are used when empty
+    ///
+    /// # Returns
+    /// The selected permission option, or `Cancelled` if no decision could be made
+    pub async fn decide(
+        &self,
+        session_id: &str,
+        tool_call_id: &str,
+        tool_name: &str,
+        tool_args: &serde_json::Value,
+        requested_options: Vec<PermissionOption>,
+    ) -> PermissionOutcome {
+        let project = self.session_project(session_id);
+        let (policy_result, outcome) = self
+            .resolve(
+                session_id,
+                tool_call_id,
+                tool_name,
+                tool_args,
+                requested_options,
+                project.as_deref(),
+            )
+            .await;
+
+        let mut record = AuditRecord::new(
+            AuditEvent::Permission,
+            session_id,
+            project,
+            tool_name,
+            tool_args,
+        );
+        record.tool_call_id = Some(tool_call_id.to_string());
+        record.policy_result = policy_result.map(str::to_string);
+        let decision = match &outcome {
+            PermissionOutcome::Selected { option_id } => {
+                if policy_result == Some("require_user_consent") {
+                    record.user_choice = Some(option_id.clone());
+                }
+                if option_id.starts_with("allow") {
+                    "allowed"
+                } else {
+                    "denied"
+                }
+            }
+            PermissionOutcome::Cancelled => "cancelled",
+        };
+        record.decision = Some(decision.to_string());
+        self.audit_log.record(record);
+
+        outcome
+    }
+
+    /// Evaluate a tool call and return the policy result with the outcome
+    ///
+    /// The policy result is `None` when the session is cancelled or evaluation failed.
+    async fn resolve(
+        &self,
+        session_id: &str,
+        tool_call_id: &str,
+        tool_name: &str,
+        tool_args: &serde_json::Value,
+        requested_options: Vec<PermissionOption>,
+        project: Option<&Path>,
+    ) -> (Option<&'static str>, PermissionOutcome) {
+        if self.cancellation_manager.is_cancelled(session_id).await {
+            tracing::info!(
+                "Session {} is cancelled, returning cancelled outcome",
+                session_id
+            );
+            return (None, PermissionOutcome::Cancelled);
+        }
+
+        let policy_result = match self
+            .permission_engine
+            .evaluate_tool_call(tool_name, tool_args, project)
+            .await
+        {
+            Ok(evaluation) => evaluation,
+            Err(e) => {
+                tracing::error!("Permission policy evaluation failed: {}", e);
+                return (None, PermissionOutcome::Cancelled);
+            }
+        };
+
+        let policy_result = match policy_result {
+            PolicyEvaluation::Allowed if self.requires_permission(tool_name) => {
+                tracing::info!(
+                    "Tool '{}' is configured to always require permission",
+                    tool_name
+                );
+                PolicyEvaluation::RequireUserConsent {
+                    options: Self::once_options(),
+                    conditions: Vec::new(),
+                }
+            }
+            other => other,
+        };
+
+        match policy_result {
+            PolicyEvaluation::Allowed => {
+                tracing::info!("Tool '{}' allowed by policy", tool_name);
+                let outcome = PermissionOutcome::Selected {
+                    option_id: "allow-once".to_string(),
+                };
+                (Some("allowed"), outcome)
+            }
+            PolicyEvaluation::Denied { reason } => {
+                tracing::info!("Tool '{}' denied by policy: {}", tool_name, reason);
+                let outcome = PermissionOutcome::Selected {
+                    option_id: "reject-once".to_string(),
+                };
+                (Some("denied"), outcome)
+            }
+            PolicyEvaluation::RequireUserConsent {
+                options,
+                conditions,
+            } => {
+                tracing::info!("Tool '{}' requires user consent", tool_name);
+
+                // If options were provided in request, use those; otherwise offer the
+                // tool handler's options that the policy allows
+                let permission_options = if !requested_options.is_empty() {
+                    requested_options
+                } else {
+                    self.permission_options(tool_call_id, tool_name, tool_args, &options)
+                        .await
+                };
+
+                let request = EnhancedPermissionRequest {
+                    session_id: session_id.to_string(),
+                    tool_request_id: tool_call_id.to_string(),
+                    tool_name: tool_name.to_string(),
+                    description: format!(
+                        "Execute {} with arguments: {}",
+                        tool_name,
+                        serde_json::to_string_pretty(tool_args)
+                            .unwrap_or_else(|_| "{}".to_string())
+                    ),
+                    arguments: tool_args.clone(),
+                    options: permission_options,
+                };
+                let outcome = self.ask_user(&request, project, conditions).await;
+                (Some("require_user_consent"), outcome)
+            }
+        }
+    }
+
+    /// The tool handler's options for a tool call, of the kinds in `allowed`
+    async fn permission_options(
+        &self,
+        tool_call_id: &str,
+        tool_name: &str,
+        tool_args: &serde_json::Value,
+        allowed: &[PermissionOption],
+    ) -> Vec<PermissionOption> {
+        let request = InternalToolRequest {
+            id: tool_call_id.to_string(),
+            name: tool_name.to_string(),
+            arguments: tool_args.clone(),
+        };
+        self.tool_handler
+            .read()
+            .await
+            .generate_permission_options(&request)
+            .into_iter()
+            .filter(|option| allowed.iter().any(|allowed| allowed.kind == option.kind))
+            .collect()
+    }
+
+    /// Resolve a consent request from stored preferences or by prompting the user
+    ///
+    /// "Always" answers are remembered for the session's project with the argument
+    /// conditions of the policy that asked for consent.
+    async fn ask_user(
+        &self,
+        request: &EnhancedPermissionRequest,
+        project: Option<&Path>,
+        conditions: Vec<ArgumentCondition>,
+    ) -> PermissionOutcome {
+        let tool_name = request.tool_name.as_str();
+
+        // Check if there's a stored preference for this tool call
+        let stored_kind = match project {
+            Some(project) => {
+                self.permission_storage
+                    .lookup(project, tool_name, &request.arguments)
+                    .await
+            }
+            None => self.permission_storage.get_preference(tool_name).await,
+        };
+        if let Some(stored_kind) = stored_kind {
+            let option_id = match stored_kind {
+                PermissionOptionKind::AllowAlways => "allow-always",
+                PermissionOptionKind::RejectAlways => "reject-always",
+                _ => {
+                    tracing::warn!("Unexpected stored permission kind: {:?}", stored_kind);
+                    "allow-once"
+                }
+            };
+
+            tracing::info!(
+                "Using stored permission preference for '{}': {}",
+                tool_name,
+                option_id
+            );
+
+            return PermissionOutcome::Selected {
+                option_id: option_id.to_string(),
+            };
+        }
+
+        // Prompt user for permission with timeout
+        let prompt_result = tokio::time::timeout(
+            self.prompt_timeout,
+            self.user_prompt_handler.prompt_for_tool_call(request),
+        )
+        .await;
+
+        let selected_option_id = match prompt_result {
+            Ok(Ok(option_id)) => option_id,
+            Ok(Err(e)) => {
+                tracing::error!("User prompt failed: {}", e);
+                return PermissionOutcome::Cancelled;
+            }
+            Err(_) => {
+                tracing::warn!(
+                    "User permission prompt timed out after {:?}",
+                    self.prompt_timeout
+                );
+                return PermissionOutcome::Cancelled;
+            }
+        };
+
+        // Find the selected option to get its kind
+        if let Some(selected_option) = request
+            .options
+            .iter()
+            .find(|opt| opt.option_id == selected_option_id)
+        {
+            // Store the permission decision if user selected "always" option
+            match project {
+                Some(project) => {
+                    self.permission_storage
+                        .remember(project, tool_name, conditions, selected_option.kind.clone())
+                        .await
+                }
+                None => tracing::debug!(
+                    "Session {} not found, not remembering the answer",
+                    request.session_id
+                ),
+            }
+
+            tracing::info!(
+                "User selected permission option: {} ({:?})",
+                selected_option.name,
+                selected_option.kind
+            );
+        }
+
+        PermissionOutcome::Selected {
+            option_id: selected_option_id,
+        }
+    }
+
+    /// Working directory of the session, which scopes remembered answers
+    fn session_project(&self, session_id: &str) -> Option<PathBuf> {
+        let session_id = SessionId::parse(session_id).ok()?;
+        match self.session_manager.get_session(&session_id) {
+            Ok(session) => session.map(|session| session.cwd),
+            Err(e) => {
+                tracing::warn!("Failed to look up session {}: {}", session_id, e);
+                None
+            }
+        }
+    }
+
+    /// Check if a tool is configured to always require explicit permission
+    fn requires_permission(&self, tool_name: &str) -> bool {
+        self.require_permission_for
+            .iter()
+            .any(|name| name == tool_name)
+    }
+
+    /// Options offered when configuration, not policy, requires consent
+    fn once_options() -> Vec<PermissionOption> {
+        vec![
+            PermissionOption {
+                option_id: "allow-once".to_string(),
+                name: "Allow once".to_string(),
+                kind: PermissionOptionKind::AllowOnce,
+            },
+            PermissionOption {
+                option_id: "reject-once".to_string(),
+                name: "Reject".to_string(),
+                kind: PermissionOptionKind::RejectOnce,
+            },
+        ]
+    }
+}
+
+#[async_trait::async_trait]
+impl ToolPermissionHandler for PermissionFlow {
+    async fn check_tool_permission(
+        &self,
+        session_id: &SessionId,
+        request: &ToolPermissionRequest,
+    ) -> ToolPermissionDecision {
+        let tool_call_id = request
+            .tool_use_id
+            .clone()
+            .unwrap_or_else(|| ulid::Ulid::new().to_string());
+        let outcome = self
+            .decide(
+                &session_id.to_string(),
+                &tool_call_id,
+                &request.tool_name,
+                &request.input,
+                Vec::new(),
+            )
+            .await;
+
+        match outcome {
+            PermissionOutcome::Selected { option_id }
+                if option_id == "allow-once" || option_id == "allow-always" =>
+            {
+                ToolPermissionDecision::Allow {
+                    updated_input: request.input.clone(),
+                }
+            }
+            PermissionOutcome::Selected { .. } => ToolPermissionDecision::Deny {
+                message: format!("Permission to use {} was denied", request.tool_name),
+                interrupt: false,
+            },
+            PermissionOutcome::Cancelled => {
+                // Ends the prompt turn, which then reports the cancelled stop reason
+                if let Err(e) = self
+                    .cancellation_manager
+                    .mark_cancelled(&session_id.to_string(), "Permission request was cancelled")
+                    .await
+                {
+                    tracing::error!("Failed to mark session {} as cancelled: {}", session_id, e);
+                }
+                ToolPermissionDecision::Deny {
+                    message: format!("Permission request for {} was cancelled", request.tool_name),
+                    interrupt: true,
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audit::AuditQuery;
+    use crate::config::AuditConfig;
+    use crate::permissions::{FilePermissionStorage, PermissionPolicy, PolicyAction, RiskLevel};
+    use crate::user_prompt::MockPromptHandler;
+    use tempfile::TempDir;
+
+    fn policy(tool_pattern: &str, default_action: PolicyAction) -> PermissionPolicy {
+        PermissionPolicy {
+            tool_pattern: tool_pattern.to_string(),
+            default_action,
+            require_user_consent: true,
+            allow_always_option: true,
+            risk_level: RiskLevel::Medium,
+            conditions: Vec::new(),
+        }
+    }
+
+    fn create_flow(
+        temp_dir: &TempDir,
+        policies: Vec<PermissionPolicy>,
+        response: Option<&str>,
+        require_permission_for: Vec<String>,
+    ) -> PermissionFlow {
+        create_flow_with_storage(
+            temp_dir,
+            policies,
+            response,
+            require_permission_for,
+            PermissionStorage::new(),
+        )
+    }
+
+    fn create_flow_with_storage(
+        temp_dir: &TempDir,
+        policies: Vec<PermissionPolicy>,
+        response: Option<&str>,
+        require_permission_for: Vec<String>,
+        permission_storage: PermissionStorage,
+    ) -> PermissionFlow {
+        let storage = FilePermissionStorage::new(temp_dir.path().to_path_buf());
+        let permission_engine = Arc::new(PermissionPolicyEngine::with_policies(
+            Box::new(storage),
+            policies,
+        ));
+        let session_manager = Arc::new(SessionManager::new());
+        let (cancellation_manager, _receiver) = CancellationManager::new(16);
+        PermissionFlow::new(
+            Arc::clone(&permission_engine),
+            Arc::new(permission_storage),
+            Arc::new(MockPromptHandler::new(response.map(str::to_string))),
+            Arc::new(RwLock::new(ToolCallHandler::new(
+                crate::tools::ToolPermissions {
+                    require_permission_for: vec![],
+                    auto_approved: vec![],
+                    forbidden_paths: vec![],
+                },
+                Arc::clone(&session_manager),
+                permission_engine,
+            ))),
+            Arc::new(cancellation_manager),
+            session_manager,
+            require_permission_for,
+            Arc::new(AuditLog::new(&AuditConfig {
+                path: Some(temp_dir.path().join("audit.jsonl")),
+                ..Default::default()
+            })),
+            Duration::from_secs(5),
+        )
+    }
+
+    /// Records the options it offers the user, who never answers
+    #[derive(Default)]
+    struct UnansweredPromptHandler {
+        offered: std::sync::Mutex<Vec<PermissionOption>>,
+    }
+
+    #[async_trait::async_trait]
+    impl UserPromptHandler for UnansweredPromptHandler {
+        async fn prompt_for_permission(
+            &self,
+            _tool_name: &str,
+            _description: &str,
+            options: &[PermissionOption],
+        ) -> crate::user_prompt::PromptResult<String> {
+            *self.offered.lock().unwrap() = options.to_vec();
+            std::future::pending().await
+        }
+    }
+
+    fn write_request() -> ToolPermissionRequest {
+        ToolPermissionRequest {
+            tool_name: "Write".to_string(),
+            input: serde_json::json!({"file_path": "hello.txt", "content": "hi"}),
+            tool_use_id: Some("toolu_123".to_string()),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_policy_allow_passes_input_through() {
+        let temp_dir = TempDir::new().unwrap();
+        let flow = create_flow(
+            &temp_dir,
+            vec![policy("Write", PolicyAction::Allow)],
+            Some("reject-once"),
+            vec![],
+        );
+
+        let decision = flow
+            .check_tool_permission(&SessionId::new(), &write_request())
+            .await;
+
+        assert_eq!(
+            decision,
+            ToolPermissionDecision::Allow {
+                updated_input: write_request().input
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_policy_deny_rejects_without_prompting() {
+        let temp_dir = TempDir::new().unwrap();
+        let flow = create_flow(
+            &temp_dir,
+            vec![policy("Write", PolicyAction::Deny)],
+            Some("allow-once"),
+            vec![],
+        );
+
+        let decision = flow
+            .check_tool_permission(&SessionId::new(), &write_request())
+            .await;
+
+        assert!(matches!(decision, ToolPermissionDecision::Deny { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_user_rejection_denies_tool() {
+        let temp_dir = TempDir::new().unwrap();
+        let flow = create_flow(
+            &temp_dir,
+            vec![policy("*", PolicyAction::AskUser)],
+            Some("reject-once"),
+            vec![],
+        );
+
+        let decision = flow
+            .check_tool_permission(&SessionId::new(), &write_request())
+            .await;
+
+        assert!(matches!(decision, ToolPermissionDecision::Deny { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_decisions_are_audited() {
+        let temp_dir = TempDir::new().unwrap();
+        let flow = create_flow(
+            &temp_dir,
+            vec![
+                policy("Read", PolicyAction::Allow),
+                policy("*", PolicyAction::AskUser),
+            ],
+            Some("reject-once"),
+            vec![],
+        );
+        let session_id = flow
+            .session_manager
+            .create_session(temp_dir.path().to_path_buf(), None)
+            .unwrap();
+
+        flow.check_tool_permission(&session_id, &write_request())
+            .await;
+        let read_request = ToolPermissionRequest {
+            tool_name: "Read".to_string(),
+            input: serde_json::json!({"file_path": "hello.txt"}),
+            tool_use_id: None,
+        };
+        flow.check_tool_permission(&session_id, &read_request).await;
+
+        let records = flow
+            .audit_log
+            .query(&AuditQuery::default())
+            .await
+            .unwrap()
+            .records;
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].tool_name, "Write");
+        assert_eq!(records[0].session_id, session_id.to_string());
+        assert_eq!(records[0].cwd.as_deref(), Some(temp_dir.path()));
+        assert_eq!(records[0].tool_call_id.as_deref(), Some("toolu_123"));
+        assert_eq!(
+            records[0].policy_result.as_deref(),
+            Some("require_user_consent")
+        );
+        assert_eq!(records[0].user_choice.as_deref(), Some("reject-once"));
+        assert_eq!(records[0].decision.as_deref(), Some("denied"));
+        assert_eq!(records[1].policy_result.as_deref(), Some("allowed"));
+        assert_eq!(records[1].user_choice, None);
+        assert_eq!(records[1].decision.as_deref(), Some("allowed"));
+    }
+
+    #[tokio::test]
+    async fn test_allow_always_is_remembered() {
+        let temp_dir = TempDir::new().unwrap();
+        let flow = create_flow(
+            &temp_dir,
+            vec![policy("*", PolicyAction::AskUser)],
+            Some("allow-always"),
+            vec![],
+        );
+        let session_id = flow
+            .session_manager
+            .create_session(temp_dir.path().to_path_buf(), None)
+            .unwrap();
+
+        let decision = flow
+            .check_tool_permission(&session_id, &write_request())
+            .await;
+        assert!(matches!(decision, ToolPermissionDecision::Allow { .. }));
+        assert_eq!(
+            flow.permission_storage
+                .lookup(temp_dir.path(), "Write", &write_request().input)
+                .await,
+            Some(PermissionOptionKind::AllowAlways)
+        );
+        // The answer applies to the project only
+        assert_eq!(flow.permission_storage.get_preference("Write").await, None);
+    }
+
+    #[tokio::test]
+    async fn test_allow_always_survives_restart_in_same_project() {
+        let temp_dir = TempDir::new().unwrap();
+        let project = temp_dir.path().join("project");
+        let other_project = temp_dir.path().join("other");
+        std::fs::create_dir_all(&project).unwrap();
+        std::fs::create_dir_all(&other_project).unwrap();
+        let config = crate::config::PermissionStoreConfig {
+            user_dir: Some(temp_dir.path().join("user")),
+            ..Default::default()
+        };
+        let policies = vec![policy("*", PolicyAction::AskUser)];
+
+        let flow = create_flow_with_storage(
+            &temp_dir,
+            policies.clone(),
+            Some("allow-always"),
+            vec![],
+            PermissionStorage::from_config(&config),
+        );
+        let session_id = flow
+            .session_manager
+            .create_session(project.clone(), None)
+            .unwrap();
+        let decision = flow
+            .check_tool_permission(&session_id, &write_request())
+            .await;
+        assert!(matches!(decision, ToolPermissionDecision::Allow { .. }));
+
+        // A new agent process rejects when asked, so an allow must come from the store
+        let restarted = create_flow_with_storage(
+            &temp_dir,
+            policies,
+            Some("reject-once"),
+            vec![],
+            PermissionStorage::from_config(&config),
+        );
+        let session_id = restarted
+            .session_manager
+            .create_session(project, None)
+            .unwrap();
+        let decision = restarted
+            .check_tool_permission(&session_id, &write_request())
+            .await;
+        assert!(matches!(decision, ToolPermissionDecision::Allow { .. }));
+
+        let other_session_id = restarted
+            .session_manager
+            .create_session(other_project, None)
+            .unwrap();
+        let decision = restarted
+            .check_tool_permission(&other_session_id, &write_request())
+            .await;
+        assert!(matches!(decision, ToolPermissionDecision::Deny { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_require_permission_for_overrides_allow_policy() {
+        let temp_dir = TempDir::new().unwrap();
+        let flow = create_flow(
+            &temp_dir,
+            vec![policy("Write", PolicyAction::Allow)],
+            Some("reject-once"),
+            vec!["Write".to_string()],
+        );
+
+        let decision = flow
+            .check_tool_permission(&SessionId::new(), &write_request())
+            .await;
+
+        assert!(matches!(decision, ToolPermissionDecision::Deny { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_unanswered_question_times_out_and_ends_the_turn() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut ask = policy("Write", PolicyAction::AskUser);
+        ask.risk_level = RiskLevel::High;
+        let handler = Arc::new(UnansweredPromptHandler::default());
+        let flow = PermissionFlow {
+            user_prompt_handler: handler.clone(),
+            prompt_timeout: Duration::from_millis(50),
+            ..create_flow(&temp_dir, vec![ask], None, vec![])
+        };
+        let session_id = SessionId::new();
+
+        let decision = flow
+            .check_tool_permission(&session_id, &write_request())
+            .await;
+
+        assert!(matches!(
+            decision,
+            ToolPermissionDecision::Deny {
+                interrupt: true,
+                ..
+            }
+        ));
+        assert!(
+            flow.cancellation_manager
+                .is_cancelled(&session_id.to_string())
+                .await
+        );
+
+        // The tool handler's options, without the "allow always" a high risk policy forbids
+        let offered = handler.offered.lock().unwrap().clone();
+        let ids: Vec<&str> = offered
+            .iter()
+            .map(|option| option.option_id.as_str())
+            .collect();
+        assert_eq!(ids, ["allow-once", "reject-once", "reject-always"]);
+        assert_eq!(offered[2].name, "Reject always");
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_session_denies_tool() {
+        let temp_dir = TempDir::new().unwrap();
+        let flow = create_flow(
+            &temp_dir,
+            vec![policy("Write", PolicyAction::Allow)],
+            Some("allow-once"),
+            vec![],
+        );
+        let session_id = SessionId::new();
+        flow.cancellation_manager
+            .mark_cancelled(&session_id.to_string(), "test")
+            .await
+            .unwrap();
+
+        let decision = flow
+            .check_tool_permission(&session_id, &write_request())
+            .await;
+
+        assert!(matches!(decision, ToolPermissionDecision::Deny { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_allow_always_keeps_policy_conditions() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut docs_policy = policy("Write", PolicyAction::AskUser);
+        docs_policy.conditions = vec![ArgumentCondition::Path {
+            glob: "docs/**".to_string(),
+            pointer: None,
+        }];
+        let flow = create_flow(
+            &temp_dir,
+            vec![docs_policy, policy("*", PolicyAction::AskUser)],
+            Some("allow-always"),
+            vec![],
+        );
+        let session_id = flow
+            .session_manager
+            .create_session(temp_dir.path().to_path_buf(), None)
+            .unwrap();
+
+        let docs_request = ToolPermissionRequest {
+            input: serde_json::json!({"file_path": "docs/guide.md", "content": "hi"}),
+            ..write_request()
+        };
+        let decision = flow.check_tool_permission(&session_id, &docs_request).await;
+        assert!(matches!(decision, ToolPermissionDecision::Allow { .. }));
+
+        let storage = &flow.permission_storage;
+        let other_docs = serde_json::json!({"file_path": "docs/api/index.md"});
+        assert_eq!(
+            storage.lookup(temp_dir.path(), "Write", &other_docs).await,
+            Some(PermissionOptionKind::AllowAlways)
+        );
+        assert_eq!(
+            storage
+                .lookup(temp_dir.path(), "Write", &write_request().input)
+                .await,
+            None
+        );
+    }
+}
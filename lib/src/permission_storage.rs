@@ -1,54 +1,119 @@
 //! Permission preference storage for "always" decisions
 //!
-//! This module provides in-memory storage for user permission preferences that
-//! persist across tool calls within a single agent session, such as "allow-always"
-//! and "reject-always" decisions.
+//! "Allow always" and "reject always" answers are remembered in one of two scopes:
+//! - the project, keyed by the session's working directory and kept in
+//!   `~/.claude-agent/permissions/projects/<hash of the directory>`
+//! - the user, applying to every project and kept in `~/.claude-agent/permissions`
 //!
-//! # Important Note
+//! Answers are never read from or written to the project itself, so a repository
+//! cannot ship answers that grant its tools permission.
 //!
-//! Preferences are stored in-memory only and do not persist across agent restarts.
-//! This is intentional for the current implementation to maintain session-level
-//! preference isolation and avoid stale permissions accumulating over time.
+//! Lookups check the project scope before the user scope. Stored answers can expire
+//! through [`StoredPermission::expires_at`]; expired answers are ignored and removed
+//! so the user is asked again. Storage without persistence keeps answers in memory
+//! for the lifetime of the agent process.
 
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::RwLock;
 
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::config::PermissionStoreConfig;
+use crate::permission_conditions::ArgumentCondition;
+use crate::permissions::{
+    self, current_timestamp, FilePermissionStorage, MemoryPermissionStorage, PermissionDecision,
+    StoredPermission,
+};
 use crate::tools::PermissionOptionKind;
 
-/// In-memory storage for user permission preferences
-///
-/// Stores permission preferences for the duration of the agent session.
-/// Preferences are lost when the agent process ends. This design prevents
-/// accumulation of stale permissions and ensures users make conscious decisions
-/// for each agent session.
+/// Directory, relative to the home directory, holding stored answers
+const PERMISSIONS_DIR: &str = ".claude-agent/permissions";
+
+/// Directory, relative to the user directory, holding the answers of each project
+const PROJECTS_DIR: &str = "projects";
+
+/// Storage backends by project directory, None for the user scope
+type ScopeStores = HashMap<Option<PathBuf>, Arc<dyn permissions::PermissionStorage>>;
+
+/// Where a remembered "always" answer applies
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PermissionScope {
+    /// Only sessions working in the same directory
+    #[default]
+    Project,
+    /// Every session of the user
+    User,
+}
+
+/// Storage for user permission preferences, scoped per project and per user
 #[derive(Clone)]
 pub struct PermissionStorage {
-    /// Map of tool name to stored permission decision
-    preferences: Arc<RwLock<HashMap<String, PermissionOptionKind>>>,
+    /// Keep answers on disk instead of in memory
+    persist: bool,
+    /// Directory of user-scoped answers when persisting, which also holds the
+    /// directories of project-scoped answers
+    user_dir: Option<PathBuf>,
+    /// Scope new answers are stored in
+    scope: PermissionScope,
+    /// How long a new answer stays valid, None for no expiry
+    expire_after: Option<Duration>,
+    /// Backends opened so far
+    stores: Arc<RwLock<ScopeStores>>,
 }
 
 impl PermissionStorage {
-    /// Create a new empty permission storage
+    /// Create a new empty permission storage kept in memory
     pub fn new() -> Self {
         Self {
-            preferences: Arc::new(RwLock::new(HashMap::new())),
+            persist: false,
+            user_dir: None,
+            scope: PermissionScope::Project,
+            expire_after: None,
+            stores: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    /// Create permission storage from the agent configuration
+    ///
+    /// Without a configured user directory, answers are kept in
+    /// `~/.claude-agent/permissions`, or in memory if the home directory is unknown.
+    pub fn from_config(config: &PermissionStoreConfig) -> Self {
+        let user_dir = config
+            .user_dir
+            .clone()
+            .or_else(|| home_dir().map(|home| home.join(PERMISSIONS_DIR)));
+        if config.persist && user_dir.is_none() {
+            tracing::warn!("Home directory unknown, permissions are kept in memory only");
+        }
+
+        Self {
+            persist: config.persist,
+            user_dir,
+            scope: config.scope,
+            expire_after: config
+                .expire_after_days
+                .map(|days| Duration::from_secs(days * 24 * 60 * 60)),
+            stores: Arc::new(RwLock::new(HashMap::new())),
         }
     }
 
-    /// Check if there is a stored preference for a tool
+    /// Check if there is a stored user-scoped preference for a tool
     ///
     /// # Arguments
     /// * `tool_name` - The name of the tool to check
     ///
     /// # Returns
-    /// The stored permission kind if one exists, None otherwise
+    /// The stored permission kind if one exists and has not expired, None otherwise
     pub async fn get_preference(&self, tool_name: &str) -> Option<PermissionOptionKind> {
-        let prefs = self.preferences.read().await;
-        prefs.get(tool_name).cloned()
+        self.lookup_in(None, tool_name, None).await
     }
 
-    /// Store a permission preference for a tool
+    /// Store a user-scoped permission preference for a tool
     ///
     /// Only "always" decisions (AllowAlways, RejectAlways) should be stored.
     /// "Once" decisions should not be stored as they apply only to a single call.
@@ -57,56 +122,212 @@ impl PermissionStorage {
     /// * `tool_name` - The name of the tool
     /// * `kind` - The permission kind to store
     pub async fn store_preference(&self, tool_name: &str, kind: PermissionOptionKind) {
-        match &kind {
-            PermissionOptionKind::AllowAlways | PermissionOptionKind::RejectAlways => {
-                let mut prefs = self.preferences.write().await;
-                prefs.insert(tool_name.to_string(), kind.clone());
-                tracing::info!(
-                    "Stored permission preference for '{}': {:?}",
-                    tool_name,
-                    kind
-                );
-            }
-            PermissionOptionKind::AllowOnce | PermissionOptionKind::RejectOnce => {
-                tracing::debug!(
-                    "Not storing 'once' permission for '{}': {:?}",
-                    tool_name,
-                    kind
-                );
-            }
+        self.store_in(None, tool_name, Vec::new(), kind).await;
+    }
+
+    /// Find the stored preference for a tool call made in a project
+    ///
+    /// Answers given for the project take precedence over user-scoped answers. Answers
+    /// stored with argument conditions apply only to calls whose arguments match, with
+    /// relative paths resolved against the project directory.
+    pub async fn lookup(
+        &self,
+        project: &Path,
+        tool_name: &str,
+        args: &Value,
+    ) -> Option<PermissionOptionKind> {
+        let call = Some((args, project));
+        match self.lookup_in(Some(project), tool_name, call).await {
+            Some(kind) => Some(kind),
+            None => self.lookup_in(None, tool_name, call).await,
         }
     }
 
-    /// Clear all stored preferences
+    /// Remember an answer given in a project, in the configured scope
+    ///
+    /// The answer applies to calls of the tool whose arguments match `conditions`.
+    pub async fn remember(
+        &self,
+        project: &Path,
+        tool_name: &str,
+        conditions: Vec<ArgumentCondition>,
+        kind: PermissionOptionKind,
+    ) {
+        let project = match self.scope {
+            PermissionScope::Project => Some(project),
+            PermissionScope::User => None,
+        };
+        self.store_in(project, tool_name, conditions, kind).await;
+    }
+
+    /// Clear all stored user-scoped preferences
     ///
     /// This is primarily useful for testing or resetting user preferences.
     pub async fn clear_all(&self) {
-        let mut prefs = self.preferences.write().await;
-        prefs.clear();
-        tracing::info!("Cleared all permission preferences");
+        match self.store(None).await.clear_all().await {
+            Ok(()) => tracing::info!("Cleared all permission preferences"),
+            Err(e) => tracing::warn!("Failed to clear permission preferences: {}", e),
+        }
     }
 
-    /// Remove a specific tool's preference
+    /// Remove a specific tool's user-scoped preference
     ///
     /// # Arguments
     /// * `tool_name` - The name of the tool whose preference should be removed
     pub async fn remove_preference(&self, tool_name: &str) -> bool {
-        let mut prefs = self.preferences.write().await;
-        let removed = prefs.remove(tool_name).is_some();
-        if removed {
-            tracing::info!("Removed permission preference for '{}'", tool_name);
+        match self.store(None).await.remove_permission(tool_name).await {
+            Ok(removed) => {
+                if removed {
+                    tracing::info!("Removed permission preference for '{}'", tool_name);
+                }
+                removed
+            }
+            Err(e) => {
+                tracing::warn!("Failed to remove permission preference: {}", e);
+                false
+            }
         }
-        removed
     }
 
-    /// Get the number of stored preferences
+    /// Get the number of stored user-scoped preferences
     ///
     /// This method is part of the public API to support monitoring and debugging
     /// of permission state. It can be used by UI components to display the number
     /// of stored preferences or by external tools to verify storage behavior.
     pub async fn count(&self) -> usize {
-        let prefs = self.preferences.read().await;
-        prefs.len()
+        match self.store(None).await.list_permissions().await {
+            Ok(permissions) => permissions.len(),
+            Err(e) => {
+                tracing::warn!("Failed to list permission preferences: {}", e);
+                0
+            }
+        }
+    }
+
+    /// Look up an unexpired preference in one scope
+    ///
+    /// Without the arguments and working directory of a call, only answers without
+    /// argument conditions are considered.
+    async fn lookup_in(
+        &self,
+        project: Option<&Path>,
+        tool_name: &str,
+        call: Option<(&Value, &Path)>,
+    ) -> Option<PermissionOptionKind> {
+        let store = self.store(project).await;
+        let permission = match call {
+            Some((args, cwd)) => store.find_permission(tool_name, args, Some(cwd)).await,
+            None => store.lookup_permission(tool_name).await,
+        };
+        let permission = match permission {
+            Ok(permission) => permission?,
+            Err(e) => {
+                tracing::warn!("Failed to read permission preferences: {}", e);
+                return None;
+            }
+        };
+
+        if permission.is_expired() {
+            tracing::info!(
+                "Stored permission preference for '{}' has expired",
+                tool_name
+            );
+            if let Err(e) = store.cleanup_expired().await {
+                tracing::warn!("Failed to remove expired permission preferences: {}", e);
+            }
+            return None;
+        }
+
+        Some(match permission.decision {
+            PermissionDecision::AllowAlways => PermissionOptionKind::AllowAlways,
+            PermissionDecision::DenyAlways => PermissionOptionKind::RejectAlways,
+        })
+    }
+
+    /// Store an "always" preference in one scope
+    async fn store_in(
+        &self,
+        project: Option<&Path>,
+        tool_name: &str,
+        conditions: Vec<ArgumentCondition>,
+        kind: PermissionOptionKind,
+    ) {
+        let decision = match &kind {
+            PermissionOptionKind::AllowAlways => PermissionDecision::AllowAlways,
+            PermissionOptionKind::RejectAlways => PermissionDecision::DenyAlways,
+            PermissionOptionKind::AllowOnce | PermissionOptionKind::RejectOnce => {
+                tracing::debug!(
+                    "Not storing 'once' permission for '{}': {:?}",
+                    tool_name,
+                    kind
+                );
+                return;
+            }
+        };
+
+        let granted_at = current_timestamp();
+        let mut context = HashMap::new();
+        if let Some(project) = project {
+            context.insert("project".to_string(), project.display().to_string());
+        }
+        let permission = StoredPermission {
+            tool_pattern: tool_name.to_string(),
+            decision,
+            granted_at,
+            expires_at: self
+                .expire_after
+                .map(|expire_after| granted_at + expire_after.as_secs()),
+            context,
+            conditions,
+        };
+
+        match self.store(project).await.store_permission(permission).await {
+            Ok(()) => tracing::info!(
+                "Stored permission preference for '{}': {:?}",
+                tool_name,
+                kind
+            ),
+            Err(e) => tracing::warn!(
+                "Failed to store permission preference for '{}': {}",
+                tool_name,
+                e
+            ),
+        }
+    }
+
+    /// Backend holding the answers of a project, or of the user for None
+    async fn store(&self, project: Option<&Path>) -> Arc<dyn permissions::PermissionStorage> {
+        // The same project reached through a symlink or a relative path shares answers
+        let key = match project {
+            Some(project) => Some(
+                tokio::fs::canonicalize(project)
+                    .await
+                    .unwrap_or_else(|_| project.to_path_buf()),
+            ),
+            None => None,
+        };
+        if let Some(store) = self.stores.read().await.get(&key) {
+            return Arc::clone(store);
+        }
+
+        let directory = match &key {
+            Some(project) => self.project_dir(project),
+            None => self.user_dir.clone(),
+        };
+        let store: Arc<dyn permissions::PermissionStorage> = match directory {
+            Some(directory) if self.persist => Arc::new(FilePermissionStorage::new(directory)),
+            _ => Arc::new(MemoryPermissionStorage::new()),
+        };
+        Arc::clone(self.stores.write().await.entry(key).or_insert(store))
+    }
+
+    /// Directory of the answers given in a project, inside the user directory
+    fn project_dir(&self, project: &Path) -> Option<PathBuf> {
+        use sha2::{Digest, Sha256};
+
+        let hash = Sha256::digest(project.as_os_str().as_encoded_bytes());
+        let name: String = hash.iter().map(|byte| format!("{:02x}", byte)).collect();
+        Some(self.user_dir.as_ref()?.join(PROJECTS_DIR).join(name))
     }
 }
 
@@ -116,6 +337,14 @@ impl Default for PermissionStorage {
     }
 }
 
+/// The user's home directory from the environment
+pub(crate) fn home_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .filter(|home| !home.is_empty())
+        .map(PathBuf::from)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -233,4 +462,201 @@ mod tests {
         // Should still have only one entry
         assert_eq!(storage.count().await, 1);
     }
+
+    fn persistent_storage(
+        temp_dir: &tempfile::TempDir,
+        scope: PermissionScope,
+    ) -> PermissionStorage {
+        PermissionStorage::from_config(&PermissionStoreConfig {
+            persist: true,
+            scope,
+            user_dir: Some(temp_dir.path().join("user")),
+            expire_after_days: None,
+        })
+    }
+
+    #[tokio::test]
+    async fn test_project_preferences_persist_per_project() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let project = temp_dir.path().join("project");
+        let other_project = temp_dir.path().join("other");
+
+        persistent_storage(&temp_dir, PermissionScope::Project)
+            .remember(
+                &project,
+                "fs_read",
+                Vec::new(),
+                PermissionOptionKind::AllowAlways,
+            )
+            .await;
+        assert!(!project.join(".claude-agent").exists());
+
+        let reopened = persistent_storage(&temp_dir, PermissionScope::Project);
+        let project_dir = reopened.project_dir(&project).unwrap();
+        assert!(project_dir.starts_with(temp_dir.path().join("user").join(PROJECTS_DIR)));
+        assert!(project_dir.join("permissions.json").exists());
+        assert_eq!(
+            reopened.lookup(&project, "fs_read", &Value::Null).await,
+            Some(PermissionOptionKind::AllowAlways)
+        );
+        assert_eq!(
+            reopened
+                .lookup(&other_project, "fs_read", &Value::Null)
+                .await,
+            None
+        );
+        assert_eq!(reopened.get_preference("fs_read").await, None);
+    }
+
+    #[tokio::test]
+    async fn test_user_preferences_apply_to_every_project() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let project = temp_dir.path().join("project");
+
+        let storage = persistent_storage(&temp_dir, PermissionScope::User);
+        storage
+            .remember(
+                &project,
+                "fs_read",
+                Vec::new(),
+                PermissionOptionKind::RejectAlways,
+            )
+            .await;
+        assert!(!temp_dir.path().join("user").join(PROJECTS_DIR).exists());
+
+        let reopened = persistent_storage(&temp_dir, PermissionScope::Project);
+        assert_eq!(
+            reopened
+                .lookup(&temp_dir.path().join("other"), "fs_read", &Value::Null)
+                .await,
+            Some(PermissionOptionKind::RejectAlways)
+        );
+
+        // A project answer takes precedence over the user answer
+        reopened
+            .remember(
+                &project,
+                "fs_read",
+                Vec::new(),
+                PermissionOptionKind::AllowAlways,
+            )
+            .await;
+        assert_eq!(
+            reopened.lookup(&project, "fs_read", &Value::Null).await,
+            Some(PermissionOptionKind::AllowAlways)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_expired_preferences_are_ignored_and_removed() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let project = temp_dir.path().join("project");
+        let storage = persistent_storage(&temp_dir, PermissionScope::Project);
+        let files = FilePermissionStorage::new(storage.project_dir(&project).unwrap());
+        permissions::PermissionStorage::store_permission(
+            &files,
+            StoredPermission {
+                tool_pattern: "fs_write".to_string(),
+                decision: PermissionDecision::AllowAlways,
+                granted_at: current_timestamp() - 3600,
+                expires_at: Some(current_timestamp() - 60),
+                context: HashMap::new(),
+                conditions: Vec::new(),
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            storage.lookup(&project, "fs_write", &Value::Null).await,
+            None
+        );
+        let remaining = permissions::PermissionStorage::list_permissions(&files)
+            .await
+            .unwrap();
+        assert!(remaining.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_new_preferences_expire_after_configured_days() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let project = temp_dir.path().join("project");
+        let storage = PermissionStorage::from_config(&PermissionStoreConfig {
+            expire_after_days: Some(7),
+            user_dir: Some(temp_dir.path().join("user")),
+            ..Default::default()
+        });
+
+        storage
+            .remember(
+                &project,
+                "fs_read",
+                Vec::new(),
+                PermissionOptionKind::AllowAlways,
+            )
+            .await;
+
+        let files = FilePermissionStorage::new(storage.project_dir(&project).unwrap());
+        let stored = permissions::PermissionStorage::lookup_permission(&files, "fs_read")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            stored.expires_at,
+            Some(stored.granted_at + 7 * 24 * 60 * 60)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_answers_shipped_in_a_project_are_ignored() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let project = temp_dir.path().join("project");
+        let shipped = FilePermissionStorage::new(project.join(PERMISSIONS_DIR));
+        permissions::PermissionStorage::store_permission(
+            &shipped,
+            StoredPermission {
+                tool_pattern: "terminal_create".to_string(),
+                decision: PermissionDecision::AllowAlways,
+                granted_at: current_timestamp(),
+                expires_at: None,
+                context: HashMap::new(),
+                conditions: Vec::new(),
+            },
+        )
+        .await
+        .unwrap();
+
+        let storage = persistent_storage(&temp_dir, PermissionScope::Project);
+        assert_eq!(
+            storage
+                .lookup(&project, "terminal_create", &Value::Null)
+                .await,
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_project_answers_are_keyed_by_the_canonical_directory() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let project = temp_dir.path().join("project");
+        std::fs::create_dir_all(project.join("src")).unwrap();
+
+        let storage = persistent_storage(&temp_dir, PermissionScope::Project);
+        storage
+            .remember(
+                &project,
+                "fs_read",
+                Vec::new(),
+                PermissionOptionKind::AllowAlways,
+            )
+            .await;
+
+        let reopened = persistent_storage(&temp_dir, PermissionScope::Project);
+        assert_eq!(
+            reopened
+                .lookup(&project.join("src/.."), "fs_read", &Value::Null)
+                .await,
+            Some(PermissionOptionKind::AllowAlways)
+        );
+    }
 }
@@ -8,10 +8,13 @@
 //! - Storage backend abstraction
 
 use crate::error::{AgentError, Result};
+use crate::permission_conditions::{self, ArgumentCondition};
+use crate::policy_file::PolicySet;
 use crate::tools::{PermissionOption, PermissionOptionKind};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tokio::fs;
 use tracing::{debug, error, info, warn};
@@ -29,6 +32,41 @@ pub struct StoredPermission {
     pub expires_at: Option<u64>,
     /// Additional context for the permission
     pub context: HashMap<String, String>,
+    /// Conditions on the tool call arguments; empty applies to every call
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub conditions: Vec<ArgumentCondition>,
+}
+
+impl StoredPermission {
+    /// Key the permission is stored under, distinct for each set of conditions
+    pub fn key(&self) -> String {
+        if self.conditions.is_empty() {
+            self.tool_pattern.clone()
+        } else {
+            format!(
+                "{} {}",
+                self.tool_pattern,
+                serde_json::to_string(&self.conditions).unwrap_or_default()
+            )
+        }
+    }
+
+    /// Whether the permission applies to a tool call
+    pub fn applies_to(
+        &self,
+        tool_name: &str,
+        args: &serde_json::Value,
+        cwd: Option<&Path>,
+    ) -> bool {
+        matches_tool_pattern(&self.tool_pattern, tool_name)
+            && permission_conditions::all_match(&self.conditions, args, cwd)
+    }
+
+    /// Whether the permission has passed its expiry time
+    pub fn is_expired(&self) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| current_timestamp() >= expires_at)
+    }
 }
 
 /// The actual permission decision
@@ -53,10 +91,26 @@ pub struct PermissionPolicy {
     pub allow_always_option: bool,
     /// Risk level of tools matching this pattern
     pub risk_level: RiskLevel,
+    /// Conditions on the tool call arguments; the policy applies only if all match
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub conditions: Vec<ArgumentCondition>,
+}
+
+impl PermissionPolicy {
+    /// Whether the policy applies to a tool call
+    pub fn applies_to(
+        &self,
+        tool_name: &str,
+        args: &serde_json::Value,
+        cwd: Option<&Path>,
+    ) -> bool {
+        matches_tool_pattern(&self.tool_pattern, tool_name)
+            && permission_conditions::all_match(&self.conditions, args, cwd)
+    }
 }
 
 /// Policy action for permission evaluation
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum PolicyAction {
     /// Allow the tool call automatically
     Allow,
@@ -67,7 +121,7 @@ pub enum PolicyAction {
 }
 
 /// Risk level for permission evaluation
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum RiskLevel {
     /// Safe operations that don't modify system state
     Low,
@@ -87,7 +141,13 @@ pub enum PolicyEvaluation {
     /// Tool call is denied
     Denied { reason: String },
     /// User consent is required with these options
-    RequireUserConsent { options: Vec<PermissionOption> },
+    ///
+    /// `conditions` are those of the matching policy; "always" answers are stored with
+    /// them so they apply to the same calls as the policy.
+    RequireUserConsent {
+        options: Vec<PermissionOption>,
+        conditions: Vec<ArgumentCondition>,
+    },
 }
 
 /// Permission storage backend trait
@@ -96,16 +156,40 @@ pub trait PermissionStorage: Send + Sync {
     /// Store a permission decision
     async fn store_permission(&self, permission: StoredPermission) -> Result<()>;
 
-    /// Lookup stored permission for a tool
+    /// Lookup stored permission for a tool that applies regardless of arguments
     async fn lookup_permission(&self, tool_name: &str) -> Result<Option<StoredPermission>>;
 
+    /// Find the most specific stored permission applying to a tool call
+    ///
+    /// Permissions for the exact tool name win over patterns, and permissions with
+    /// more argument conditions over those with fewer. Expired permissions are
+    /// returned too so callers can tell the user's answer has lapsed.
+    async fn find_permission(
+        &self,
+        tool_name: &str,
+        args: &serde_json::Value,
+        cwd: Option<&Path>,
+    ) -> Result<Option<StoredPermission>> {
+        Ok(self
+            .list_permissions()
+            .await?
+            .into_iter()
+            .filter(|permission| permission.applies_to(tool_name, args, cwd))
+            .max_by_key(|permission| {
+                (
+                    permission.tool_pattern == tool_name,
+                    permission.conditions.len(),
+                )
+            }))
+    }
+
     /// List all stored permissions
     async fn list_permissions(&self) -> Result<Vec<StoredPermission>>;
 
     /// Remove expired permissions
     async fn cleanup_expired(&self) -> Result<usize>;
 
-    /// Remove a specific permission
+    /// Remove the permissions stored for a tool pattern, whatever their conditions
     async fn remove_permission(&self, tool_pattern: &str) -> Result<bool>;
 
     /// Clear all permissions
@@ -190,12 +274,10 @@ impl FilePermissionStorage {
 impl PermissionStorage for FilePermissionStorage {
     async fn store_permission(&self, permission: StoredPermission) -> Result<()> {
         let mut permissions = self.load_permissions().await?;
-        permissions.insert(permission.tool_pattern.clone(), permission);
+        let key = permission.key();
+        permissions.insert(key.clone(), permission);
         self.save_permissions(&permissions).await?;
-        info!(
-            "Stored permission for tool pattern: {}",
-            permissions.keys().last().unwrap()
-        );
+        info!("Stored permission for tool pattern: {}", key);
         Ok(())
     }
 
@@ -208,11 +290,13 @@ impl PermissionStorage for FilePermissionStorage {
         }
 
         // Try pattern matching
-        for (pattern, permission) in permissions {
-            if matches_tool_pattern(&pattern, tool_name) {
+        for permission in permissions.into_values() {
+            if permission.conditions.is_empty()
+                && matches_tool_pattern(&permission.tool_pattern, tool_name)
+            {
                 debug!(
                     "Found matching permission pattern '{}' for tool '{}'",
-                    pattern, tool_name
+                    permission.tool_pattern, tool_name
                 );
                 return Ok(Some(permission));
             }
@@ -251,7 +335,9 @@ impl PermissionStorage for FilePermissionStorage {
 
     async fn remove_permission(&self, tool_pattern: &str) -> Result<bool> {
         let mut permissions = self.load_permissions().await?;
-        let removed = permissions.remove(tool_pattern).is_some();
+        let original_count = permissions.len();
+        permissions.retain(|_, permission| permission.tool_pattern != tool_pattern);
+        let removed = permissions.len() < original_count;
         if removed {
             self.save_permissions(&permissions).await?;
             info!("Removed permission for tool pattern: {}", tool_pattern);
@@ -272,17 +358,75 @@ impl PermissionStorage for FilePermissionStorage {
     }
 }
 
+/// In-memory permission storage, kept for the lifetime of the process
+#[derive(Debug, Default)]
+pub struct MemoryPermissionStorage {
+    permissions: tokio::sync::RwLock<HashMap<String, StoredPermission>>,
+}
+
+impl MemoryPermissionStorage {
+    /// Create empty in-memory permission storage
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait::async_trait]
+impl PermissionStorage for MemoryPermissionStorage {
+    async fn store_permission(&self, permission: StoredPermission) -> Result<()> {
+        let mut permissions = self.permissions.write().await;
+        permissions.insert(permission.key(), permission);
+        Ok(())
+    }
+
+    async fn lookup_permission(&self, tool_name: &str) -> Result<Option<StoredPermission>> {
+        let permissions = self.permissions.read().await;
+        if let Some(permission) = permissions.get(tool_name) {
+            return Ok(Some(permission.clone()));
+        }
+        Ok(permissions
+            .values()
+            .find(|permission| {
+                permission.conditions.is_empty()
+                    && matches_tool_pattern(&permission.tool_pattern, tool_name)
+            })
+            .cloned())
+    }
+
+    async fn list_permissions(&self) -> Result<Vec<StoredPermission>> {
+        Ok(self.permissions.read().await.values().cloned().collect())
+    }
+
+    async fn cleanup_expired(&self) -> Result<usize> {
+        let mut permissions = self.permissions.write().await;
+        let original_count = permissions.len();
+        permissions.retain(|_, perm| !perm.is_expired());
+        Ok(original_count - permissions.len())
+    }
+
+    async fn remove_permission(&self, tool_pattern: &str) -> Result<bool> {
+        let mut permissions = self.permissions.write().await;
+        let original_count = permissions.len();
+        permissions.retain(|_, permission| permission.tool_pattern != tool_pattern);
+        Ok(permissions.len() < original_count)
+    }
+
+    async fn clear_all(&self) -> Result<()> {
+        self.permissions.write().await.clear();
+        Ok(())
+    }
+}
+
 /// Permission policy engine for evaluating tool call permissions
 pub struct PermissionPolicyEngine {
     storage: Box<dyn PermissionStorage>,
-    policies: Vec<PermissionPolicy>,
+    policies: std::sync::RwLock<Arc<PolicySet>>,
 }
 
 impl PermissionPolicyEngine {
     /// Create new permission policy engine with storage backend
     pub fn new(storage: Box<dyn PermissionStorage>) -> Self {
-        let policies = default_permission_policies();
-        Self { storage, policies }
+        Self::with_policies(storage, default_permission_policies())
     }
 
     /// Create with custom policies
@@ -290,17 +434,47 @@ impl PermissionPolicyEngine {
         storage: Box<dyn PermissionStorage>,
         policies: Vec<PermissionPolicy>,
     ) -> Self {
-        Self { storage, policies }
+        Self::with_policy_set(storage, PolicySet::fixed(policies))
+    }
+
+    /// Create with policies that include the policy files of session directories
+    pub fn with_policy_set(storage: Box<dyn PermissionStorage>, policies: PolicySet) -> Self {
+        Self {
+            storage,
+            policies: std::sync::RwLock::new(Arc::new(policies)),
+        }
+    }
+
+    /// Replace the policies, keeping stored permissions
+    ///
+    /// Calls evaluated after this returns use the new policies.
+    pub fn set_policies(&self, policies: Vec<PermissionPolicy>) {
+        self.set_policy_set(PolicySet::fixed(policies));
+    }
+
+    /// Replace the policies with a policy set, keeping stored permissions
+    ///
+    /// Repository policy files are read again when a session next needs them.
+    pub fn set_policy_set(&self, policies: PolicySet) {
+        if let Ok(mut current) = self.policies.write() {
+            *current = Arc::new(policies);
+        }
     }
 
     /// Evaluate a tool call against stored permissions and policies
+    ///
+    /// # Arguments
+    /// * `tool_name` - Name of the tool being called
+    /// * `args` - Arguments of the tool call, matched against argument conditions
+    /// * `cwd` - Working directory of the session, used to resolve relative paths
     pub async fn evaluate_tool_call(
         &self,
         tool_name: &str,
         args: &serde_json::Value,
+        cwd: Option<&Path>,
     ) -> Result<PolicyEvaluation> {
-        // First check if we have a stored permission for this tool
-        if let Some(stored) = self.storage.lookup_permission(tool_name).await? {
+        // First check if we have a stored permission for this tool call
+        if let Some(stored) = self.storage.find_permission(tool_name, args, cwd).await? {
             // Check if stored permission is still valid
             if let Some(expires_at) = stored.expires_at {
                 if current_timestamp() >= expires_at {
@@ -323,9 +497,15 @@ impl PermissionPolicyEngine {
             }
         }
 
-        // Evaluate against policies
-        for policy in &self.policies {
-            if matches_tool_pattern(&policy.tool_pattern, tool_name) {
+        // Evaluate against the policies of the session's directory
+        let policy_set = self
+            .policies
+            .read()
+            .map(|policies| Arc::clone(&policies))
+            .map_err(|_| AgentError::Internal("Permission policies lock poisoned".to_string()))?;
+        let policies = policy_set.policies_for(cwd)?;
+        for policy in policies.iter() {
+            if policy.applies_to(tool_name, args, cwd) {
                 debug!(
                     "Applying policy '{}' to tool '{}'",
                     policy.tool_pattern, tool_name
@@ -341,13 +521,15 @@ impl PermissionPolicyEngine {
         );
         Ok(PolicyEvaluation::RequireUserConsent {
             options: self.generate_permission_options(tool_name, RiskLevel::Medium),
+            conditions: Vec::new(),
         })
     }
 
-    /// Store a permission decision
+    /// Store a permission decision for the tool calls matching `conditions`
     pub async fn store_permission_decision(
         &self,
         tool_name: &str,
+        conditions: Vec<ArgumentCondition>,
         decision: PermissionDecision,
         expires_in: Option<Duration>,
     ) -> Result<()> {
@@ -360,6 +542,7 @@ impl PermissionPolicyEngine {
             granted_at: now,
             expires_at,
             context: HashMap::new(),
+            conditions,
         };
 
         self.storage.store_permission(stored_permission).await
@@ -382,6 +565,7 @@ impl PermissionPolicyEngine {
                     PolicyEvaluation::RequireUserConsent {
                         options: self
                             .generate_permission_options(tool_name, policy.risk_level.clone()),
+                        conditions: policy.conditions.clone(),
                     }
                 } else {
                     PolicyEvaluation::Allowed
@@ -472,7 +656,7 @@ fn matches_tool_pattern(pattern: &str, tool_name: &str) -> bool {
 }
 
 /// Get current Unix timestamp
-fn current_timestamp() -> u64 {
+pub(crate) fn current_timestamp() -> u64 {
     SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .unwrap_or_default()
@@ -480,8 +664,8 @@ fn current_timestamp() -> u64 {
 }
 
 /// Default permission policies for common tool patterns
-fn default_permission_policies() -> Vec<PermissionPolicy> {
-    vec![
+pub(crate) fn default_permission_policies() -> Vec<PermissionPolicy> {
+    let mut policies = vec![
         // File system read operations - low risk
         PermissionPolicy {
             tool_pattern: "fs_read*".to_string(),
@@ -489,6 +673,7 @@ fn default_permission_policies() -> Vec<PermissionPolicy> {
             require_user_consent: false,
             allow_always_option: true,
             risk_level: RiskLevel::Low,
+            conditions: Vec::new(),
         },
         // File system write operations - medium risk
         PermissionPolicy {
@@ -497,6 +682,7 @@ fn default_permission_policies() -> Vec<PermissionPolicy> {
             require_user_consent: true,
             allow_always_option: true,
             risk_level: RiskLevel::Medium,
+            conditions: Vec::new(),
         },
         // Terminal operations - high risk
         PermissionPolicy {
@@ -505,6 +691,7 @@ fn default_permission_policies() -> Vec<PermissionPolicy> {
             require_user_consent: true,
             allow_always_option: false,
             risk_level: RiskLevel::High,
+            conditions: Vec::new(),
         },
         // Network operations - high risk
         PermissionPolicy {
@@ -513,16 +700,51 @@ fn default_permission_policies() -> Vec<PermissionPolicy> {
             require_user_consent: true,
             allow_always_option: false,
             risk_level: RiskLevel::High,
+            conditions: Vec::new(),
         },
-        // Default for unknown tools - medium risk
+        // Claude CLI shell commands - high risk
         PermissionPolicy {
-            tool_pattern: "*".to_string(),
+            tool_pattern: "Bash".to_string(),
             default_action: PolicyAction::AskUser,
             require_user_consent: true,
-            allow_always_option: true,
-            risk_level: RiskLevel::Medium,
+            allow_always_option: false,
+            risk_level: RiskLevel::High,
+            conditions: Vec::new(),
         },
-    ]
+        // Claude CLI web access - high risk
+        PermissionPolicy {
+            tool_pattern: "Web*".to_string(),
+            default_action: PolicyAction::AskUser,
+            require_user_consent: true,
+            allow_always_option: false,
+            risk_level: RiskLevel::High,
+            conditions: Vec::new(),
+        },
+    ];
+
+    // Claude CLI read-only tools - low risk
+    for tool in ["Read", "Glob", "Grep", "LS"] {
+        policies.push(PermissionPolicy {
+            tool_pattern: tool.to_string(),
+            default_action: PolicyAction::Allow,
+            require_user_consent: false,
+            allow_always_option: true,
+            risk_level: RiskLevel::Low,
+            conditions: Vec::new(),
+        });
+    }
+
+    // Default for unknown tools, including Claude CLI Edit and Write - medium risk
+    policies.push(PermissionPolicy {
+        tool_pattern: "*".to_string(),
+        default_action: PolicyAction::AskUser,
+        require_user_consent: true,
+        allow_always_option: true,
+        risk_level: RiskLevel::Medium,
+        conditions: Vec::new(),
+    });
+
+    policies
 }
 
 #[cfg(test)]
@@ -545,6 +767,7 @@ mod tests {
             granted_at: current_timestamp(),
             expires_at: None,
             context: HashMap::new(),
+            conditions: Vec::new(),
         };
 
         storage.store_permission(permission.clone()).await.unwrap();
@@ -583,6 +806,7 @@ mod tests {
             granted_at: current_timestamp() - 3600, // 1 hour ago
             expires_at: Some(current_timestamp() - 1800), // expired 30 min ago
             context: HashMap::new(),
+            conditions: Vec::new(),
         };
 
         // Add valid permission
@@ -592,6 +816,7 @@ mod tests {
             granted_at: current_timestamp(),
             expires_at: Some(current_timestamp() + 3600), // expires in 1 hour
             context: HashMap::new(),
+            conditions: Vec::new(),
         };
 
         storage.store_permission(expired_permission).await.unwrap();
@@ -616,14 +841,14 @@ mod tests {
 
         // Test fs_read (should be allowed by default policy)
         let result = engine
-            .evaluate_tool_call("fs_read_file", &serde_json::json!({}))
+            .evaluate_tool_call("fs_read_file", &serde_json::json!({}), None)
             .await
             .unwrap();
         assert!(matches!(result, PolicyEvaluation::Allowed));
 
         // Test fs_write (should require user consent)
         let result = engine
-            .evaluate_tool_call("fs_write_file", &serde_json::json!({}))
+            .evaluate_tool_call("fs_write_file", &serde_json::json!({}), None)
             .await
             .unwrap();
         assert!(matches!(
@@ -633,7 +858,7 @@ mod tests {
 
         // Test terminal (should require user consent)
         let result = engine
-            .evaluate_tool_call("terminal_create", &serde_json::json!({}))
+            .evaluate_tool_call("terminal_create", &serde_json::json!({}), None)
             .await
             .unwrap();
         assert!(matches!(
@@ -642,6 +867,47 @@ mod tests {
         ));
     }
 
+    #[tokio::test]
+    async fn test_policy_engine_claude_cli_tools() {
+        let storage = create_test_storage();
+        let engine = PermissionPolicyEngine::new(Box::new(storage));
+
+        // Read-only CLI tools run without prompting
+        let result = engine
+            .evaluate_tool_call("Read", &serde_json::json!({"file_path": "/tmp/a"}), None)
+            .await
+            .unwrap();
+        assert!(matches!(result, PolicyEvaluation::Allowed));
+
+        // Bash is high risk: no "allow always" option
+        let result = engine
+            .evaluate_tool_call(
+                "Bash",
+                &serde_json::json!({"command": "rm -rf target"}),
+                None,
+            )
+            .await
+            .unwrap();
+        match result {
+            PolicyEvaluation::RequireUserConsent { options, .. } => assert!(!options
+                .iter()
+                .any(|o| matches!(o.kind, PermissionOptionKind::AllowAlways))),
+            other => panic!("Expected RequireUserConsent, got {:?}", other),
+        }
+
+        // Edit and Write fall back to the medium risk default
+        for tool in ["Edit", "Write"] {
+            let result = engine
+                .evaluate_tool_call(tool, &serde_json::json!({}), None)
+                .await
+                .unwrap();
+            assert!(matches!(
+                result,
+                PolicyEvaluation::RequireUserConsent { .. }
+            ));
+        }
+    }
+
     #[tokio::test]
     async fn test_stored_permission_override() {
         let storage = create_test_storage();
@@ -649,15 +915,160 @@ mod tests {
 
         // Store a deny-always permission for fs_write
         engine
-            .store_permission_decision("fs_write_file", PermissionDecision::DenyAlways, None)
+            .store_permission_decision(
+                "fs_write_file",
+                Vec::new(),
+                PermissionDecision::DenyAlways,
+                None,
+            )
             .await
             .unwrap();
 
         // Should be denied even though policy would ask user
         let result = engine
-            .evaluate_tool_call("fs_write_file", &serde_json::json!({}))
+            .evaluate_tool_call("fs_write_file", &serde_json::json!({}), None)
             .await
             .unwrap();
         assert!(matches!(result, PolicyEvaluation::Denied { .. }));
     }
+
+    #[tokio::test]
+    async fn test_policy_conditions_match_arguments() {
+        let storage = create_test_storage();
+        let policies = vec![
+            PermissionPolicy {
+                tool_pattern: "fs_write*".to_string(),
+                default_action: PolicyAction::Allow,
+                require_user_consent: false,
+                allow_always_option: true,
+                risk_level: RiskLevel::Medium,
+                conditions: vec![ArgumentCondition::Path {
+                    glob: "target/**".to_string(),
+                    pointer: None,
+                }],
+            },
+            PermissionPolicy {
+                tool_pattern: "Bash".to_string(),
+                default_action: PolicyAction::Allow,
+                require_user_consent: false,
+                allow_always_option: true,
+                risk_level: RiskLevel::High,
+                conditions: vec![ArgumentCondition::Command {
+                    pattern: "cargo test*".to_string(),
+                    pointer: None,
+                }],
+            },
+        ]
+        .into_iter()
+        .chain(default_permission_policies())
+        .collect();
+        let engine = PermissionPolicyEngine::with_policies(Box::new(storage), policies);
+        let cwd = Some(Path::new("/work/repo"));
+
+        let result = engine
+            .evaluate_tool_call(
+                "fs_write_file",
+                &serde_json::json!({"path": "target/out.txt"}),
+                cwd,
+            )
+            .await
+            .unwrap();
+        assert!(matches!(result, PolicyEvaluation::Allowed));
+
+        let result = engine
+            .evaluate_tool_call(
+                "fs_write_file",
+                &serde_json::json!({"path": "/work/repo/src/lib.rs"}),
+                cwd,
+            )
+            .await
+            .unwrap();
+        assert!(matches!(
+            result,
+            PolicyEvaluation::RequireUserConsent { .. }
+        ));
+
+        let result = engine
+            .evaluate_tool_call(
+                "Bash",
+                &serde_json::json!({"command": "cargo test -p claude-agent-lib"}),
+                cwd,
+            )
+            .await
+            .unwrap();
+        assert!(matches!(result, PolicyEvaluation::Allowed));
+
+        let result = engine
+            .evaluate_tool_call(
+                "Bash",
+                &serde_json::json!({"command": "cargo publish"}),
+                cwd,
+            )
+            .await
+            .unwrap();
+        assert!(matches!(
+            result,
+            PolicyEvaluation::RequireUserConsent { .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_stored_permission_with_conditions() {
+        let storage = create_test_storage();
+        let engine = PermissionPolicyEngine::new(Box::new(storage));
+        let conditions = vec![ArgumentCondition::Argument {
+            pointer: "/database".to_string(),
+            equals: Some(serde_json::json!("staging")),
+            pattern: None,
+        }];
+
+        engine
+            .store_permission_decision(
+                "mcp__db__query",
+                conditions.clone(),
+                PermissionDecision::AllowAlways,
+                None,
+            )
+            .await
+            .unwrap();
+        engine
+            .store_permission_decision(
+                "mcp__db__query",
+                Vec::new(),
+                PermissionDecision::DenyAlways,
+                None,
+            )
+            .await
+            .unwrap();
+
+        // The conditional permission is more specific than the unconditional one
+        let result = engine
+            .evaluate_tool_call(
+                "mcp__db__query",
+                &serde_json::json!({"database": "staging"}),
+                None,
+            )
+            .await
+            .unwrap();
+        assert!(matches!(result, PolicyEvaluation::Allowed));
+
+        let result = engine
+            .evaluate_tool_call(
+                "mcp__db__query",
+                &serde_json::json!({"database": "production"}),
+                None,
+            )
+            .await
+            .unwrap();
+        assert!(matches!(result, PolicyEvaluation::Denied { .. }));
+
+        // Both decisions are stored, and removed together by tool pattern
+        assert_eq!(engine.storage.list_permissions().await.unwrap().len(), 2);
+        assert!(engine
+            .storage
+            .remove_permission("mcp__db__query")
+            .await
+            .unwrap());
+        assert!(engine.storage.list_permissions().await.unwrap().is_empty());
+    }
 }
@@ -8,26 +8,115 @@
 //! ## Input (stdin to claude)
 //! ```json
 //! {"type":"user","message":{"role":"user","content":"What is 2+2?"}}
+//! {"type":"user","message":{"role":"user","content":[{"type":"text","text":"What is this?"},{"type":"image","source":{"type":"base64","media_type":"image/png","data":"..."}}]}}
 //! ```
 //!
 //! ## Output (stdout from claude)
 //! ```json
 //! {"type":"system","subtype":"init","cwd":"/path","session_id":"uuid","tools":[...]}
 //! {"type":"assistant","message":{"content":[{"type":"tool_use","id":"toolu_123","name":"read_file","input":{...}}]}}
+//! {"type":"user","message":{"role":"user","content":[{"type":"tool_result","tool_use_id":"toolu_123","content":"..."}]}}
 //! {"type":"result","subtype":"success","total_cost_usd":0.114}
 //! ```
+//!
+//! ## Control protocol
+//! With `--permission-prompt-tool stdio` the claude CLI asks for permission before running a
+//! tool and waits for the matching response on stdin:
+//! ```json
+//! {"type":"control_request","request_id":"req_1","request":{"subtype":"can_use_tool","tool_name":"Write","input":{...},"tool_use_id":"toolu_123"}}
+//! {"type":"control_response","response":{"subtype":"success","request_id":"req_1","response":{"behavior":"allow","updatedInput":{...}}}}
+//! ```
+//!
+//! The ACP server sends control requests the same way, e.g. to stop the running turn:
+//! ```json
+//! {"type":"control_request","request_id":"interrupt_1","request":{"subtype":"interrupt"}}
+//! ```
 
+use crate::content_block_processor::{
+    ContentProcessingSummary, ProcessedContent, ProcessedContentType,
+};
+use crate::claude::TokenUsageInfo;
+use crate::tool_types::{ToolCallContent, ToolCallReport, ToolCallStatus, ToolKind};
 use crate::{AgentError, Result};
 use agent_client_protocol::{
-    ContentBlock, SessionId, SessionNotification, SessionUpdate, TextContent,
+    ContentBlock, EmbeddedResourceResource, SessionId, SessionNotification, SessionUpdate,
+    TextContent, ToolCall, ToolCallId, ToolCallUpdate, ToolCallUpdateFields,
 };
+use base64::{engine::general_purpose, Engine as _};
 use serde::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
 
+/// Image media types accepted by the Messages API
+const SUPPORTED_IMAGE_MEDIA_TYPES: &[&str] =
+    &["image/jpeg", "image/png", "image/gif", "image/webp"];
+
 /// Result information from stream-json result messages
 #[derive(Debug, Clone)]
 pub struct StreamResult {
     pub stop_reason: Option<String>,
+    /// Tokens used by the turn the result completes
+    pub usage: Option<TokenUsageInfo>,
+    /// Cumulative cost of the claude conversation in USD, including earlier turns
+    pub total_cost_usd: Option<f64>,
+}
+
+/// Token usage reported by a partial message event while a turn streams
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamUsage {
+    /// A model request of the turn started (`message_start`), with its usage so far
+    Started(TokenUsageInfo),
+    /// The running model request's usage grew (`message_delta`); counts are cumulative
+    Updated(TokenUsageInfo),
+}
+
+/// A control request emitted by the claude CLI on stdout
+#[derive(Debug, Clone)]
+pub struct ControlRequest {
+    /// Identifier that must be echoed back in the control response
+    pub request_id: String,
+    /// The decoded request payload
+    pub kind: ControlRequestKind,
+}
+
+/// Payload of a control request
+#[derive(Debug, Clone)]
+pub enum ControlRequestKind {
+    /// Claude wants to run a tool and needs a permission decision
+    CanUseTool(ToolPermissionRequest),
+    /// A control request subtype this agent does not handle
+    Unsupported { subtype: String },
+}
+
+/// A tool permission check requested by the claude CLI
+#[derive(Debug, Clone, Deserialize)]
+pub struct ToolPermissionRequest {
+    /// Claude's tool name, e.g. `Bash`, `Edit` or `Write`
+    pub tool_name: String,
+    /// Arguments the tool would be called with
+    #[serde(default)]
+    pub input: JsonValue,
+    /// Id of the tool_use item this check belongs to, when the CLI reports it
+    #[serde(default)]
+    pub tool_use_id: Option<String>,
+}
+
+/// Permission decision returned to the claude CLI for a tool permission check
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "behavior", rename_all = "snake_case")]
+pub enum ToolPermissionDecision {
+    /// Run the tool with the given (possibly unchanged) input
+    Allow {
+        #[serde(rename = "updatedInput")]
+        updated_input: JsonValue,
+    },
+    /// Refuse the tool call; the message is reported to the model as the tool error
+    ///
+    /// With `interrupt`, the CLI also ends the turn instead of letting the model go on.
+    Deny {
+        message: String,
+        #[serde(skip_serializing_if = "std::ops::Not::not")]
+        interrupt: bool,
+    },
 }
 
 /// Protocol translator for converting between ACP and stream-json formats
@@ -36,10 +125,10 @@ pub struct ProtocolTranslator;
 impl ProtocolTranslator {
     /// Convert ACP ContentBlocks to stream-json for claude stdin
     ///
-    /// Currently only supports single text content blocks. The claude CLI's stream-json
-    /// format accepts a simple string for user messages, which limits us to text-only content.
-    /// Complex content arrays (images, audio, etc.) would require the full Messages API format
-    /// which is not supported by the CLI's stream-json stdin interface.
+    /// Processes `content` with a default
+    /// [`ContentBlockProcessor`](crate::content_block_processor::ContentBlockProcessor) and
+    /// translates the result, see [`Self::processed_content_to_stream_json`]. A prompt the
+    /// caller already processed should be translated from its summary instead.
     ///
     /// # Arguments
     /// * `content` - The content blocks to translate
@@ -48,27 +137,66 @@ impl ProtocolTranslator {
     /// A JSON string formatted for stream-json input
     ///
     /// # Errors
-    /// Returns error if content is not a single text block, or if serialization fails
+    /// Returns error if a content block fails processing, or if serialization fails
     pub fn acp_to_stream_json(content: Vec<ContentBlock>) -> Result<String> {
-        let content_str = if content.len() == 1 {
-            if let ContentBlock::Text(text_content) = &content[0] {
-                text_content.text.clone()
-            } else {
-                return Err(AgentError::Internal(
-                    "Only text content blocks are currently supported".to_string(),
-                ));
+        let summary = crate::content_block_processor::ContentBlockProcessor::default()
+            .process_content_blocks(&content)
+            .map_err(|e| AgentError::Protocol(format!("Failed to process content: {}", e)))?;
+        Self::processed_content_to_stream_json(&content, &summary)
+    }
+
+    /// Convert processed ACP ContentBlocks to stream-json for claude stdin
+    ///
+    /// A prompt consisting of a single text block is sent as a plain string. Anything else
+    /// is sent as a Messages API content array built from the decoded content:
+    ///
+    /// - Text blocks become `text` blocks
+    /// - Images in a format the model accepts become base64 `image` blocks
+    /// - Embedded text resources become plain-text `document` blocks
+    /// - Embedded PDF blobs become base64 `document` blocks, text blobs plain-text ones
+    /// - Audio, resource links, unsupported formats and blocks that failed processing are
+    ///   sent as `text` blocks carrying their text representation
+    ///
+    /// # Arguments
+    /// * `content` - The original content blocks
+    /// * `summary` - The result of processing `content` with a
+    ///   [`ContentBlockProcessor`](crate::content_block_processor::ContentBlockProcessor)
+    ///
+    /// # Returns
+    /// A JSON string formatted for stream-json input
+    ///
+    /// # Errors
+    /// Returns error if the summary does not match the content, or if serialization fails
+    pub fn processed_content_to_stream_json(
+        content: &[ContentBlock],
+        summary: &ContentProcessingSummary,
+    ) -> Result<String> {
+        if content.len() != summary.processed_contents.len() {
+            return Err(AgentError::Internal(format!(
+                "Processed content count ({}) does not match content block count ({})",
+                summary.processed_contents.len(),
+                content.len()
+            )));
+        }
+
+        let message_content = match content {
+            [ContentBlock::Text(text_content)] => {
+                UserMessageContent::Text(text_content.text.clone())
             }
-        } else {
-            return Err(AgentError::Internal(
-                "Only single content blocks are currently supported".to_string(),
-            ));
+            _ => UserMessageContent::Blocks(
+                content
+                    .iter()
+                    .zip(&summary.processed_contents)
+                    .map(|(block, processed)| Self::to_user_content_block(block, processed))
+                    .collect(),
+            ),
         };
 
         let message = StreamJsonUserMessage {
             r#type: "user".to_string(),
             message: UserMessage {
                 role: "user".to_string(),
-                content: content_str,
+                content: message_content,
             },
         };
 
@@ -77,12 +205,83 @@ impl ProtocolTranslator {
         })
     }
 
+    /// Convert a single processed content block to a Messages API content block
+    fn to_user_content_block(
+        block: &ContentBlock,
+        processed: &ProcessedContent,
+    ) -> UserContentBlock {
+        let text_fallback = || UserContentBlock::Text {
+            text: processed.text_representation.clone(),
+        };
+
+        if processed.metadata.contains_key("processing_failed") {
+            return text_fallback();
+        }
+
+        match (block, &processed.content_type, &processed.binary_data) {
+            (ContentBlock::Text(text_content), _, _) => UserContentBlock::Text {
+                text: text_content.text.clone(),
+            },
+            (_, ProcessedContentType::Image { mime_type }, Some(data))
+                if SUPPORTED_IMAGE_MEDIA_TYPES.contains(&mime_type.as_str()) =>
+            {
+                UserContentBlock::Image {
+                    source: ContentSource::base64(mime_type, data),
+                }
+            }
+            (
+                ContentBlock::Resource(resource),
+                ProcessedContentType::EmbeddedResource { uri, .. },
+                _,
+            ) => match &resource.resource {
+                EmbeddedResourceResource::TextResourceContents(text_resource) => {
+                    UserContentBlock::Document {
+                        source: ContentSource::Text {
+                            media_type: "text/plain".to_string(),
+                            data: text_resource.text.clone(),
+                        },
+                        title: uri.clone(),
+                    }
+                }
+                EmbeddedResourceResource::BlobResourceContents(blob_resource) => {
+                    let mime_type = blob_resource.mime_type.as_deref().unwrap_or_default();
+                    match &processed.binary_data {
+                        Some(data) if mime_type == "application/pdf" => {
+                            UserContentBlock::Document {
+                                source: ContentSource::base64(mime_type, data),
+                                title: uri.clone(),
+                            }
+                        }
+                        Some(data) if SUPPORTED_IMAGE_MEDIA_TYPES.contains(&mime_type) => {
+                            UserContentBlock::Image {
+                                source: ContentSource::base64(mime_type, data),
+                            }
+                        }
+                        Some(data) if mime_type.starts_with("text/") => {
+                            match std::str::from_utf8(data) {
+                                Ok(text) => UserContentBlock::Document {
+                                    source: ContentSource::Text {
+                                        media_type: "text/plain".to_string(),
+                                        data: text.to_string(),
+                                    },
+                                    title: uri.clone(),
+                                },
+                                Err(_) => text_fallback(),
+                            }
+                        }
+                        _ => text_fallback(),
+                    }
+                }
+            },
+            _ => text_fallback(),
+        }
+    }
+
     /// Convert stream-json line from claude to ACP SessionNotification
     ///
-    /// Converts a single line of stream-json output from the claude CLI into an ACP notification.
-    /// Note: The claude CLI can output messages with multiple content blocks (e.g., text + tool_use),
-    /// but ACP SessionUpdate::AgentMessageChunk only supports a single ContentBlock per notification.
-    /// When multiple content items are present, only the first is returned, with a debug log for the rest.
+    /// Convenience wrapper around [`Self::stream_json_to_acp_notifications`] for callers that
+    /// only handle one notification per line. When a line produces several notifications
+    /// (e.g. a tool call followed by its in-progress update), only the first is returned.
     ///
     /// # Arguments
     /// * `line` - A single line of JSON from claude stdout
@@ -96,6 +295,33 @@ impl ProtocolTranslator {
         line: &str,
         session_id: &SessionId,
     ) -> Result<Option<SessionNotification>> {
+        Ok(Self::stream_json_to_acp_notifications(line, session_id)?
+            .into_iter()
+            .next())
+    }
+
+    /// Convert stream-json line from claude to ACP SessionNotifications
+    ///
+    /// Converts a single line of stream-json output from the claude CLI into ACP notifications:
+    ///
+    /// - `stream_event` text deltas become `AgentMessageChunk` notifications
+    /// - `tool_use` items in assistant messages become a `ToolCall` notification with
+    ///   `pending` status, immediately followed by a `ToolCallUpdate` to `in_progress`,
+    ///   because the claude CLI starts executing the tool as soon as it reports it
+    /// - `tool_result` items in user messages become a `ToolCallUpdate` to `completed`
+    ///   or `failed` carrying the tool output
+    ///
+    /// # Arguments
+    /// * `line` - A single line of JSON from claude stdout
+    /// * `session_id` - The session ID for the notifications
+    ///
+    /// # Returns
+    /// * `Ok(notifications)` - The notifications for this line, possibly empty
+    /// * `Err(...)` - Parse error or invalid message structure
+    pub fn stream_json_to_acp_notifications(
+        line: &str,
+        session_id: &SessionId,
+    ) -> Result<Vec<SessionNotification>> {
         // Parse the JSON line
         let parsed: JsonValue = serde_json::from_str(line).map_err(|e| {
             let truncated_line: String = line.chars().take(100).collect();
@@ -110,6 +336,12 @@ impl ProtocolTranslator {
             AgentError::Internal("Missing 'type' field in stream-json".to_string())
         })?;
 
+        let notification = |update: SessionUpdate| SessionNotification {
+            session_id: session_id.clone(),
+            update,
+            meta: None,
+        };
+
         match msg_type {
             "assistant" => {
                 // When using --include-partial-messages, the claude CLI sends both:
@@ -121,7 +353,6 @@ impl ProtocolTranslator {
                 //
                 // For TOOL_USE content: we MUST process assistant messages because
                 // tool_use content does NOT come through stream_events.
-                
                 let assistant_msg: StreamJsonAssistantMessage = serde_json::from_value(parsed)
                     .map_err(|e| {
                         AgentError::Internal(format!("Failed to parse assistant message: {}", e))
@@ -130,48 +361,84 @@ impl ProtocolTranslator {
                 // Validate message type
                 assistant_msg.validate()?;
 
-                // Check first content item to determine if this is tool use
-                if let Some(content_item) = assistant_msg.message.content.first() {
+                let mut notifications = Vec::new();
+                for content_item in &assistant_msg.message.content {
                     match content_item {
-                        ContentItem::Text { text, .. } => {
+                        ContentItem::Text { text } => {
                             // Ignore text content - already received via stream_events
-                            tracing::debug!("🚫 ASSISTANT text message IGNORED ({}chars): '{}'", text.len(), text.chars().take(50).collect::<String>());
-                            Ok(None)
+                            tracing::debug!(
+                                "🚫 ASSISTANT text message IGNORED ({}chars): '{}'",
+                                text.len(),
+                                text.chars().take(50).collect::<String>()
+                            );
                         }
-                        ContentItem::ToolUse { id, name, .. } => {
+                        ContentItem::ToolUse { id, name, input } => {
                             // Process tool use - doesn't come through stream_events
                             tracing::debug!("🔧 ASSISTANT tool_use message: {} ({})", name, id);
-                            let content_block = Self::parse_content_item(content_item)?;
-                            Ok(Some(SessionNotification {
-                                session_id: session_id.clone(),
-                                update: SessionUpdate::AgentMessageChunk {
-                                    content: content_block,
-                                },
-                                meta: None,
-                            }))
+                            let (tool_call, started) = Self::tool_use_to_acp(id, name, input);
+                            notifications.push(notification(SessionUpdate::ToolCall(tool_call)));
+                            notifications
+                                .push(notification(SessionUpdate::ToolCallUpdate(started)));
+                        }
+                        ContentItem::Other => {
+                            tracing::debug!("Ignoring unsupported assistant content item");
                         }
                     }
-                } else {
-                    Ok(None)
                 }
+                Ok(notifications)
             }
             "user" => {
-                // User messages from keepalive pings should be filtered.
-                // We send empty user messages periodically to force the claude CLI to flush
-                // its stdout buffer (solving a buffering bug), but these should not be
-                // forwarded to clients.
-                tracing::debug!("Received user message (keepalive ping, filtered)");
-                Ok(None)
+                // User messages either carry tool results for earlier tool_use items, or are
+                // echoes of keepalive pings. We send empty user messages periodically to force
+                // the claude CLI to flush its stdout buffer (solving a buffering bug), and those
+                // should not be forwarded to clients.
+                let tool_results: Vec<ToolResultItem> = parsed
+                    .get("message")
+                    .and_then(|m| m.get("content"))
+                    .and_then(|c| c.as_array())
+                    .map(|items| {
+                        items
+                            .iter()
+                            .filter(|item| {
+                                item.get("type").and_then(|t| t.as_str()) == Some("tool_result")
+                            })
+                            .filter_map(|item| serde_json::from_value(item.clone()).ok())
+                            .collect()
+                    })
+                    .unwrap_or_default();
+
+                if tool_results.is_empty() {
+                    tracing::debug!("Received user message (keepalive ping, filtered)");
+                }
+
+                Ok(tool_results
+                    .iter()
+                    .map(|result| {
+                        tracing::debug!(
+                            "🔧 USER tool_result for {} (error: {})",
+                            result.tool_use_id,
+                            result.is_error
+                        );
+                        notification(SessionUpdate::ToolCallUpdate(Self::tool_result_to_acp(
+                            result,
+                        )))
+                    })
+                    .collect())
             }
             "system" => {
                 // System messages are metadata only, don't notify
                 tracing::debug!("Received system message (metadata only)");
-                Ok(None)
+                Ok(Vec::new())
             }
             "result" => {
                 // Result messages are metadata only, don't notify
                 tracing::debug!("Received result message (metadata only)");
-                Ok(None)
+                Ok(Vec::new())
+            }
+            "control_request" | "control_cancel_request" | "control_response" => {
+                // Control messages are answered by the client, not forwarded as updates
+                tracing::debug!("Received {} (handled by control protocol)", msg_type);
+                Ok(Vec::new())
             }
             "stream_event" => {
                 // Stream events contain partial message chunks (when --include-partial-messages is used)
@@ -185,74 +452,112 @@ impl ProtocolTranslator {
                                 .and_then(|d| d.get("text"))
                                 .and_then(|t| t.as_str())
                             {
-                                tracing::debug!("📨 STREAM_EVENT chunk: {} chars: '{}'", text.len(), text.chars().take(50).collect::<String>());
-                                return Ok(Some(SessionNotification {
-                                    session_id: session_id.clone(),
-                                    update: SessionUpdate::AgentMessageChunk {
-                                        content: ContentBlock::Text(TextContent {
-                                            text: text.to_string(),
-                                            annotations: None,
-                                            meta: None,
-                                        }),
-                                    },
-                                    meta: None,
-                                }));
+                                tracing::debug!(
+                                    "📨 STREAM_EVENT chunk: {} chars: '{}'",
+                                    text.len(),
+                                    text.chars().take(50).collect::<String>()
+                                );
+                                return Ok(vec![notification(SessionUpdate::AgentMessageChunk {
+                                    content: ContentBlock::Text(TextContent {
+                                        text: text.to_string(),
+                                        annotations: None,
+                                        meta: None,
+                                    }),
+                                })]);
                             }
                         }
                     }
                 }
                 // Ignore other stream_event types (message_start, content_block_start, etc.)
                 tracing::debug!("Received stream_event (non-delta, ignored)");
-                Ok(None)
+                Ok(Vec::new())
             }
             _ => {
                 tracing::warn!("Unknown stream-json message type: {}", msg_type);
-                Ok(None)
+                Ok(Vec::new())
             }
         }
     }
 
-    /// Convert a content item from stream-json to ACP ContentBlock
+    /// Convert a stream-json tool_use item to an ACP tool call and its in-progress update
     ///
-    /// Note: Tool use is converted to text representation because ACP ContentBlock
-    /// does not currently have a ToolUse variant. The ACP protocol defines ContentBlock
-    /// as: Text, Image, Audio, ResourceLink, and Resource only.
-    ///
-    /// The text representation uses single-line JSON format to match stream-json conventions.
-    fn parse_content_item(item: &ContentItem) -> Result<ContentBlock> {
-        match item {
-            ContentItem::Text { text, .. } => Ok(ContentBlock::Text(TextContent {
-                text: text.clone(),
-                annotations: None,
-                meta: None,
-            })),
-            ContentItem::ToolUse {
-                id, name, input, ..
-            } => {
-                // ACP ContentBlock does not have a ToolUse variant, so convert to text
-                let tool_json = serde_json::json!({
-                    "type": "tool_use",
-                    "id": id,
-                    "name": name,
-                    "input": input,
-                });
-                Ok(ContentBlock::Text(TextContent {
-                    text: serde_json::to_string(&tool_json).unwrap_or_default(),
-                    annotations: None,
-                    meta: None,
-                }))
-            }
+    /// The claude CLI tool_use id is used as the ACP tool call id so that the matching
+    /// tool_result can be correlated without any additional state. The CLI tool name is
+    /// reported in the tool call meta as `tool_name`.
+    fn tool_use_to_acp(id: &str, name: &str, input: &JsonValue) -> (ToolCall, ToolCallUpdate) {
+        let mut report = ToolCallReport::new(
+            id.to_string(),
+            ToolCallReport::generate_title(name, input),
+            ToolKind::classify_tool(name, input),
+            name.to_string(),
+        );
+        report.set_raw_input(input.clone());
+        for location in ToolCallReport::extract_file_locations(name, input) {
+            report.add_location(location);
         }
+
+        let mut tool_call = report.to_acp_tool_call();
+        tool_call.meta = Some(serde_json::json!({ "tool_name": name }));
+
+        // The tool call was just reported in full; the update only needs the new status
+        report.mark_state_sent();
+        report.update_status(ToolCallStatus::InProgress);
+
+        (tool_call, report.to_acp_tool_call_update())
     }
 
-    /// Parse result message to extract stop_reason
+    /// Convert a stream-json tool_result item to a final ACP tool call update
+    fn tool_result_to_acp(result: &ToolResultItem) -> ToolCallUpdate {
+        let status = if result.is_error {
+            ToolCallStatus::Failed
+        } else {
+            ToolCallStatus::Completed
+        };
+
+        let content = match &result.content {
+            JsonValue::String(text) => vec![text.clone()],
+            JsonValue::Array(items) => items
+                .iter()
+                .filter_map(|item| item.get("text").and_then(|t| t.as_str()))
+                .map(str::to_string)
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        ToolCallUpdate {
+            id: ToolCallId(result.tool_use_id.clone().into()),
+            fields: ToolCallUpdateFields {
+                status: Some(status.to_acp_status()),
+                content: Some(
+                    content
+                        .into_iter()
+                        .map(|text| {
+                            ToolCallContent::Content {
+                                content: ContentBlock::Text(TextContent {
+                                    text,
+                                    annotations: None,
+                                    meta: None,
+                                }),
+                            }
+                            .to_acp_content()
+                        })
+                        .collect(),
+                ),
+                raw_output: Some(result.content.clone()),
+                ..Default::default()
+            },
+            meta: None,
+        }
+    }
+
+    /// Parse result message to extract stop_reason, token usage and cost
     ///
     /// # Arguments
     /// * `line` - A single line of JSON from claude stdout
     ///
     /// # Returns
     /// * `Ok(Some(StreamResult))` - Successfully parsed result message
-    /// * `Ok(None)` - Not a result message or no stop_reason present
+    /// * `Ok(None)` - Not a result message
     /// * `Err(...)` - Parse error
     pub fn parse_result_message(line: &str) -> Result<Option<StreamResult>> {
         let parsed: JsonValue = serde_json::from_str(line)
@@ -263,13 +568,185 @@ impl ProtocolTranslator {
                 .get("stop_reason")
                 .and_then(|s| s.as_str())
                 .map(|s| s.to_string());
-
-            return Ok(Some(StreamResult { stop_reason }));
+            let usage = parsed
+                .get("usage")
+                .and_then(|usage| serde_json::from_value(usage.clone()).ok());
+            let total_cost_usd = parsed.get("total_cost_usd").and_then(|v| v.as_f64());
+
+            return Ok(Some(StreamResult {
+                stop_reason,
+                usage,
+                total_cost_usd,
+            }));
         }
 
         Ok(None)
     }
 
+    /// Parse the token usage carried by a `message_start` or `message_delta` stream event
+    ///
+    /// # Arguments
+    /// * `line` - A single line of JSON from claude stdout
+    ///
+    /// # Returns
+    /// The reported usage, or `None` for other messages and unparseable lines
+    pub fn parse_stream_usage(line: &str) -> Option<StreamUsage> {
+        let parsed: JsonValue = serde_json::from_str(line).ok()?;
+        if parsed.get("type").and_then(|v| v.as_str()) != Some("stream_event") {
+            return None;
+        }
+
+        let event = parsed.get("event")?;
+        match event.get("type").and_then(|v| v.as_str())? {
+            "message_start" => {
+                let usage = event.get("message")?.get("usage")?;
+                serde_json::from_value(usage.clone())
+                    .ok()
+                    .map(StreamUsage::Started)
+            }
+            "message_delta" => {
+                let usage = event.get("usage")?;
+                serde_json::from_value(usage.clone())
+                    .ok()
+                    .map(StreamUsage::Updated)
+            }
+            _ => None,
+        }
+    }
+
+    /// Extract claude's own conversation id from a stream-json message
+    ///
+    /// Only the `result` message is considered: it carries the `session_id` that
+    /// `claude --resume` accepts once a turn of that conversation has been saved. The
+    /// `system` init message names the conversation before anything can be resumed.
+    ///
+    /// # Arguments
+    /// * `line` - A single line of JSON from claude stdout
+    ///
+    /// # Returns
+    /// The claude session id, or `None` for other messages and unparseable lines
+    pub fn parse_claude_session_id(line: &str) -> Option<String> {
+        let parsed: JsonValue = serde_json::from_str(line).ok()?;
+
+        match parsed.get("type").and_then(|v| v.as_str()) {
+            Some("result") => parsed
+                .get("session_id")
+                .and_then(|v| v.as_str())
+                .filter(|id| !id.is_empty())
+                .map(|id| id.to_string()),
+            _ => None,
+        }
+    }
+
+    /// Parse a control request sent by claude on stdout
+    ///
+    /// # Arguments
+    /// * `line` - A single line of JSON from claude stdout
+    ///
+    /// # Returns
+    /// * `Ok(Some(ControlRequest))` - Successfully parsed control request
+    /// * `Ok(None)` - Not a control request
+    /// * `Err(...)` - Parse error or malformed control request
+    pub fn parse_control_request(line: &str) -> Result<Option<ControlRequest>> {
+        let parsed: JsonValue = serde_json::from_str(line).map_err(|e| {
+            AgentError::Internal(format!("Failed to parse control request: {}", e))
+        })?;
+
+        if parsed.get("type").and_then(|v| v.as_str()) != Some("control_request") {
+            return Ok(None);
+        }
+
+        let message: StreamJsonControlRequest = serde_json::from_value(parsed).map_err(|e| {
+            AgentError::Protocol(format!("Malformed control request: {}", e))
+        })?;
+        let subtype = message
+            .request
+            .get("subtype")
+            .and_then(|v| v.as_str())
+            .unwrap_or_default()
+            .to_string();
+
+        let kind = match subtype.as_str() {
+            "can_use_tool" => ControlRequestKind::CanUseTool(
+                serde_json::from_value(message.request).map_err(|e| {
+                    AgentError::Protocol(format!("Malformed can_use_tool request: {}", e))
+                })?,
+            ),
+            _ => ControlRequestKind::Unsupported { subtype },
+        };
+
+        Ok(Some(ControlRequest {
+            request_id: message.request_id,
+            kind,
+        }))
+    }
+
+    /// Convert a tool permission decision to a control response for claude stdin
+    ///
+    /// # Arguments
+    /// * `request_id` - The id of the control request being answered
+    /// * `decision` - The permission decision for the tool call
+    ///
+    /// # Errors
+    /// Returns error if serialization fails
+    pub fn permission_decision_to_stream_json(
+        request_id: &str,
+        decision: &ToolPermissionDecision,
+    ) -> Result<String> {
+        let response = serde_json::to_value(decision).map_err(|e| {
+            AgentError::Internal(format!("Failed to serialize permission decision: {}", e))
+        })?;
+
+        Self::control_response_to_stream_json(ControlResponse::Success {
+            request_id: request_id.to_string(),
+            response,
+        })
+    }
+
+    /// Convert a control request failure to a control response for claude stdin
+    ///
+    /// # Arguments
+    /// * `request_id` - The id of the control request being answered
+    /// * `error` - Description of why the request could not be handled
+    ///
+    /// # Errors
+    /// Returns error if serialization fails
+    pub fn control_error_to_stream_json(request_id: &str, error: &str) -> Result<String> {
+        Self::control_response_to_stream_json(ControlResponse::Error {
+            request_id: request_id.to_string(),
+            error: error.to_string(),
+        })
+    }
+
+    /// Build a control request for claude stdin that stops the running turn
+    ///
+    /// Claude answers with a control response and ends the turn with a result message.
+    ///
+    /// # Errors
+    /// Returns error if serialization fails
+    pub fn interrupt_request_to_stream_json(request_id: &str) -> Result<String> {
+        let message = serde_json::json!({
+            "type": "control_request",
+            "request_id": request_id,
+            "request": {"subtype": "interrupt"},
+        });
+
+        serde_json::to_string(&message).map_err(|e| {
+            AgentError::Internal(format!("Failed to serialize control request: {}", e))
+        })
+    }
+
+    fn control_response_to_stream_json(response: ControlResponse) -> Result<String> {
+        let message = StreamJsonControlResponse {
+            r#type: "control_response".to_string(),
+            response,
+        };
+
+        serde_json::to_string(&message).map_err(|e| {
+            AgentError::Internal(format!("Failed to serialize control response: {}", e))
+        })
+    }
+
     /// Convert tool result to stream-json for claude stdin
     ///
     /// # Arguments
@@ -314,7 +791,46 @@ struct StreamJsonUserMessage {
 #[derive(Serialize, Deserialize)]
 struct UserMessage {
     role: String,
-    content: String,
+    content: UserMessageContent,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum UserMessageContent {
+    Text(String),
+    Blocks(Vec<UserContentBlock>),
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum UserContentBlock {
+    Text {
+        text: String,
+    },
+    Image {
+        source: ContentSource,
+    },
+    Document {
+        source: ContentSource,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        title: Option<String>,
+    },
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ContentSource {
+    Base64 { media_type: String, data: String },
+    Text { media_type: String, data: String },
+}
+
+impl ContentSource {
+    fn base64(media_type: &str, data: &[u8]) -> Self {
+        Self::Base64 {
+            media_type: media_type.to_string(),
+            data: general_purpose::STANDARD.encode(data),
+        }
+    }
 }
 
 #[derive(Deserialize)]
@@ -352,6 +868,17 @@ enum ContentItem {
         name: String,
         input: JsonValue,
     },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Deserialize)]
+struct ToolResultItem {
+    tool_use_id: String,
+    #[serde(default)]
+    content: JsonValue,
+    #[serde(default)]
+    is_error: bool,
 }
 
 #[derive(Serialize)]
@@ -379,9 +906,35 @@ struct ToolResultTextContent {
     text: String,
 }
 
+#[derive(Deserialize)]
+struct StreamJsonControlRequest {
+    request_id: String,
+    request: JsonValue,
+}
+
+#[derive(Serialize)]
+struct StreamJsonControlResponse {
+    r#type: String,
+    response: ControlResponse,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "subtype", rename_all = "snake_case")]
+enum ControlResponse {
+    Success {
+        request_id: String,
+        response: JsonValue,
+    },
+    Error {
+        request_id: String,
+        error: String,
+    },
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::content_block_processor::ContentBlockProcessor;
 
     #[test]
     fn test_acp_to_stream_json_simple_text() {
@@ -403,6 +956,116 @@ mod tests {
         assert_eq!(parsed["message"]["content"], "Hello, world!");
     }
 
+    #[test]
+    fn test_acp_to_stream_json_text_and_image() {
+        // Test: Text plus an image produces a Messages API content array
+        let png_data = "iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR42mP8/5+hHgAHggJ/PchI7wAAAABJRU5ErkJggg==";
+        let content = vec![
+            ContentBlock::Text(TextContent {
+                text: "What is in this screenshot?".to_string(),
+                annotations: None,
+                meta: None,
+            }),
+            ContentBlock::Image(agent_client_protocol::ImageContent {
+                data: png_data.to_string(),
+                mime_type: "image/png".to_string(),
+                uri: None,
+                annotations: None,
+                meta: None,
+            }),
+        ];
+
+        let json_str = ProtocolTranslator::acp_to_stream_json(content).unwrap();
+        let parsed: serde_json::Value = serde_json::from_str(&json_str).unwrap();
+
+        assert_eq!(parsed["type"], "user");
+        let blocks = parsed["message"]["content"].as_array().unwrap();
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[0]["type"], "text");
+        assert_eq!(blocks[0]["text"], "What is in this screenshot?");
+        assert_eq!(blocks[1]["type"], "image");
+        assert_eq!(blocks[1]["source"]["type"], "base64");
+        assert_eq!(blocks[1]["source"]["media_type"], "image/png");
+        assert_eq!(blocks[1]["source"]["data"], png_data);
+    }
+
+    #[test]
+    fn test_acp_to_stream_json_resources() {
+        // Test: Embedded resources become documents, resource links stay textual
+        use agent_client_protocol::{
+            BlobResourceContents, EmbeddedResource, ResourceLink, TextResourceContents,
+        };
+
+        let content = vec![
+            ContentBlock::Resource(EmbeddedResource {
+                resource: EmbeddedResourceResource::TextResourceContents(TextResourceContents {
+                    uri: "file:///project/src/main.rs".to_string(),
+                    text: "fn main() {}".to_string(),
+                    mime_type: Some("text/x-rust".to_string()),
+                    meta: None,
+                }),
+                annotations: None,
+                meta: None,
+            }),
+            ContentBlock::Resource(EmbeddedResource {
+                resource: EmbeddedResourceResource::BlobResourceContents(BlobResourceContents {
+                    uri: "file:///project/spec.pdf".to_string(),
+                    blob: "JVBERi0xLjQK".to_string(),
+                    mime_type: Some("application/pdf".to_string()),
+                    meta: None,
+                }),
+                annotations: None,
+                meta: None,
+            }),
+            ContentBlock::ResourceLink(ResourceLink {
+                uri: "file:///project/README.md".to_string(),
+                name: "README.md".to_string(),
+                description: None,
+                mime_type: None,
+                size: None,
+                title: None,
+                annotations: None,
+                meta: None,
+            }),
+        ];
+
+        let json_str = ProtocolTranslator::acp_to_stream_json(content).unwrap();
+        let parsed: serde_json::Value = serde_json::from_str(&json_str).unwrap();
+        let blocks = parsed["message"]["content"].as_array().unwrap();
+        assert_eq!(blocks.len(), 3);
+
+        assert_eq!(blocks[0]["type"], "document");
+        assert_eq!(blocks[0]["source"]["type"], "text");
+        assert_eq!(blocks[0]["source"]["data"], "fn main() {}");
+        assert_eq!(blocks[0]["title"], "file:///project/src/main.rs");
+
+        assert_eq!(blocks[1]["type"], "document");
+        assert_eq!(blocks[1]["source"]["type"], "base64");
+        assert_eq!(blocks[1]["source"]["media_type"], "application/pdf");
+
+        assert_eq!(blocks[2]["type"], "text");
+        assert!(blocks[2]["text"]
+            .as_str()
+            .unwrap()
+            .contains("file:///project/README.md"));
+    }
+
+    #[test]
+    fn test_processed_content_to_stream_json_mismatched_summary() {
+        // Test: A summary that does not correspond to the content is rejected
+        let content = vec![ContentBlock::Text(TextContent {
+            text: "Hello".to_string(),
+            annotations: None,
+            meta: None,
+        })];
+        let summary = ContentBlockProcessor::default()
+            .process_content_blocks(&[])
+            .unwrap();
+
+        let result = ProtocolTranslator::processed_content_to_stream_json(&content, &summary);
+        assert!(result.is_err());
+    }
+
     #[test]
     fn test_stream_json_to_acp_assistant_text() {
         // Test: Assistant text messages should be filtered out (duplicate prevention)
@@ -454,7 +1117,10 @@ mod tests {
 
         let result = ProtocolTranslator::stream_json_to_acp(line, &session_id);
         assert!(result.is_ok());
-        assert!(result.unwrap().is_none(), "Keepalive ping messages should be filtered");
+        assert!(
+            result.unwrap().is_none(),
+            "Keepalive ping messages should be filtered"
+        );
     }
 
     #[test]
@@ -562,10 +1228,70 @@ mod tests {
         assert!(result.unwrap().is_none());
     }
 
+    #[test]
+    fn test_parse_result_message_usage_and_cost() {
+        let line = r#"{"type":"result","subtype":"success","total_cost_usd":0.0208864,"usage":{"input_tokens":619,"cache_creation_input_tokens":5120,"cache_read_input_tokens":33792,"output_tokens":9,"service_tier":"standard"}}"#;
+        let result = ProtocolTranslator::parse_result_message(line)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(result.total_cost_usd, Some(0.0208864));
+        let usage = result.usage.unwrap();
+        assert_eq!(usage.input_tokens, 619);
+        assert_eq!(usage.cache_creation_input_tokens, 5120);
+        assert_eq!(usage.cache_read_input_tokens, 33792);
+        assert_eq!(usage.output_tokens, 9);
+        assert_eq!(usage.total(), 619 + 5120 + 9);
+    }
+
+    #[test]
+    fn test_parse_claude_session_id() {
+        // A conversation that has not finished a turn cannot be resumed yet
+        let init = r#"{"type":"system","subtype":"init","cwd":"/path","session_id":"abc-123","tools":[]}"#;
+        assert_eq!(ProtocolTranslator::parse_claude_session_id(init), None);
+
+        let result = r#"{"type":"result","subtype":"success","session_id":"abc-456","total_cost_usd":0.1}"#;
+        assert_eq!(
+            ProtocolTranslator::parse_claude_session_id(result).as_deref(),
+            Some("abc-456")
+        );
+
+        let event = r#"{"type":"stream_event","session_id":"abc-123","event":{}}"#;
+        assert_eq!(ProtocolTranslator::parse_claude_session_id(event), None);
+        assert_eq!(ProtocolTranslator::parse_claude_session_id("not json"), None);
+    }
+
+    #[test]
+    fn test_parse_stream_usage() {
+        let start = r#"{"type":"stream_event","event":{"type":"message_start","message":{"id":"msg_1","usage":{"input_tokens":12,"cache_creation_input_tokens":300,"cache_read_input_tokens":4000,"output_tokens":1}}}}"#;
+        assert_eq!(
+            ProtocolTranslator::parse_stream_usage(start),
+            Some(StreamUsage::Started(TokenUsageInfo {
+                input_tokens: 12,
+                output_tokens: 1,
+                cache_creation_input_tokens: 300,
+                cache_read_input_tokens: 4000,
+            }))
+        );
+
+        let delta = r#"{"type":"stream_event","event":{"type":"message_delta","delta":{"stop_reason":"end_turn"},"usage":{"output_tokens":57}}}"#;
+        assert_eq!(
+            ProtocolTranslator::parse_stream_usage(delta),
+            Some(StreamUsage::Updated(TokenUsageInfo {
+                output_tokens: 57,
+                ..Default::default()
+            }))
+        );
+
+        let text = r#"{"type":"stream_event","event":{"type":"content_block_delta","delta":{"text":"Hi"}}}"#;
+        assert_eq!(ProtocolTranslator::parse_stream_usage(text), None);
+        assert_eq!(ProtocolTranslator::parse_stream_usage("not json"), None);
+    }
+
     #[test]
     fn test_stream_json_to_acp_assistant_tool_use() {
-        // Test: Convert assistant tool use message from stream-json to ACP
-        let line = r#"{"type":"assistant","message":{"content":[{"type":"tool_use","id":"toolu_123","name":"read_file","input":{"path":"test.txt"}}]}}"#;
+        // Test: Convert assistant tool use message from stream-json to an ACP tool call
+        let line = r#"{"type":"assistant","message":{"content":[{"type":"tool_use","id":"toolu_123","name":"Read","input":{"file_path":"/project/test.txt"}}]}}"#;
         let session_id = SessionId("test_session".into());
 
         let result = ProtocolTranslator::stream_json_to_acp(line, &session_id);
@@ -576,17 +1302,106 @@ mod tests {
 
         let notification = notification.unwrap();
         match notification.update {
-            SessionUpdate::AgentMessageChunk { content } => {
-                if let ContentBlock::Text(text) = content {
-                    // Tool use is temporarily converted to text
-                    assert!(text.text.contains("tool_use"));
-                    assert!(text.text.contains("toolu_123"));
-                    assert!(text.text.contains("read_file"));
-                } else {
-                    panic!("Expected text content block");
+            SessionUpdate::ToolCall(tool_call) => {
+                assert_eq!(tool_call.id.0.as_ref(), "toolu_123");
+                assert_eq!(tool_call.title, "Reading test.txt");
+                assert_eq!(tool_call.kind, agent_client_protocol::ToolKind::Read);
+                assert_eq!(
+                    tool_call.status,
+                    agent_client_protocol::ToolCallStatus::Pending
+                );
+                assert_eq!(tool_call.locations.len(), 1);
+                assert_eq!(
+                    tool_call.raw_input,
+                    Some(serde_json::json!({"file_path": "/project/test.txt"}))
+                );
+                assert_eq!(tool_call.meta.unwrap()["tool_name"], "Read");
+            }
+            _ => panic!("Expected ToolCall"),
+        }
+    }
+
+    #[test]
+    fn test_tool_use_reports_pending_then_in_progress() {
+        // Test: A tool_use produces the tool call followed by an in_progress status update
+        let line = r#"{"type":"assistant","message":{"content":[{"type":"tool_use","id":"toolu_789","name":"Bash","input":{"command":"ls"}}]}}"#;
+        let session_id = SessionId("test_session".into());
+
+        let notifications =
+            ProtocolTranslator::stream_json_to_acp_notifications(line, &session_id).unwrap();
+        assert_eq!(notifications.len(), 2);
+
+        assert!(matches!(
+            &notifications[0].update,
+            SessionUpdate::ToolCall(tool_call)
+                if tool_call.kind == agent_client_protocol::ToolKind::Execute
+        ));
+        match &notifications[1].update {
+            SessionUpdate::ToolCallUpdate(update) => {
+                assert_eq!(update.id.0.as_ref(), "toolu_789");
+                assert_eq!(
+                    update.fields.status,
+                    Some(agent_client_protocol::ToolCallStatus::InProgress)
+                );
+                // Only the status changed since the tool call was reported
+                assert!(update.fields.title.is_none());
+                assert!(update.fields.raw_input.is_none());
+            }
+            _ => panic!("Expected ToolCallUpdate"),
+        }
+    }
+
+    #[test]
+    fn test_tool_result_completes_tool_call() {
+        // Test: A tool_result user message completes the matching tool call
+        let line = r#"{"type":"user","message":{"role":"user","content":[{"type":"tool_result","tool_use_id":"toolu_123","content":[{"type":"text","text":"file contents"}]}]}}"#;
+        let session_id = SessionId("test_session".into());
+
+        let notifications =
+            ProtocolTranslator::stream_json_to_acp_notifications(line, &session_id).unwrap();
+        assert_eq!(notifications.len(), 1);
+
+        match &notifications[0].update {
+            SessionUpdate::ToolCallUpdate(update) => {
+                assert_eq!(update.id.0.as_ref(), "toolu_123");
+                assert_eq!(
+                    update.fields.status,
+                    Some(agent_client_protocol::ToolCallStatus::Completed)
+                );
+                let content = update.fields.content.as_ref().unwrap();
+                assert_eq!(content.len(), 1);
+                match &content[0] {
+                    agent_client_protocol::ToolCallContent::Content {
+                        content: ContentBlock::Text(text),
+                    } => assert_eq!(text.text, "file contents"),
+                    _ => panic!("Expected text content"),
                 }
+                assert!(update.fields.raw_output.is_some());
             }
-            _ => panic!("Expected AgentMessageChunk"),
+            _ => panic!("Expected ToolCallUpdate"),
+        }
+    }
+
+    #[test]
+    fn test_tool_result_error_fails_tool_call() {
+        // Test: A tool_result flagged as an error fails the matching tool call
+        let line = r#"{"type":"user","message":{"role":"user","content":[{"type":"tool_result","tool_use_id":"toolu_456","content":"No such file","is_error":true}]}}"#;
+        let session_id = SessionId("test_session".into());
+
+        let notifications =
+            ProtocolTranslator::stream_json_to_acp_notifications(line, &session_id).unwrap();
+        assert_eq!(notifications.len(), 1);
+
+        match &notifications[0].update {
+            SessionUpdate::ToolCallUpdate(update) => {
+                assert_eq!(update.id.0.as_ref(), "toolu_456");
+                assert_eq!(
+                    update.fields.status,
+                    Some(agent_client_protocol::ToolCallStatus::Failed)
+                );
+                assert_eq!(update.fields.content.as_ref().unwrap().len(), 1);
+            }
+            _ => panic!("Expected ToolCallUpdate"),
         }
     }
 
@@ -655,17 +1470,11 @@ mod tests {
 
         let notification = notification.unwrap();
         match notification.update {
-            SessionUpdate::AgentMessageChunk { content } => {
-                if let ContentBlock::Text(text) = content {
-                    // Tool use is converted to JSON text
-                    assert!(text.text.contains("tool_use"));
-                    assert!(text.text.contains("toolu_456"));
-                    assert!(text.text.contains("bash"));
-                } else {
-                    panic!("Expected text content block");
-                }
+            SessionUpdate::ToolCall(tool_call) => {
+                assert_eq!(tool_call.id.0.as_ref(), "toolu_456");
+                assert_eq!(tool_call.meta.unwrap()["tool_name"], "bash");
             }
-            _ => panic!("Expected AgentMessageChunk"),
+            _ => panic!("Expected ToolCall"),
         }
     }
 
@@ -679,15 +1488,22 @@ mod tests {
         let chunk1 = r#"{"type":"stream_event","event":{"type":"content_block_delta","delta":{"text":"Hello"}}}"#;
         let result1 = ProtocolTranslator::stream_json_to_acp(chunk1, &session_id);
         assert!(result1.is_ok());
-        assert!(result1.unwrap().is_some(), "Expected chunk1 to be processed");
+        assert!(
+            result1.unwrap().is_some(),
+            "Expected chunk1 to be processed"
+        );
 
         let chunk2 = r#"{"type":"stream_event","event":{"type":"content_block_delta","delta":{"text":" world"}}}"#;
         let result2 = ProtocolTranslator::stream_json_to_acp(chunk2, &session_id);
         assert!(result2.is_ok());
-        assert!(result2.unwrap().is_some(), "Expected chunk2 to be processed");
+        assert!(
+            result2.unwrap().is_some(),
+            "Expected chunk2 to be processed"
+        );
 
         // Step 2: Receive assistant message with full text (this should be filtered)
-        let full_message = r#"{"type":"assistant","message":{"content":[{"type":"text","text":"Hello world"}]}}"#;
+        let full_message =
+            r#"{"type":"assistant","message":{"content":[{"type":"text","text":"Hello world"}]}}"#;
         let result3 = ProtocolTranslator::stream_json_to_acp(full_message, &session_id);
         assert!(result3.is_ok());
         assert!(
@@ -695,4 +1511,117 @@ mod tests {
             "Expected full assistant text message to be filtered (duplicate prevention)"
         );
     }
+    #[test]
+    fn test_parse_control_request_can_use_tool() {
+        let line = r#"{"type":"control_request","request_id":"req_1","request":{"subtype":"can_use_tool","tool_name":"Write","input":{"file_path":"hello.txt","content":"hi"},"tool_use_id":"toolu_123"}}"#;
+
+        let request = ProtocolTranslator::parse_control_request(line)
+            .unwrap()
+            .expect("Expected a control request");
+
+        assert_eq!(request.request_id, "req_1");
+        match request.kind {
+            ControlRequestKind::CanUseTool(permission) => {
+                assert_eq!(permission.tool_name, "Write");
+                assert_eq!(permission.input["file_path"], "hello.txt");
+                assert_eq!(permission.tool_use_id.as_deref(), Some("toolu_123"));
+            }
+            other => panic!("Expected CanUseTool, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_control_request_unsupported_subtype() {
+        let line = r#"{"type":"control_request","request_id":"req_2","request":{"subtype":"hook_callback"}}"#;
+
+        let request = ProtocolTranslator::parse_control_request(line)
+            .unwrap()
+            .unwrap();
+
+        assert!(matches!(
+            request.kind,
+            ControlRequestKind::Unsupported { ref subtype } if subtype == "hook_callback"
+        ));
+    }
+
+    #[test]
+    fn test_parse_control_request_ignores_other_messages() {
+        let line = r#"{"type":"result","subtype":"success"}"#;
+        assert!(ProtocolTranslator::parse_control_request(line)
+            .unwrap()
+            .is_none());
+
+        // Control requests never become session notifications
+        let session_id = SessionId("test_session".into());
+        let control = r#"{"type":"control_request","request_id":"req_1","request":{"subtype":"can_use_tool","tool_name":"Bash","input":{}}}"#;
+        assert!(
+            ProtocolTranslator::stream_json_to_acp_notifications(control, &session_id)
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_permission_decision_to_stream_json() {
+        let allow = ProtocolTranslator::permission_decision_to_stream_json(
+            "req_1",
+            &ToolPermissionDecision::Allow {
+                updated_input: serde_json::json!({"command": "ls"}),
+            },
+        )
+        .unwrap();
+        let parsed: JsonValue = serde_json::from_str(&allow).unwrap();
+        assert_eq!(parsed["type"], "control_response");
+        assert_eq!(parsed["response"]["subtype"], "success");
+        assert_eq!(parsed["response"]["request_id"], "req_1");
+        assert_eq!(parsed["response"]["response"]["behavior"], "allow");
+        assert_eq!(
+            parsed["response"]["response"]["updatedInput"]["command"],
+            "ls"
+        );
+
+        let deny = ProtocolTranslator::permission_decision_to_stream_json(
+            "req_2",
+            &ToolPermissionDecision::Deny {
+                message: "Rejected by user".to_string(),
+                interrupt: false,
+            },
+        )
+        .unwrap();
+        let parsed: JsonValue = serde_json::from_str(&deny).unwrap();
+        assert_eq!(parsed["response"]["response"]["behavior"], "deny");
+        assert_eq!(parsed["response"]["response"]["message"], "Rejected by user");
+        assert!(parsed["response"]["response"].get("interrupt").is_none());
+
+        let interrupt = ProtocolTranslator::permission_decision_to_stream_json(
+            "req_3",
+            &ToolPermissionDecision::Deny {
+                message: "Cancelled".to_string(),
+                interrupt: true,
+            },
+        )
+        .unwrap();
+        let parsed: JsonValue = serde_json::from_str(&interrupt).unwrap();
+        assert_eq!(parsed["response"]["response"]["interrupt"], true);
+    }
+
+    #[test]
+    fn test_interrupt_request_to_stream_json() {
+        let line = ProtocolTranslator::interrupt_request_to_stream_json("interrupt_1").unwrap();
+        let parsed: JsonValue = serde_json::from_str(&line).unwrap();
+        assert_eq!(parsed["type"], "control_request");
+        assert_eq!(parsed["request_id"], "interrupt_1");
+        assert_eq!(parsed["request"]["subtype"], "interrupt");
+    }
+
+    #[test]
+    fn test_control_error_to_stream_json() {
+        let line =
+            ProtocolTranslator::control_error_to_stream_json("req_3", "Unsupported").unwrap();
+        let parsed: JsonValue = serde_json::from_str(&line).unwrap();
+        assert_eq!(parsed["type"], "control_response");
+        assert_eq!(parsed["response"]["subtype"], "error");
+        assert_eq!(parsed["response"]["request_id"], "req_3");
+        assert_eq!(parsed["response"]["error"], "Unsupported");
+    }
 }
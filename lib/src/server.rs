@@ -5,7 +5,9 @@
 
 use crate::{agent::ClaudeAgent, config::AgentConfig, error::AgentError};
 use agent_client_protocol::Agent;
+use futures::stream::{FuturesUnordered, StreamExt};
 use serde::Serialize;
+use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
 use tokio::sync::broadcast;
@@ -34,6 +36,13 @@ struct JsonRpcNotification {
     params: agent_client_protocol::SessionNotification,
 }
 
+/// Methods that run one at a time per session
+///
+/// Turns on a session share one claude process, so these wait for earlier requests on
+/// the same session. Everything else, including `session/cancel`, runs as soon as it
+/// arrives.
+const SESSION_ORDERED_METHODS: &[&str] = &["session/load", "session/set-mode", "session/prompt"];
+
 /// The main ACP server that handles JSON-RPC communication
 pub struct ClaudeAgentServer {
     agent: Arc<ClaudeAgent>,
@@ -52,10 +61,16 @@ impl ClaudeAgentServer {
         })
     }
 
+    /// The agent served by this server, for example to reload its configuration
+    pub fn agent(&self) -> &Arc<ClaudeAgent> {
+        &self.agent
+    }
+
     /// Start the server with custom streams
     ///
     /// # Concurrency Model
     /// This method handles requests and notifications concurrently using `tokio::join!`.
+    /// Requests themselves are dispatched concurrently as well (see `handle_requests`).
     /// A broadcast channel coordinates shutdown: when the request handler completes
     /// (connection closed), it signals the notification handler to stop gracefully.
     ///
@@ -86,7 +101,6 @@ impl ClaudeAgentServer {
         // Capacity of 1 is sufficient since we only send a single shutdown signal
         let (shutdown_tx, mut shutdown_rx) = tokio::sync::broadcast::channel::<()>(1);
 
-        // Handle requests directly without spawning (avoids Send issues)
         // IMPORTANT: Use the original notification_receiver, NOT resubscribe()!
         // resubscribe() creates a NEW receiver that only gets messages sent AFTER this point,
         // causing us to miss all the streaming chunks sent during prompt processing.
@@ -104,6 +118,20 @@ impl ClaudeAgentServer {
                 )
             })?;
 
+        // Requests and notifications the agent sends to the client through its
+        // ClientConnection; the client's responses are routed back in handle_requests
+        let mut client_messages = agent
+            .client_connection()
+            .take_outgoing_receiver()
+            .ok_or_else(|| {
+                AgentError::Protocol(
+                    "Client connection already taken (server started twice?)".to_string(),
+                )
+            })?;
+
+        // MCP servers announcing new tools or prompts change the commands of every session
+        let mut mcp_command_changes = agent.mcp_command_changes();
+
         // Handle requests and signal shutdown when done
         let request_handler = async {
             let result =
@@ -113,7 +141,7 @@ impl ClaudeAgentServer {
             result
         };
 
-        // Handle notifications until shutdown signal
+        // Handle notifications and agent-to-client requests until shutdown signal
         let notification_handler = async {
             info!("Notification handler started, waiting for notifications");
             loop {
@@ -134,6 +162,17 @@ impl ClaudeAgentServer {
                             }
                         }
                     }
+                    Some(message) = client_messages.recv() => {
+                        if let Err(e) = Self::send_line(Arc::clone(&writer), &message).await {
+                            error!("Failed to send request to client: {} - shutting down notification handler", e);
+                            break;
+                        }
+                    }
+                    Ok(()) = mcp_command_changes.changed() => {
+                        if let Err(e) = agent.refresh_available_commands().await {
+                            warn!("Failed to update available commands after MCP list change: {}", e);
+                        }
+                    }
                     _ = shutdown_rx.recv() => {
                         info!("Notification handler received shutdown signal");
                         break;
@@ -156,6 +195,14 @@ impl ClaudeAgentServer {
     }
 
     /// Handle incoming JSON-RPC requests
+    ///
+    /// Requests are dispatched concurrently as they arrive, so a long `session/prompt`
+    /// doesn't hold up `session/cancel` or requests for other sessions. The agent's
+    /// futures are not `Send`, so in-flight requests are polled on this task instead of
+    /// being spawned. Requests listed in [`SESSION_ORDERED_METHODS`] run one at a time per
+    /// session, in the order they were received. Responses to the agent's own requests
+    /// are handed to its [`crate::client_connection::ClientConnection`]. When the reader
+    /// closes, requests still in flight are finished before returning.
     async fn handle_requests<R, W>(
         reader: R,
         writer: Arc<tokio::sync::Mutex<W>>,
@@ -167,18 +214,57 @@ impl ClaudeAgentServer {
     {
         info!("Request handler started, waiting for requests");
         let mut lines = BufReader::new(reader).lines();
+        let mut in_flight = FuturesUnordered::new();
+        let mut session_queues: HashMap<String, Arc<tokio::sync::Mutex<()>>> = HashMap::new();
+        let mut reader_open = true;
+
+        loop {
+            tokio::select! {
+                line = lines.next_line(), if reader_open => {
+                    let Some(line) = line? else {
+                        info!("Connection closed, finishing {} in-flight requests", in_flight.len());
+                        // Nothing can answer the agent's requests anymore
+                        agent.client_connection().close();
+                        reader_open = false;
+                        continue;
+                    };
+                    info!("Received line: {}", line);
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    let message: serde_json::Value = match serde_json::from_str(&line) {
+                        Ok(message) => message,
+                        Err(e) => {
+                            error!("Failed to parse message: {}", e);
+                            continue;
+                        }
+                    };
 
-        while let Some(line) = lines.next_line().await? {
-            info!("Received line: {}", line);
-            if line.trim().is_empty() {
-                continue;
-            }
+                    // Messages without a method answer requests the agent sent
+                    if message.get("method").is_none() {
+                        agent.client_connection().handle_response(&message);
+                        continue;
+                    }
 
-            // Handle request directly to avoid Send issues
-            if let Err(e) =
-                Self::handle_single_request(line, Arc::clone(&writer), Arc::clone(&agent)).await
-            {
-                error!("Failed to handle request: {}", e);
+                    let queue = Self::session_queue(&message, &mut session_queues);
+                    let writer = Arc::clone(&writer);
+                    let agent = Arc::clone(&agent);
+                    // FuturesUnordered polls new futures in push order, so requests for a
+                    // session queue on its mutex in the order they arrived
+                    in_flight.push(async move {
+                        let _turn = match &queue {
+                            Some(queue) => Some(queue.lock().await),
+                            None => None,
+                        };
+                        Self::handle_single_request(message, writer, agent).await
+                    });
+                }
+                Some(result) = in_flight.next(), if !in_flight.is_empty() => {
+                    if let Err(e) = result {
+                        error!("Failed to handle request: {}", e);
+                    }
+                }
+                else => break,
             }
         }
 
@@ -186,18 +272,34 @@ impl ClaudeAgentServer {
         Ok(())
     }
 
+    /// The ordering queue for a request that must not overlap others on its session
+    ///
+    /// Queues no request is holding or waiting on are dropped along the way.
+    fn session_queue(
+        request: &serde_json::Value,
+        session_queues: &mut HashMap<String, Arc<tokio::sync::Mutex<()>>>,
+    ) -> Option<Arc<tokio::sync::Mutex<()>>> {
+        session_queues.retain(|_, queue| Arc::strong_count(queue) > 1);
+
+        let method = request.get("method")?.as_str()?;
+        if !SESSION_ORDERED_METHODS.contains(&method) {
+            return None;
+        }
+        let session_id = request.get("params")?.get("sessionId")?.as_str()?;
+        Some(Arc::clone(
+            session_queues.entry(session_id.to_string()).or_default(),
+        ))
+    }
+
     /// Handle a single JSON-RPC request
     async fn handle_single_request<W>(
-        line: String,
+        request: serde_json::Value,
         writer: Arc<tokio::sync::Mutex<W>>,
         agent: Arc<ClaudeAgent>,
     ) -> crate::Result<()>
     where
         W: AsyncWrite + Unpin + Send + 'static,
     {
-        // Parse the JSON-RPC request
-        let request: serde_json::Value = serde_json::from_str(&line)?;
-
         let method = request
             .get("method")
             .and_then(|v| v.as_str())
@@ -270,15 +372,30 @@ impl ClaudeAgentServer {
                 let req = serde_json::from_value(params)?;
                 agent.cancel(req).await.map(|_| serde_json::Value::Null)
             }
-            // Handle extension methods through ext_method
+            // Extension methods start with `_`, which is stripped as the ACP library does
             _ => {
+                let Some(ext_method) = method.strip_prefix('_') else {
+                    tracing::warn!("Unknown method: {}", method);
+                    if is_notification {
+                        return Ok(());
+                    }
+                    return Self::send_response(
+                        writer,
+                        serde_json::json!({
+                            "jsonrpc": "2.0",
+                            "id": id,
+                            "error": agent_client_protocol::Error::method_not_found(),
+                        }),
+                    )
+                    .await;
+                };
                 let params_raw = agent_client_protocol::RawValue::from_string(params.to_string())
                     .map_err(|_| {
                     AgentError::Protocol("Failed to convert params to RawValue".to_string())
                 })?;
 
                 let ext_request = agent_client_protocol::ExtRequest {
-                    method: method.to_string().into(),
+                    method: ext_method.to_string().into(),
                     params: Arc::from(params_raw),
                 };
                 agent
@@ -356,6 +473,20 @@ impl ClaudeAgentServer {
         Ok(())
     }
 
+    /// Send a serialized JSON-RPC message from the agent to the client
+    async fn send_line<W>(writer: Arc<tokio::sync::Mutex<W>>, message: &str) -> crate::Result<()>
+    where
+        W: AsyncWrite + Unpin + Send + 'static,
+    {
+        tracing::debug!("Sending message to client: {}", message);
+
+        let mut writer_guard = writer.lock().await;
+        writer_guard.write_all(message.as_bytes()).await?;
+        writer_guard.write_all(b"\n").await?;
+        writer_guard.flush().await?;
+        Ok(())
+    }
+
     /// Send a session update notification.
     ///
     /// Wraps the notification in JSON-RPC 2.0 format and serializes it with proper
@@ -938,4 +1069,200 @@ mod tests {
 
         server_result.expect("Server should complete successfully");
     }
+
+    async fn send_message<W: AsyncWrite + Unpin>(writer: &mut W, message: serde_json::Value) {
+        writer
+            .write_all(format!("{}\n", message).as_bytes())
+            .await
+            .unwrap();
+    }
+
+    /// Read messages until the response with the given id, returning it and the ids of
+    /// other responses seen before it
+    async fn read_response<R: tokio::io::AsyncBufRead + Unpin>(
+        reader: &mut R,
+        id: i64,
+    ) -> (serde_json::Value, Vec<i64>) {
+        let mut earlier = Vec::new();
+        loop {
+            let mut line = String::new();
+            tokio::time::timeout(
+                tokio::time::Duration::from_secs(10),
+                reader.read_line(&mut line),
+            )
+            .await
+            .expect("response should arrive")
+            .unwrap();
+            let message: serde_json::Value = serde_json::from_str(&line).unwrap();
+            match message.get("id").and_then(|id| id.as_i64()) {
+                Some(response_id) if response_id == id => return (message, earlier),
+                Some(response_id) => earlier.push(response_id),
+                None => {}
+            }
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_cancel_interrupts_prompt_in_flight() {
+        use std::os::unix::fs::PermissionsExt;
+
+        // A claude CLI that accepts the prompt and only ends the turn when interrupted
+        let temp_dir = tempfile::tempdir().unwrap();
+        let binary_path = temp_dir.path().join("fake-claude");
+        std::fs::write(
+            &binary_path,
+            "#!/bin/sh\nread line\nread interrupt\necho '{\"type\":\"result\",\"subtype\":\"error_during_execution\"}'\nexec sleep 30\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&binary_path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let mut config = AgentConfig::default();
+        config.claude.binary_path = binary_path;
+        let server = ClaudeAgentServer::new(config).await.unwrap();
+
+        let (mut client_writer, server_reader) = duplex(sizes::buffers::DUPLEX_STREAM_BUFFER);
+        let (server_writer, client_reader) = duplex(sizes::buffers::DUPLEX_STREAM_BUFFER);
+        let cwd = temp_dir.path().to_path_buf();
+
+        let client_task = async move {
+            let mut reader = BufReader::new(client_reader);
+            send_message(
+                &mut client_writer,
+                serde_json::json!({
+                    "jsonrpc": "2.0", "id": 1, "method": "session/new",
+                    "params": {"cwd": cwd, "mcpServers": []}
+                }),
+            )
+            .await;
+            let (response, _) = read_response(&mut reader, 1).await;
+            let session_id = response["result"]["sessionId"]
+                .as_str()
+                .unwrap()
+                .to_string();
+
+            // A prompt that won't finish by itself, then a request that must not wait for it
+            for message in [
+                serde_json::json!({
+                    "jsonrpc": "2.0", "id": 2, "method": "session/prompt",
+                    "params": {"sessionId": session_id, "prompt": [{"type": "text", "text": "Hello"}]}
+                }),
+                serde_json::json!({
+                    "jsonrpc": "2.0", "id": 3, "method": "_claude_agent/session/usage",
+                    "params": {"sessionId": session_id}
+                }),
+            ] {
+                send_message(&mut client_writer, message).await;
+            }
+            let (usage, earlier) = read_response(&mut reader, 3).await;
+            assert!(usage.get("result").is_some());
+            assert!(earlier.is_empty(), "prompt should still be running");
+
+            send_message(
+                &mut client_writer,
+                serde_json::json!({
+                    "jsonrpc": "2.0", "method": "session/cancel",
+                    "params": {"sessionId": session_id}
+                }),
+            )
+            .await;
+            let (prompt, _) = read_response(&mut reader, 2).await;
+            assert_eq!(prompt["result"]["stopReason"], "cancelled");
+        };
+
+        tokio::select! {
+            result = server.start_with_streams(server_reader, server_writer) => {
+                panic!("server stopped early: {:?}", result)
+            }
+            _ = client_task => {}
+        }
+    }
+
+    #[tokio::test]
+    async fn test_extension_methods_are_called_with_an_underscore() {
+        let server = create_test_server().await;
+
+        let (mut client_writer, server_reader) = duplex(sizes::buffers::DUPLEX_STREAM_BUFFER);
+        let (server_writer, client_reader) = duplex(sizes::buffers::DUPLEX_STREAM_BUFFER);
+
+        let client_task = async move {
+            let mut reader = BufReader::new(client_reader);
+            for (id, method) in [(1, "_claude_agent/mcp/status"), (2, "mcp/status")] {
+                send_message(
+                    &mut client_writer,
+                    serde_json::json!({"jsonrpc": "2.0", "id": id, "method": method, "params": {}}),
+                )
+                .await;
+            }
+
+            let (status, _) = read_response(&mut reader, 1).await;
+            assert_eq!(status["result"]["servers"], serde_json::json!([]));
+            let (unknown, _) = read_response(&mut reader, 2).await;
+            assert_eq!(unknown["error"]["code"], -32601);
+        };
+
+        tokio::select! {
+            result = server.start_with_streams(server_reader, server_writer) => {
+                panic!("server stopped early: {:?}", result)
+            }
+            _ = client_task => {}
+        }
+    }
+
+    #[tokio::test]
+    async fn test_agent_request_is_answered_by_client() {
+        use agent_client_protocol::Client;
+
+        let server = create_test_server().await;
+        let client_connection = Arc::clone(server.agent.client_connection());
+        let (mut client_writer, server_reader) = duplex(sizes::buffers::DUPLEX_STREAM_BUFFER);
+        let (server_writer, client_reader) = duplex(sizes::buffers::DUPLEX_STREAM_BUFFER);
+
+        let client_task = async move {
+            // Wait for the server to start writing outbound messages
+            while !client_connection.is_connected() {
+                tokio::task::yield_now().await;
+            }
+            let request =
+                client_connection.read_text_file(agent_client_protocol::ReadTextFileRequest {
+                    session_id: agent_client_protocol::SessionId("session-1".into()),
+                    path: "/project/README.md".into(),
+                    line: None,
+                    limit: None,
+                    meta: None,
+                });
+            let editor = async {
+                let mut reader = BufReader::new(client_reader);
+                let mut line = String::new();
+                reader.read_line(&mut line).await.unwrap();
+                let message: serde_json::Value = serde_json::from_str(&line).unwrap();
+                assert_eq!(message["method"], "fs/read_text_file");
+                assert_eq!(message["params"]["sessionId"], "session-1");
+                send_message(
+                    &mut client_writer,
+                    serde_json::json!({
+                        "jsonrpc": "2.0",
+                        "id": message["id"],
+                        "result": {"content": "# Project"}
+                    }),
+                )
+                .await;
+                client_writer
+            };
+
+            let (response, client_writer) = tokio::join!(request, editor);
+            assert_eq!(response.unwrap().content, "# Project");
+
+            // Requests fail right away once the client is gone
+            drop(client_writer);
+            while client_connection.is_connected() {
+                tokio::task::yield_now().await;
+            }
+        };
+
+        let (result, _) = tokio::join!(
+            server.start_with_streams(server_reader, server_writer),
+            client_task
+        );
+        result.unwrap();
+    }
 }
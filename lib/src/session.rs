@@ -9,6 +9,8 @@ use std::sync::{Arc, RwLock};
 use std::time::{Duration, SystemTime};
 use ulid::Ulid;
 
+use crate::session_store::{InMemorySessionStore, SessionStore};
+
 /// Session identifier with ACP-compliant format
 ///
 /// # Format
@@ -215,6 +217,109 @@ pub struct Session {
     pub turn_token_count: u64,
     /// Current session mode identifier for ACP current mode updates
     pub current_mode: Option<String>,
+    /// Claude CLI's own conversation id, used to resume the conversation with `--resume`
+    #[serde(default)]
+    pub claude_session_id: Option<String>,
+    /// Token usage and cost accumulated over all turns
+    #[serde(default)]
+    pub usage: SessionUsage,
+}
+
+/// Token usage and cost accumulated over a session, as reported by the claude CLI
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SessionUsage {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cache_creation_input_tokens: u64,
+    pub cache_read_input_tokens: u64,
+    /// Total cost in USD
+    pub total_cost_usd: f64,
+    /// Number of turns that reported usage
+    pub turns: u64,
+    /// Last cumulative cost reported by the claude conversation, used to derive turn costs
+    #[serde(default)]
+    pub reported_cost_usd: f64,
+}
+
+impl SessionUsage {
+    /// Add a completed turn's usage and return the turn's cost in USD
+    ///
+    /// The CLI reports the cumulative cost of its conversation, which survives `--resume`.
+    /// A reported cost below the previous one means a new conversation was started, so the
+    /// whole reported amount belongs to this turn.
+    pub fn record_turn(
+        &mut self,
+        usage: Option<&crate::claude::TokenUsageInfo>,
+        reported_cost_usd: Option<f64>,
+    ) -> f64 {
+        if let Some(usage) = usage {
+            self.add_tokens(usage);
+        }
+        self.turns += 1;
+
+        let Some(reported_cost_usd) = reported_cost_usd else {
+            return 0.0;
+        };
+        let turn_cost_usd = if reported_cost_usd >= self.reported_cost_usd {
+            reported_cost_usd - self.reported_cost_usd
+        } else {
+            reported_cost_usd
+        };
+        self.reported_cost_usd = reported_cost_usd;
+        self.total_cost_usd += turn_cost_usd;
+        turn_cost_usd
+    }
+
+    /// Add token counts without counting a turn or a cost
+    pub fn add_tokens(&mut self, usage: &crate::claude::TokenUsageInfo) {
+        self.input_tokens += usage.input_tokens;
+        self.output_tokens += usage.output_tokens;
+        self.cache_creation_input_tokens += usage.cache_creation_input_tokens;
+        self.cache_read_input_tokens += usage.cache_read_input_tokens;
+    }
+
+    /// Tokens processed over the session, excluding cache reads
+    pub fn total_tokens(&self) -> u64 {
+        self.input_tokens + self.cache_creation_input_tokens + self.output_tokens
+    }
+}
+
+/// Request parameters for the `_claude_agent/session/usage` extension method
+#[derive(Debug, Deserialize)]
+pub struct SessionUsageParams {
+    /// Session identifier
+    #[serde(rename = "sessionId")]
+    pub session_id: String,
+}
+
+/// Response for the `_claude_agent/session/usage` extension method
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionUsageResponse {
+    pub session_id: String,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cache_creation_input_tokens: u64,
+    pub cache_read_input_tokens: u64,
+    pub total_tokens: u64,
+    pub total_cost_usd: f64,
+    pub turns: u64,
+}
+
+impl SessionUsageResponse {
+    /// Build the response for a session's accumulated usage
+    pub fn new(session_id: &SessionId, usage: &SessionUsage) -> Self {
+        Self {
+            session_id: session_id.to_string(),
+            input_tokens: usage.input_tokens,
+            output_tokens: usage.output_tokens,
+            cache_creation_input_tokens: usage.cache_creation_input_tokens,
+            cache_read_input_tokens: usage.cache_read_input_tokens,
+            total_tokens: usage.total_tokens(),
+            total_cost_usd: usage.total_cost_usd,
+            turns: usage.turns,
+        }
+    }
 }
 
 impl Session {
@@ -249,6 +354,8 @@ impl Session {
             turn_request_count: 0,
             turn_token_count: 0,
             current_mode: None,
+            claude_session_id: None,
+            usage: SessionUsage::default(),
         }
     }
 
@@ -351,9 +458,14 @@ pub enum MessageRole {
 }
 
 /// Thread-safe session manager
+///
+/// Active sessions are cached in memory and every change is written through to the
+/// configured [`SessionStore`]. Sessions missing from the cache (e.g. after a restart)
+/// are loaded from the store on first access.
 #[derive(Debug)]
 pub struct SessionManager {
     sessions: Arc<RwLock<HashMap<SessionId, Session>>>,
+    store: Arc<dyn SessionStore>,
     cleanup_interval: Duration,
     max_session_age: Duration,
 }
@@ -361,22 +473,33 @@ pub struct SessionManager {
 impl SessionManager {
     /// Create a new session manager with default settings
     pub fn new() -> Self {
-        Self {
-            sessions: Arc::new(RwLock::new(HashMap::new())),
-            cleanup_interval: Duration::from_secs(300), // 5 minutes
-            max_session_age: Duration::from_secs(3600), // 1 hour
-        }
+        Self::with_store(Arc::new(InMemorySessionStore::new()))
     }
 
     /// Create a new session manager with custom cleanup settings
     pub fn with_cleanup_settings(cleanup_interval: Duration, max_session_age: Duration) -> Self {
         Self {
-            sessions: Arc::new(RwLock::new(HashMap::new())),
             cleanup_interval,
             max_session_age,
+            ..Self::new()
+        }
+    }
+
+    /// Create a new session manager backed by the given session store
+    pub fn with_store(store: Arc<dyn SessionStore>) -> Self {
+        Self {
+            sessions: Arc::new(RwLock::new(HashMap::new())),
+            store,
+            cleanup_interval: Duration::from_secs(300), // 5 minutes
+            max_session_age: Duration::from_secs(3600), // 1 hour
         }
     }
 
+    /// Get the backing session store
+    pub fn store(&self) -> &Arc<dyn SessionStore> {
+        &self.store
+    }
+
     /// Create a new session with specified working directory and return its ID
     ///
     /// # Arguments
@@ -386,6 +509,7 @@ impl SessionManager {
     /// Returns error if:
     /// - Working directory validation fails
     /// - Session storage write lock cannot be acquired
+    /// - The session cannot be written to the session store
     pub fn create_session(&self, cwd: PathBuf, client_capabilities: Option<agent_client_protocol::ClientCapabilities>) -> crate::Result<SessionId> {
         // Validate working directory before creating session
         crate::session_validation::validate_working_directory(&cwd).map_err(|e| {
@@ -401,22 +525,64 @@ impl SessionManager {
             .write()
             .map_err(|_| crate::AgentError::Session("Failed to acquire write lock".to_string()))?;
 
+        self.store.save_session(&session)?;
         sessions.insert(session_id, session);
         tracing::debug!("Created new session: {}", session_id);
         Ok(session_id)
     }
 
     /// Get a session by ID
+    ///
+    /// Sessions not in the in-memory cache are loaded from the session store.
     pub fn get_session(&self, session_id: &SessionId) -> crate::Result<Option<Session>> {
-        let sessions = self
+        {
+            let sessions = self.sessions.read().map_err(|_| {
+                crate::AgentError::Session("Failed to acquire read lock".to_string())
+            })?;
+
+            if let Some(session) = sessions.get(session_id) {
+                return Ok(Some(session.clone()));
+            }
+        }
+
+        let mut sessions = self
             .sessions
-            .read()
-            .map_err(|_| crate::AgentError::Session("Failed to acquire read lock".to_string()))?;
+            .write()
+            .map_err(|_| crate::AgentError::Session("Failed to acquire write lock".to_string()))?;
 
-        Ok(sessions.get(session_id).cloned())
+        Ok(self
+            .load_into_cache(&mut sessions, session_id)?
+            .map(|session| session.clone()))
+    }
+
+    /// Load a session from the store into the cache if it is not already cached
+    fn load_into_cache<'a>(
+        &self,
+        sessions: &'a mut HashMap<SessionId, Session>,
+        session_id: &SessionId,
+    ) -> crate::Result<Option<&'a mut Session>> {
+        if !sessions.contains_key(session_id) {
+            match self.store.load_session(session_id)? {
+                Some(session) => {
+                    tracing::info!(
+                        "Restored session {} from store with {} messages",
+                        session_id,
+                        session.context.len()
+                    );
+                    sessions.insert(*session_id, session);
+                }
+                None => return Ok(None),
+            }
+        }
+
+        Ok(sessions.get_mut(session_id))
     }
 
     /// Update a session using a closure
+    ///
+    /// Messages appended by the closure are written to the store incrementally; any other
+    /// change to the message history causes a full snapshot to be stored. The cached
+    /// session only changes once the store accepted the update.
     pub fn update_session<F>(&self, session_id: &SessionId, updater: F) -> crate::Result<()>
     where
         F: FnOnce(&mut Session),
@@ -426,9 +592,21 @@ impl SessionManager {
             .write()
             .map_err(|_| crate::AgentError::Session("Failed to acquire write lock".to_string()))?;
 
-        if let Some(session) = sessions.get_mut(session_id) {
-            updater(session);
+        if let Some(cached) = self.load_into_cache(&mut sessions, session_id)? {
+            let mut session = cached.clone();
+            let previous_len = session.context.len();
+            updater(&mut session);
             session.update_access_time();
+
+            if session.context.len() >= previous_len {
+                for message in &session.context[previous_len..] {
+                    self.store.append_message(session_id, message)?;
+                }
+                self.store.save_metadata(&session)?;
+            } else {
+                self.store.save_session(&session)?;
+            }
+            *cached = session;
             tracing::debug!("Updated session: {}", session_id);
         } else {
             tracing::warn!("Attempted to update non-existent session: {}", session_id);
@@ -437,7 +615,32 @@ impl SessionManager {
         Ok(())
     }
 
+    /// Append a message to a session's context
+    ///
+    /// Only the new message is written to the session store.
+    pub fn add_message(&self, session_id: &SessionId, message: Message) -> crate::Result<()> {
+        let mut sessions = self
+            .sessions
+            .write()
+            .map_err(|_| crate::AgentError::Session("Failed to acquire write lock".to_string()))?;
+
+        match self.load_into_cache(&mut sessions, session_id)? {
+            Some(session) => {
+                self.store.append_message(session_id, &message)?;
+                session.add_message(message);
+                tracing::debug!("Added message to session: {}", session_id);
+                Ok(())
+            }
+            None => Err(crate::AgentError::Session(format!(
+                "Session not found: {}",
+                session_id
+            ))),
+        }
+    }
+
     /// Remove a session and return it if it existed
+    ///
+    /// The session is also removed from the session store.
     pub fn remove_session(&self, session_id: &SessionId) -> crate::Result<Option<Session>> {
         let mut sessions = self
             .sessions
@@ -445,19 +648,36 @@ impl SessionManager {
             .map_err(|_| crate::AgentError::Session("Failed to acquire write lock".to_string()))?;
 
         let removed = sessions.remove(session_id);
-        if removed.is_some() {
+        let stored = self.store.remove_session(session_id)?;
+        if removed.is_some() || stored {
             tracing::debug!("Removed session: {}", session_id);
         }
         Ok(removed)
     }
 
-    /// List all session IDs
+    /// List all session IDs, including stored sessions not yet loaded into memory
     pub fn list_sessions(&self) -> crate::Result<Vec<SessionId>> {
         let sessions = self
             .sessions
             .read()
             .map_err(|_| crate::AgentError::Session("Failed to acquire read lock".to_string()))?;
 
+        let mut session_ids: Vec<SessionId> = sessions.keys().cloned().collect();
+        for session_id in self.store.list_sessions()? {
+            if !sessions.contains_key(&session_id) {
+                session_ids.push(session_id);
+            }
+        }
+        Ok(session_ids)
+    }
+
+    /// List the IDs of the sessions loaded into memory
+    pub fn active_sessions(&self) -> crate::Result<Vec<SessionId>> {
+        let sessions = self
+            .sessions
+            .read()
+            .map_err(|_| crate::AgentError::Session("Failed to acquire read lock".to_string()))?;
+
         Ok(sessions.keys().cloned().collect())
     }
 
@@ -483,10 +703,13 @@ impl SessionManager {
             .write()
             .map_err(|_| crate::AgentError::Session("Failed to acquire write lock".to_string()))?;
 
-        if let Some(session) = sessions.get_mut(session_id) {
-            let commands_changed = session.has_available_commands_changed(&commands);
+        if let Some(cached) = self.load_into_cache(&mut sessions, session_id)? {
+            let commands_changed = cached.has_available_commands_changed(&commands);
             if commands_changed {
+                let mut session = cached.clone();
                 session.update_available_commands(commands);
+                self.store.save_metadata(&session)?;
+                *cached = session;
                 tracing::debug!("Updated available commands for session: {}", session_id);
                 Ok(true)
             } else {
@@ -1139,4 +1362,155 @@ mod tests {
             .unwrap();
         assert!(!update_sent);
     }
+
+    #[test]
+    fn test_session_manager_writes_through_to_store() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let store: Arc<dyn SessionStore> = Arc::new(crate::session_store::FileSessionStore::new(
+            temp_dir.path().to_path_buf(),
+        ));
+        let manager = SessionManager::with_store(Arc::clone(&store));
+        let cwd = std::env::current_dir().unwrap();
+
+        let session_id = manager.create_session(cwd.clone(), None).unwrap();
+        manager
+            .add_message(
+                &session_id,
+                Message::new(MessageRole::User, "Hello".to_string()),
+            )
+            .unwrap();
+        manager
+            .update_session(&session_id, |session| {
+                session.current_mode = Some("code".to_string());
+                session.add_message(Message::new(MessageRole::Assistant, "Hi".to_string()));
+            })
+            .unwrap();
+
+        let stored = store.load_session(&session_id).unwrap().unwrap();
+        assert_eq!(stored.cwd, cwd);
+        assert_eq!(stored.context.len(), 2);
+        assert_eq!(stored.current_mode.as_deref(), Some("code"));
+
+        manager.remove_session(&session_id).unwrap();
+        assert!(store.load_session(&session_id).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_failed_store_write_leaves_session_unchanged() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let store: Arc<dyn SessionStore> = Arc::new(crate::session_store::FileSessionStore::new(
+            temp_dir.path().to_path_buf(),
+        ));
+        let manager = SessionManager::with_store(Arc::clone(&store));
+        let session_id = manager
+            .create_session(std::env::current_dir().unwrap(), None)
+            .unwrap();
+
+        // Messages cannot be appended once the session file is gone
+        store.remove_session(&session_id).unwrap();
+        let result = manager.update_session(&session_id, |session| {
+            session.current_mode = Some("code".to_string());
+            session.add_message(Message::new(MessageRole::User, "Hello".to_string()));
+        });
+        assert!(result.is_err());
+
+        let session = manager.get_session(&session_id).unwrap().unwrap();
+        assert_eq!(session.current_mode, None);
+        assert!(session.context.is_empty());
+    }
+
+    #[test]
+    fn test_session_manager_restores_from_store() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let cwd = std::env::current_dir().unwrap();
+
+        let session_id = {
+            let manager = SessionManager::with_store(Arc::new(
+                crate::session_store::FileSessionStore::new(temp_dir.path().to_path_buf()),
+            ));
+            let session_id = manager.create_session(cwd.clone(), None).unwrap();
+            manager
+                .add_message(
+                    &session_id,
+                    Message::new(MessageRole::User, "Remember me".to_string()),
+                )
+                .unwrap();
+            session_id
+        };
+
+        // A fresh manager simulates an agent restart
+        let manager = SessionManager::with_store(Arc::new(
+            crate::session_store::FileSessionStore::new(temp_dir.path().to_path_buf()),
+        ));
+        assert_eq!(manager.list_sessions().unwrap(), vec![session_id]);
+
+        let session = manager.get_session(&session_id).unwrap().unwrap();
+        assert_eq!(session.cwd, cwd);
+        assert_eq!(session.context.len(), 1);
+        assert_eq!(session.context[0].content, "Remember me");
+    }
+
+    #[test]
+    fn test_claude_session_id_is_persisted() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let store = Arc::new(crate::session_store::FileSessionStore::new(
+            temp_dir.path().to_path_buf(),
+        ));
+        let manager = SessionManager::with_store(store.clone());
+        let session_id = manager
+            .create_session(std::env::current_dir().unwrap(), None)
+            .unwrap();
+
+        manager
+            .update_session(&session_id, |session| {
+                session.claude_session_id = Some("claude-abc".to_string());
+            })
+            .unwrap();
+
+        let manager = SessionManager::with_store(store);
+        let session = manager.get_session(&session_id).unwrap().unwrap();
+        assert_eq!(session.claude_session_id.as_deref(), Some("claude-abc"));
+    }
+
+    #[test]
+    fn test_session_usage_derives_turn_cost() {
+        let mut usage = SessionUsage::default();
+        let turn = crate::claude::TokenUsageInfo {
+            input_tokens: 100,
+            output_tokens: 10,
+            ..Default::default()
+        };
+
+        // The CLI reports cumulative conversation cost
+        assert_eq!(usage.record_turn(Some(&turn), Some(0.5)), 0.5);
+        assert!((usage.record_turn(Some(&turn), Some(0.75)) - 0.25).abs() < 1e-9);
+        assert_eq!(usage.turns, 2);
+        assert_eq!(usage.input_tokens, 200);
+        assert_eq!(usage.total_tokens(), 220);
+
+        // A lower cumulative cost means a new claude conversation was started
+        assert_eq!(usage.record_turn(None, Some(0.1)), 0.1);
+        assert!((usage.total_cost_usd - 0.85).abs() < 1e-9);
+        assert_eq!(usage.turns, 3);
+    }
+
+    #[test]
+    fn test_session_without_claude_session_id_deserializes() {
+        let session = Session::new(SessionId::new(), std::env::current_dir().unwrap());
+        let mut value = serde_json::to_value(&session).unwrap();
+        value.as_object_mut().unwrap().remove("claude_session_id");
+
+        let session: Session = serde_json::from_value(value).unwrap();
+        assert_eq!(session.claude_session_id, None);
+    }
+
+    #[test]
+    fn test_add_message_to_nonexistent_session() {
+        let manager = SessionManager::new();
+        let result = manager.add_message(
+            &SessionId::new(),
+            Message::new(MessageRole::User, "Hello".to_string()),
+        );
+        assert!(result.is_err());
+    }
 }
@@ -864,4 +864,47 @@ mod tests {
             );
         }
     }
+
+    #[tokio::test]
+    async fn test_load_session_enhanced_after_restart() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let cwd = std::env::current_dir().unwrap();
+
+        let session_id = {
+            let session_manager = SessionManager::with_store(std::sync::Arc::new(
+                crate::session_store::FileSessionStore::new(temp_dir.path().to_path_buf()),
+            ));
+            let session_id = session_manager.create_session(cwd.clone(), None).unwrap();
+            session_manager
+                .add_message(
+                    &session_id,
+                    Message::new(MessageRole::User, "Hello".to_string()),
+                )
+                .unwrap();
+            session_manager
+                .add_message(
+                    &session_id,
+                    Message::new(MessageRole::Assistant, "Hi there".to_string()),
+                )
+                .unwrap();
+            session_id
+        };
+
+        let session_manager = SessionManager::with_store(std::sync::Arc::new(
+            crate::session_store::FileSessionStore::new(temp_dir.path().to_path_buf()),
+        ));
+        let loader = EnhancedSessionLoader::new(session_manager);
+
+        let request = LoadSessionRequest {
+            session_id: agent_client_protocol::SessionId(session_id.to_string().into()),
+            cwd,
+            mcp_servers: vec![],
+            meta: None,
+        };
+
+        let (session, notifications) = loader.load_session_enhanced(&request, true).await.unwrap();
+        assert_eq!(session.id, session_id);
+        assert_eq!(session.context.len(), 2);
+        assert_eq!(notifications.len(), 2);
+    }
 }
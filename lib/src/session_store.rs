@@ -0,0 +1,568 @@
+//! Persistent storage backends for conversation sessions
+//!
+//! [`SessionManager`](crate::session::SessionManager) keeps active sessions in memory and
+//! writes every change through to a [`SessionStore`], so sessions survive agent restarts
+//! and can be resumed with `session/load`.
+//!
+//! # Backends
+//!
+//! - [`InMemorySessionStore`]: Process-local storage, the default for tests and
+//!   deployments that do not need persistence.
+//! - [`FileSessionStore`]: One append-only JSONL file per session under a storage directory.
+//!
+//! # JSONL Format
+//!
+//! Each line of `<session_id>.jsonl` is a tagged record:
+//!
+//! ```json
+//! {"record":"metadata","session":{"id":"sess_...","cwd":"/repo","context":[],...}}
+//! {"record":"message","message":{"role":"User","content":"Hello","timestamp":{...}}}
+//! ```
+//!
+//! Messages are appended as they arrive. Metadata records are appended whenever session
+//! fields change; the last metadata record wins when the file is loaded. After enough
+//! metadata records the file is rewritten as a single snapshot. A truncated trailing line
+//! (e.g. from a crash mid-write) is skipped with a warning.
+
+use crate::session::{Message, Session, SessionId};
+use crate::{AgentError, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::sync::RwLock;
+
+/// Storage backend for sessions
+///
+/// Methods are synchronous because [`SessionManager`](crate::session::SessionManager)
+/// performs write-through while holding its own lock.
+pub trait SessionStore: Send + Sync + std::fmt::Debug {
+    /// Store a complete snapshot of a session, replacing any previous state
+    fn save_session(&self, session: &Session) -> Result<()>;
+
+    /// Store updated session fields without rewriting the message history
+    ///
+    /// The default implementation stores a full snapshot.
+    fn save_metadata(&self, session: &Session) -> Result<()> {
+        self.save_session(session)
+    }
+
+    /// Append a single message to a stored session
+    fn append_message(&self, session_id: &SessionId, message: &Message) -> Result<()>;
+
+    /// Load a session, returning None if it is not stored
+    fn load_session(&self, session_id: &SessionId) -> Result<Option<Session>>;
+
+    /// Remove a stored session, returning whether it existed
+    fn remove_session(&self, session_id: &SessionId) -> Result<bool>;
+
+    /// List the IDs of all stored sessions
+    fn list_sessions(&self) -> Result<Vec<SessionId>>;
+}
+
+/// In-memory session storage
+///
+/// Sessions are lost when the process exits.
+#[derive(Debug, Default)]
+pub struct InMemorySessionStore {
+    sessions: RwLock<HashMap<SessionId, Session>>,
+}
+
+impl InMemorySessionStore {
+    /// Create a new empty in-memory store
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl SessionStore for InMemorySessionStore {
+    fn save_session(&self, session: &Session) -> Result<()> {
+        let mut sessions = self
+            .sessions
+            .write()
+            .map_err(|_| AgentError::Session("Failed to acquire write lock".to_string()))?;
+        sessions.insert(session.id, session.clone());
+        Ok(())
+    }
+
+    fn append_message(&self, session_id: &SessionId, message: &Message) -> Result<()> {
+        let mut sessions = self
+            .sessions
+            .write()
+            .map_err(|_| AgentError::Session("Failed to acquire write lock".to_string()))?;
+        let session = sessions.get_mut(session_id).ok_or_else(|| {
+            AgentError::Session(format!("Session not found in store: {}", session_id))
+        })?;
+        session.context.push(message.clone());
+        Ok(())
+    }
+
+    fn load_session(&self, session_id: &SessionId) -> Result<Option<Session>> {
+        let sessions = self
+            .sessions
+            .read()
+            .map_err(|_| AgentError::Session("Failed to acquire read lock".to_string()))?;
+        Ok(sessions.get(session_id).cloned())
+    }
+
+    fn remove_session(&self, session_id: &SessionId) -> Result<bool> {
+        let mut sessions = self
+            .sessions
+            .write()
+            .map_err(|_| AgentError::Session("Failed to acquire write lock".to_string()))?;
+        Ok(sessions.remove(session_id).is_some())
+    }
+
+    fn list_sessions(&self) -> Result<Vec<SessionId>> {
+        let sessions = self
+            .sessions
+            .read()
+            .map_err(|_| AgentError::Session("Failed to acquire read lock".to_string()))?;
+        Ok(sessions.keys().cloned().collect())
+    }
+}
+
+/// A single line in a session JSONL file
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "record", rename_all = "snake_case")]
+enum SessionRecord {
+    /// Session fields; `context` is always empty and rebuilt from message records
+    Metadata { session: Box<Session> },
+    /// A message appended to the session context
+    Message { message: Message },
+}
+
+/// File-based session storage using one JSONL file per session
+#[derive(Debug)]
+pub struct FileSessionStore {
+    storage_path: PathBuf,
+    /// Serializes file access within this process
+    write_lock: std::sync::Mutex<()>,
+    /// Metadata records appended to each session file since it was last written whole
+    appended_metadata: std::sync::Mutex<HashMap<SessionId, usize>>,
+}
+
+impl FileSessionStore {
+    /// File extension used for session files
+    const EXTENSION: &'static str = "jsonl";
+
+    /// Number of metadata records appended before a file is rewritten as a snapshot
+    const MAX_APPENDED_METADATA_RECORDS: usize = 64;
+
+    /// Create a new file-based store rooted at the given directory
+    ///
+    /// The directory is created on first write.
+    pub fn new(storage_path: PathBuf) -> Self {
+        Self {
+            storage_path,
+            write_lock: std::sync::Mutex::new(()),
+            appended_metadata: std::sync::Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Get the directory holding session files
+    pub fn storage_path(&self) -> &PathBuf {
+        &self.storage_path
+    }
+
+    /// Get the path to a session's JSONL file
+    fn session_file_path(&self, session_id: &SessionId) -> PathBuf {
+        self.storage_path
+            .join(format!("{}.{}", session_id, Self::EXTENSION))
+    }
+
+    fn ensure_storage_dir(&self) -> Result<()> {
+        fs::create_dir_all(&self.storage_path).map_err(|e| {
+            AgentError::Session(format!(
+                "Failed to create session storage directory {}: {}",
+                self.storage_path.display(),
+                e
+            ))
+        })
+    }
+
+    fn metadata_record(session: &Session) -> SessionRecord {
+        let mut metadata = session.clone();
+        metadata.context.clear();
+        SessionRecord::Metadata {
+            session: Box::new(metadata),
+        }
+    }
+
+    fn encode(record: &SessionRecord) -> Result<String> {
+        let mut line = serde_json::to_string(record)?;
+        line.push('\n');
+        Ok(line)
+    }
+
+    fn append_records(&self, session_id: &SessionId, records: &[SessionRecord]) -> Result<()> {
+        let mut buffer = String::new();
+        for record in records {
+            buffer.push_str(&Self::encode(record)?);
+        }
+
+        let _guard = self
+            .write_lock
+            .lock()
+            .map_err(|_| AgentError::Session("Failed to acquire session file lock".to_string()))?;
+        self.ensure_storage_dir()?;
+
+        let path = self.session_file_path(session_id);
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|e| {
+                AgentError::Session(format!(
+                    "Failed to open session file {}: {}",
+                    path.display(),
+                    e
+                ))
+            })?;
+        file.write_all(buffer.as_bytes()).map_err(|e| {
+            AgentError::Session(format!(
+                "Failed to write session file {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+        Ok(())
+    }
+}
+
+impl SessionStore for FileSessionStore {
+    fn save_session(&self, session: &Session) -> Result<()> {
+        let mut buffer = Self::encode(&Self::metadata_record(session))?;
+        for message in &session.context {
+            buffer.push_str(&Self::encode(&SessionRecord::Message {
+                message: message.clone(),
+            })?);
+        }
+
+        let _guard = self
+            .write_lock
+            .lock()
+            .map_err(|_| AgentError::Session("Failed to acquire session file lock".to_string()))?;
+        self.ensure_storage_dir()?;
+
+        // Write to a temporary file and rename so a crash never leaves a half-written snapshot
+        let path = self.session_file_path(&session.id);
+        let temp_path = path.with_extension(format!("{}.tmp", Self::EXTENSION));
+        fs::write(&temp_path, buffer).map_err(|e| {
+            AgentError::Session(format!(
+                "Failed to write session file {}: {}",
+                temp_path.display(),
+                e
+            ))
+        })?;
+        fs::rename(&temp_path, &path).map_err(|e| {
+            let _ = fs::remove_file(&temp_path);
+            AgentError::Session(format!(
+                "Failed to replace session file {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+
+        drop(_guard);
+        if let Ok(mut appended) = self.appended_metadata.lock() {
+            appended.remove(&session.id);
+        }
+
+        tracing::debug!("Saved session snapshot to {}", path.display());
+        Ok(())
+    }
+
+    fn save_metadata(&self, session: &Session) -> Result<()> {
+        if !self.session_file_path(&session.id).exists() {
+            return self.save_session(session);
+        }
+
+        // Rewrite the file now and then so superseded metadata does not pile up
+        let appended = match self.appended_metadata.lock() {
+            Ok(mut appended) => {
+                let count = appended.entry(session.id).or_default();
+                *count += 1;
+                *count
+            }
+            Err(_) => 0,
+        };
+        if appended > Self::MAX_APPENDED_METADATA_RECORDS {
+            tracing::debug!(
+                "Compacting session file of {} ({} metadata records)",
+                session.id,
+                appended
+            );
+            return self.save_session(session);
+        }
+        self.append_records(&session.id, &[Self::metadata_record(session)])
+    }
+
+    fn append_message(&self, session_id: &SessionId, message: &Message) -> Result<()> {
+        if !self.session_file_path(session_id).exists() {
+            return Err(AgentError::Session(format!(
+                "Session not found in store: {}",
+                session_id
+            )));
+        }
+        self.append_records(
+            session_id,
+            &[SessionRecord::Message {
+                message: message.clone(),
+            }],
+        )
+    }
+
+    fn load_session(&self, session_id: &SessionId) -> Result<Option<Session>> {
+        let path = self.session_file_path(session_id);
+        let file = match fs::File::open(&path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => {
+                return Err(AgentError::Session(format!(
+                    "Failed to open session file {}: {}",
+                    path.display(),
+                    e
+                )))
+            }
+        };
+
+        let mut session: Option<Session> = None;
+        let mut messages = Vec::new();
+
+        for (index, line) in BufReader::new(file).lines().enumerate() {
+            let line = line.map_err(|e| {
+                AgentError::Session(format!(
+                    "Failed to read session file {}: {}",
+                    path.display(),
+                    e
+                ))
+            })?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            match serde_json::from_str::<SessionRecord>(&line) {
+                Ok(SessionRecord::Metadata { session: metadata }) => session = Some(*metadata),
+                Ok(SessionRecord::Message { message }) => messages.push(message),
+                Err(e) => {
+                    tracing::warn!(
+                        "Skipping unreadable record at line {} of {}: {}",
+                        index + 1,
+                        path.display(),
+                        e
+                    );
+                }
+            }
+        }
+
+        match session {
+            Some(mut session) => {
+                session.context = messages;
+                Ok(Some(session))
+            }
+            None => Err(AgentError::Session(format!(
+                "Session file {} has no metadata record",
+                path.display()
+            ))),
+        }
+    }
+
+    fn remove_session(&self, session_id: &SessionId) -> Result<bool> {
+        let _guard = self
+            .write_lock
+            .lock()
+            .map_err(|_| AgentError::Session("Failed to acquire session file lock".to_string()))?;
+
+        let path = self.session_file_path(session_id);
+        match fs::remove_file(&path) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(AgentError::Session(format!(
+                "Failed to remove session file {}: {}",
+                path.display(),
+                e
+            ))),
+        }
+    }
+
+    fn list_sessions(&self) -> Result<Vec<SessionId>> {
+        let entries = match fs::read_dir(&self.storage_path) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => {
+                return Err(AgentError::Session(format!(
+                    "Failed to list session directory {}: {}",
+                    self.storage_path.display(),
+                    e
+                )))
+            }
+        };
+
+        let mut session_ids = Vec::new();
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some(Self::EXTENSION) {
+                continue;
+            }
+            if let Some(session_id) = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(|stem| SessionId::parse(stem).ok())
+            {
+                session_ids.push(session_id);
+            }
+        }
+        Ok(session_ids)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::MessageRole;
+    use tempfile::tempdir;
+
+    fn test_session() -> Session {
+        Session::new(SessionId::new(), std::env::temp_dir())
+    }
+
+    #[test]
+    fn test_in_memory_store_round_trip() {
+        let store = InMemorySessionStore::new();
+        let session = test_session();
+
+        store.save_session(&session).unwrap();
+        store
+            .append_message(
+                &session.id,
+                &Message::new(MessageRole::User, "Hello".to_string()),
+            )
+            .unwrap();
+
+        let loaded = store.load_session(&session.id).unwrap().unwrap();
+        assert_eq!(loaded.id, session.id);
+        assert_eq!(loaded.context.len(), 1);
+        assert_eq!(store.list_sessions().unwrap(), vec![session.id]);
+
+        assert!(store.remove_session(&session.id).unwrap());
+        assert!(store.load_session(&session.id).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_file_store_round_trip() {
+        let temp_dir = tempdir().unwrap();
+        let store = FileSessionStore::new(temp_dir.path().join("sessions"));
+        let session = test_session();
+
+        store.save_session(&session).unwrap();
+        store
+            .append_message(
+                &session.id,
+                &Message::new(MessageRole::User, "Hello".to_string()),
+            )
+            .unwrap();
+        store
+            .append_message(
+                &session.id,
+                &Message::new(MessageRole::Assistant, "Hi there".to_string()),
+            )
+            .unwrap();
+
+        let loaded = store.load_session(&session.id).unwrap().unwrap();
+        assert_eq!(loaded.id, session.id);
+        assert_eq!(loaded.cwd, session.cwd);
+        assert_eq!(loaded.context.len(), 2);
+        assert_eq!(loaded.context[1].content, "Hi there");
+        assert_eq!(store.list_sessions().unwrap(), vec![session.id]);
+    }
+
+    #[test]
+    fn test_file_store_last_metadata_wins() {
+        let temp_dir = tempdir().unwrap();
+        let store = FileSessionStore::new(temp_dir.path().to_path_buf());
+        let mut session = test_session();
+
+        store.save_session(&session).unwrap();
+        session.current_mode = Some("architect".to_string());
+        store.save_metadata(&session).unwrap();
+
+        let loaded = store.load_session(&session.id).unwrap().unwrap();
+        assert_eq!(loaded.current_mode.as_deref(), Some("architect"));
+    }
+
+    #[test]
+    fn test_file_store_compacts_metadata_on_write() {
+        let temp_dir = tempdir().unwrap();
+        let store = FileSessionStore::new(temp_dir.path().to_path_buf());
+        let mut session = test_session();
+        session.add_message(Message::new(MessageRole::User, "Hello".to_string()));
+        store.save_session(&session).unwrap();
+
+        let path = store.session_file_path(&session.id);
+        let line_count = || fs::read_to_string(&path).unwrap().lines().count();
+        for _ in 0..FileSessionStore::MAX_APPENDED_METADATA_RECORDS {
+            store.save_metadata(&session).unwrap();
+        }
+        assert_eq!(
+            line_count(),
+            2 + FileSessionStore::MAX_APPENDED_METADATA_RECORDS
+        );
+
+        // Loading never writes
+        let modified = fs::metadata(&path).unwrap().modified().unwrap();
+        let loaded = store.load_session(&session.id).unwrap().unwrap();
+        assert_eq!(loaded.context.len(), 1);
+        assert_eq!(fs::metadata(&path).unwrap().modified().unwrap(), modified);
+        assert_eq!(
+            line_count(),
+            2 + FileSessionStore::MAX_APPENDED_METADATA_RECORDS
+        );
+
+        session.current_mode = Some("architect".to_string());
+        store.save_metadata(&session).unwrap();
+        assert_eq!(line_count(), 2);
+        let loaded = store.load_session(&session.id).unwrap().unwrap();
+        assert_eq!(loaded.current_mode.as_deref(), Some("architect"));
+        assert_eq!(loaded.context.len(), 1);
+    }
+
+    #[test]
+    fn test_file_store_skips_truncated_line() {
+        let temp_dir = tempdir().unwrap();
+        let store = FileSessionStore::new(temp_dir.path().to_path_buf());
+        let session = test_session();
+
+        store.save_session(&session).unwrap();
+        store
+            .append_message(
+                &session.id,
+                &Message::new(MessageRole::User, "Hello".to_string()),
+            )
+            .unwrap();
+
+        let path = store.session_file_path(&session.id);
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(br#"{"record":"message","mess"#).unwrap();
+
+        let loaded = store.load_session(&session.id).unwrap().unwrap();
+        assert_eq!(loaded.context.len(), 1);
+    }
+
+    #[test]
+    fn test_file_store_missing_session() {
+        let temp_dir = tempdir().unwrap();
+        let store = FileSessionStore::new(temp_dir.path().to_path_buf());
+        let session_id = SessionId::new();
+
+        assert!(store.load_session(&session_id).unwrap().is_none());
+        assert!(!store.remove_session(&session_id).unwrap());
+        assert!(store
+            .append_message(
+                &session_id,
+                &Message::new(MessageRole::User, "Hello".to_string())
+            )
+            .is_err());
+    }
+}
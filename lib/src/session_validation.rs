@@ -84,10 +84,11 @@ fn validate_directory_permissions(path: &Path) -> SessionSetupResult<()> {
         Ok(_) => {
             // Directory is readable, now check if we can access it (execute permission)
             // On Unix systems, execute permission on a directory means we can traverse it
+            let original_dir = std::env::current_dir().ok();
             match std::env::set_current_dir(path) {
                 Ok(_) => {
                     // Restore the original directory
-                    if let Ok(original_dir) = std::env::current_dir() {
+                    if let Some(original_dir) = original_dir {
                         let _ = std::env::set_current_dir(&original_dir);
                     }
                     Ok(())
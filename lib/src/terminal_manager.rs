@@ -3,18 +3,34 @@
 //! This module provides comprehensive terminal session management following
 //! the Agent Client Protocol (ACP) specification.
 
+use crate::config::TerminalConfig;
+use futures::future::BoxFuture;
+use futures::FutureExt;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::process::Stdio;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt};
 use tokio::process::{Child, Command};
-use tokio::sync::RwLock;
+use tokio::sync::{watch, RwLock};
 use tokio::task::JoinHandle;
 
+/// How long output is still collected after the process exits
+///
+/// Background processes started by a command can keep its output open; their later
+/// output still reaches the buffer, but waiting for it is capped.
+const OUTPUT_DRAIN_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// How often a running command is checked for exit
+const EXIT_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
 /// Manages terminal sessions for command execution
 #[derive(Debug, Clone)]
 pub struct TerminalManager {
     pub terminals: Arc<RwLock<HashMap<String, TerminalSession>>>,
+    config: TerminalConfig,
 }
 
 /// Terminal lifecycle state
@@ -88,9 +104,16 @@ pub struct TerminalSession {
     pub output_byte_limit: u64,
     pub output_buffer: Arc<RwLock<Vec<u8>>>,
     pub buffer_truncated: Arc<RwLock<bool>>,
+    /// Bytes of output added since the terminal was created, including dropped ones;
+    /// updated while the output buffer is locked for writing
+    pub output_written: Arc<AtomicU64>,
     pub exit_status: Arc<RwLock<Option<ExitStatus>>>,
     pub state: Arc<RwLock<TerminalState>>,
     pub output_task: Option<JoinHandle<()>>,
+    /// Becomes `true` once the process output has been read to the end
+    pub output_closed: Option<watch::Receiver<bool>>,
+    /// Notified whenever output is added or the process output ends
+    pub output_changed: watch::Sender<()>,
     pub timeout_config: TimeoutConfig,
 }
 
@@ -172,6 +195,24 @@ pub struct ExitStatus {
     pub signal: Option<String>,
 }
 
+/// Output a terminal produced after a given offset, for pushing it to the client
+///
+/// Offsets count bytes of output since the terminal was created, so a reader that
+/// passes `next_offset` back in receives each byte at most once.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TerminalOutputChunk {
+    /// Output after the requested offset, ending at a character boundary
+    pub output: String,
+    /// Offset of the first byte of `output`
+    pub offset: u64,
+    /// Offset to ask for next time
+    pub next_offset: u64,
+    /// Whether output after the requested offset was dropped from the buffer first
+    pub skipped: bool,
+    /// Whether the command's output has ended
+    pub finished: bool,
+}
+
 /// ACP-compliant request parameters for terminal/release method
 #[derive(Debug, Deserialize)]
 pub struct TerminalReleaseParams {
@@ -184,11 +225,22 @@ pub struct TerminalReleaseParams {
 impl TerminalManager {
     /// Create a new terminal manager
     pub fn new() -> Self {
+        Self::with_config(TerminalConfig::default())
+    }
+
+    /// Create a terminal manager running commands with the given shell settings
+    pub fn with_config(config: TerminalConfig) -> Self {
         Self {
             terminals: Arc::new(RwLock::new(HashMap::new())),
+            config,
         }
     }
 
+    /// The shell settings commands run with
+    pub fn config(&self) -> &TerminalConfig {
+        &self.config
+    }
+
     /// Generate ACP-compliant terminal ID with "term_" prefix
     fn generate_terminal_id(&self) -> String {
         format!("term_{}", ulid::Ulid::new())
@@ -213,9 +265,12 @@ impl TerminalManager {
             output_byte_limit: 1_048_576, // 1MB default
             output_buffer: Arc::new(RwLock::new(Vec::new())),
             buffer_truncated: Arc::new(RwLock::new(false)),
+            output_written: Arc::new(AtomicU64::new(0)),
             exit_status: Arc::new(RwLock::new(None)),
             state: Arc::new(RwLock::new(TerminalState::Created)),
             output_task: None,
+            output_closed: None,
+            output_changed: watch::Sender::new(()),
             timeout_config: TimeoutConfig::default(),
         };
 
@@ -270,9 +325,12 @@ impl TerminalManager {
             output_byte_limit: params.output_byte_limit.unwrap_or(1_048_576), // 1MB default
             output_buffer: Arc::new(RwLock::new(Vec::new())),
             buffer_truncated: Arc::new(RwLock::new(false)),
+            output_written: Arc::new(AtomicU64::new(0)),
             exit_status: Arc::new(RwLock::new(None)),
             state: Arc::new(RwLock::new(TerminalState::Created)),
             output_task: None,
+            output_closed: None,
+            output_changed: watch::Sender::new(()),
             timeout_config: TimeoutConfig::default(),
         };
 
@@ -356,71 +414,263 @@ impl TerminalManager {
         Ok(environment)
     }
 
-    /// Execute a command in the specified terminal session
-    pub async fn execute_command(&self, terminal_id: &str, command: &str) -> crate::Result<String> {
+    /// Start the command of a terminal created by `create_terminal_with_command`
+    ///
+    /// The command runs through the configured shell, in a pseudo-terminal when enabled.
+    /// Its output is appended to the terminal's output buffer as it is produced, so
+    /// `terminal/output` shows live progress.
+    pub async fn start_terminal(&self, terminal_id: &str) -> crate::Result<()> {
         let mut terminals = self.terminals.write().await;
         let session = terminals.get_mut(terminal_id).ok_or_else(|| {
-            crate::AgentError::ToolExecution(format!("Terminal {} not found", terminal_id))
+            crate::AgentError::Protocol(format!("Terminal not found: {}", terminal_id))
         })?;
 
-        tracing::info!("Executing command in terminal {}: {}", terminal_id, command);
+        if session.process.is_some() {
+            return Err(crate::AgentError::Protocol(format!(
+                "Terminal {} has already been started",
+                terminal_id
+            )));
+        }
+        let command = session.command.clone().ok_or_else(|| {
+            crate::AgentError::Protocol(format!("Terminal {} has no command", terminal_id))
+        })?;
+
+        let args = session.args.clone();
+        self.spawn_in_session(session, &command, &args, None)
+            .await?;
 
-        // Parse command and arguments
-        let parts: Vec<&str> = command.split_whitespace().collect();
-        if parts.is_empty() {
+        tracing::info!("Started terminal {}: {}", terminal_id, command);
+        Ok(())
+    }
+
+    /// Execute a command in the specified terminal session
+    ///
+    /// The command line is interpreted by the configured shell, so pipes, quotes,
+    /// redirects and variable expansions work. Output is appended to the terminal's
+    /// output buffer while the command runs; the returned text holds the output of this
+    /// command only.
+    pub async fn execute_command(&self, terminal_id: &str, command: &str) -> crate::Result<String> {
+        if command.trim().is_empty() {
             return Err(crate::AgentError::ToolExecution(
                 "Empty command".to_string(),
             ));
         }
 
-        let program = parts[0];
-        let args = &parts[1..];
+        let transcript = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let (process, output_closed) = {
+            let mut terminals = self.terminals.write().await;
+            let session = terminals.get_mut(terminal_id).ok_or_else(|| {
+                crate::AgentError::ToolExecution(format!("Terminal {} not found", terminal_id))
+            })?;
 
-        // Transition to Running state
-        *session.state.write().await = TerminalState::Running;
+            tracing::info!("Executing command in terminal {}: {}", terminal_id, command);
 
-        // Execute command
-        let output = Command::new(program)
-            .args(args)
-            .current_dir(&session.working_dir)
-            .envs(&session.environment)
-            .output()
-            .await
-            .map_err(|e| {
-                crate::AgentError::ToolExecution(format!("Failed to execute command: {}", e))
+            self.spawn_in_session(session, command, &[], Some(transcript.clone()))
+                .await
+                .map_err(|e| {
+                    crate::AgentError::ToolExecution(format!("Failed to execute command: {}", e))
+                })?;
+            (session.process.clone(), session.output_closed.clone())
+        };
+        let process = process
+            .ok_or_else(|| crate::AgentError::ToolExecution("Command did not start".to_string()))?;
+
+        // Poll instead of waiting on the child so terminal/kill can still reach it
+        let status = loop {
+            let exited = process.write().await.try_wait().map_err(|e| {
+                crate::AgentError::ToolExecution(format!("Failed to wait for command: {}", e))
             })?;
+            match exited {
+                Some(status) => break status,
+                None => tokio::time::sleep(EXIT_POLL_INTERVAL).await,
+            }
+        };
+        wait_for_output(output_closed).await;
+
+        // Transition to Finished state and set exit status, unless the terminal was
+        // released or killed in the meantime
+        if let Some(session) = self.terminals.read().await.get(terminal_id) {
+            if session.get_state().await == TerminalState::Running {
+                session
+                    .set_exit_status(ExitStatus {
+                        exit_code: status.code(),
+                        signal: TerminalSession::get_signal_name(&status),
+                    })
+                    .await;
+                *session.state.write().await = TerminalState::Finished;
+            }
+        }
 
-        // Transition to Finished state and set exit status
-        let exit_status = ExitStatus {
-            exit_code: output.status.code(),
-            signal: None,
+        let output = {
+            let transcript = transcript.lock().unwrap_or_else(|e| e.into_inner());
+            String::from_utf8_lossy(&transcript).to_string()
         };
-        session.set_exit_status(exit_status).await;
-        *session.state.write().await = TerminalState::Finished;
+        let result = command_result(status.code(), &output);
 
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        let stderr = String::from_utf8_lossy(&output.stderr);
+        tracing::info!("Command completed with exit code: {:?}", status.code());
+        Ok(result)
+    }
 
-        let result = if output.status.success() {
-            if stdout.is_empty() {
-                "Command completed successfully (exit code: 0)".to_string()
-            } else {
-                format!("Command output:\n{}", stdout)
-            }
+    /// Spawn `command` through the shell for a terminal and start collecting its output
+    ///
+    /// Replaces the terminal's process, clears its exit status and moves it to the
+    /// Running state. A process still running is killed together with the processes it
+    /// started. When `transcript` is given, the output is also copied there, bounded by
+    /// the terminal's output byte limit.
+    async fn spawn_in_session(
+        &self,
+        session: &mut TerminalSession,
+        command: &str,
+        args: &[String],
+        transcript: Option<Arc<std::sync::Mutex<Vec<u8>>>>,
+    ) -> crate::Result<()> {
+        session.stop_process().await;
+
+        let mut shell = self.shell_command(command, args);
+        shell
+            .current_dir(&session.working_dir)
+            .envs(&session.environment)
+            .kill_on_drop(true);
+
+        let sink = OutputSink {
+            buffer: session.output_buffer.clone(),
+            truncated: session.buffer_truncated.clone(),
+            written: session.output_written.clone(),
+            limit: session.output_byte_limit,
+            transcript,
+            changed: session.output_changed.clone(),
+        };
+
+        #[cfg(unix)]
+        let spawned = if self.config.use_pty {
+            spawn_in_pty(shell, sink)
         } else {
-            let exit_code = output.status.code().unwrap_or(-1);
-            if stderr.is_empty() {
-                format!("Command failed (exit code: {})", exit_code)
-            } else {
-                format!("Command failed (exit code: {}):\n{}", exit_code, stderr)
-            }
+            spawn_with_pipes(shell, sink)
         };
+        #[cfg(not(unix))]
+        let spawned = spawn_with_pipes(shell, sink);
+        let (child, capture) = spawned.map_err(|e| {
+            crate::AgentError::ToolExecution(format!(
+                "Failed to start {} with shell {}: {}",
+                command, self.config.shell, e
+            ))
+        })?;
 
-        tracing::info!(
-            "Command completed with exit code: {:?}",
-            output.status.code()
-        );
-        Ok(result)
+        let (closed_tx, closed_rx) = watch::channel(false);
+        let output_changed = session.output_changed.clone();
+        let output_task = tokio::spawn(async move {
+            capture.await;
+            let _ = closed_tx.send(true);
+            output_changed.send_replace(());
+        });
+
+        session.process = Some(Arc::new(RwLock::new(child)));
+        session.output_task = Some(output_task);
+        session.output_closed = Some(closed_rx);
+        *session.exit_status.write().await = None;
+        *session.state.write().await = TerminalState::Running;
+        Ok(())
+    }
+
+    /// Program and arguments that run the command line `command` through the shell
+    pub fn shell_invocation(&self, command: &str) -> (String, Vec<String>) {
+        let invocation = self.shell_command(command, &[]);
+        let invocation = invocation.as_std();
+        (
+            invocation.get_program().to_string_lossy().into_owned(),
+            invocation
+                .get_args()
+                .map(|arg| arg.to_string_lossy().into_owned())
+                .collect(),
+        )
+    }
+
+    /// Working directory of a terminal, `None` if there is no such terminal
+    pub async fn working_directory(&self, terminal_id: &str) -> Option<std::path::PathBuf> {
+        let terminals = self.terminals.read().await;
+        Some(terminals.get(terminal_id)?.working_dir.clone())
+    }
+
+    /// Build the shell invocation running `command`
+    ///
+    /// Without arguments `command` is a shell command line. With arguments it names a
+    /// program, which receives them unchanged as positional parameters.
+    fn shell_command(&self, command: &str, args: &[String]) -> Command {
+        let shell = &self.config.shell;
+        let mut invocation = Command::new(shell);
+
+        let is_cmd = std::path::Path::new(shell)
+            .file_stem()
+            .is_some_and(|stem| stem.eq_ignore_ascii_case("cmd"));
+        if is_cmd {
+            invocation.arg("/C").arg(command).args(args);
+        } else if args.is_empty() {
+            invocation.arg("-c").arg(command);
+        } else {
+            invocation
+                .arg("-c")
+                .arg("\"$0\" \"$@\"")
+                .arg(command)
+                .args(args);
+        }
+        invocation
+    }
+
+    /// Subscribe to a terminal's output
+    ///
+    /// The receiver is notified whenever output is added and once the command's output
+    /// ends; read the output itself with [`Self::output_since`].
+    pub async fn subscribe_output(&self, terminal_id: &str) -> crate::Result<watch::Receiver<()>> {
+        let terminals = self.terminals.read().await;
+        let session = terminals.get(terminal_id).ok_or_else(|| {
+            crate::AgentError::Protocol(format!("Terminal not found: {}", terminal_id))
+        })?;
+        Ok(session.output_changed.subscribe())
+    }
+
+    /// Offset at the end of a terminal's output so far, `None` if there is no such terminal
+    pub async fn output_offset(&self, terminal_id: &str) -> Option<u64> {
+        let terminals = self.terminals.read().await;
+        let session = terminals.get(terminal_id)?;
+        let _buffer = session.output_buffer.read().await;
+        Some(session.output_written.load(Ordering::Acquire))
+    }
+
+    /// Output of a terminal after `offset`, `None` once it has been released
+    ///
+    /// Output dropped from the buffer by the byte limit is skipped. A character cut
+    /// in half by a read is held back until its remaining bytes arrive.
+    pub async fn output_since(
+        &self,
+        terminal_id: &str,
+        offset: u64,
+    ) -> Option<TerminalOutputChunk> {
+        let terminals = self.terminals.read().await;
+        let session = terminals.get(terminal_id)?;
+        if session.is_released().await {
+            return None;
+        }
+        let finished = session
+            .output_closed
+            .as_ref()
+            .is_some_and(|closed| *closed.borrow());
+
+        let buffer = session.output_buffer.read().await;
+        let written = session.output_written.load(Ordering::Acquire);
+        let buffer_start = written - buffer.len() as u64;
+        let start = offset.clamp(buffer_start, written);
+        let new_bytes = &buffer[(start - buffer_start) as usize..];
+        let complete = match std::str::from_utf8(new_bytes) {
+            Err(e) if e.error_len().is_none() && !finished => e.valid_up_to(),
+            _ => new_bytes.len(),
+        };
+        Some(TerminalOutputChunk {
+            output: String::from_utf8_lossy(&new_bytes[..complete]).into_owned(),
+            offset: start,
+            next_offset: start + complete as u64,
+            skipped: offset < buffer_start,
+            finished,
+        })
     }
 
     /// Change the working directory for a terminal session
@@ -458,10 +708,7 @@ impl TerminalManager {
     pub async fn remove_terminal(&self, terminal_id: &str) -> crate::Result<()> {
         let mut terminals = self.terminals.write().await;
         if let Some(mut session) = terminals.remove(terminal_id) {
-            if let Some(process) = session.process.take() {
-                let mut proc = process.write().await;
-                let _ = proc.kill().await;
-            }
+            session.stop_process().await;
             tracing::info!("Removed terminal session: {}", terminal_id);
         }
         Ok(())
@@ -607,25 +854,217 @@ impl Default for TerminalManager {
     }
 }
 
+/// Output of a running command, copied into a terminal's output buffer
+struct OutputSink {
+    buffer: Arc<RwLock<Vec<u8>>>,
+    truncated: Arc<RwLock<bool>>,
+    written: Arc<AtomicU64>,
+    limit: u64,
+    transcript: Option<Arc<std::sync::Mutex<Vec<u8>>>>,
+    changed: watch::Sender<()>,
+}
+
+impl OutputSink {
+    async fn append(&self, data: &[u8]) {
+        {
+            let mut buffer = self.buffer.write().await;
+            let mut truncated = self.truncated.write().await;
+            append_bounded(&mut buffer, &mut truncated, self.limit, data);
+            self.written.fetch_add(data.len() as u64, Ordering::Release);
+        }
+        self.append_transcript(data);
+        self.changed.send_replace(());
+    }
+
+    fn append_transcript(&self, data: &[u8]) {
+        if let Some(transcript) = &self.transcript {
+            let mut transcript = transcript.lock().unwrap_or_else(|e| e.into_inner());
+            append_bounded(&mut transcript, &mut false, self.limit, data);
+        }
+    }
+}
+
+/// Append `data`, then drop bytes from the front so at most `limit` bytes remain
+///
+/// Truncation happens at a UTF-8 character boundary and sets `truncated`.
+fn append_bounded(buffer: &mut Vec<u8>, truncated: &mut bool, limit: u64, data: &[u8]) {
+    // Always append the new data first
+    buffer.extend_from_slice(data);
+
+    // Then truncate from beginning if we exceed the limit
+    let limit = limit as usize;
+    if buffer.len() > limit {
+        let excess = buffer.len() - limit;
+
+        // Find a safe UTF-8 boundary to truncate at
+        let truncate_point = TerminalSession::find_utf8_boundary(buffer, excess);
+        buffer.drain(0..truncate_point);
+        *truncated = true;
+    }
+}
+
+/// Describe the outcome of a command for the tool result
+///
+/// `exit_code` is `None` when the command was ended by a signal.
+pub(crate) fn command_result(exit_code: Option<i32>, output: &str) -> String {
+    match exit_code {
+        Some(0) if output.is_empty() => "Command completed successfully (exit code: 0)".to_string(),
+        Some(0) => format!("Command output:\n{}", output),
+        _ => {
+            let exit_code = exit_code.unwrap_or(-1);
+            if output.is_empty() {
+                format!("Command failed (exit code: {})", exit_code)
+            } else {
+                format!("Command failed (exit code: {}):\n{}", exit_code, output)
+            }
+        }
+    }
+}
+
+/// Spawn a command with its output on pipes; stdout and stderr share the buffer
+fn spawn_with_pipes(
+    mut command: Command,
+    sink: OutputSink,
+) -> std::io::Result<(Child, BoxFuture<'static, ()>)> {
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    // A process group of its own lets terminal/kill reach every process of a pipeline
+    #[cfg(unix)]
+    command.process_group(0);
+
+    let mut child = command.spawn()?;
+    let stdout = child.stdout.take();
+    let stderr = child.stderr.take();
+    let capture = async move {
+        tokio::join!(read_pipe(stdout, &sink), read_pipe(stderr, &sink));
+    };
+    Ok((child, capture.boxed()))
+}
+
+async fn read_pipe(pipe: Option<impl AsyncRead + Unpin>, sink: &OutputSink) {
+    let Some(mut pipe) = pipe else {
+        return;
+    };
+    let mut chunk = [0u8; 4096];
+    loop {
+        match pipe.read(&mut chunk).await {
+            Ok(0) => break,
+            Ok(n) => sink.append(&chunk[..n]).await,
+            Err(e) => {
+                tracing::debug!("Stopped reading terminal output: {}", e);
+                break;
+            }
+        }
+    }
+}
+
+/// Spawn a command attached to a new pseudo-terminal as its controlling terminal
+#[cfg(unix)]
+fn spawn_in_pty(
+    mut command: Command,
+    sink: OutputSink,
+) -> std::io::Result<(Child, BoxFuture<'static, ()>)> {
+    use std::io::Read;
+    use std::os::fd::AsRawFd;
+
+    let size = nix::pty::Winsize {
+        ws_row: 24,
+        ws_col: 80,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    let pty = nix::pty::openpty(&size, None)?;
+    // Commands spawned for other terminals must not inherit this terminal
+    for fd in [pty.master.as_raw_fd(), pty.slave.as_raw_fd()] {
+        // SAFETY: `fd` is open and owned by `pty` for the duration of the call
+        if unsafe { nix::libc::fcntl(fd, nix::libc::F_SETFD, nix::libc::FD_CLOEXEC) } == -1 {
+            return Err(std::io::Error::last_os_error());
+        }
+    }
+    let terminal = std::fs::File::from(pty.slave);
+    command
+        .stdin(Stdio::from(terminal.try_clone()?))
+        .stdout(Stdio::from(terminal.try_clone()?))
+        .stderr(Stdio::from(terminal));
+    // SAFETY: only async-signal-safe calls between fork and exec
+    unsafe {
+        command.pre_exec(|| {
+            nix::unistd::setsid()?;
+            if nix::libc::ioctl(0, nix::libc::TIOCSCTTY, 0) == -1 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+
+    let child = command.spawn()?;
+    // Close our copies of the terminal so reads end once the command exits
+    drop(command);
+
+    // Read the master without blocking a thread: dropping the capture closes it even
+    // while a background process still holds the terminal open
+    // SAFETY: the master fd is open and owned by `pty` for the duration of the call
+    if unsafe {
+        nix::libc::fcntl(
+            pty.master.as_raw_fd(),
+            nix::libc::F_SETFL,
+            nix::libc::O_NONBLOCK,
+        )
+    } == -1
+    {
+        return Err(std::io::Error::last_os_error());
+    }
+    let master = tokio::io::unix::AsyncFd::new(std::fs::File::from(pty.master))?;
+    let capture = async move {
+        let mut chunk = [0u8; 4096];
+        loop {
+            let Ok(mut ready) = master.readable().await else {
+                break;
+            };
+            match ready.try_io(|master| master.get_ref().read(&mut chunk)) {
+                Ok(Ok(0)) => break,
+                Ok(Ok(n)) => sink.append(&chunk[..n]).await,
+                Ok(Err(e)) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                // Linux reports EIO once no process has the terminal open
+                Ok(Err(_)) => break,
+                Err(_would_block) => continue,
+            }
+        }
+    };
+    Ok((child, capture.boxed()))
+}
+
+/// Signal the process group led by `pid`, or only the process when it leads none
+#[cfg(unix)]
+fn signal_process_group(
+    pid: nix::unistd::Pid,
+    signal: nix::sys::signal::Signal,
+) -> nix::Result<()> {
+    nix::sys::signal::killpg(pid, signal).or_else(|_| nix::sys::signal::kill(pid, signal))
+}
+
+/// Wait until a command's output has been read to the end, at most `OUTPUT_DRAIN_TIMEOUT`
+async fn wait_for_output(output_closed: Option<watch::Receiver<bool>>) {
+    if let Some(mut output_closed) = output_closed {
+        let _ = tokio::time::timeout(
+            OUTPUT_DRAIN_TIMEOUT,
+            output_closed.wait_for(|closed| *closed),
+        )
+        .await;
+    }
+}
+
 impl TerminalSession {
     /// Add output data to the buffer, enforcing byte limits with character-boundary truncation
     pub async fn add_output(&self, data: &[u8]) {
         let mut buffer = self.output_buffer.write().await;
         let mut truncated = self.buffer_truncated.write().await;
-
-        // Always append the new data first
-        buffer.extend_from_slice(data);
-
-        // Then truncate from beginning if we exceed the limit
-        let limit = self.output_byte_limit as usize;
-        if buffer.len() > limit {
-            let excess = buffer.len() - limit;
-
-            // Find a safe UTF-8 boundary to truncate at
-            let truncate_point = Self::find_utf8_boundary(&buffer, excess);
-            buffer.drain(0..truncate_point);
-            *truncated = true;
-        }
+        append_bounded(&mut buffer, &mut truncated, self.output_byte_limit, data);
+        self.output_written
+            .fetch_add(data.len() as u64, Ordering::Release);
+        self.output_changed.send_replace(());
     }
 
     /// Find the nearest UTF-8 character boundary at or after the given position
@@ -768,6 +1207,9 @@ impl TerminalSession {
             })?
         };
 
+        // Let the output reader catch up before reporting the exit
+        wait_for_output(self.output_closed.clone()).await;
+
         // Convert to our ExitStatus
         let exit_status = ExitStatus {
             exit_code: status.code(),
@@ -839,7 +1281,7 @@ impl TerminalSession {
 
     #[cfg(unix)]
     async fn kill_process_unix(&self, process: &Arc<RwLock<Child>>) -> crate::Result<()> {
-        use nix::sys::signal::{kill, Signal};
+        use nix::sys::signal::Signal;
         use nix::unistd::Pid;
 
         let pid = {
@@ -853,7 +1295,7 @@ impl TerminalSession {
 
         // Send SIGTERM for graceful shutdown
         tracing::debug!("Sending SIGTERM to process {}", pid);
-        kill(pid, Signal::SIGTERM).map_err(|e| {
+        signal_process_group(pid, Signal::SIGTERM).map_err(|e| {
             crate::AgentError::ToolExecution(format!("Failed to send SIGTERM: {}", e))
         })?;
 
@@ -885,7 +1327,7 @@ impl TerminalSession {
                     "Graceful shutdown timed out, sending SIGKILL to process {}",
                     pid
                 );
-                kill(pid, Signal::SIGKILL).map_err(|e| {
+                signal_process_group(pid, Signal::SIGKILL).map_err(|e| {
                     crate::AgentError::ToolExecution(format!("Failed to send SIGKILL: {}", e))
                 })?;
 
@@ -925,6 +1367,31 @@ impl TerminalSession {
         Ok(())
     }
 
+    /// Kill the terminal's process, if it is still running, and take it out of the session
+    ///
+    /// The whole process group is killed: `kill_on_drop` only reaches the shell, and the
+    /// commands it started would keep running. The output reader then gets
+    /// `OUTPUT_DRAIN_TIMEOUT` to finish before it is stopped, since a process that left the
+    /// group can keep the terminal open.
+    async fn stop_process(&mut self) {
+        if let Some(process) = self.process.take() {
+            let mut proc = process.write().await;
+            #[cfg(unix)]
+            if let Some(pid) = proc.id() {
+                let _ = nix::sys::signal::killpg(
+                    nix::unistd::Pid::from_raw(pid as i32),
+                    nix::sys::signal::Signal::SIGKILL,
+                );
+            }
+            let _ = proc.kill().await;
+            tracing::debug!("Killed terminal process");
+        }
+        if let Some(task) = self.output_task.take() {
+            wait_for_output(self.output_closed.clone()).await;
+            task.abort();
+        }
+    }
+
     /// Release terminal resources
     ///
     /// ACP terminal/release method implementation:
@@ -936,18 +1403,14 @@ impl TerminalSession {
     ///
     /// Proper release prevents resource leaks and ensures clean shutdown.
     pub async fn release(&mut self) -> crate::Result<()> {
-        // Kill process if still running
-        if let Some(process) = self.process.take() {
-            let mut proc = process.write().await;
-            let _ = proc.kill().await;
-            tracing::debug!("Killed process during terminal release");
-        }
-
         // Abort output task if running
         if let Some(task) = self.output_task.take() {
             task.abort();
         }
 
+        // Kill process if still running
+        self.stop_process().await;
+
         // Clear output buffers to free memory
         self.output_buffer.write().await.clear();
         *self.buffer_truncated.write().await = false;
@@ -1176,9 +1639,12 @@ mod tests {
             output_byte_limit: 1024,
             output_buffer: Arc::new(RwLock::new(Vec::new())),
             buffer_truncated: Arc::new(RwLock::new(false)),
+            output_written: Arc::new(AtomicU64::new(0)),
             exit_status: Arc::new(RwLock::new(None)),
             state: Arc::new(RwLock::new(TerminalState::Created)),
             output_task: None,
+            output_closed: None,
+            output_changed: watch::Sender::new(()),
             timeout_config: TimeoutConfig::default(),
         };
 
@@ -1594,9 +2060,12 @@ mod tests {
             output_byte_limit: 100, // Small limit for testing truncation
             output_buffer: Arc::new(RwLock::new(Vec::new())),
             buffer_truncated: Arc::new(RwLock::new(false)),
+            output_written: Arc::new(AtomicU64::new(0)),
             exit_status: Arc::new(RwLock::new(None)),
             state: Arc::new(RwLock::new(TerminalState::Created)),
             output_task: None,
+            output_closed: None,
+            output_changed: watch::Sender::new(()),
             timeout_config: TimeoutConfig::default(),
         }
     }
@@ -1878,4 +2347,280 @@ mod tests {
         assert!(session.is_output_truncated().await);
         assert!(output.len() <= 100);
     }
+
+    async fn terminal_output(manager: &TerminalManager, terminal_id: &str) -> String {
+        let terminals = manager.terminals.read().await;
+        terminals
+            .get(terminal_id)
+            .unwrap()
+            .get_output_string()
+            .await
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_execute_command_runs_through_shell() {
+        let manager = TerminalManager::new();
+        let temp_dir = tempfile::tempdir().unwrap();
+        let terminal_id = manager
+            .create_terminal(Some(temp_dir.path().to_string_lossy().to_string()))
+            .await
+            .unwrap();
+
+        let result = manager
+            .execute_command(
+                &terminal_id,
+                "GREETING='hello world'; echo \"$GREETING\" | tr a-z A-Z > out.txt && cat out.txt",
+            )
+            .await
+            .unwrap();
+        assert_eq!(result, "Command output:\nHELLO WORLD\n");
+        assert_eq!(
+            terminal_output(&manager, &terminal_id).await,
+            "HELLO WORLD\n"
+        );
+
+        let result = manager
+            .execute_command(&terminal_id, "echo oops >&2; exit 3")
+            .await
+            .unwrap();
+        assert_eq!(result, "Command failed (exit code: 3):\noops\n");
+
+        let terminals = manager.terminals.read().await;
+        let session = terminals.get(&terminal_id).unwrap();
+        assert_eq!(session.get_state().await, TerminalState::Finished);
+        assert_eq!(session.get_exit_status().await.unwrap().exit_code, Some(3));
+        assert_eq!(session.get_output_string().await, "HELLO WORLD\noops\n");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_execute_command_output_is_visible_while_running() {
+        let manager = Arc::new(TerminalManager::new());
+        let terminal_id = manager.create_terminal(None).await.unwrap();
+
+        let command = {
+            let manager = manager.clone();
+            let terminal_id = terminal_id.clone();
+            tokio::spawn(async move {
+                manager
+                    .execute_command(&terminal_id, "echo started; sleep 1; echo done")
+                    .await
+            })
+        };
+
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(5);
+        while terminal_output(&manager, &terminal_id).await != "started\n" {
+            assert!(tokio::time::Instant::now() < deadline, "no live output");
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert!(!command.is_finished());
+
+        let result = command.await.unwrap().unwrap();
+        assert_eq!(result, "Command output:\nstarted\ndone\n");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_start_terminal_passes_arguments_unchanged() {
+        let manager = TerminalManager::new();
+        let session_manager = create_test_session_manager().await;
+        let session_id = session_manager
+            .create_session(std::env::temp_dir(), None)
+            .unwrap();
+
+        let params = TerminalCreateParams {
+            session_id: session_id.to_string(),
+            command: "printf".to_string(),
+            args: Some(vec![
+                "%s|".to_string(),
+                "two words".to_string(),
+                "$HOME".to_string(),
+            ]),
+            env: None,
+            cwd: None,
+            output_byte_limit: None,
+        };
+        let terminal_id = manager
+            .create_terminal_with_command(&session_manager, params)
+            .await
+            .unwrap();
+        manager.start_terminal(&terminal_id).await.unwrap();
+        assert!(manager.start_terminal(&terminal_id).await.is_err());
+
+        let terminals = manager.terminals.read().await;
+        let session = terminals.get(&terminal_id).unwrap();
+        let status = session.wait_for_exit().await.unwrap();
+        assert_eq!(status.exit_code, Some(0));
+        assert_eq!(session.get_output_string().await, "two words|$HOME|");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_execute_command_in_pseudo_terminal() {
+        let manager = TerminalManager::with_config(TerminalConfig {
+            use_pty: true,
+            ..Default::default()
+        });
+        let terminal_id = manager.create_terminal(None).await.unwrap();
+
+        let result = manager
+            .execute_command(&terminal_id, "test -t 0 && test -t 1 && echo interactive")
+            .await
+            .unwrap();
+        assert_eq!(result, "Command output:\ninteractive\r\n");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_pseudo_terminal_is_not_inherited() {
+        let manager = TerminalManager::with_config(TerminalConfig {
+            use_pty: true,
+            ..Default::default()
+        });
+        let terminal_id = manager.create_terminal(None).await.unwrap();
+
+        // The shell has the terminal on its standard streams and on no other descriptor
+        let script = r#"tty=$(readlink /proc/$$/fd/0)
+for fd in /proc/$$/fd/*; do
+  case ${fd##*/} in 0|1|2) ;; *) [ "$(readlink "$fd")" = "$tty" ] && echo "leaked ${fd##*/}" ;; esac
+done
+echo checked"#;
+        let result = manager.execute_command(&terminal_id, script).await.unwrap();
+        assert_eq!(result, "Command output:\nchecked\r\n");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_release_closes_a_pseudo_terminal_held_by_another_session() {
+        let manager = TerminalManager::with_config(TerminalConfig {
+            use_pty: true,
+            ..Default::default()
+        });
+        let session_manager = create_test_session_manager().await;
+        let temp_dir = tempfile::tempdir().unwrap();
+        let session_id = session_manager
+            .create_session(temp_dir.path().to_path_buf(), None)
+            .unwrap()
+            .to_string();
+
+        // The writer leaves the process group, so only closing the terminal stops it
+        let params = TerminalCreateParams {
+            session_id: session_id.clone(),
+            command: "setsid sh -c 'while echo tick; do sleep 0.1; done; touch closed' & sleep 30"
+                .to_string(),
+            args: None,
+            env: None,
+            cwd: Some(temp_dir.path().to_string_lossy().into_owned()),
+            output_byte_limit: None,
+        };
+        let terminal_id = manager
+            .create_terminal_with_command(&session_manager, params)
+            .await
+            .unwrap();
+        manager.start_terminal(&terminal_id).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(300)).await;
+
+        let params = TerminalReleaseParams {
+            session_id,
+            terminal_id,
+        };
+        manager
+            .release_terminal(&session_manager, params)
+            .await
+            .unwrap();
+
+        let closed = temp_dir.path().join("closed");
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while !closed.exists() {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+        })
+        .await
+        .expect("terminal stayed open after release");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_execute_command_stops_the_running_pipeline() {
+        let manager = TerminalManager::new();
+        let session_manager = create_test_session_manager().await;
+        let session_id = session_manager
+            .create_session(std::env::temp_dir(), None)
+            .unwrap();
+
+        let params = TerminalCreateParams {
+            session_id: session_id.to_string(),
+            command: "sleep 30 | cat".to_string(),
+            args: None,
+            env: None,
+            cwd: None,
+            output_byte_limit: None,
+        };
+        let terminal_id = manager
+            .create_terminal_with_command(&session_manager, params)
+            .await
+            .unwrap();
+        manager.start_terminal(&terminal_id).await.unwrap();
+        let mut output_closed = {
+            let terminals = manager.terminals.read().await;
+            terminals[&terminal_id].output_closed.clone().unwrap()
+        };
+
+        let result = manager
+            .execute_command(&terminal_id, "echo next")
+            .await
+            .unwrap();
+        assert_eq!(result, "Command output:\nnext\n");
+
+        // cat only sees end of input once sleep, the other pipeline process, is gone
+        tokio::time::timeout(
+            Duration::from_secs(5),
+            output_closed.wait_for(|closed| *closed),
+        )
+        .await
+        .expect("old pipeline kept running")
+        .unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_kill_stops_every_process_of_a_pipeline() {
+        let manager = TerminalManager::new();
+        let session_manager = create_test_session_manager().await;
+        let session_id = session_manager
+            .create_session(std::env::temp_dir(), None)
+            .unwrap();
+
+        let params = TerminalCreateParams {
+            session_id: session_id.to_string(),
+            command: "sleep 30 | cat".to_string(),
+            args: None,
+            env: None,
+            cwd: None,
+            output_byte_limit: None,
+        };
+        let terminal_id = manager
+            .create_terminal_with_command(&session_manager, params)
+            .await
+            .unwrap();
+        manager.start_terminal(&terminal_id).await.unwrap();
+
+        let terminals = manager.terminals.read().await;
+        let session = terminals.get(&terminal_id).unwrap();
+        tokio::time::timeout(Duration::from_secs(10), session.kill_process())
+            .await
+            .expect("kill timed out")
+            .unwrap();
+
+        // cat only sees end of input once sleep, the other pipeline process, is gone
+        let mut output_closed = session.output_closed.clone().unwrap();
+        tokio::time::timeout(
+            Duration::from_secs(5),
+            output_closed.wait_for(|closed| *closed),
+        )
+        .await
+        .expect("pipeline kept running")
+        .unwrap();
+    }
 }
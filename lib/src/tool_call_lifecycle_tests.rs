@@ -625,6 +625,167 @@ mod tests {
         }
     }
 
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_embedded_terminal_streams_output() {
+        let (handler, mut receiver) = create_test_handler().await;
+        let session_manager = handler.get_session_manager();
+        let internal_session_id = session_manager
+            .create_session(std::env::temp_dir(), None)
+            .expect("Should create session");
+        let session_id = SessionId(internal_session_id.to_string().into());
+
+        let report = handler
+            .create_tool_call_report(&session_id, "Bash", &json!({"command": "cargo build"}))
+            .await;
+        let tool_call_id = report.tool_call_id.clone();
+
+        let params = crate::terminal_manager::TerminalCreateParams {
+            session_id: session_id.0.to_string(),
+            command: "echo building; sleep 0.5; echo done".to_string(),
+            args: None,
+            env: None,
+            cwd: None,
+            output_byte_limit: None,
+        };
+        let terminal_id = handler
+            .execute_with_embedded_terminal(&session_id, &tool_call_id, params)
+            .await
+            .expect("Execute with embedded terminal should succeed");
+
+        // Collect the output pushed with each update until the output ends
+        let mut chunks = Vec::new();
+        let mut texts = Vec::new();
+        let final_content = loop {
+            let notification =
+                tokio::time::timeout(std::time::Duration::from_secs(10), receiver.recv())
+                    .await
+                    .expect("Output stream stalled")
+                    .expect("Should receive notification");
+            let SessionUpdate::ToolCallUpdate(update) = notification.update else {
+                continue;
+            };
+            let Some(meta) = update.meta else {
+                continue;
+            };
+            let output = &meta["terminalOutput"];
+            assert_eq!(output["terminalId"], terminal_id.as_str());
+            assert_eq!(output["skipped"], false);
+            if !output["output"].as_str().unwrap().is_empty() {
+                chunks.push(output["output"].as_str().unwrap().to_string());
+            }
+            let content = update.fields.content.expect("Should include content");
+            match &content[1] {
+                agent_client_protocol::ToolCallContent::Content {
+                    content: agent_client_protocol::ContentBlock::Text(text),
+                } => texts.push(text.text.clone()),
+                _ => panic!("Expected terminal output as text content"),
+            }
+            if output["finished"] == true {
+                break content;
+            }
+        };
+
+        // The meta of each update carries only new output
+        assert_eq!(chunks, ["building\n", "done\n"]);
+
+        // The content shows the output so far, the last one all of it after the terminal
+        assert_eq!(texts.first().map(String::as_str), Some("building\n"));
+        assert!(matches!(
+            final_content[0],
+            agent_client_protocol::ToolCallContent::Terminal { .. }
+        ));
+        match &final_content[1] {
+            agent_client_protocol::ToolCallContent::Content {
+                content: agent_client_protocol::ContentBlock::Text(text),
+            } => assert_eq!(text.text, "building\ndone\n"),
+            _ => panic!("Expected terminal output as text content"),
+        }
+
+        // The completion update carries the final output too
+        let completed = handler
+            .complete_tool_call_report(&session_id, &tool_call_id, None)
+            .await
+            .expect("Tool call should still be active");
+        assert_eq!(completed.content.len(), 2);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_terminal_command_streams_only_its_own_output() {
+        let permissions = ToolPermissions {
+            require_permission_for: vec![],
+            auto_approved: vec!["terminal_create".to_string(), "terminal_write".to_string()],
+            forbidden_paths: vec![],
+        };
+        let session_manager = Arc::new(SessionManager::new());
+        let temp_dir = tempfile::tempdir().unwrap();
+        let storage = FilePermissionStorage::new(temp_dir.path().to_path_buf());
+        let permission_engine = Arc::new(PermissionPolicyEngine::new(Box::new(storage)));
+        let mut handler = ToolCallHandler::new(permissions, session_manager, permission_engine);
+        handler.set_client_capabilities(agent_client_protocol::ClientCapabilities {
+            fs: Default::default(),
+            terminal: true,
+            meta: None,
+        });
+        let (sender, mut receiver) = NotificationSender::new(64);
+        handler.set_notification_sender(sender);
+        let session_id = SessionId("sess_01ARZ3NDEKTSV4RRFFQ69G5FAV".into());
+
+        let request = |id: &str, name: &str, arguments: serde_json::Value| {
+            crate::tools::InternalToolRequest {
+                id: id.to_string(),
+                name: name.to_string(),
+                arguments,
+            }
+        };
+        let created = handler
+            .handle_tool_request(&session_id, request("create", "terminal_create", json!({})))
+            .await
+            .unwrap();
+        let crate::tools::ToolCallResult::Success(message) = created else {
+            panic!("Terminal creation should succeed");
+        };
+        let terminal_id = message.split_whitespace().last().unwrap().to_string();
+
+        for (id, command, expected) in [
+            ("first", "echo first", "first\n"),
+            (
+                "second",
+                "echo second; sleep 0.3; echo more",
+                "second\nmore\n",
+            ),
+        ] {
+            let arguments = json!({"terminal_id": terminal_id, "command": command});
+            handler
+                .handle_tool_request(&session_id, request(id, "terminal_write", arguments))
+                .await
+                .unwrap();
+
+            let mut streamed = String::new();
+            let mut embedded = false;
+            while let Ok(notification) = receiver.try_recv() {
+                let SessionUpdate::ToolCallUpdate(update) = notification.update else {
+                    continue;
+                };
+                if let Some(content) = &update.fields.content {
+                    embedded |= content.iter().any(|content| {
+                        matches!(
+                            content,
+                            agent_client_protocol::ToolCallContent::Terminal { terminal_id: tid }
+                                if tid.0.as_ref() == terminal_id
+                        )
+                    });
+                }
+                if let Some(meta) = update.meta {
+                    streamed.push_str(meta["terminalOutput"]["output"].as_str().unwrap());
+                }
+            }
+            assert!(embedded, "Terminal should be embedded in the tool call");
+            assert_eq!(streamed, expected);
+        }
+    }
+
     #[tokio::test]
     async fn test_terminal_embedding_with_tool_call_completion() {
         let (handler, mut receiver) = create_test_handler().await;
@@ -691,4 +852,89 @@ mod tests {
             _ => panic!("Expected ToolCallUpdate completion notification"),
         }
     }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_mcp_progress_is_forwarded_as_tool_call_updates() {
+        // Reports progress twice before answering each tool call
+        let script = r#"
+            while IFS= read -r line; do
+              id=$(printf '%s' "$line" | sed -n 's/^{"id":\([0-9]*\),.*/\1/p')
+              case "$line" in
+                *'"method":"initialize"'*)
+                  printf '{"jsonrpc":"2.0","id":%s,"result":{}}\n' "$id" ;;
+                *'"method":"tools/list"'*)
+                  printf '{"jsonrpc":"2.0","id":%s,"result":{"tools":[{"name":"index"}]}}\n' "$id" ;;
+                *'"method":"tools/call"'*)
+                  for step in 1 2; do
+                    printf '{"jsonrpc":"2.0","method":"notifications/progress","params":{"progressToken":%s,"progress":%s,"total":2}}\n' "$id" "$step"
+                  done
+                  printf '{"jsonrpc":"2.0","id":%s,"result":{"content":[{"type":"text","text":"indexed"}]}}\n' "$id" ;;
+              esac
+            done
+        "#;
+        let mut mcp_manager = crate::mcp::McpServerManager::new();
+        mcp_manager
+            .connect_servers(vec![crate::config::McpServerConfig::Stdio(
+                crate::config::StdioTransport {
+                    name: "indexer".to_string(),
+                    command: "sh".to_string(),
+                    args: vec!["-c".to_string(), script.to_string()],
+                    env: vec![],
+                    cwd: None,
+                },
+            )])
+            .await
+            .unwrap();
+        let mcp_manager = Arc::new(mcp_manager);
+
+        let permissions = ToolPermissions {
+            require_permission_for: vec![],
+            auto_approved: vec!["indexer:index".to_string()],
+            forbidden_paths: vec![],
+        };
+        let temp_dir = tempfile::tempdir().unwrap();
+        let storage = FilePermissionStorage::new(temp_dir.path().to_path_buf());
+        let mut handler = ToolCallHandler::new_with_mcp_manager(
+            permissions,
+            Arc::clone(&mcp_manager),
+            Arc::new(SessionManager::new()),
+            Arc::new(PermissionPolicyEngine::new(Box::new(storage))),
+        );
+        let (sender, mut receiver) = NotificationSender::new(32);
+        handler.set_notification_sender(sender);
+
+        let session_id = SessionId("test_session".into());
+        let request = crate::tools::InternalToolRequest {
+            id: "call-1".to_string(),
+            name: "indexer:index".to_string(),
+            arguments: json!({}),
+        };
+        let result = handler
+            .handle_tool_request(&session_id, request)
+            .await
+            .unwrap();
+        assert!(
+            matches!(result, crate::tools::ToolCallResult::Success(ref text) if text == "indexed")
+        );
+
+        // Progress is reported between the start and the completion of the tool call
+        let mut progress = Vec::new();
+        loop {
+            let notification = receiver.try_recv().expect("Should receive notification");
+            let SessionUpdate::ToolCallUpdate(update) = notification.update else {
+                continue;
+            };
+            if update.fields.status == Some(ToolCallStatus::Completed.to_acp_status()) {
+                break;
+            }
+            if let Some(meta) = update.meta {
+                assert_eq!(meta["mcpProgress"]["total"], 2.0);
+                progress.push(meta["mcpProgress"]["progress"].as_f64().unwrap());
+            }
+        }
+        assert_eq!(progress, [1.0, 2.0]);
+
+        mcp_manager.shutdown().await.unwrap();
+    }
 }
@@ -20,6 +20,14 @@ impl ToolKind {
         // Complete reporting enables rich client experiences and debugging.
 
         match tool_name {
+            // Claude CLI built-in tools
+            "Read" | "NotebookRead" => ToolKind::Read,
+            "Write" | "Edit" | "MultiEdit" | "NotebookEdit" => ToolKind::Edit,
+            "Glob" | "Grep" | "LS" => ToolKind::Search,
+            "Bash" | "BashOutput" | "KillShell" => ToolKind::Execute,
+            "WebFetch" | "WebSearch" => ToolKind::Fetch,
+            "Task" | "TodoWrite" | "ExitPlanMode" => ToolKind::Think,
+
             // File system read operations
             "fs_read_text_file" | "fs_read" | "read_file" => ToolKind::Read,
 
@@ -91,11 +99,76 @@ impl ToolKind {
     }
 }
 
+impl ToolKind {
+    /// Classify an MCP tool, letting the server's annotations refine the name-based kind
+    ///
+    /// A tool with `readOnlyHint` is a Read unless its name already marks it as a search
+    /// or fetch; an otherwise unclassified tool with `openWorldHint` is a Fetch.
+    pub fn classify_mcp_tool(
+        tool_name: &str,
+        arguments: &serde_json::Value,
+        tool: &crate::mcp::McpTool,
+    ) -> Self {
+        let kind = Self::classify_tool(tool_name, arguments);
+        let Some(annotations) = &tool.annotations else {
+            return kind;
+        };
+
+        match kind {
+            ToolKind::Search | ToolKind::Fetch if annotations.read_only_hint == Some(true) => kind,
+            _ if annotations.read_only_hint == Some(true) => ToolKind::Read,
+            ToolKind::Other if annotations.open_world_hint == Some(true) => ToolKind::Fetch,
+            _ => kind,
+        }
+    }
+}
+
 /// Tool title generation system for human-readable descriptions
 impl ToolCallReport {
     /// Generate a context-aware human-readable title based on tool name and parameters
     pub fn generate_title(tool_name: &str, arguments: &serde_json::Value) -> String {
+        let file_name = |field: &str| {
+            arguments.get(field).and_then(|v| v.as_str()).map(|path| {
+                std::path::Path::new(path)
+                    .file_name()
+                    .and_then(|n| n.to_str())
+                    .unwrap_or(path)
+                    .to_string()
+            })
+        };
+
         match tool_name {
+            // Claude CLI built-in tools
+            "Read" => file_name("file_path")
+                .map(|name| format!("Reading {}", name))
+                .unwrap_or_else(|| "Reading file".to_string()),
+            "Write" => file_name("file_path")
+                .map(|name| format!("Writing to {}", name))
+                .unwrap_or_else(|| "Writing file".to_string()),
+            "Edit" | "MultiEdit" => file_name("file_path")
+                .map(|name| format!("Editing {}", name))
+                .unwrap_or_else(|| "Editing file".to_string()),
+            "Bash" => arguments
+                .get("description")
+                .or_else(|| arguments.get("command"))
+                .and_then(|v| v.as_str())
+                .map(|command| format!("Running {}", command))
+                .unwrap_or_else(|| "Running command".to_string()),
+            "Glob" | "Grep" => arguments
+                .get("pattern")
+                .and_then(|v| v.as_str())
+                .map(|pattern| format!("Searching for '{}'", pattern))
+                .unwrap_or_else(|| "Searching files".to_string()),
+            "WebFetch" => arguments
+                .get("url")
+                .and_then(|v| v.as_str())
+                .map(|url| format!("Fetching {}", url))
+                .unwrap_or_else(|| "Fetching URL".to_string()),
+            "WebSearch" => arguments
+                .get("query")
+                .and_then(|v| v.as_str())
+                .map(|query| format!("Searching the web for '{}'", query))
+                .unwrap_or_else(|| "Searching the web".to_string()),
             "fs_read_text_file" | "fs_read" => {
                 if let Some(path) = arguments.get("path").and_then(|v| v.as_str()) {
                     format!(
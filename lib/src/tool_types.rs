@@ -327,6 +327,36 @@ impl ToolCallReport {
         self.content.push(content);
     }
 
+    /// Show the current output of an embedded terminal as text after its terminal content
+    ///
+    /// Clients that cannot display the terminal itself still see its output. Replaces the
+    /// text set by an earlier call; does nothing when the terminal is not embedded.
+    pub fn set_terminal_output(&mut self, terminal_id: &str, output: String) {
+        let Some(index) = self.content.iter().position(|content| {
+            matches!(content, ToolCallContent::Terminal { terminal_id: id } if id == terminal_id)
+        }) else {
+            return;
+        };
+
+        let text = ToolCallContent::Content {
+            content: agent_client_protocol::ContentBlock::Text(
+                agent_client_protocol::TextContent {
+                    annotations: None,
+                    text: output,
+                    meta: None,
+                },
+            ),
+        };
+        match self.content.get_mut(index + 1) {
+            Some(
+                existing @ ToolCallContent::Content {
+                    content: agent_client_protocol::ContentBlock::Text(_),
+                },
+            ) => *existing = text,
+            _ => self.content.insert(index + 1, text),
+        }
+    }
+
     /// Add a file location to this tool call
     pub fn add_location(&mut self, location: ToolCallLocation) {
         self.locations.push(location);
@@ -1340,4 +1370,32 @@ mod tests {
             agent_client_protocol::ToolCallStatus::Completed
         );
     }
+
+    #[test]
+    fn test_set_terminal_output_replaces_previous_output() {
+        let mut report = ToolCallReport::new(
+            "test_010".to_string(),
+            "Run tests".to_string(),
+            ToolKind::Execute,
+            "Bash".to_string(),
+        );
+
+        // Nothing to attach the output to yet
+        report.set_terminal_output("term_123", "ignored".to_string());
+        assert!(report.content.is_empty());
+
+        report.add_content(ToolCallContent::Terminal {
+            terminal_id: "term_123".to_string(),
+        });
+        report.set_terminal_output("term_123", "compiling\n".to_string());
+        report.set_terminal_output("term_123", "compiling\nfinished\n".to_string());
+
+        assert_eq!(report.content.len(), 2);
+        match &report.content[1] {
+            ToolCallContent::Content {
+                content: agent_client_protocol::ContentBlock::Text(text),
+            } => assert_eq!(text.text, "compiling\nfinished\n"),
+            other => panic!("Expected text content, got {:?}", other),
+        }
+    }
 }
@@ -64,8 +64,15 @@ use serde_json::Value;
 
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::RwLock;
 
+/// Minimum time between two output updates of an embedded terminal
+const TERMINAL_OUTPUT_UPDATE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How long a finished command's last output update may take before the tool call completes
+const TERMINAL_OUTPUT_FLUSH_TIMEOUT: Duration = Duration::from_secs(1);
+
 /// Internal representation of a tool request from an LLM
 #[derive(Debug, Clone)]
 pub struct InternalToolRequest {
@@ -119,6 +126,10 @@ pub struct ToolCallHandler {
     session_manager: Arc<crate::session::SessionManager>,
     /// Permission policy engine for evaluating tool call permissions
     permission_engine: Arc<crate::permissions::PermissionPolicyEngine>,
+    /// Audit trail of policy results and tool executions
+    audit_log: Arc<crate::audit::AuditLog>,
+    /// Connection to the ACP client, which serves file and terminal operations it declared
+    client: Option<Arc<crate::client_connection::ClientConnection>>,
 }
 
 impl std::fmt::Debug for ToolCallHandler {
@@ -133,6 +144,8 @@ impl std::fmt::Debug for ToolCallHandler {
             .field("file_operations", &"<RwLock<HashMap>>")
             .field("session_manager", &"<SessionManager>")
             .field("permission_engine", &"<PermissionPolicyEngine>")
+            .field("audit_log", &self.audit_log.is_enabled())
+            .field("client", &self.client.is_some())
             .finish()
     }
 }
@@ -170,6 +183,31 @@ pub struct PermissionOption {
     pub kind: PermissionOptionKind,
 }
 
+impl PermissionOption {
+    /// Convert to the protocol's permission option
+    pub fn to_acp_option(&self) -> agent_client_protocol::PermissionOption {
+        agent_client_protocol::PermissionOption {
+            id: agent_client_protocol::PermissionOptionId(self.option_id.as_str().into()),
+            name: self.name.clone(),
+            kind: match self.kind {
+                PermissionOptionKind::AllowOnce => {
+                    agent_client_protocol::PermissionOptionKind::AllowOnce
+                }
+                PermissionOptionKind::AllowAlways => {
+                    agent_client_protocol::PermissionOptionKind::AllowAlways
+                }
+                PermissionOptionKind::RejectOnce => {
+                    agent_client_protocol::PermissionOptionKind::RejectOnce
+                }
+                PermissionOptionKind::RejectAlways => {
+                    agent_client_protocol::PermissionOptionKind::RejectAlways
+                }
+            },
+            meta: None,
+        }
+    }
+}
+
 /// ACP permission option kinds as defined in the specification
 #[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "snake_case")]
@@ -249,6 +287,8 @@ impl ToolCallHandler {
             file_operations: Arc::new(RwLock::new(HashMap::new())),
             session_manager,
             permission_engine,
+            audit_log: Arc::new(crate::audit::AuditLog::disabled()),
+            client: None,
         }
     }
 
@@ -269,6 +309,8 @@ impl ToolCallHandler {
             file_operations: Arc::new(RwLock::new(HashMap::new())),
             session_manager,
             permission_engine,
+            audit_log: Arc::new(crate::audit::AuditLog::disabled()),
+            client: None,
         }
     }
 
@@ -312,6 +354,14 @@ impl ToolCallHandler {
         active_calls.clone()
     }
 
+    /// Definition of an MCP tool as declared by its server, `None` for other tools
+    async fn mcp_tool_definition(&self, tool_name: &str) -> Option<crate::mcp::McpTool> {
+        match &self.mcp_manager {
+            Some(mcp_manager) => mcp_manager.tool_definition(tool_name).await,
+            None => None,
+        }
+    }
+
     /// Create and track a new tool call report with ACP-compliant session notification
     pub async fn create_tool_call_report(
         &self,
@@ -320,8 +370,19 @@ impl ToolCallHandler {
         arguments: &serde_json::Value,
     ) -> ToolCallReport {
         let tool_call_id = self.generate_tool_call_id().await;
-        let title = ToolCallReport::generate_title(tool_name, arguments);
-        let kind = ToolKind::classify_tool(tool_name, arguments);
+        let (title, kind) = match self.mcp_tool_definition(tool_name).await {
+            Some(tool) => (
+                tool.title
+                    .clone()
+                    .or_else(|| tool.annotations.as_ref().and_then(|a| a.title.clone()))
+                    .unwrap_or_else(|| ToolCallReport::generate_title(tool_name, arguments)),
+                ToolKind::classify_mcp_tool(tool_name, arguments, &tool),
+            ),
+            None => (
+                ToolCallReport::generate_title(tool_name, arguments),
+                ToolKind::classify_tool(tool_name, arguments),
+            ),
+        };
 
         let mut report =
             ToolCallReport::new(tool_call_id.clone(), title, kind, tool_name.to_string());
@@ -549,6 +610,11 @@ impl ToolCallHandler {
         self.embed_terminal_in_tool_call(session_id, tool_call_id, terminal_id.clone())
             .await?;
 
+        // Start the command once the client knows where its output appears
+        let output_changes = self.terminal_manager.subscribe_output(&terminal_id).await?;
+        self.terminal_manager.start_terminal(&terminal_id).await?;
+        self.stream_terminal_output(session_id, tool_call_id, &terminal_id, output_changes, 0);
+
         tracing::info!(
             tool_call_id = %tool_call_id,
             terminal_id = %terminal_id,
@@ -559,6 +625,149 @@ impl ToolCallHandler {
         Ok(terminal_id)
     }
 
+    /// Push the output of an embedded terminal to the client while its command runs
+    ///
+    /// Each tool_call_update carries the tool call's content with everything after
+    /// `offset` as text after the terminal content, so clients show live output without
+    /// polling `terminal/output`. The output added since the previous update is also sent
+    /// in `meta.terminalOutput.output` with its byte `offset`; `skipped` is set when output
+    /// was dropped by the byte limit before it could be sent. Output arriving in quick
+    /// succession is batched into one update per `TERMINAL_OUTPUT_UPDATE_INTERVAL`.
+    /// Streaming stops when the output ends, the terminal is released or the tool call is
+    /// no longer active; the returned task ends then.
+    fn stream_terminal_output(
+        &self,
+        session_id: &agent_client_protocol::SessionId,
+        tool_call_id: &str,
+        terminal_id: &str,
+        mut output_changes: tokio::sync::watch::Receiver<()>,
+        offset: u64,
+    ) -> tokio::task::JoinHandle<()> {
+        let sender = self.notification_sender.clone();
+        let terminal_manager = Arc::clone(&self.terminal_manager);
+        let active_tool_calls = Arc::clone(&self.active_tool_calls);
+        let session_id = session_id.clone();
+        let tool_call_id = tool_call_id.to_string();
+        let terminal_id = terminal_id.to_string();
+
+        tokio::spawn(async move {
+            let Some(sender) = sender else {
+                return;
+            };
+            let mut next_offset = offset;
+            loop {
+                let terminal_dropped = output_changes.changed().await.is_err();
+                let Some(chunk) = terminal_manager
+                    .output_since(&terminal_id, next_offset)
+                    .await
+                else {
+                    break;
+                };
+
+                if !chunk.output.is_empty() || chunk.skipped || chunk.finished {
+                    let output = terminal_manager
+                        .output_since(&terminal_id, offset)
+                        .await
+                        .map(|all| all.output)
+                        .unwrap_or_default();
+                    let update = {
+                        let mut active_calls = active_tool_calls.write().await;
+                        let Some(report) = active_calls.get_mut(&tool_call_id) else {
+                            break;
+                        };
+                        report.set_terminal_output(&terminal_id, output);
+                        let mut update = report.to_acp_tool_call_update_with_context(true);
+                        report.mark_state_sent();
+                        update.meta = Some(serde_json::json!({
+                            "terminalOutput": {
+                                "terminalId": terminal_id,
+                                "output": chunk.output,
+                                "offset": chunk.offset,
+                                "skipped": chunk.skipped,
+                                "finished": chunk.finished,
+                            }
+                        }));
+                        update
+                    };
+
+                    let notification = agent_client_protocol::SessionNotification {
+                        session_id: session_id.clone(),
+                        update: agent_client_protocol::SessionUpdate::ToolCallUpdate(update),
+                        meta: None,
+                    };
+                    if let Err(e) = sender.send_update(notification).await {
+                        tracing::warn!(
+                            tool_call_id = %tool_call_id,
+                            terminal_id = %terminal_id,
+                            error = %e,
+                            "Failed to send terminal output update"
+                        );
+                    }
+                    next_offset = chunk.next_offset;
+                }
+
+                if terminal_dropped || chunk.finished {
+                    break;
+                }
+                tokio::time::sleep(TERMINAL_OUTPUT_UPDATE_INTERVAL).await;
+            }
+        })
+    }
+
+    /// Forward the progress an MCP server reports for a tool call to the client
+    ///
+    /// Each report becomes a tool_call_update whose `meta.mcpProgress` holds the
+    /// progress, total and message. Forwarding stops when the server call finishes
+    /// and drops the sender, or when the tool call is no longer active; the returned
+    /// task ends then.
+    fn stream_mcp_progress(
+        &self,
+        session_id: &agent_client_protocol::SessionId,
+        tool_call_id: &str,
+        mut progress: tokio::sync::mpsc::UnboundedReceiver<crate::mcp::McpProgress>,
+    ) -> tokio::task::JoinHandle<()> {
+        let sender = self.notification_sender.clone();
+        let active_tool_calls = Arc::clone(&self.active_tool_calls);
+        let session_id = session_id.clone();
+        let tool_call_id = tool_call_id.to_string();
+
+        tokio::spawn(async move {
+            while let Some(report) = progress.recv().await {
+                let Some(sender) = &sender else {
+                    continue;
+                };
+                let update = {
+                    let active_calls = active_tool_calls.read().await;
+                    let Some(tool_call) = active_calls.get(&tool_call_id) else {
+                        break;
+                    };
+                    let mut update = tool_call.to_acp_tool_call_update();
+                    update.meta = Some(serde_json::json!({
+                        "mcpProgress": {
+                            "progress": report.progress,
+                            "total": report.total,
+                            "message": report.message,
+                        }
+                    }));
+                    update
+                };
+
+                let notification = agent_client_protocol::SessionNotification {
+                    session_id: session_id.clone(),
+                    update: agent_client_protocol::SessionUpdate::ToolCallUpdate(update),
+                    meta: None,
+                };
+                if let Err(e) = sender.send_update(notification).await {
+                    tracing::warn!(
+                        tool_call_id = %tool_call_id,
+                        error = %e,
+                        "Failed to send MCP progress update"
+                    );
+                }
+            }
+        })
+    }
+
     /// Fail and remove a tool call from tracking with ACP-compliant session notification
     pub async fn fail_tool_call_report(
         &self,
@@ -662,6 +871,8 @@ impl ToolCallHandler {
             file_operations: Arc::new(RwLock::new(HashMap::new())),
             session_manager,
             permission_engine,
+            audit_log: Arc::new(crate::audit::AuditLog::disabled()),
+            client: None,
         }
     }
 
@@ -683,6 +894,8 @@ impl ToolCallHandler {
             file_operations: Arc::new(RwLock::new(HashMap::new())),
             session_manager,
             permission_engine,
+            audit_log: Arc::new(crate::audit::AuditLog::disabled()),
+            client: None,
         }
     }
 
@@ -699,6 +912,38 @@ impl ToolCallHandler {
         self.notification_sender = Some(sender);
     }
 
+    /// Set the audit log that records policy results and tool executions
+    pub fn set_audit_log(&mut self, audit_log: Arc<crate::audit::AuditLog>) {
+        self.audit_log = audit_log;
+    }
+
+    /// Set the connection to the ACP client
+    ///
+    /// While a client is connected, file reads and writes and terminal commands are sent
+    /// to it for every capability it declared, so they see unsaved editor buffers and
+    /// run in the client's terminals. Without a connected client they run locally.
+    pub fn set_client_connection(
+        &mut self,
+        client: Arc<crate::client_connection::ClientConnection>,
+    ) {
+        self.client = Some(client);
+    }
+
+    /// The connected client, if it declared the capability `declared` checks
+    fn capable_client(
+        &self,
+        declared: impl Fn(&agent_client_protocol::ClientCapabilities) -> bool,
+    ) -> Option<&Arc<crate::client_connection::ClientConnection>> {
+        let client = self
+            .client
+            .as_ref()
+            .filter(|client| client.is_connected())?;
+        self.client_capabilities
+            .as_ref()
+            .is_some_and(declared)
+            .then_some(client)
+    }
+
     /// Get the session manager reference for testing and internal operations
     #[cfg(test)]
     pub fn get_session_manager(&self) -> &Arc<crate::session::SessionManager> {
@@ -816,6 +1061,10 @@ impl ToolCallHandler {
 
         // Check if tool is in auto_approved list (legacy permission system compatibility)
         let is_auto_approved = self.permissions.auto_approved.contains(&request.name);
+        let cwd = crate::session::SessionId::parse(&session_id.0)
+            .ok()
+            .and_then(|id| self.session_manager.get_session(&id).ok().flatten())
+            .map(|session| session.cwd);
 
         // Evaluate permission policy for this tool call (unless auto-approved)
         let policy_evaluation = if is_auto_approved {
@@ -826,10 +1075,32 @@ impl ToolCallHandler {
             crate::permissions::PolicyEvaluation::Allowed
         } else {
             self.permission_engine
-                .evaluate_tool_call(&request.name, &request.arguments)
+                .evaluate_tool_call(&request.name, &request.arguments, cwd.as_deref())
                 .await?
         };
 
+        let mut audit_record = crate::audit::AuditRecord::new(
+            crate::audit::AuditEvent::Permission,
+            &session_id.0,
+            cwd,
+            &request.name,
+            &request.arguments,
+        );
+        audit_record.tool_call_id = Some(tool_report.tool_call_id.clone());
+        let (policy_result, decision) = match &policy_evaluation {
+            crate::permissions::PolicyEvaluation::Allowed => ("allowed", Some("allowed")),
+            crate::permissions::PolicyEvaluation::Denied { .. } => ("denied", Some("denied")),
+            // The user's answer is audited when the permission request is resolved
+            crate::permissions::PolicyEvaluation::RequireUserConsent { .. } => {
+                ("require_user_consent", None)
+            }
+        };
+        audit_record.policy_result = Some(policy_result.to_string());
+        audit_record.decision = decision.map(str::to_string);
+        if decision.is_some() {
+            self.audit_log.record(audit_record.clone());
+        }
+
         match policy_evaluation {
             crate::permissions::PolicyEvaluation::Denied { reason } => {
                 // Policy denies this tool call - fail immediately
@@ -842,7 +1113,7 @@ impl ToolCallHandler {
                 .await;
                 return Ok(ToolCallResult::Error(reason));
             }
-            crate::permissions::PolicyEvaluation::RequireUserConsent { options } => {
+            crate::permissions::PolicyEvaluation::RequireUserConsent { options, .. } => {
                 // Policy requires user consent - create permission request
                 tracing::info!("Tool call requires user consent: {}", request.name);
                 let description =
@@ -878,7 +1149,29 @@ impl ToolCallHandler {
         })
         .await;
 
-        match self.execute_tool_request(session_id, &request).await {
+        let started = std::time::Instant::now();
+        let result = self
+            .execute_tool_request(session_id, &tool_report.tool_call_id, &request)
+            .await;
+        self.audit_log.record_execution(
+            audit_record,
+            started,
+            match &result {
+                Ok(response) => crate::audit::ExecutionOutcome::from_output(
+                    &request.name,
+                    false,
+                    response,
+                    response.len(),
+                ),
+                Err(e) => crate::audit::ExecutionOutcome {
+                    failed: true,
+                    error: Some(e.to_string()),
+                    ..Default::default()
+                },
+            },
+        );
+
+        match result {
             Ok(response) => {
                 // Complete the tool call with success
                 let completed_report = self
@@ -990,12 +1283,20 @@ impl ToolCallHandler {
     async fn execute_tool_request(
         &self,
         session_id: &agent_client_protocol::SessionId,
+        tool_call_id: &str,
         request: &InternalToolRequest,
     ) -> crate::Result<String> {
-        // Check if this is an MCP tool call
+        // Check if this is an MCP tool call; progress it reports is forwarded to the client
         if let Some(server_name) = self.extract_mcp_server_name(&request.name) {
             if let Some(ref mcp_manager) = self.mcp_manager {
-                return mcp_manager.execute_tool_call(server_name, request).await;
+                let (progress_tx, progress_rx) = tokio::sync::mpsc::unbounded_channel();
+                let progress = self.stream_mcp_progress(session_id, tool_call_id, progress_rx);
+                let result = mcp_manager
+                    .execute_tool_call_with_progress(server_name, request, Some(progress_tx))
+                    .await;
+                // Report all progress before the tool call completes
+                let _ = progress.await;
+                return result;
             }
         }
 
@@ -1005,7 +1306,10 @@ impl ToolCallHandler {
             "fs_write" => self.handle_fs_write(session_id, request).await,
             "fs_list" => self.handle_fs_list(session_id, request).await,
             "terminal_create" => self.handle_terminal_create(request).await,
-            "terminal_write" => self.handle_terminal_write(request).await,
+            "terminal_write" => {
+                self.handle_terminal_write(session_id, tool_call_id, request)
+                    .await
+            }
             _ => Err(crate::AgentError::ToolExecution(format!(
                 "Unknown tool: {}",
                 request.name
@@ -1113,8 +1417,26 @@ impl ToolCallHandler {
             )));
         }
 
-        // Read file using tokio::fs for async operation
-        let read_result = tokio::fs::read_to_string(path_str).await;
+        // The client knows about unsaved editor buffers, so it reads the file when it can
+        let read_result = match self.capable_client(|caps| caps.fs.read_text_file) {
+            Some(client) => client
+                .request::<_, agent_client_protocol::ReadTextFileResponse>(
+                    agent_client_protocol::CLIENT_METHOD_NAMES.fs_read_text_file,
+                    &agent_client_protocol::ReadTextFileRequest {
+                        session_id: session_id.clone(),
+                        path: path.to_path_buf(),
+                        line: None,
+                        limit: None,
+                        meta: None,
+                    },
+                )
+                .await
+                .map(|response| response.content)
+                .map_err(|e| e.to_string()),
+            None => tokio::fs::read_to_string(path_str)
+                .await
+                .map_err(|e| e.to_string()),
+        };
 
         match read_result {
             Ok(content) => {
@@ -1239,8 +1561,25 @@ impl ToolCallHandler {
             }
         }
 
-        // Write file using tokio::fs for async operation
-        let write_result = tokio::fs::write(path_str, content).await;
+        // The client updates its editor buffer along with the file when it can
+        let write_result = match self.capable_client(|caps| caps.fs.write_text_file) {
+            Some(client) => client
+                .request::<_, Value>(
+                    agent_client_protocol::CLIENT_METHOD_NAMES.fs_write_text_file,
+                    &agent_client_protocol::WriteTextFileRequest {
+                        session_id: session_id.clone(),
+                        path: path.to_path_buf(),
+                        content: content.to_string(),
+                        meta: None,
+                    },
+                )
+                .await
+                .map(|_| ())
+                .map_err(|e| e.to_string()),
+            None => tokio::fs::write(path_str, content)
+                .await
+                .map_err(|e| e.to_string()),
+        };
 
         match write_result {
             Ok(_) => {
@@ -1421,7 +1760,15 @@ impl ToolCallHandler {
     }
 
     /// Handle terminal write/command execution operations
-    async fn handle_terminal_write(&self, request: &InternalToolRequest) -> crate::Result<String> {
+    ///
+    /// The terminal is embedded in the tool call and the command's output is streamed
+    /// to the client while it runs.
+    async fn handle_terminal_write(
+        &self,
+        session_id: &agent_client_protocol::SessionId,
+        tool_call_id: &str,
+        request: &InternalToolRequest,
+    ) -> crate::Result<String> {
         // ACP requires that we only use features the client declared support for.
         // Always check client capabilities before attempting operations.
         // This prevents protocol violations and ensures compatibility.
@@ -1454,12 +1801,129 @@ impl ToolCallHandler {
             return Ok(result);
         }
 
+        if let Some(client) = self.capable_client(|caps| caps.terminal) {
+            let client = Arc::clone(client);
+            return self
+                .run_in_client_terminal(&client, session_id, tool_call_id, terminal_id, command)
+                .await;
+        }
+
+        // Stream only the output of this command, starting where the terminal's ends now
+        let offset = self
+            .terminal_manager
+            .output_offset(terminal_id)
+            .await
+            .ok_or_else(|| {
+                crate::AgentError::ToolExecution(format!("Terminal {} not found", terminal_id))
+            })?;
+        let output_changes = self.terminal_manager.subscribe_output(terminal_id).await?;
+        self.embed_terminal_in_tool_call(session_id, tool_call_id, terminal_id.to_string())
+            .await?;
+        let mut streaming = self.stream_terminal_output(
+            session_id,
+            tool_call_id,
+            terminal_id,
+            output_changes,
+            offset,
+        );
+
         // Execute the command
         let result = self
             .terminal_manager
             .execute_command(terminal_id, command)
+            .await;
+
+        // Report the rest of the output before the tool call completes, unless a process
+        // left behind keeps the output open
+        if result.is_err()
+            || tokio::time::timeout(TERMINAL_OUTPUT_FLUSH_TIMEOUT, &mut streaming)
+                .await
+                .is_err()
+        {
+            streaming.abort();
+        }
+        result
+    }
+
+    /// Run a command of a terminal tool in a terminal the client creates
+    ///
+    /// The command runs through the configured shell in the terminal's working directory.
+    /// The client's terminal is embedded in the tool call, so the client shows its output
+    /// live, and released once the command's output has been collected.
+    async fn run_in_client_terminal(
+        &self,
+        client: &crate::client_connection::ClientConnection,
+        session_id: &agent_client_protocol::SessionId,
+        tool_call_id: &str,
+        terminal_id: &str,
+        command: &str,
+    ) -> crate::Result<String> {
+        use agent_client_protocol::CLIENT_METHOD_NAMES;
+
+        let client_error = |e: agent_client_protocol::Error| {
+            crate::AgentError::ToolExecution(format!("Client terminal failed: {}", e))
+        };
+        let cwd = self
+            .terminal_manager
+            .working_directory(terminal_id)
+            .await
+            .ok_or_else(|| {
+                crate::AgentError::ToolExecution(format!("Terminal {} not found", terminal_id))
+            })?;
+        let (shell, args) = self.terminal_manager.shell_invocation(command);
+
+        let created: agent_client_protocol::CreateTerminalResponse = client
+            .request(
+                CLIENT_METHOD_NAMES.terminal_create,
+                &agent_client_protocol::CreateTerminalRequest {
+                    session_id: session_id.clone(),
+                    command: shell,
+                    args,
+                    env: Vec::new(),
+                    cwd: Some(cwd),
+                    output_byte_limit: None,
+                    meta: None,
+                },
+            )
+            .await
+            .map_err(client_error)?;
+        let client_terminal = created.terminal_id;
+        self.embed_terminal_in_tool_call(session_id, tool_call_id, client_terminal.0.to_string())
             .await?;
-        Ok(result)
+
+        let terminal_request = serde_json::json!({
+            "sessionId": session_id,
+            "terminalId": client_terminal,
+        });
+        let finished = async {
+            let exited: agent_client_protocol::WaitForTerminalExitResponse = client
+                .request(
+                    CLIENT_METHOD_NAMES.terminal_wait_for_exit,
+                    &terminal_request,
+                )
+                .await?;
+            let output: agent_client_protocol::TerminalOutputResponse = client
+                .request(CLIENT_METHOD_NAMES.terminal_output, &terminal_request)
+                .await?;
+            Ok((exited.exit_status, output.output))
+        }
+        .await;
+        if let Err(e) = client
+            .request::<_, Value>(CLIENT_METHOD_NAMES.terminal_release, &terminal_request)
+            .await
+        {
+            tracing::warn!(
+                "Failed to release client terminal {}: {}",
+                client_terminal.0,
+                e
+            );
+        }
+
+        let (exit_status, output) = finished.map_err(client_error)?;
+        let exit_code = exit_status
+            .exit_code
+            .map(|code| i32::try_from(code).unwrap_or(i32::MAX));
+        Ok(crate::terminal_manager::command_result(exit_code, &output))
     }
 }
 
@@ -1835,6 +2299,7 @@ impl TerminalMethodHandler {
             .terminal_manager
             .create_terminal_with_command(&self.session_manager, params)
             .await?;
+        self.terminal_manager.start_terminal(&terminal_id).await?;
 
         Ok(TerminalCreateResponse { terminal_id })
     }
@@ -1987,8 +2452,14 @@ mod tests {
 
     #[tokio::test]
     async fn test_permission_required() {
-        let handler = create_test_handler();
+        let mut handler = create_test_handler();
         let session_id = create_test_session_id();
+        let temp_dir = tempfile::tempdir().unwrap();
+        let audit_log = Arc::new(crate::audit::AuditLog::new(&crate::config::AuditConfig {
+            path: Some(temp_dir.path().join("audit.jsonl")),
+            ..Default::default()
+        }));
+        handler.set_audit_log(Arc::clone(&audit_log));
 
         let request = InternalToolRequest {
             id: "test-id".to_string(),
@@ -2012,6 +2483,13 @@ mod tests {
             }
             _ => panic!("Expected permission required"),
         }
+        // The decision is audited once the user answers, not before
+        let records = audit_log
+            .query(&crate::audit::AuditQuery::default())
+            .await
+            .unwrap()
+            .records;
+        assert!(records.is_empty());
     }
 
     #[tokio::test]
@@ -2276,6 +2754,129 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_file_and_terminal_tools_go_to_a_capable_client() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("main.rs");
+        std::fs::write(&file_path, "saved on disk").unwrap();
+
+        let permissions = ToolPermissions {
+            require_permission_for: vec![],
+            auto_approved: vec![
+                "fs_read".to_string(),
+                "fs_write".to_string(),
+                "terminal_write".to_string(),
+            ],
+            forbidden_paths: vec![],
+        };
+        let session_manager = Arc::new(crate::session::SessionManager::new());
+        let (mut handler, session_id) =
+            create_test_handler_with_session(permissions, session_manager, temp_dir.path());
+        let client = Arc::new(crate::client_connection::ClientConnection::new(
+            Duration::from_secs(5),
+        ));
+        let mut outgoing = client.take_outgoing_receiver().unwrap();
+        handler.set_client_connection(Arc::clone(&client));
+
+        // A client that answers every request and reports what it was asked
+        let (requests_tx, mut requests) = tokio::sync::mpsc::unbounded_channel();
+        let fake_client = tokio::spawn({
+            let client = Arc::clone(&client);
+            async move {
+                while let Some(message) = outgoing.recv().await {
+                    let request: Value = serde_json::from_str(&message).unwrap();
+                    let result = match request["method"].as_str().unwrap() {
+                        "fs/read_text_file" => json!({"content": "unsaved buffer"}),
+                        "terminal/create" => json!({"terminalId": "client-term-1"}),
+                        "terminal/wait_for_exit" => json!({"exitCode": 0}),
+                        "terminal/output" => json!({"output": "hi\n", "truncated": false}),
+                        _ => Value::Null,
+                    };
+                    client.handle_response(&json!({"id": request["id"], "result": result}));
+                    let _ = requests_tx.send(request);
+                }
+            }
+        });
+
+        let read = InternalToolRequest {
+            id: "read".to_string(),
+            name: "fs_read".to_string(),
+            arguments: json!({"path": file_path.to_string_lossy()}),
+        };
+        match handler
+            .handle_tool_request(&session_id, read)
+            .await
+            .unwrap()
+        {
+            ToolCallResult::Success(content) => assert_eq!(content, "unsaved buffer"),
+            other => panic!("Read should succeed, got {:?}", other),
+        }
+        let request = requests.recv().await.unwrap();
+        assert_eq!(request["method"], "fs/read_text_file");
+        assert_eq!(request["params"]["sessionId"], json!(session_id));
+        assert_eq!(
+            request["params"]["path"],
+            file_path.to_string_lossy().as_ref()
+        );
+
+        let write = InternalToolRequest {
+            id: "write".to_string(),
+            name: "fs_write".to_string(),
+            arguments: json!({"path": file_path.to_string_lossy(), "content": "edited"}),
+        };
+        let result = handler
+            .handle_tool_request(&session_id, write)
+            .await
+            .unwrap();
+        assert!(matches!(result, ToolCallResult::Success(_)));
+        let request = requests.recv().await.unwrap();
+        assert_eq!(request["method"], "fs/write_text_file");
+        assert_eq!(request["params"]["content"], "edited");
+        assert_eq!(
+            std::fs::read_to_string(&file_path).unwrap(),
+            "saved on disk"
+        );
+
+        let terminal_id = handler
+            .get_terminal_manager()
+            .create_terminal(Some(temp_dir.path().to_string_lossy().to_string()))
+            .await
+            .unwrap();
+        let command = InternalToolRequest {
+            id: "command".to_string(),
+            name: "terminal_write".to_string(),
+            arguments: json!({"terminal_id": terminal_id, "command": "echo hi"}),
+        };
+        match handler
+            .handle_tool_request(&session_id, command)
+            .await
+            .unwrap()
+        {
+            ToolCallResult::Success(output) => assert_eq!(output, "Command output:\nhi\n"),
+            other => panic!("Command should succeed, got {:?}", other),
+        }
+        let mut methods = Vec::new();
+        while let Ok(request) = requests.try_recv() {
+            methods.push(request["method"].as_str().unwrap().to_string());
+            if request["method"] == "terminal/create" {
+                assert_eq!(request["params"]["args"].as_array().unwrap().len(), 2);
+                assert_eq!(request["params"]["args"][1], "echo hi");
+            }
+        }
+        assert_eq!(
+            methods,
+            [
+                "terminal/create",
+                "terminal/wait_for_exit",
+                "terminal/output",
+                "terminal/release"
+            ]
+        );
+
+        client.close();
+        fake_client.abort();
+    }
+
     #[tokio::test]
     async fn test_fs_list() {
         use tempfile::TempDir;
@@ -3190,9 +3791,12 @@ mod tests {
             output_byte_limit: 10, // Very small for testing
             output_buffer: Arc::new(RwLock::new(Vec::new())),
             buffer_truncated: Arc::new(RwLock::new(false)),
+            output_written: Arc::new(std::sync::atomic::AtomicU64::new(0)),
             exit_status: Arc::new(RwLock::new(None)),
             state: Arc::new(RwLock::new(crate::terminal_manager::TerminalState::Created)),
             output_task: None,
+            output_closed: None,
+            output_changed: tokio::sync::watch::Sender::new(()),
             timeout_config: crate::terminal_manager::TimeoutConfig::default(),
         };
 
@@ -3517,6 +4121,41 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_mcp_tool_annotations_refine_kind() {
+        let tool = |annotations: serde_json::Value| -> crate::mcp::McpTool {
+            serde_json::from_value(json!({"name": "tool", "annotations": annotations})).unwrap()
+        };
+        let read_only = tool(json!({"readOnlyHint": true}));
+        let open_world = tool(json!({"openWorldHint": true}));
+
+        // Name-based kinds that already read stay as they are
+        assert_eq!(
+            ToolKind::classify_mcp_tool("mcp__docs__search", &json!({}), &read_only),
+            ToolKind::Search
+        );
+        assert_eq!(
+            ToolKind::classify_mcp_tool("mcp__db__query", &json!({}), &read_only),
+            ToolKind::Read
+        );
+        assert_eq!(
+            ToolKind::classify_mcp_tool("mcp__git__update_ref", &json!({}), &read_only),
+            ToolKind::Read
+        );
+        assert_eq!(
+            ToolKind::classify_mcp_tool("mcp__slack__post", &json!({}), &open_world),
+            ToolKind::Fetch
+        );
+        assert_eq!(
+            ToolKind::classify_mcp_tool(
+                "mcp__db__query",
+                &json!({}),
+                &crate::mcp::McpTool::named("query")
+            ),
+            ToolKind::Other
+        );
+    }
+
     #[tokio::test]
     async fn test_tool_title_generation() {
         // Test file operations with paths
@@ -5,9 +5,13 @@
 
 use async_trait::async_trait;
 use std::io::Write;
+use std::sync::Arc;
 use tokio::io::{AsyncBufReadExt, BufReader};
 
-use crate::tools::PermissionOption;
+use crate::agent::CancellationManager;
+use crate::client_connection::ClientConnection;
+use crate::tool_types::{ToolCallReport, ToolKind};
+use crate::tools::{EnhancedPermissionRequest, PermissionOption};
 
 /// Result type for user prompt operations
 pub type PromptResult<T> = Result<T, PromptError>;
@@ -26,6 +30,14 @@ pub enum PromptError {
     /// Operation timed out
     #[error("Prompt timed out")]
     Timeout,
+
+    /// The prompt turn was cancelled before the user answered
+    #[error("Prompt was cancelled")]
+    Cancelled,
+
+    /// The ACP client could not be asked
+    #[error("Client request failed: {0}")]
+    Client(String),
 }
 
 /// Trait for handling user prompts for permission requests
@@ -46,9 +58,28 @@ pub trait UserPromptHandler: Send + Sync {
         description: &str,
         options: &[PermissionOption],
     ) -> PromptResult<String>;
+
+    /// Prompt the user to approve a tool call of a session
+    ///
+    /// The default implementation asks with [`UserPromptHandler::prompt_for_permission`];
+    /// handlers that forward the question to the ACP client need the session and tool call.
+    ///
+    /// # Returns
+    /// The selected option ID, or an error if the prompt failed
+    async fn prompt_for_tool_call(
+        &self,
+        request: &EnhancedPermissionRequest,
+    ) -> PromptResult<String> {
+        self.prompt_for_permission(&request.tool_name, &request.description, &request.options)
+            .await
+    }
 }
 
 /// Console-based user prompt handler that reads from stdin
+///
+/// Questions are written to stderr. Only for agents that do not serve ACP over stdio:
+/// there stdin carries the client's JSON-RPC messages, and the agent asks the client
+/// with [`ClientPromptHandler`] instead.
 pub struct ConsolePromptHandler;
 
 impl ConsolePromptHandler {
@@ -73,23 +104,23 @@ impl UserPromptHandler for ConsolePromptHandler {
         options: &[PermissionOption],
     ) -> PromptResult<String> {
         // Print the permission request header
-        println!("\n━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
-        println!("🔐 PERMISSION REQUEST");
-        println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
-        println!("\nTool: {}", tool_name);
-        println!("Action: {}", description);
-        println!("\nAvailable options:");
+        eprintln!("\n━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+        eprintln!("🔐 PERMISSION REQUEST");
+        eprintln!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+        eprintln!("\nTool: {}", tool_name);
+        eprintln!("Action: {}", description);
+        eprintln!("\nAvailable options:");
 
         // Print numbered options
         for (idx, option) in options.iter().enumerate() {
-            println!("  {}. {} - {:?}", idx + 1, option.name, option.kind);
+            eprintln!("  {}. {} - {:?}", idx + 1, option.name, option.kind);
         }
 
-        println!("\n━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+        eprintln!("\n━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
 
         // Prompt for input
-        print!("Enter your choice (1-{}): ", options.len());
-        std::io::stdout().flush()?;
+        eprint!("Enter your choice (1-{}): ", options.len());
+        std::io::stderr().flush()?;
 
         // Read user input asynchronously
         let stdin = tokio::io::stdin();
@@ -113,12 +144,134 @@ impl UserPromptHandler for ConsolePromptHandler {
 
         // Return the selected option ID
         let selected_option = &options[selection - 1];
-        println!("✓ Selected: {}\n", selected_option.name);
+        eprintln!("✓ Selected: {}\n", selected_option.name);
 
         Ok(selected_option.option_id.clone())
     }
 }
 
+/// Prompt handler that asks the ACP client with `session/request_permission`
+///
+/// The editor shows the tool call and options to the user and answers with the selected
+/// option, so nothing is read from the console the stdio transport uses. Cancelling the
+/// session while the question is open ends the prompt with [`PromptError::Cancelled`].
+pub struct ClientPromptHandler {
+    client: Arc<ClientConnection>,
+    cancellation_manager: Arc<CancellationManager>,
+}
+
+impl ClientPromptHandler {
+    /// Create a handler that sends permission requests over the client connection
+    pub fn new(
+        client: Arc<ClientConnection>,
+        cancellation_manager: Arc<CancellationManager>,
+    ) -> Self {
+        Self {
+            client,
+            cancellation_manager,
+        }
+    }
+
+    /// Build the ACP request for a tool call awaiting permission
+    fn permission_request(
+        request: &EnhancedPermissionRequest,
+    ) -> agent_client_protocol::RequestPermissionRequest {
+        agent_client_protocol::RequestPermissionRequest {
+            session_id: agent_client_protocol::SessionId(request.session_id.as_str().into()),
+            tool_call: agent_client_protocol::ToolCallUpdate {
+                id: agent_client_protocol::ToolCallId(request.tool_request_id.as_str().into()),
+                fields: agent_client_protocol::ToolCallUpdateFields {
+                    kind: Some(
+                        ToolKind::classify_tool(&request.tool_name, &request.arguments)
+                            .to_acp_kind(),
+                    ),
+                    status: Some(agent_client_protocol::ToolCallStatus::Pending),
+                    title: Some(ToolCallReport::generate_title(
+                        &request.tool_name,
+                        &request.arguments,
+                    )),
+                    raw_input: Some(request.arguments.clone()),
+                    ..Default::default()
+                },
+                meta: None,
+            },
+            options: request
+                .options
+                .iter()
+                .map(PermissionOption::to_acp_option)
+                .collect(),
+            meta: None,
+        }
+    }
+
+    /// Resolve once the session is cancelled
+    async fn session_cancelled(&self, session_id: &str) {
+        let mut cancellations = self.cancellation_manager.subscribe();
+        if self.cancellation_manager.is_cancelled(session_id).await {
+            return;
+        }
+        loop {
+            match cancellations.recv().await {
+                Ok(cancelled) if cancelled == session_id => return,
+                Ok(_) => {}
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => {
+                    if self.cancellation_manager.is_cancelled(session_id).await {
+                        return;
+                    }
+                }
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => {
+                    std::future::pending::<()>().await
+                }
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl UserPromptHandler for ClientPromptHandler {
+    async fn prompt_for_permission(
+        &self,
+        tool_name: &str,
+        _description: &str,
+        _options: &[PermissionOption],
+    ) -> PromptResult<String> {
+        Err(PromptError::InvalidInput(format!(
+            "Asking the client about {} requires a session and tool call",
+            tool_name
+        )))
+    }
+
+    async fn prompt_for_tool_call(
+        &self,
+        request: &EnhancedPermissionRequest,
+    ) -> PromptResult<String> {
+        let permission_request = Self::permission_request(request);
+        let response: agent_client_protocol::RequestPermissionResponse = tokio::select! {
+            response = self.client.request(
+                agent_client_protocol::CLIENT_METHOD_NAMES.session_request_permission,
+                &permission_request,
+            ) => response.map_err(|e| PromptError::Client(e.to_string()))?,
+            _ = self.session_cancelled(&request.session_id) => {
+                tracing::info!(
+                    "Session {} cancelled while waiting for permission to run {}",
+                    request.session_id,
+                    request.tool_name
+                );
+                return Err(PromptError::Cancelled);
+            }
+        };
+
+        match response.outcome {
+            agent_client_protocol::RequestPermissionOutcome::Selected { option_id } => {
+                Ok(option_id.0.to_string())
+            }
+            agent_client_protocol::RequestPermissionOutcome::Cancelled => {
+                Err(PromptError::Cancelled)
+            }
+        }
+    }
+}
+
 /// Mock prompt handler for testing that always returns a specific option
 #[cfg(test)]
 pub struct MockPromptHandler {
@@ -199,4 +352,104 @@ mod tests {
 
         assert_eq!(result, "allow-once");
     }
+
+    fn client_handler() -> (
+        ClientPromptHandler,
+        Arc<CancellationManager>,
+        tokio::sync::mpsc::UnboundedReceiver<String>,
+        Arc<ClientConnection>,
+    ) {
+        let client = Arc::new(ClientConnection::new(std::time::Duration::from_secs(5)));
+        let outgoing = client.take_outgoing_receiver().unwrap();
+        let (cancellation_manager, _receiver) = CancellationManager::new(16);
+        let cancellation_manager = Arc::new(cancellation_manager);
+        let handler =
+            ClientPromptHandler::new(Arc::clone(&client), Arc::clone(&cancellation_manager));
+        (handler, cancellation_manager, outgoing, client)
+    }
+
+    fn bash_request() -> EnhancedPermissionRequest {
+        EnhancedPermissionRequest {
+            session_id: "session-1".to_string(),
+            tool_request_id: "toolu_42".to_string(),
+            tool_name: "Bash".to_string(),
+            description: "Execute Bash".to_string(),
+            arguments: serde_json::json!({"command": "cargo test"}),
+            options: vec![
+                PermissionOption {
+                    option_id: "allow-always".to_string(),
+                    name: "Allow always".to_string(),
+                    kind: PermissionOptionKind::AllowAlways,
+                },
+                PermissionOption {
+                    option_id: "reject-once".to_string(),
+                    name: "Reject".to_string(),
+                    kind: PermissionOptionKind::RejectOnce,
+                },
+            ],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_client_prompt_returns_selected_option() {
+        let (handler, _cancellation, mut outgoing, client) = client_handler();
+        let request = bash_request();
+
+        let editor = async {
+            let message: serde_json::Value =
+                serde_json::from_str(&outgoing.recv().await.unwrap()).unwrap();
+            assert_eq!(message["method"], "session/request_permission");
+            assert_eq!(message["params"]["sessionId"], "session-1");
+            assert_eq!(message["params"]["toolCall"]["toolCallId"], "toolu_42");
+            assert_eq!(message["params"]["toolCall"]["kind"], "execute");
+            assert_eq!(message["params"]["options"][0]["optionId"], "allow-always");
+            assert_eq!(message["params"]["options"][0]["kind"], "allow_always");
+            client.handle_response(&serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": message["id"],
+                "result": {"outcome": {"outcome": "selected", "optionId": "allow-always"}}
+            }));
+        };
+
+        let (result, _) = tokio::join!(handler.prompt_for_tool_call(&request), editor);
+        assert_eq!(result.unwrap(), "allow-always");
+    }
+
+    #[tokio::test]
+    async fn test_client_cancelled_outcome_is_cancelled() {
+        let (handler, _cancellation, mut outgoing, client) = client_handler();
+        let request = bash_request();
+
+        let editor = async {
+            let message: serde_json::Value =
+                serde_json::from_str(&outgoing.recv().await.unwrap()).unwrap();
+            client.handle_response(&serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": message["id"],
+                "result": {"outcome": {"outcome": "cancelled"}}
+            }));
+        };
+
+        let (result, _) = tokio::join!(handler.prompt_for_tool_call(&request), editor);
+        assert!(matches!(result, Err(PromptError::Cancelled)));
+    }
+
+    #[tokio::test]
+    async fn test_session_cancellation_ends_client_prompt() {
+        let (handler, cancellation_manager, mut outgoing, client) = client_handler();
+        let request = bash_request();
+
+        // The client never answers; cancelling the session ends the wait
+        let cancel = async {
+            outgoing.recv().await.unwrap();
+            cancellation_manager
+                .mark_cancelled("session-1", "test")
+                .await
+                .unwrap();
+        };
+
+        let (result, _) = tokio::join!(handler.prompt_for_tool_call(&request), cancel);
+        assert!(matches!(result, Err(PromptError::Cancelled)));
+        assert_eq!(client.pending_requests(), 0);
+    }
 }
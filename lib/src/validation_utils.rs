@@ -175,11 +175,10 @@ pub fn exceeds_max_length(value: &str, max_length: usize) -> bool {
 /// use claude_agent_lib::validation_utils::validate_max_length;
 ///
 /// assert!(validate_max_length("hi", 10, "username").is_none());
-/// let result = validate_max_length("hello", 3, "username");
-/// assert!(result.is_some());
-/// assert!(result.unwrap().contains("username"));
-/// assert!(result.unwrap().contains("5"));
-/// assert!(result.unwrap().contains("3"));
+/// let result = validate_max_length("hello", 3, "username").unwrap();
+/// assert!(result.contains("username"));
+/// assert!(result.contains("5"));
+/// assert!(result.contains("3"));
 /// ```
 pub fn validate_max_length(value: &str, max_length: usize, field_name: &str) -> Option<String> {
     if exceeds_max_length(value, max_length) {